@@ -0,0 +1,96 @@
+#ifndef OPENMW_CONFIG_H
+#define OPENMW_CONFIG_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Opaque handle to a loaded configuration
+ */
+typedef struct OmwConfig OmwConfig;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Version of the binary interface, same as `ABI_VERSION`
+ */
+uint32_t omwcfg_abi_version(void);
+
+/**
+ * Bits for every optional feature this build includes, same as `CAPABILITIES`
+ */
+uint32_t omwcfg_capabilities(void);
+
+/**
+ * Loads the configuration chain starting at `path`, or the default location if `path` is null.
+ * Returns null on failure.
+ *
+ * # Safety
+ * `path` must be null or a valid, NUL-terminated string.
+ */
+OmwConfig *omwcfg_open(const char *path);
+
+/**
+ * Frees a configuration from `omwcfg_open`. Null is ignored.
+ *
+ * # Safety
+ * `config` must be null or a pointer from `omwcfg_open` which hasn't been freed yet.
+ */
+void omwcfg_free(OmwConfig *config);
+
+/**
+ * How many `content=` entries are enabled
+ *
+ * # Safety
+ * `config` must be a live pointer from `omwcfg_open`.
+ */
+size_t omwcfg_content_count(const OmwConfig *config);
+
+/**
+ * The content file at `index` in load order, or null if it's out of range.
+ * The string has to be released with `omwcfg_string_free`.
+ *
+ * # Safety
+ * `config` must be a live pointer from `omwcfg_open`.
+ */
+char *omwcfg_content_at(const OmwConfig *config, size_t index);
+
+/**
+ * Enables a plugin at the end of the load order. Returns 0 on success and -1 on failure.
+ *
+ * # Safety
+ * `config` must be a live pointer from `omwcfg_open`, and `name` a valid, NUL-terminated string.
+ */
+int omwcfg_add_content(OmwConfig *config, const char *name);
+
+/**
+ * Writes the user openmw.cfg, same as `save_user`. Returns 0 on success and -1 on failure.
+ *
+ * # Safety
+ * `config` must be a live pointer from `omwcfg_open`.
+ */
+int omwcfg_save_user(const OmwConfig *config);
+
+/**
+ * Describes the last failure on this thread, or null if nothing has failed yet.
+ * The string stays owned by the library, and is only valid until the next failing call on the same thread.
+ */
+const char *omwcfg_last_error(void);
+
+/**
+ * Frees a string returned by the library. Null is ignored.
+ *
+ * # Safety
+ * `value` must be null or a string from this library which hasn't been freed yet.
+ */
+void omwcfg_string_free(char *value);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* OPENMW_CONFIG_H */
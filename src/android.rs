@@ -0,0 +1,84 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Where Android ports keep their files.
+//! Every port picks its own folder on shared storage, so the root can come from the app itself,
+//! The `OPENMW_ANDROID_ROOT` environment variable, or failing both, the folder the original port uses.
+//!
+//! Directories picked through the Storage Access Framework arrive as `content://` URIs rather than paths.
+//! Those are kept exactly as written, since resolving them against the config directory would mangle them.
+
+use std::{
+    path::PathBuf,
+    sync::{PoisonError, RwLock},
+};
+
+pub const ANDROID_ROOT_ENV: &str = "OPENMW_ANDROID_ROOT";
+
+/// Where the original Android port keeps everything
+pub const DEFAULT_ANDROID_ROOT: &str = "/storage/emulated/0/Alpha3";
+
+pub type AndroidRootProvider = Box<dyn Fn() -> PathBuf + Send + Sync>;
+
+static PROVIDER: RwLock<Option<AndroidRootProvider>> = RwLock::new(None);
+
+/// Lets the app decide the root, such as from `Context.getExternalFilesDir` over JNI. `None` goes back to the defaults.
+/// Only affects Android builds, apart from `android_root` itself.
+pub fn set_android_root_provider(provider: Option<AndroidRootProvider>) {
+    *PROVIDER.write().unwrap_or_else(PoisonError::into_inner) = provider;
+}
+
+/// The folder holding `config/` and the user data, on Android
+pub fn android_root() -> PathBuf {
+    if let Some(provider) = PROVIDER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        return provider();
+    }
+
+    match std::env::var_os(ANDROID_ROOT_ENV) {
+        Some(root) if !root.is_empty() => PathBuf::from(root),
+        _ => PathBuf::from(DEFAULT_ANDROID_ROOT),
+    }
+}
+
+/// A Storage Access Framework URI, which only the app's content resolver can open
+pub fn is_content_uri(value: &str) -> bool {
+    value
+        .get(..10)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("content://"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OpenMWConfiguration;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_android_root_provider() {
+        set_android_root_provider(Some(Box::new(|| PathBuf::from("/sdcard/MyPort"))));
+        assert_eq!(android_root(), PathBuf::from("/sdcard/MyPort"));
+
+        set_android_root_provider(None);
+        if std::env::var_os(ANDROID_ROOT_ENV).is_none() {
+            assert_eq!(android_root(), PathBuf::from(DEFAULT_ANDROID_ROOT));
+        }
+    }
+
+    #[test]
+    fn test_content_uris_are_kept() {
+        let uri = "content://com.android.externalstorage.documents/tree/primary%3AMorrowind";
+        assert!(is_content_uri(uri));
+        assert!(!is_content_uri("/storage/emulated/0/Morrowind"));
+
+        let dir = temp_config("content_uri", &format!("data=\"{uri}\"\n"));
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        assert_eq!(config.data_directories(), vec![&PathBuf::from(uri)]);
+    }
+}
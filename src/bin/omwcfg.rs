@@ -0,0 +1,262 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! `omwcfg`: inspects and edits the openmw.cfg chain from a shell.
+//! Everything here goes through the public API, so anything it needs that's missing belongs in the library instead.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use openmw_config::{OpenMWConfiguration, SettingValue};
+
+const USAGE: &str = "\
+Usage: omwcfg [--config <path>] <command>
+
+Commands:
+  list <kind>            Print every entry of a kind, in load order
+  add <kind> <value>     Add an entry to the user openmw.cfg
+  remove <kind> <value>  Remove an entry, from whichever openmw.cfg defines it
+  validate [--json]      Report problems with the configuration. Exits with 1 for warnings and 2 for errors.
+  where <key>            Show which openmw.cfg (and line) sets a key or fallback
+
+Kinds: content, groundcover, archive, data, config
+
+Without --config, the chain is found the same way OpenMW does, including OPENMW_CONFIG and OPENMW_CONFIG_DIR.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Content,
+    Groundcover,
+    Archive,
+    Data,
+    Config,
+}
+
+impl Kind {
+    fn parse(word: &str) -> Result<Self, String> {
+        match word {
+            "content" => Ok(Kind::Content),
+            "groundcover" => Ok(Kind::Groundcover),
+            "archive" | "fallback-archive" => Ok(Kind::Archive),
+            "data" => Ok(Kind::Data),
+            "config" => Ok(Kind::Config),
+            _ => Err(format!("Unknown kind of entry: {word}")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    List(Kind),
+    Add(Kind, String),
+    Remove(Kind, String),
+    Validate { json: bool },
+    Where(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    config: Option<PathBuf>,
+    command: Command,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut config = None;
+    let mut words = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--config" => {
+                config = Some(PathBuf::from(args.next().ok_or("--config needs a path")?))
+            }
+            _ => words.push(arg),
+        }
+    }
+
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let command = match words.as_slice() {
+        ["list", kind] => Command::List(Kind::parse(kind)?),
+        ["add", kind, value] => Command::Add(Kind::parse(kind)?, value.to_string()),
+        ["remove", kind, value] => Command::Remove(Kind::parse(kind)?, value.to_string()),
+        ["validate"] => Command::Validate { json: false },
+        ["validate", "--json"] => Command::Validate { json: true },
+        ["where", key] => Command::Where(key.to_string()),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    Ok(Args { config, command })
+}
+
+fn list(config: &OpenMWConfiguration, kind: Kind) -> Vec<String> {
+    match kind {
+        Kind::Content => config.content_files().into_iter().cloned().collect(),
+        Kind::Groundcover => config.groundcover().into_iter().cloned().collect(),
+        Kind::Archive => config.fallback_archives().into_iter().cloned().collect(),
+        Kind::Data => config
+            .data_directories()
+            .into_iter()
+            .map(|dir| dir.display().to_string())
+            .collect(),
+        Kind::Config => config
+            .sub_configs()
+            .map(|dir| dir.parsed().display().to_string())
+            .collect(),
+    }
+}
+
+/// Relative directories mean relative to wherever the command was run, not to the openmw.cfg
+fn absolute(value: &str) -> PathBuf {
+    std::path::absolute(value).unwrap_or_else(|_| PathBuf::from(value))
+}
+
+fn add(config: &mut OpenMWConfiguration, kind: Kind, value: &str) -> Result<(), String> {
+    match kind {
+        Kind::Content => config.add_content_file(value),
+        Kind::Groundcover => config.add_groundcover_file(value),
+        Kind::Archive => config.add_archive_file(value),
+        Kind::Data => {
+            config.add_data_directory(absolute(value));
+            Ok(())
+        }
+        Kind::Config => config.add_sub_config(&absolute(value)),
+    }
+    .map_err(|error| error.to_string())
+}
+
+fn remove(config: &mut OpenMWConfiguration, kind: Kind, value: &str) -> Result<(), String> {
+    match kind {
+        Kind::Content => config.remove_content_file(value),
+        Kind::Groundcover => config.remove_groundcover_file(value),
+        Kind::Archive => config.remove_archive_file(value),
+        Kind::Data => {
+            config.remove_data_directory(&PathBuf::from(value));
+            config.remove_data_directory(&absolute(value));
+        }
+        Kind::Config => {
+            if !config.remove_sub_config(&absolute(value)) {
+                config.remove_sub_config(&PathBuf::from(value));
+            }
+        }
+    }
+
+    // Only removals from a file in the chain can be saved, so that doubles as the check for whether anything matched
+    match config.is_dirty() {
+        true => Ok(()),
+        false => Err(format!("Nothing matched {value}")),
+    }
+}
+
+fn where_is(config: &OpenMWConfiguration, key: &str) -> Vec<String> {
+    config
+        .settings_matching(|setting| {
+            setting.key() == key
+                || matches!(setting, SettingValue::GameSetting(fallback) if fallback.key() == key)
+        })
+        .map(|setting| {
+            let meta = setting.meta();
+            let location = match meta.line() {
+                Some(line) => format!("{}:{line}", meta.source_config().display()),
+                None => meta.source_config().display().to_string(),
+            };
+
+            format!(
+                "{location}: {}",
+                setting.to_string().lines().last().unwrap_or_default()
+            )
+        })
+        .collect()
+}
+
+fn run(args: Args) -> Result<ExitCode, String> {
+    let mut config = match args.config {
+        Some(path) => OpenMWConfiguration::new(Some(path)),
+        None => OpenMWConfiguration::from_env(),
+    }
+    .map_err(|error| error.to_string())?;
+
+    match args.command {
+        Command::List(kind) => list(&config, kind)
+            .iter()
+            .for_each(|entry| println!("{entry}")),
+        Command::Where(key) => {
+            let found = where_is(&config, &key);
+            if found.is_empty() {
+                return Err(format!("{key} isn't set anywhere in the chain"));
+            }
+            found.iter().for_each(|entry| println!("{entry}"));
+        }
+        Command::Validate { json } => {
+            let outcome = config.validation_outcome();
+            match json {
+                true => println!("{}", outcome.to_json()),
+                false => outcome.issues.iter().for_each(|issue| {
+                    println!(
+                        "{}[{}]: {}",
+                        issue.severity.as_str(),
+                        issue.code,
+                        issue.message
+                    )
+                }),
+            }
+
+            return Ok(ExitCode::from(outcome.exit_code() as u8));
+        }
+        Command::Add(kind, value) => {
+            add(&mut config, kind, &value)?;
+            config.save_all()?;
+        }
+        Command::Remove(kind, value) => {
+            remove(&mut config, kind, &value)?;
+            config.save_all()?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    match parse_args(std::env::args().skip(1)).and_then(run) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Args, String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            args("--config /tmp/cfg add content Foo.esp").unwrap(),
+            Args {
+                config: Some(PathBuf::from("/tmp/cfg")),
+                command: Command::Add(Kind::Content, "Foo.esp".into()),
+            }
+        );
+        assert_eq!(
+            args("list fallback-archive").unwrap().command,
+            Command::List(Kind::Archive)
+        );
+        assert_eq!(
+            args("where iMaxSpeed").unwrap().command,
+            Command::Where("iMaxSpeed".into())
+        );
+        assert_eq!(
+            args("validate --json").unwrap().command,
+            Command::Validate { json: true }
+        );
+        assert!(args("list plugins").is_err());
+        assert!(args("add content").is_err());
+        assert!(args("--config").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::{fmt, ops};
+
+/// Version of the exported binary interface.
+/// This only gets bumped when something an FFI consumer or plugin links against changes incompatibly,
+/// So it's safe to compare against whatever value a consumer was built with.
+pub const ABI_VERSION: u32 = 1;
+
+/// The set of optional subsystems which were compiled into this build of the crate.
+/// Consumers (and especially FFI consumers) should check this before reaching for a feature-gated symbol,
+/// Rather than finding out the hard way that it doesn't exist.
+pub const CAPABILITIES: Capabilities = Capabilities::compiled();
+
+/// Bitflags describing optional subsystems.
+/// Hand-rolled instead of pulling in `bitflags` for four constants.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// settings.cfg support
+    pub const SETTINGS_CFG: Self = Self(1 << 0);
+    /// Watching the configuration chain for changes
+    pub const WATCH: Self = Self(1 << 1);
+    /// VFS overlay construction from data directories
+    pub const VFS: Self = Self(1 << 2);
+    /// The `extern "C"` interface
+    pub const FFI: Self = Self(1 << 3);
+    /// Reading Bethesda archives, and layering them into the VFS overlay
+    pub const BSA: Self = Self(1 << 4);
+    /// Translating messages through Fluent
+    pub const FLUENT: Self = Self(1 << 5);
+    /// Watching the configuration chain through an async stream
+    pub const TOKIO: Self = Self(1 << 6);
+    /// Reading openmw.cfg files saved in legacy Windows encodings
+    pub const ENCODING: Self = Self(1 << 7);
+    /// Rendering errors and validation issues as annotated source snippets
+    pub const DIAGNOSTICS: Self = Self(1 << 8);
+    /// Parse traces reported through `tracing` instead of `CFG_DEBUG`
+    pub const TRACING: Self = Self(1 << 9);
+    /// Reading sub-configurations and checking data directories on a thread pool
+    pub const PARALLEL: Self = Self(1 << 10);
+    /// Compiling TOML manifests into an openmw.cfg
+    pub const MANIFEST: Self = Self(1 << 11);
+    /// Reading and editing the engine's input bindings
+    pub const INPUT: Self = Self(1 << 12);
+    /// Installing mods straight from zip and 7z downloads
+    pub const ARCHIVE_INSTALL: Self = Self(1 << 13);
+    /// Compiling YAML manifests as well as TOML ones
+    pub const MANIFEST_YAML: Self = Self(1 << 14);
+
+    const ALL: [(Self, &'static str); 15] = [
+        (Self::SETTINGS_CFG, "settings-cfg"),
+        (Self::WATCH, "watch"),
+        (Self::VFS, "vfs"),
+        (Self::FFI, "ffi"),
+        (Self::BSA, "bsa"),
+        (Self::FLUENT, "fluent"),
+        (Self::TOKIO, "tokio"),
+        (Self::ENCODING, "encoding"),
+        (Self::DIAGNOSTICS, "diagnostics"),
+        (Self::TRACING, "tracing"),
+        (Self::PARALLEL, "parallel"),
+        (Self::MANIFEST, "manifest"),
+        (Self::INPUT, "input"),
+        (Self::ARCHIVE_INSTALL, "archive-install"),
+        (Self::MANIFEST_YAML, "manifest-yaml"),
+    ];
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Capabilities of the current build, derived from its cargo features
+    pub const fn compiled() -> Self {
+        let mut bits = 0;
+
+        if cfg!(feature = "settings-cfg") {
+            bits |= Self::SETTINGS_CFG.0;
+        }
+        if cfg!(feature = "watch") {
+            bits |= Self::WATCH.0;
+        }
+        if cfg!(feature = "vfs") {
+            bits |= Self::VFS.0;
+        }
+        if cfg!(feature = "ffi") {
+            bits |= Self::FFI.0;
+        }
+        if cfg!(feature = "bsa") {
+            bits |= Self::BSA.0;
+        }
+        if cfg!(feature = "fluent") {
+            bits |= Self::FLUENT.0;
+        }
+        if cfg!(feature = "tokio") {
+            bits |= Self::TOKIO.0;
+        }
+        if cfg!(feature = "encoding") {
+            bits |= Self::ENCODING.0;
+        }
+        if cfg!(feature = "diagnostics") {
+            bits |= Self::DIAGNOSTICS.0;
+        }
+        if cfg!(feature = "tracing") {
+            bits |= Self::TRACING.0;
+        }
+        if cfg!(feature = "parallel") {
+            bits |= Self::PARALLEL.0;
+        }
+        if cfg!(feature = "manifest") {
+            bits |= Self::MANIFEST.0;
+        }
+        if cfg!(feature = "input") {
+            bits |= Self::INPUT.0;
+        }
+        if cfg!(feature = "archive-install") {
+            bits |= Self::ARCHIVE_INSTALL.0;
+        }
+        if cfg!(feature = "manifest-yaml") {
+            bits |= Self::MANIFEST_YAML.0;
+        }
+
+        Self(bits)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Unknown bits are dropped, so values handed over from a newer ABI are still usable
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        Self(
+            bits & (Self::SETTINGS_CFG.0
+                | Self::WATCH.0
+                | Self::VFS.0
+                | Self::FFI.0
+                | Self::BSA.0
+                | Self::FLUENT.0
+                | Self::TOKIO.0
+                | Self::ENCODING.0
+                | Self::DIAGNOSTICS.0
+                | Self::TRACING.0
+                | Self::PARALLEL.0
+                | Self::MANIFEST.0
+                | Self::INPUT.0
+                | Self::ARCHIVE_INSTALL.0
+                | Self::MANIFEST_YAML.0),
+        )
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Feature names for every capability which is present
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        Self::ALL
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+    }
+}
+
+impl ops::BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl ops::BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.names().collect::<Vec<_>>().join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compiled_matches_features() {
+        assert_eq!(
+            CAPABILITIES.contains(Capabilities::VFS),
+            cfg!(feature = "vfs")
+        );
+        assert_eq!(
+            CAPABILITIES.contains(Capabilities::FFI),
+            cfg!(feature = "ffi")
+        );
+        assert_eq!(
+            CAPABILITIES.contains(Capabilities::WATCH),
+            cfg!(feature = "watch")
+        );
+        assert_eq!(
+            CAPABILITIES.contains(Capabilities::SETTINGS_CFG),
+            cfg!(feature = "settings-cfg")
+        );
+        assert_eq!(
+            CAPABILITIES.contains(Capabilities::MANIFEST_YAML),
+            cfg!(feature = "manifest-yaml")
+        );
+    }
+
+    #[test]
+    fn test_from_bits_truncate_drops_unknown_bits() {
+        let caps = Capabilities::from_bits_truncate(u32::MAX);
+
+        assert!(caps.contains(Capabilities::VFS | Capabilities::FFI));
+        assert_eq!(caps.bits(), 0b111_1111_1111_1111);
+    }
+
+    #[test]
+    fn test_display_lists_names() {
+        let caps = Capabilities::WATCH | Capabilities::FFI;
+        assert_eq!(caps.to_string(), "watch,ffi");
+        assert_eq!(Capabilities::empty().to_string(), "");
+    }
+}
@@ -5,34 +5,152 @@
 
 use std::{
     fmt::{self, Display},
-    fs::{OpenOptions, create_dir_all, metadata, read_to_string},
     path::{Path, PathBuf},
 };
 
-use crate::{ConfigError, GameSetting, bail_config};
+use crate::{ConfigError, GameSetting, SettingHandle, SettingOrigin, bail_config};
 use std::collections::HashSet;
 
 pub mod directorysetting;
 use directorysetting::DirectorySetting;
 
 pub mod filesetting;
-use filesetting::FileSetting;
+use filesetting::{ContentKind, FileSetting};
 
 pub mod gamesetting;
-use gamesetting::GameSettingType;
+use gamesetting::{ColorValue, GameSettingType};
 
 pub mod genericsetting;
 use genericsetting::GenericSetting;
 
 pub mod encodingsetting;
-use encodingsetting::EncodingSetting;
+
+pub mod flagsetting;
+use encodingsetting::{EncodingSetting, EncodingType};
+use flagsetting::FlagSetting;
+
+mod cli;
+pub use cli::CLI_SOURCE;
+
+pub mod bulk;
+
+pub mod comments;
+
+pub mod conflicts;
+
+pub mod disabled;
+
+pub mod discovery;
+
+pub mod document;
+use document::{Document, DocumentLine};
+
+pub mod ini;
+
+pub mod launcher_cfg;
+
+pub mod interop;
+
+pub mod location;
+
+pub mod merge;
+
+pub mod messages;
+
+pub mod duplicates;
+
+mod equality;
+
+pub mod events;
+use events::{ConfigNotification, EventSinkSlot};
+
+pub mod bootstrap;
+
+mod cache;
+mod dirty;
+
+pub mod fs;
+use fs::FsSlot;
+
+pub mod install;
+
+pub mod installation;
+
+pub mod lint;
+
+pub mod loadorder;
+
+pub mod modcontents;
+
+pub mod subconfig;
+use dirty::DirtySources;
+
+pub mod parseoptions;
+
+pub mod plugins;
+
+pub mod project;
+
+pub mod saves;
+
+pub mod screenshots;
+
+pub mod search;
+
+pub mod settingcontext;
+
+pub mod render;
+
+pub mod single;
+
+pub mod userdata;
+
+pub mod snapshot;
+
+pub mod stats;
+use parseoptions::{DuplicatePolicy, FileEncoding, ParseOptions};
+
+pub mod validation;
+
+#[cfg(feature = "watch")]
+pub mod watch;
+
+#[cfg(feature = "vfs")]
+pub mod vfs;
+
+#[cfg(feature = "bsa")]
+pub mod bsa;
+
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+
+#[cfg(feature = "parallel")]
+mod parallel;
+
+#[cfg(feature = "manifest")]
+pub mod manifest;
+
+#[cfg(feature = "manifest")]
+pub mod modlist;
+
+#[cfg(feature = "archive-install")]
+pub mod archive_install;
+
+#[cfg(feature = "input")]
+pub mod input;
+
+#[cfg(feature = "settings-cfg")]
+pub mod settings_cfg;
+
+#[cfg(feature = "settings-cfg")]
+pub mod postprocessing;
 
 #[macro_use]
 pub mod error;
 #[macro_use]
 mod singletonsetting;
 mod strings;
-mod util;
+pub(crate) mod util;
 
 #[derive(Clone, Debug)]
 pub enum SettingValue {
@@ -44,9 +162,13 @@ pub enum SettingValue {
     Encoding(EncodingSetting),
     SubConfiguration(DirectorySetting),
     Generic(GenericSetting),
+    Flag(FlagSetting),
     ContentFile(FileSetting),
     BethArchive(FileSetting),
     Groundcover(FileSetting),
+    /// An entry which was switched off without removing it, written as an `#omwcfg:disabled` comment.
+    /// Only `content=`, `groundcover=`, `fallback-archive=` and `data=` entries can be disabled, see `disable_setting`.
+    Disabled(Box<SettingValue>),
 }
 
 impl Display for SettingValue {
@@ -80,6 +202,7 @@ impl Display for SettingValue {
                 sub_config.original()
             ),
             SettingValue::Generic(generic) => generic.to_string(),
+            SettingValue::Flag(flag) => flag.to_string(),
             SettingValue::ContentFile(plugin) => {
                 format!("{}content={}", plugin.meta().comment, plugin.value(),)
             }
@@ -93,9 +216,26 @@ impl Display for SettingValue {
             SettingValue::Groundcover(grass) => {
                 format!("{}groundcover={}", grass.meta().comment, grass.value())
             }
+            SettingValue::Disabled(setting) => {
+                let text = setting.to_string();
+                let comment = setting.meta().comment();
+                return write!(
+                    f,
+                    "{comment}{} {}",
+                    comments::DISABLED_MARKER,
+                    &text[comment.len()..]
+                );
+            }
         };
 
-        writeln!(f, "{str}")
+        let inline_comment = self.meta().inline_comment();
+        if inline_comment.is_empty() {
+            return writeln!(f, "{str}");
+        }
+
+        // Some settings end with their own newline already, the comment still belongs on the value's line
+        let body = str.trim_end_matches('\n');
+        writeln!(f, "{body} {inline_comment}{}", &str[body.len()..])
     }
 }
 
@@ -112,6 +252,38 @@ impl From<DirectorySetting> for SettingValue {
 }
 
 impl SettingValue {
+    /// The key this setting is written with in openmw.cfg
+    pub fn key(&self) -> &str {
+        match self {
+            SettingValue::DataDirectory(_) => "data",
+            SettingValue::GameSetting(_) => "fallback",
+            SettingValue::UserData(_) => "user-data",
+            SettingValue::DataLocal(_) => "data-local",
+            SettingValue::Resources(_) => "resources",
+            SettingValue::Encoding(_) => "encoding",
+            SettingValue::SubConfiguration(_) => "config",
+            SettingValue::Generic(setting) => setting.key(),
+            SettingValue::Flag(setting) => setting.key(),
+            SettingValue::ContentFile(_) => "content",
+            SettingValue::BethArchive(_) => "fallback-archive",
+            SettingValue::Groundcover(_) => "groundcover",
+            SettingValue::Disabled(setting) => setting.key(),
+        }
+    }
+
+    pub fn handle(&self) -> SettingHandle {
+        self.meta().handle()
+    }
+
+    /// What a `content=` or `groundcover=` entry is. `None` for every other setting.
+    pub fn content_kind(&self) -> Option<ContentKind> {
+        match self {
+            SettingValue::ContentFile(setting) => Some(setting.kind()),
+            SettingValue::Groundcover(_) => Some(ContentKind::Groundcover),
+            _ => None,
+        }
+    }
+
     pub fn meta(&self) -> &crate::GameSettingMeta {
         match self {
             SettingValue::BethArchive(setting) => setting.meta(),
@@ -125,13 +297,33 @@ impl SettingValue {
             SettingValue::SubConfiguration(setting) => setting.meta(),
             SettingValue::Encoding(setting) => setting.meta(),
             SettingValue::Generic(setting) => setting.meta(),
+            SettingValue::Flag(setting) => setting.meta(),
+            SettingValue::Disabled(setting) => setting.meta(),
+        }
+    }
+
+    pub(crate) fn meta_mut(&mut self) -> &mut crate::GameSettingMeta {
+        match self {
+            SettingValue::BethArchive(setting) => setting.meta_mut(),
+            SettingValue::Groundcover(setting) => setting.meta_mut(),
+            SettingValue::UserData(setting) => setting.meta_mut(),
+            SettingValue::DataLocal(setting) => setting.meta_mut(),
+            SettingValue::DataDirectory(setting) => setting.meta_mut(),
+            SettingValue::ContentFile(setting) => setting.meta_mut(),
+            SettingValue::GameSetting(setting) => setting.meta_mut(),
+            SettingValue::Resources(setting) => setting.meta_mut(),
+            SettingValue::SubConfiguration(setting) => setting.meta_mut(),
+            SettingValue::Encoding(setting) => setting.meta_mut(),
+            SettingValue::Generic(setting) => setting.meta_mut(),
+            SettingValue::Flag(setting) => setting.meta_mut(),
+            SettingValue::Disabled(setting) => setting.meta_mut(),
         }
     }
 }
 
-macro_rules! insert_dir_setting {
-    ($self:ident, $variant:ident, $value:expr, $config_dir:expr, $comment:expr) => {{
-        let mut actual_dir = util::input_config_path($config_dir)?;
+macro_rules! dir_setting {
+    ($self:ident, $value:expr, $config_dir:expr, $comment:expr) => {{
+        let mut actual_dir = util::input_config_path(&*$self.fs, $config_dir)?;
 
         if actual_dir
             .file_name()
@@ -150,27 +342,66 @@ macro_rules! insert_dir_setting {
                 .map_err(|io_err| ConfigError::Io(io_err))?;
         }
 
-        $self
-            .settings
-            .push(SettingValue::$variant(DirectorySetting::new(
-                $value,
-                actual_dir.to_path_buf(),
-                $comment,
-            )));
+        DirectorySetting::with_queued_comment($value, actual_dir.to_path_buf(), $comment)
     }};
 }
 
+macro_rules! insert_dir_setting {
+    ($self:ident, $variant:ident, $value:expr, $config_dir:expr, $comment:expr) => {{
+        let setting = dir_setting!($self, $value, $config_dir, $comment);
+        $self.push_setting(SettingValue::$variant(setting));
+    }};
+}
+
+/// How many openmw.cfg files may be nested inside each other through `config=` before loading gives up
+pub const MAX_CONFIG_DEPTH: usize = 32;
+
 /// Core struct representing the composed OpenMW configuration,
 /// After it has been fully resolved.
 #[derive(Debug, Default)]
 pub struct OpenMWConfiguration {
     root_config: PathBuf,
-    settings: Vec<SettingValue>,
+    /// Shared with any snapshots, and only copied when it's changed while one is still around
+    settings: std::sync::Arc<Vec<SettingValue>>,
+    project: Option<PathBuf>,
+    event_sink: EventSinkSlot,
+    observers: events::Observers,
+    fs: FsSlot,
+    dirty: DirtySources,
+    /// Files currently being loaded, outermost first
+    load_stack: Vec<PathBuf>,
+    /// Sub-configurations read ahead of time by `ParseOptions::parallel`
+    #[cfg(feature = "parallel")]
+    prefetched: std::collections::HashMap<PathBuf, std::io::Result<Vec<u8>>>,
+    #[cfg(feature = "parallel")]
+    parallel: bool,
+    /// Only set while `load_cached` is running
+    cache: Option<cache::ConfigCache>,
+    serializer_info: Vec<SerializerInfo>,
+    omit_serializer_footer: bool,
+    /// Content, groundcover and archive names are compared case-insensitively
+    ignore_case: bool,
+    /// Data directories are canonicalized as they're added
+    resolve_symlinks: bool,
+    /// Where OpenMW is installed, to tell a local openmw.cfg apart from the rest
+    install_dir: Option<PathBuf>,
+    documents: document::Documents,
+    /// Saving and anything else which writes to disk is refused
+    read_only: bool,
+    /// Opened through `SingleConfig`, so `config=` lines are kept but never followed
+    single_file: bool,
+}
+
+/// A serializer footer found while loading, meaning the file was written out by this crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializerInfo {
+    pub config: PathBuf,
+    pub version: String,
 }
 
 impl OpenMWConfiguration {
     pub fn from_env() -> Result<Self, ConfigError> {
-        if let Ok(explicit_path) = std::env::var("OPENMW_CONFIG") {
+        if let Ok(explicit_path) = std::env::var(crate::paths::CONFIG_ENV) {
             let explicit_path: PathBuf = shellexpand::tilde(&explicit_path).into_owned().into();
 
             return Self::new(Some(explicit_path));
@@ -193,66 +424,150 @@ impl OpenMWConfiguration {
         Self::new(None)
     }
 
+    /// Starts the chain where the engine does: the local openmw.cfg next to the executable if there is one (a portable install),
+    /// Otherwise the global one, which reach the user openmw.cfg through their own `config=` lines.
+    /// Falls back to the user openmw.cfg on its own when neither exists, like `new(None)`.
+    pub fn from_system_defaults() -> Result<Self, ConfigError> {
+        let install_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+
+        Self::from_system_defaults_in(install_dir.as_deref())
+    }
+
+    /// Same as `from_system_defaults`, for launchers which know where OpenMW is installed but don't live next to it
+    pub fn from_system_defaults_in(install_dir: Option<&Path>) -> Result<Self, ConfigError> {
+        let root = install_dir
+            .map(Path::to_path_buf)
+            .into_iter()
+            .chain(crate::default_global_config_path())
+            .find(|dir| dir.join("openmw.cfg").is_file());
+
+        let mut config = Self::new(root)?;
+        config.set_install_dir(install_dir.map(Path::to_path_buf));
+        Ok(config)
+    }
+
     pub fn new(path: Option<PathBuf>) -> Result<Self, ConfigError> {
-        let mut config = OpenMWConfiguration::default();
+        Self::new_with_options(path, ParseOptions::default())
+    }
+
+    /// Like `new`, but lenient options allow loading a chain which contains broken lines
+    pub fn new_with_options(
+        path: Option<PathBuf>,
+        options: ParseOptions,
+    ) -> Result<Self, ConfigError> {
+        Self::open(path, options, None)
+    }
+
+    fn open(
+        path: Option<PathBuf>,
+        options: ParseOptions,
+        cache: Option<cache::ConfigCache>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = OpenMWConfiguration {
+            cache,
+            fs: FsSlot(options.fs.clone()),
+            ignore_case: options.ignore_case,
+            resolve_symlinks: options.resolve_symlinks,
+            read_only: options.read_only,
+            ..Default::default()
+        };
         let root_config = match path {
-            Some(path) => util::input_config_path(&path)?,
+            Some(path) => util::input_config_path(&*config.fs, &path)?,
             None => crate::default_config_path().join("openmw.cfg"),
         };
 
         config.root_config = root_config;
 
-        match config.load(&config.root_config.to_owned()) {
+        #[cfg(feature = "parallel")]
+        {
+            config.parallel = options.parallel;
+        }
+
+        let loaded = match config.restore_cached_chain() {
+            true => Ok(()),
+            false => config
+                .load(&config.root_config.to_owned(), &options)
+                .map(|()| config.cache_loaded_chain()),
+        };
+
+        match loaded {
             Err(error) => Err(error),
             Ok(_) => {
                 if let Some(dir) = config.data_local() {
-                    let path = dir.parsed();
-
-                    let path_meta = metadata(path);
-                    if !path_meta.is_ok() {
-                        if let Err(error) = create_dir_all(path) {
-                            util::debug_log(format!(
-                                "WARNING: Attempted to crete a data-local directory at {path:?}, but failed: {error}"
-                            ))
-                        };
-                    }
-
-                    config
-                        .settings
-                        .push(SettingValue::DataDirectory(dir.clone()));
+                    if options.create_missing_dirs
+                        && !options.read_only
+                        && let Err(error) = config.local_data_dir_ensure()
+                    {
+                        util::debug_log(format_args!(
+                            "WARNING: Attempted to create a data-local directory at {:?}, but failed: {error}",
+                            dir.parsed()
+                        ))
+                    };
+
+                    // The implicit data directory is a separate entry from data-local itself
+                    let mut data_local_dir = dir.clone();
+                    data_local_dir.meta.handle = SettingHandle::next();
+                    data_local_dir.meta.origin = SettingOrigin::Synthetic;
+
+                    config.push_setting(SettingValue::DataDirectory(data_local_dir));
                 }
 
-                if let Some(setting) = config.resources() {
-                    let dir = setting.parsed();
+                util::debug_log(format_args!("{:#?}", config.settings));
 
-                    let morrowind_vfs = DirectorySetting::new(
-                        dir.join("vfs-mw").to_string_lossy().to_string(),
-                        setting.meta.source_config.to_path_buf(),
-                        &mut setting.meta.comment.to_string(),
-                    );
+                config.dirty.take();
+                config.set_event_sink(options.event_sink);
 
-                    let engine_vfs = DirectorySetting::new(
-                        dir.join("vfs").to_string_lossy().to_string(),
-                        setting.meta.source_config.to_path_buf(),
-                        &mut setting.meta.comment.to_string(),
-                    );
+                Ok(config)
+            }
+        }
+    }
 
-                    config
-                        .settings
-                        .insert(0, SettingValue::DataDirectory(morrowind_vfs));
+    /// Serializer footers found while loading, one for each file in the chain that was written by this crate
+    pub fn serializer_info(&self) -> &[SerializerInfo] {
+        &self.serializer_info
+    }
 
-                    config
-                        .settings
-                        .insert(0, SettingValue::DataDirectory(engine_vfs));
-                }
+    /// Whether `Display` ends with a footer naming the crate version, which it does by default
+    pub fn set_serializer_footer(&mut self, enabled: bool) {
+        self.omit_serializer_footer = !enabled;
+    }
 
-                util::debug_log(format!("{:#?}", config.settings));
+    /// Whether content, groundcover and archive lookups ignore case, like the engine does.
+    /// Off unless the configuration was loaded with `ParseOptions::ignoring_case`.
+    pub fn set_case_insensitive(&mut self, enabled: bool) {
+        self.ignore_case = enabled;
+    }
 
-                Ok(config)
+    pub fn is_case_insensitive(&self) -> bool {
+        self.ignore_case
+    }
+
+    /// Whether data directories resolve to their canonical paths, which `ParseOptions::resolving_symlinks` turns on.
+    /// Turning it on resolves the ones already there as well, and turning it off puts them back as written.
+    pub fn set_resolve_symlinks(&mut self, enabled: bool) {
+        self.resolve_symlinks = enabled;
+
+        for setting in std::sync::Arc::make_mut(&mut self.settings) {
+            if let SettingValue::DataDirectory(dir) = setting {
+                match enabled {
+                    true => dir.resolve_symlinks(&*self.fs),
+                    false => dir.forget_resolved(),
+                }
             }
         }
     }
 
+    pub fn resolves_symlinks(&self) -> bool {
+        self.resolve_symlinks
+    }
+
+    /// Set by `ParseOptions::read_only`, in which case nothing gets written to disk
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     /// Path to the configuration file which is the root of the configuration chain
     /// Typically, this will be whatever is defined in the `Paths` documentation for the appropriate platform:
     /// https://openmw.readthedocs.io/en/latest/reference/modding/paths.html#configuration-files-and-log-files
@@ -290,9 +605,12 @@ impl OpenMWConfiguration {
     /// and will be the one which is modifiable by OpenMW-Launcher and OpenMW proper.
     ///
     /// See https://openmw.readthedocs.io/en/latest/reference/modding/paths.html#configuration-sources for examples and further explanation of multiple config sources.
-
     /// Path to the highest-level configuration *directory*
     pub fn user_config_path(&self) -> PathBuf {
+        if self.single_file {
+            return self.root_config_dir();
+        }
+
         util::user_config_path(
             &self.sub_configs().map(|setting| setting.parsed()).collect(),
             &self.root_config_dir(),
@@ -322,6 +640,29 @@ impl OpenMWConfiguration {
         }
     }
 
+    pub fn encoding_type(&self) -> Option<&EncodingType> {
+        self.encoding().map(EncodingSetting::encoding_type)
+    }
+
+    /// Sets `encoding=` in the user openmw.cfg, or removes it with `None`
+    pub fn set_encoding_type(&mut self, encoding: Option<EncodingType>) {
+        let source = self.user_config_path().join("openmw.cfg");
+
+        self.set_encoding(encoding.map(|encoding| {
+            let mut setting = EncodingSetting::new(encoding);
+            setting.meta_mut().source_config = source;
+            setting
+        }));
+    }
+
+    /// Text from plugins and saves in the configured codepage, or win1252 like the engine when there's no `encoding=`
+    #[cfg(feature = "encoding")]
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> std::borrow::Cow<'a, str> {
+        self.encoding_type()
+            .unwrap_or(&EncodingType::WIN1252)
+            .decode(bytes)
+    }
+
     /// Content files are the actual *mods* or plugins which are created by either OpenCS or Bethesda's construction set
     /// These entries only refer to the names and ordering of content files.
     /// vfstool-lib should be used to derive paths
@@ -331,6 +672,9 @@ impl OpenMWConfiguration {
             .collect()
     }
 
+    /// Iteration order is always the engine's load order.
+    /// This holds across every mutation this crate offers: additions go to the end, removals close the gap,
+    /// And insertions shift everything after them down by one.
     pub fn content_files_iter(&self) -> impl Iterator<Item = &FileSetting> {
         self.settings.iter().filter_map(|setting| match setting {
             SettingValue::ContentFile(plugin) => Some(plugin),
@@ -338,40 +682,107 @@ impl OpenMWConfiguration {
         })
     }
 
+    /// The `.omwgame` and `.esm` entries in the content list, in load order
+    pub fn game_files(&self) -> impl Iterator<Item = &FileSetting> {
+        self.content_files_iter()
+            .filter(|setting| setting.kind().is_game_file())
+    }
+
+    /// The `.esp` and `.omwaddon` entries in the content list, in load order
+    pub fn addon_files(&self) -> impl Iterator<Item = &FileSetting> {
+        self.content_files_iter()
+            .filter(|setting| setting.kind().is_addon())
+    }
+
+    /// Content entries of one kind, in load order
+    pub fn content_files_of_kind(&self, kind: ContentKind) -> impl Iterator<Item = &FileSetting> {
+        self.content_files_iter()
+            .filter(move |setting| setting.kind() == kind)
+    }
+
+    /// The `.omwscripts` entries in the content list, in load order
+    pub fn lua_scripts(&self) -> impl Iterator<Item = &FileSetting> {
+        self.content_files_iter()
+            .filter(|setting| setting.is_lua_scripts())
+    }
+
+    /// Content files alongside their (zero-based) position in the load order
+    pub fn content_files_enumerated(&self) -> impl Iterator<Item = (usize, &FileSetting)> {
+        self.content_files_iter().enumerate()
+    }
+
+    /// Adds a content file at a specific load order position, shifting later ones down.
+    /// Positions past the end of the load order append.
+    pub fn insert_content_file(
+        &mut self,
+        position: usize,
+        content_file: &str,
+    ) -> Result<(), ConfigError> {
+        self.add_content_file(content_file)?;
+
+        let index = self.settings.len() - 1;
+        self.move_content_file(index, position);
+
+        Ok(())
+    }
+
+    /// Moves the content file at `index` (into the full settings list) to the given load order position
+    fn move_content_file(&mut self, index: usize, position: usize) {
+        let plugin = self.settings_mut().remove(index);
+
+        let target = self
+            .settings
+            .iter()
+            .enumerate()
+            .filter(|(_, setting)| matches!(setting, SettingValue::ContentFile(_)))
+            .map(|(index, _)| index)
+            .nth(position);
+
+        let target = target.unwrap_or_else(|| {
+            self.settings
+                .iter()
+                .rposition(|setting| matches!(setting, SettingValue::ContentFile(_)))
+                .map_or(self.settings.len(), |last| last + 1)
+        });
+
+        self.settings_mut().insert(target, plugin);
+        self.notify(ConfigNotification::SettingMoved(&self.settings[target]));
+    }
+
+    /// Only enabled entries count, not ones saved as `#omwcfg:disabled`
     pub fn has_content_file(&self, file_name: &str) -> bool {
         self.settings.iter().any(|setting| match setting {
-            SettingValue::ContentFile(plugin) => plugin == file_name,
+            SettingValue::ContentFile(plugin) => plugin.is_named(file_name, self.ignore_case),
             _ => false,
         })
     }
 
     pub fn has_groundcover_file(&self, file_name: &str) -> bool {
         self.settings.iter().any(|setting| match setting {
-            SettingValue::Groundcover(plugin) => plugin == file_name,
+            SettingValue::Groundcover(plugin) => plugin.is_named(file_name, self.ignore_case),
             _ => false,
         })
     }
 
     pub fn has_archive_file(&self, file_name: &str) -> bool {
         self.settings.iter().any(|setting| match setting {
-            SettingValue::BethArchive(archive) => archive == file_name,
+            SettingValue::BethArchive(archive) => archive.is_named(file_name, self.ignore_case),
             _ => false,
         })
     }
 
     pub fn has_data_dir(&self, file_name: &str) -> bool {
         self.settings.iter().any(|setting| match setting {
-            SettingValue::DataDirectory(data_dir) => {
-                data_dir.parsed().to_string_lossy() == file_name
-            }
+            SettingValue::DataDirectory(data_dir) => data_dir.parsed().as_os_str() == file_name,
             _ => false,
         })
     }
 
+    /// Enables the plugin at the end of the load order, or where it was if it's only disabled
     pub fn add_content_file(&mut self, content_file: &str) -> Result<(), ConfigError> {
         let duplicate = self.settings.iter().find_map(|setting| match setting {
             SettingValue::ContentFile(plugin) => {
-                if plugin == content_file {
+                if plugin.is_named(content_file, self.ignore_case) {
                     Some(plugin)
                 } else {
                     None
@@ -388,12 +799,17 @@ impl OpenMWConfiguration {
             )
         };
 
-        self.settings
-            .push(SettingValue::ContentFile(FileSetting::new(
-                content_file,
-                &self.user_config_path().join("openmw.cfg"),
-                &mut String::default(),
-            )));
+        let ignore_case = self.ignore_case;
+        if self.enable_first_disabled(|setting| {
+            matches!(setting, SettingValue::ContentFile(plugin) if plugin.is_named(content_file, ignore_case))
+        }) {
+            return Ok(());
+        }
+
+        self.push_setting(SettingValue::ContentFile(FileSetting::new(
+            content_file,
+            &self.user_config_path().join("openmw.cfg"),
+        )));
 
         Ok(())
     }
@@ -404,6 +820,7 @@ impl OpenMWConfiguration {
             .collect()
     }
 
+    /// Same ordering guarantees as `content_files_iter`
     pub fn groundcover_iter(&self) -> impl Iterator<Item = &FileSetting> {
         self.settings.iter().filter_map(|setting| match setting {
             SettingValue::Groundcover(grass) => Some(grass),
@@ -411,10 +828,14 @@ impl OpenMWConfiguration {
         })
     }
 
+    pub fn groundcover_enumerated(&self) -> impl Iterator<Item = (usize, &FileSetting)> {
+        self.groundcover_iter().enumerate()
+    }
+
     pub fn add_groundcover_file(&mut self, content_file: &str) -> Result<(), ConfigError> {
         let duplicate = self.settings.iter().find_map(|setting| match setting {
             SettingValue::Groundcover(plugin) => {
-                if plugin == content_file {
+                if plugin.is_named(content_file, self.ignore_case) {
                     Some(plugin)
                 } else {
                     None
@@ -431,65 +852,116 @@ impl OpenMWConfiguration {
             )
         };
 
-        self.settings
-            .push(SettingValue::Groundcover(FileSetting::new(
-                content_file,
-                &self.user_config_path().join("openmw.cfg"),
-                &mut String::default(),
-            )));
+        let ignore_case = self.ignore_case;
+        if self.enable_first_disabled(|setting| {
+            matches!(setting, SettingValue::Groundcover(plugin) if plugin.is_named(content_file, ignore_case))
+        }) {
+            return Ok(());
+        }
+
+        self.push_setting(SettingValue::Groundcover(FileSetting::new(
+            content_file,
+            &self.user_config_path().join("openmw.cfg"),
+        )));
 
         Ok(())
     }
 
+    /// Disabled copies of the plugin go as well
     pub fn remove_content_file(&mut self, file_name: &str) {
-        self.clear_matching(|setting| match setting {
-            SettingValue::ContentFile(existing_file) => existing_file == file_name,
+        let ignore_case = self.ignore_case;
+        self.clear_matching(|setting| match setting.enabled_form() {
+            SettingValue::ContentFile(existing_file) => {
+                existing_file.is_named(file_name, ignore_case)
+            }
             _ => false,
         });
     }
 
     pub fn remove_groundcover_file(&mut self, file_name: &str) {
-        self.clear_matching(|setting| match setting {
-            SettingValue::Groundcover(existing_file) => existing_file == file_name,
+        let ignore_case = self.ignore_case;
+        self.clear_matching(|setting| match setting.enabled_form() {
+            SettingValue::Groundcover(existing_file) => {
+                existing_file.is_named(file_name, ignore_case)
+            }
             _ => false,
         });
     }
 
     pub fn remove_archive_file(&mut self, file_name: &str) {
+        let ignore_case = self.ignore_case;
         self.clear_matching(|setting| match setting {
-            SettingValue::BethArchive(existing_file) => existing_file == file_name,
+            SettingValue::BethArchive(existing_file) => {
+                existing_file.is_named(file_name, ignore_case)
+            }
             _ => false,
         });
     }
 
     /// Removed any path matching either the relativized original version in openmw.cfg or
     /// the fully resolved absolute version the config itself relies on
-    pub fn remove_data_directory(&mut self, data_dir: &PathBuf) {
+    pub fn remove_data_directory(&mut self, data_dir: &Path) {
+        self.clear_matching(|setting| match setting {
+            SettingValue::DataDirectory(existing_data_dir) => existing_data_dir.refers_to(data_dir),
+            _ => false,
+        });
+    }
+
+    /// Same as remove_data_directory, but only drops entries which were defined by the given openmw.cfg
+    /// Either the file itself or the directory containing it may be used.
+    pub fn remove_data_directory_from(&mut self, data_dir: &Path, source_config: &Path) {
         self.clear_matching(|setting| match setting {
             SettingValue::DataDirectory(existing_data_dir) => {
-                existing_data_dir.parsed() == data_dir
-                    || existing_data_dir.original() == &data_dir.to_string_lossy().to_string()
+                existing_data_dir.meta().is_from(source_config)
+                    && existing_data_dir.refers_to(data_dir)
             }
             _ => false,
         });
     }
 
+    /// Puts `resources/vfs` and `resources/vfs-mw` in front of the data directories, like the engine does when it starts.
+    /// They're only there for lookups, so saving never writes them out. Does nothing without `resources=`, or if they were already added.
+    pub fn with_engine_resource_dirs(mut self) -> Self {
+        let Some(resources) = self.resources() else {
+            return self;
+        };
+
+        let source = resources.meta.source_config.clone();
+        let dirs = [
+            resources.parsed().join("vfs"),
+            resources.parsed().join("vfs-mw"),
+        ];
+
+        for dir in dirs.iter().rev() {
+            if self.data_directories_iter().any(|existing| {
+                existing.parsed() == dir && existing.meta.origin() == SettingOrigin::Synthetic
+            }) {
+                continue;
+            }
+
+            let mut setting = DirectorySetting::from_path(dir, source.clone());
+            setting.meta.origin = SettingOrigin::Synthetic;
+            self.settings_mut()
+                .insert(0, SettingValue::DataDirectory(setting));
+        }
+
+        self
+    }
+
     /// Does not validate duplicate data directories
     /// Jest don't feel like it atm
     /// Let's add comments later after we're not super burned out on this whole config thing
     pub fn add_data_directory(&mut self, dir: PathBuf) {
-        self.settings
-            .push(SettingValue::DataDirectory(DirectorySetting::new(
-                dir.to_string_lossy(),
-                self.user_config_path().join("openmw.cfg"),
-                &mut String::default(),
-            )))
+        self.push_setting(SettingValue::DataDirectory(DirectorySetting::from_path(
+            &dir,
+            self.user_config_path().join("openmw.cfg"),
+        )))
     }
 
     pub fn add_archive_file(&mut self, archive_file: &str) -> Result<(), ConfigError> {
         let duplicate = self.settings.iter().find_map(|setting| match setting {
             SettingValue::BethArchive(archive) => {
-                if archive == archive_file {
+                if archive.is_named(archive_file, self.ignore_case) {
                     Some(archive)
                 } else {
                     None
@@ -506,12 +978,10 @@ impl OpenMWConfiguration {
             )
         };
 
-        self.settings
-            .push(SettingValue::BethArchive(FileSetting::new(
-                archive_file,
-                &self.user_config_path().join("openmw.cfg"),
-                &mut String::default(),
-            )));
+        self.push_setting(SettingValue::BethArchive(FileSetting::new(
+            archive_file,
+            &self.user_config_path().join("openmw.cfg"),
+        )));
 
         Ok(())
     }
@@ -522,6 +992,7 @@ impl OpenMWConfiguration {
             .collect()
     }
 
+    /// Same ordering guarantees as `content_files_iter`; later archives take priority over earlier ones
     pub fn fallback_archives_iter(&self) -> impl Iterator<Item = &FileSetting> {
         self.settings.iter().filter_map(|setting| match setting {
             SettingValue::BethArchive(archive) => Some(archive),
@@ -529,18 +1000,20 @@ impl OpenMWConfiguration {
         })
     }
 
+    pub fn fallback_archives_enumerated(&self) -> impl Iterator<Item = (usize, &FileSetting)> {
+        self.fallback_archives_iter().enumerate()
+    }
+
     /// This early iteration of the crate provides no input validation for setter functions.
     pub fn set_content_files(&mut self, plugins: Option<Vec<String>>) {
         self.clear_matching(|setting| matches!(setting, SettingValue::ContentFile(_)));
 
         if let Some(plugins) = plugins {
             plugins.into_iter().for_each(|plugin| {
-                self.settings
-                    .push(SettingValue::ContentFile(FileSetting::new(
-                        &plugin,
-                        &self.user_config_path(),
-                        &mut String::default(),
-                    )))
+                self.push_setting(SettingValue::ContentFile(FileSetting::new(
+                    &plugin,
+                    &self.user_config_path().join("openmw.cfg"),
+                )))
             })
         }
     }
@@ -550,12 +1023,10 @@ impl OpenMWConfiguration {
 
         if let Some(archives) = archives {
             archives.into_iter().for_each(|archive| {
-                self.settings
-                    .push(SettingValue::BethArchive(FileSetting::new(
-                        &archive,
-                        &self.user_config_path(),
-                        &mut String::default(),
-                    )))
+                self.push_setting(SettingValue::BethArchive(FileSetting::new(
+                    &archive,
+                    &self.user_config_path().join("openmw.cfg"),
+                )))
             })
         }
     }
@@ -567,14 +1038,116 @@ impl OpenMWConfiguration {
     where
         P: Fn(&SettingValue) -> bool + 'a,
     {
-        self.settings.iter().filter(move |s| predicate(*s))
+        self.settings.iter().filter(move |s| predicate(s))
+    }
+
+    /// Every setting in load order, alongside the handle which may be used to refer back to it later
+    pub fn settings_with_handles(&self) -> impl Iterator<Item = (SettingHandle, &SettingValue)> {
+        self.settings
+            .iter()
+            .map(|setting| (setting.handle(), setting))
+    }
+
+    pub fn get_setting(&self, handle: &SettingHandle) -> Option<&SettingValue> {
+        self.settings
+            .iter()
+            .find(|setting| setting.handle() == *handle)
+    }
+
+    /// Removes exactly one line of configuration, regardless of whether other entries resolve to the same value.
+    /// Returns the removed setting, or None if the handle doesn't belong to this configuration (anymore).
+    pub fn remove_setting(&mut self, handle: &SettingHandle) -> Option<SettingValue> {
+        let index = self
+            .settings
+            .iter()
+            .position(|setting| setting.handle() == *handle)?;
+
+        Some(self.remove_setting_at(index))
+    }
+
+    /// Changes exactly one line of configuration, which keeps its handle and its place in the list.
+    /// Returns the updated setting, or None if the handle doesn't belong to this configuration (anymore).
+    /// The entry has to stay the same kind of setting, and a plugin or archive can't be renamed to one which is already enabled,
+    /// Otherwise nothing changes and an error is returned.
+    pub fn update_setting(
+        &mut self,
+        handle: &SettingHandle,
+        edit: impl FnOnce(&mut SettingValue),
+    ) -> Result<Option<&SettingValue>, ConfigError> {
+        let Some(index) = self
+            .settings
+            .iter()
+            .position(|setting| setting.handle() == *handle)
+        else {
+            return Ok(None);
+        };
+
+        let mut updated = self.settings[index].clone();
+        edit(&mut updated);
+        updated.meta_mut().handle = *handle;
+
+        let kind = |setting: &SettingValue| match setting.is_disabled() {
+            true => format!("disabled {}=", setting.key()),
+            false => format!("{}=", setting.key()),
+        };
+        let original = &self.settings[index];
+        if std::mem::discriminant(original) != std::mem::discriminant(&updated)
+            || kind(original) != kind(&updated)
+        {
+            bail_config!(setting_kind_changed, kind(original), kind(&updated));
+        }
+
+        let ignore_case = self.ignore_case;
+        let others = || {
+            self.settings
+                .iter()
+                .enumerate()
+                .filter(move |(other, _)| *other != index)
+                .map(|(_, setting)| setting)
+        };
+        let source = updated.meta().source_config().to_path_buf();
+        match &updated {
+            SettingValue::ContentFile(plugin)
+                if others().any(|other| {
+                    matches!(other, SettingValue::ContentFile(existing) if existing.is_named(plugin.value(), ignore_case))
+                }) =>
+            {
+                bail_config!(duplicate_content_file, plugin.value().clone(), source)
+            }
+            SettingValue::Groundcover(plugin)
+                if others().any(|other| {
+                    matches!(other, SettingValue::Groundcover(existing) if existing.is_named(plugin.value(), ignore_case))
+                }) =>
+            {
+                bail_config!(duplicate_groundcover_file, plugin.value().clone(), source)
+            }
+            SettingValue::BethArchive(archive)
+                if others().any(|other| {
+                    matches!(other, SettingValue::BethArchive(existing) if existing.is_named(archive.value(), ignore_case))
+                }) =>
+            {
+                bail_config!(duplicate_archive_file, archive.value().clone(), source)
+            }
+            _ => {}
+        }
+
+        self.replace_setting_at(index, updated);
+        Ok(self.settings.get(index))
     }
 
     pub fn clear_matching<P>(&mut self, predicate: P)
     where
         P: Fn(&SettingValue) -> bool,
     {
-        self.settings.retain(|s| !predicate(s));
+        let (removed, kept): (Vec<SettingValue>, Vec<SettingValue>) =
+            std::mem::take(self.settings_mut())
+                .into_iter()
+                .partition(|setting| predicate(setting));
+        *self.settings_mut() = kept;
+
+        removed
+            .iter()
+            .for_each(|setting| self.notify(ConfigNotification::SettingRemoved(setting)));
     }
 
     /// This early iteration of the crate provides no input validation for setter functions.
@@ -583,15 +1156,12 @@ impl OpenMWConfiguration {
 
         if let Some(dirs) = dirs {
             let config_path = self.user_config_path();
-            let mut empty = String::default();
 
             dirs.into_iter().for_each(|dir| {
-                self.settings
-                    .push(SettingValue::DataDirectory(DirectorySetting::new(
-                        dir.to_string_lossy(),
-                        config_path.clone(),
-                        &mut empty,
-                    )))
+                self.push_setting(SettingValue::DataDirectory(DirectorySetting::from_path(
+                    &dir,
+                    config_path.clone(),
+                )))
             })
         }
     }
@@ -607,11 +1177,11 @@ impl OpenMWConfiguration {
     ) -> Result<(), ConfigError> {
         let new_setting = GameSettingType::try_from((
             base_value.to_owned(),
-            config_path.unwrap_or(self.user_config_path()),
+            config_path.unwrap_or(self.user_config_path().join("openmw.cfg")),
             comment,
         ))?;
 
-        self.settings.push(SettingValue::GameSetting(new_setting));
+        self.push_setting(SettingValue::GameSetting(new_setting));
 
         Ok(())
     }
@@ -621,16 +1191,15 @@ impl OpenMWConfiguration {
         self.clear_matching(|setting| matches!(setting, SettingValue::GameSetting(_)));
 
         if let Some(settings) = settings {
-            let config_path = self.user_config_path();
+            let config_path = self.user_config_path().join("openmw.cfg");
             let mut empty = String::default();
 
             settings.into_iter().try_for_each(|setting| {
-                self.settings
-                    .push(SettingValue::GameSetting(GameSettingType::try_from((
-                        setting,
-                        config_path.clone(),
-                        &mut empty,
-                    ))?));
+                self.push_setting(SettingValue::GameSetting(GameSettingType::try_from((
+                    setting,
+                    config_path.clone(),
+                    &mut empty,
+                ))?));
 
                 Ok::<(), ConfigError>(())
             })?
@@ -639,29 +1208,172 @@ impl OpenMWConfiguration {
         Ok(())
     }
 
-    pub fn sub_configs(&self) -> impl Iterator<Item = &DirectorySetting> {
-        self.settings.iter().filter_map(|setting| match setting {
-            SettingValue::SubConfiguration(subconfig) => Some(subconfig),
-            _ => None,
-        })
+    /// Every value of a key this crate has no dedicated type for, in load order.
+    /// Single-valued keys just come back with one entry.
+    pub fn get_generic(&self, key: &str) -> Vec<&String> {
+        self.generic_values(key)
+            .map(GenericSetting::value)
+            .collect()
     }
 
-    /// Fallback entries are k/v pairs baked into the value side of k/v pairs in `fallback=` entries of openmw.cfg
-    /// They are used to express settings which are defined in Morrowind.ini for things such as:
-    /// weather, lighting behaviors, UI Colors, and levelup messages
-    pub fn game_settings(&self) -> impl Iterator<Item = &GameSettingType> {
-        let mut unique_settings = Vec::new();
-        let mut seen = HashSet::new();
+    /// Every occurrence of a key, in load order
+    pub fn generic_values<'a>(
+        &'a self,
+        key: &str,
+    ) -> impl Iterator<Item = &'a GenericSetting> + use<'a> {
+        let key = key.to_string();
 
-        for setting in self.settings.iter().rev() {
-            if let SettingValue::GameSetting(gs) = setting {
-                if seen.insert(gs.to_string()) {
-                    unique_settings.push(gs);
-                }
-            }
+        self.settings
+            .iter()
+            .filter_map(move |setting| match setting {
+                SettingValue::Generic(generic) if *generic.key() == key => Some(generic),
+                _ => None,
+            })
+    }
+
+    /// Adds another occurrence of a key to the user config, after all of its existing values
+    pub fn append_generic(&mut self, key: &str, value: &str) {
+        self.push_setting(SettingValue::Generic(GenericSetting::new(
+            key,
+            value,
+            &self.user_config_path().join("openmw.cfg"),
+        )));
+    }
+
+    /// Removes the occurrence of a key at `index`, as counted by `generic_values`
+    pub fn remove_generic_at(&mut self, key: &str, index: usize) -> Option<GenericSetting> {
+        let position = self
+            .settings
+            .iter()
+            .enumerate()
+            .filter(|(_, setting)| {
+                matches!(setting, SettingValue::Generic(generic) if generic.key() == key)
+            })
+            .map(|(position, _)| position)
+            .nth(index)?;
+
+        match self.remove_setting_at(position) {
+            SettingValue::Generic(generic) => Some(generic),
+            _ => unreachable!("position was filtered to generic settings"),
         }
+    }
 
-        unique_settings.into_iter()
+    /// Replaces every value of a key with the given ones, which are added to the user config.
+    /// `None` removes the key entirely.
+    pub fn set_generic(&mut self, key: &str, values: Option<Vec<String>>) {
+        self.clear_matching(
+            |setting| matches!(setting, SettingValue::Generic(generic) if generic.key() == key),
+        );
+
+        if let Some(values) = values {
+            let source = self.user_config_path().join("openmw.cfg");
+
+            values.into_iter().for_each(|value| {
+                self.push_setting(SettingValue::Generic(GenericSetting::new(
+                    key, &value, &source,
+                )))
+            })
+        }
+    }
+
+    /// Valueless entries, which are on just by being present
+    pub fn flags(&self) -> impl Iterator<Item = &FlagSetting> {
+        self.settings.iter().filter_map(|setting| match setting {
+            SettingValue::Flag(flag) => Some(flag),
+            _ => None,
+        })
+    }
+
+    pub fn has_flag(&self, key: &str) -> bool {
+        self.flags().any(|flag| flag.key() == key)
+    }
+
+    /// Turning a flag on adds it to the user config if it isn't already set anywhere.
+    /// Turning it off removes every occurrence.
+    pub fn set_flag(&mut self, key: &str, enabled: bool) {
+        if !enabled {
+            self.clear_matching(
+                |setting| matches!(setting, SettingValue::Flag(flag) if flag.key() == key),
+            );
+        } else if !self.has_flag(key) {
+            let flag = FlagSetting::new(key, &self.user_config_path().join("openmw.cfg"));
+            self.push_setting(SettingValue::Flag(flag));
+        }
+    }
+
+    pub fn sub_configs(&self) -> impl Iterator<Item = &DirectorySetting> {
+        self.settings.iter().filter_map(|setting| match setting {
+            SettingValue::SubConfiguration(subconfig) => Some(subconfig),
+            _ => None,
+        })
+    }
+
+    /// Fallback entries are k/v pairs baked into the value side of k/v pairs in `fallback=` entries of openmw.cfg
+    /// They are used to express settings which are defined in Morrowind.ini for things such as:
+    /// weather, lighting behaviors, UI Colors, and levelup messages
+    pub fn game_settings(&self) -> impl Iterator<Item = &GameSettingType> {
+        let mut unique_settings = Vec::new();
+        let mut seen = HashSet::new();
+
+        for setting in self.settings.iter().rev() {
+            if let SettingValue::GameSetting(gs) = setting
+                && seen.insert(gs.to_string())
+            {
+                unique_settings.push(gs);
+            }
+        }
+
+        unique_settings.into_iter()
+    }
+
+    /// Fallbacks whose key starts with `{section}_`, such as every `Weather_*` entry
+    pub fn game_settings_in_section<'a>(
+        &'a self,
+        section: &'a str,
+    ) -> impl Iterator<Item = &'a GameSettingType> {
+        self.game_settings()
+            .filter(move |setting| setting.section() == Some(section))
+    }
+
+    /// Every fallback section in use, sorted and without repeats
+    pub fn sections(&self) -> Vec<&str> {
+        let mut sections: Vec<&str> = self
+            .game_settings()
+            .filter_map(GameSettingType::section)
+            .collect();
+
+        sections.sort_unstable();
+        sections.dedup();
+        sections
+    }
+
+    /// Changes the effective definition of a fallback in place, so its comment and source config are kept.
+    /// The new value has to fit the type the entry was loaded as.
+    pub fn set_game_setting_value(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let Some(setting) =
+            self.settings_mut()
+                .iter_mut()
+                .rev()
+                .find_map(|setting| match setting {
+                    SettingValue::GameSetting(game_setting) if game_setting.key() == key => {
+                        Some(game_setting)
+                    }
+                    _ => None,
+                })
+        else {
+            bail_config!(unknown_game_setting, key)
+        };
+
+        setting.set_value(value)?;
+
+        let setting = self
+            .get_game_setting(key)
+            .map(|setting| SettingValue::GameSetting(setting.clone()));
+        if let Some(setting) = &setting {
+            self.notify(ConfigNotification::SettingChanged(setting));
+        }
+
+        Ok(())
     }
 
     /// Retrieves a gamesetting according to its name.
@@ -681,6 +1393,42 @@ impl OpenMWConfiguration {
         None
     }
 
+    /// Looks up a fallback and converts it in one step.
+    /// `Ok(None)` means the key isn't set at all, while an entry of some other type is an error.
+    fn typed_game_setting<T>(
+        &self,
+        key: &str,
+        requested: &'static str,
+        convert: impl Fn(&GameSettingType) -> Option<T>,
+    ) -> Result<Option<T>, ConfigError> {
+        let Some(setting) = self.get_game_setting(key) else {
+            return Ok(None);
+        };
+
+        match convert(setting) {
+            Some(value) => Ok(Some(value)),
+            None => bail_config!(game_setting_wrong_type, key, setting.type_name(), requested),
+        }
+    }
+
+    pub fn game_setting_int(&self, key: &str) -> Result<Option<i64>, ConfigError> {
+        self.typed_game_setting(key, "int", GameSettingType::as_int)
+    }
+
+    /// Integer values are accepted as well
+    pub fn game_setting_float(&self, key: &str) -> Result<Option<f64>, ConfigError> {
+        self.typed_game_setting(key, "float", GameSettingType::as_float)
+    }
+
+    pub fn game_setting_color(&self, key: &str) -> Result<Option<ColorValue>, ConfigError> {
+        self.typed_game_setting(key, "color", GameSettingType::as_color)
+    }
+
+    /// Every fallback is a valid string, so this never fails; numbers and colors come back as written
+    pub fn game_setting_string(&self, key: &str) -> Option<String> {
+        self.get_game_setting(key).map(GameSettingType::value)
+    }
+
     /// Data directories are the bulk of an OpenMW Configuration's contents,
     /// Composing the list of files from which a VFS is constructed.
     /// For a VFS implementation, see: https://github.com/magicaldave/vfstool/tree/main/vfstool_lib
@@ -702,162 +1450,187 @@ impl OpenMWConfiguration {
         })
     }
 
-    fn load(&mut self, config_dir: &Path) -> Result<(), ConfigError> {
-        util::debug_log(format!("BEGIN CONFIG PARSING: {config_dir:?}"));
+    /// Applies the duplicate policy to a list entry about to be loaded, returning whether it should be added
+    fn admit_entry(
+        &mut self,
+        policy: DuplicatePolicy,
+        options: &ParseOptions,
+        is_repeat: impl Fn(&SettingValue) -> bool,
+        repeat_error: impl FnOnce() -> ConfigError,
+    ) -> Result<bool, ConfigError> {
+        if !self.settings.iter().any(&is_repeat) {
+            return Ok(true);
+        }
+
+        match policy {
+            DuplicatePolicy::Error => Err(repeat_error()),
+            DuplicatePolicy::Warn => {
+                options.warn(&repeat_error());
+                Ok(true)
+            }
+            DuplicatePolicy::KeepFirst => Ok(false),
+            DuplicatePolicy::KeepLast => {
+                self.clear_matching(is_repeat);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Whatever gets loaded comes straight from disk, so it never counts as a change.
+    /// Files which end up including themselves, or chains nested deeper than `MAX_CONFIG_DEPTH`, are refused.
+    fn load(&mut self, config_dir: &Path, options: &ParseOptions) -> Result<(), ConfigError> {
+        let cfg_file = match self.fs.is_dir(config_dir) {
+            true => config_dir.join("openmw.cfg"),
+            false => config_dir.to_path_buf(),
+        };
+
+        let canonical = |path: &Path| self.fs.canonicalize(path).unwrap_or(path.to_path_buf());
+        let this_file = canonical(&cfg_file);
+
+        if let Some(start) = self
+            .load_stack
+            .iter()
+            .position(|loading| canonical(loading) == this_file)
+        {
+            let mut chain = self.load_stack[start..].to_vec();
+            chain.push(cfg_file);
+            bail_config!(config_cycle, chain);
+        } else if self.load_stack.len() >= MAX_CONFIG_DEPTH {
+            bail_config!(config_chain_too_deep, MAX_CONFIG_DEPTH, cfg_file);
+        }
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("openmw_cfg", path = %cfg_file.display()).entered();
 
-        if !config_dir.exists() {
+        self.load_stack.push(cfg_file);
+        let dirty = self.dirty.take();
+
+        let result = self.load_file(config_dir, options);
+
+        self.dirty.restore(dirty);
+        self.load_stack.pop();
+
+        if self.load_stack.is_empty() {
+            self.documents.finish(&self.settings);
+        }
+
+        #[cfg(feature = "parallel")]
+        if self.load_stack.is_empty() {
+            self.prefetched.clear();
+        }
+
+        result
+    }
+
+    fn load_file(&mut self, config_dir: &Path, options: &ParseOptions) -> Result<(), ConfigError> {
+        util::debug_log(format_args!("BEGIN CONFIG PARSING: {config_dir:?}"));
+
+        if !self.fs.exists(config_dir) {
             bail_config!(cannot_find, config_dir);
         }
 
-        let cfg_file_path = match config_dir.is_dir() {
+        let cfg_file_path = match self.fs.is_dir(config_dir) {
             true => config_dir.join("openmw.cfg"),
             false => config_dir.to_path_buf(),
         };
 
-        let lines = read_to_string(&cfg_file_path)?;
+        let lines = self.config_text(&cfg_file_path, options)?;
+        let mut document = Document::new(&lines);
 
         let mut queued_comment = String::new();
         let mut sub_configs: Vec<(String, String)> = Vec::new();
 
-        for line in lines.lines() {
+        let mut sub_config_lines = Vec::new();
+
+        for (index, line) in lines.lines().enumerate() {
+            let line_number = index + 1;
             let trimmed = line.trim();
+            let disabled = trimmed
+                .strip_prefix(comments::DISABLED_MARKER)
+                .map(str::trim_start)
+                .filter(|rest| disabled::can_disable_line(rest));
 
             if trimmed.is_empty() {
                 queued_comment.push('\n');
+                document.push(DocumentLine::Blank(line.to_string()));
+                continue;
+            } else if let Some(version) = trimmed.strip_prefix(comments::SERIALIZER_FOOTER) {
+                // Not kept as a comment, otherwise every save would stack another footer
+                self.serializer_info.push(SerializerInfo {
+                    config: cfg_file_path.clone(),
+                    version: version.trim().to_string(),
+                });
+                document.push(DocumentLine::Other(line.to_string()));
                 continue;
-            } else if trimmed.starts_with('#') {
+            } else if trimmed.starts_with('#') && disabled.is_none() {
                 queued_comment.push_str(line);
                 queued_comment.push('\n');
+                document.push(DocumentLine::Comment(line.to_string()));
                 continue;
             }
 
-            let tokens: Vec<&str> = trimmed.splitn(2, '=').collect();
-            if tokens.len() < 2 {
-                bail_config!(invalid_line, trimmed.into(), config_dir.to_path_buf());
-            }
-
-            let key = tokens[0].trim();
-            let value = tokens[1].trim().to_string();
-
-            match key {
-                "content" => {
-                    self.settings.iter().try_for_each(|setting| match setting {
-                        SettingValue::ContentFile(plugin) => {
-                            if *plugin == &value {
-                                bail_config!(duplicate_content_file, value.to_owned(), config_dir)
-                            } else {
-                                Ok(())
-                            }
-                        }
-                        _ => Ok(()),
-                    })?;
-
-                    self.settings
-                        .push(SettingValue::ContentFile(FileSetting::new(
-                            &value,
-                            &config_dir,
-                            &mut queued_comment,
-                        )));
-                }
-                "groundcover" => {
-                    self.settings.iter().try_for_each(|setting| match setting {
-                        SettingValue::Groundcover(plugin) => {
-                            if *plugin == &value {
-                                bail_config!(
-                                    duplicate_groundcover_file,
-                                    value.to_owned(),
-                                    config_dir
-                                )
-                            } else {
-                                Ok(())
-                            }
-                        }
-                        _ => Ok(()),
-                    })?;
-
-                    self.settings
-                        .push(SettingValue::Groundcover(FileSetting::new(
-                            &value,
-                            &config_dir,
-                            &mut queued_comment,
-                        )));
-                }
-                "fallback-archive" => {
-                    self.settings.iter().try_for_each(|setting| match setting {
-                        SettingValue::BethArchive(archive) => {
-                            if *archive == &value {
-                                bail_config!(duplicate_archive_file, value.to_owned(), config_dir)
-                            } else {
-                                Ok(())
-                            }
-                        }
-                        _ => Ok(()),
-                    })?;
-
-                    self.settings
-                        .push(SettingValue::BethArchive(FileSetting::new(
-                            &value,
-                            &config_dir,
-                            &mut queued_comment,
-                        )));
-                }
-                "fallback" => {
-                    self.set_game_setting(
-                        &value,
-                        Some(config_dir.to_owned()),
-                        &mut queued_comment,
-                    )?;
-                }
-                "encoding" => self.set_encoding(Some(EncodingSetting::try_from((
-                    value,
+            let (trimmed, inline_comment) = match options.inline_comments {
+                true => strings::split_inline_comment(disabled.unwrap_or(trimmed)),
+                false => (disabled.unwrap_or(trimmed), None),
+            };
+
+            let first_new = SettingHandle::upcoming();
+            let settings_before = self.settings.len();
+
+            let parsed = match disabled {
+                Some(_) => self.parse_disabled_line(trimmed, config_dir, &mut queued_comment),
+                None => self.parse_line(
+                    trimmed,
                     config_dir,
                     &mut queued_comment,
-                ))?)),
-                "config" => {
-                    sub_configs.push((value, std::mem::take(&mut queued_comment)));
-                }
-                "data" => {
-                    insert_dir_setting!(
-                        self,
-                        DataDirectory,
-                        &value,
-                        &config_dir,
-                        &mut queued_comment
-                    )
-                }
-                "resources" => {
-                    insert_dir_setting!(self, Resources, &value, &config_dir, &mut queued_comment)
-                }
-                "user-data" => {
-                    insert_dir_setting!(self, UserData, &value, &config_dir, &mut queued_comment)
-                }
-                "data-local" => {
-                    insert_dir_setting!(self, DataLocal, &value, &config_dir, &mut queued_comment)
-                }
-                "replace" => match value.to_lowercase().as_str() {
-                    "content" => self.set_content_files(None),
-                    "data" => self.set_data_directories(None),
-                    "fallback" => self.set_game_settings(None)?,
-                    "fallback-archives" => self.set_fallback_archives(None),
-                    "data-local" => self.set_data_local(None),
-                    "resources" => self.set_resources(None),
-                    "user-data" => self.set_userdata(None),
-                    "config" => {
-                        self.settings.clear();
-                    }
-                    _ => {
-                        // eprintln!("Warning: Unrecognized replacement option: {value}")
-                    }
+                    &mut sub_configs,
+                    options,
+                ),
+            };
+            if let Err(error) = parsed {
+                options.recover(error.at_line(line_number))?;
+            }
+
+            // A line creates one setting at most, either pushed onto the end or replacing a singleton in place
+            let created = match self.settings.len() > settings_before {
+                true => self.settings_mut().last_mut(),
+                false => self
+                    .settings_mut()
+                    .iter_mut()
+                    .rev()
+                    .find(|setting| setting.handle() >= first_new),
+            };
+            let created = created.filter(|setting| setting.handle() >= first_new);
+            document.push(match &created {
+                Some(setting) => DocumentLine::Setting {
+                    handle: setting.handle(),
+                    raw: line.to_string(),
+                    rendered: String::new(),
                 },
-                _ => {
-                    let setting = GenericSetting::new(key, &value, config_dir, &mut queued_comment);
-                    self.settings.push(SettingValue::Generic(setting));
+                None => DocumentLine::Other(line.to_string()),
+            });
+
+            if let Some(setting) = created {
+                setting.meta_mut().line = Some(line_number);
+                setting.meta_mut().origin = SettingOrigin::File;
+                if let Some(inline_comment) = inline_comment {
+                    setting.meta_mut().set_inline_comment(inline_comment);
                 }
+
+                #[cfg(feature = "tracing")]
+                tracing::trace!(line = line_number, setting = %setting.to_string().trim_end());
+            }
+
+            if sub_configs.len() > sub_config_lines.len() {
+                sub_config_lines.push((line_number, inline_comment.unwrap_or_default()));
             }
         }
 
+        let document_path = cfg_file_path.clone();
+        self.documents.insert(cfg_file_path, document);
+
         // This shit with file/directory is very hard to keep track of and should be refactored post-release, but for now it isn't important
-        let cfg_file_path = match config_dir.is_dir() {
+        let cfg_file_path = match self.fs.is_dir(config_dir) {
             true => config_dir,
             false => config_dir
                 .parent()
@@ -865,18 +1638,54 @@ impl OpenMWConfiguration {
         }
         .to_path_buf();
 
-        sub_configs.into_iter().try_for_each(
-            |(subconfig_path, mut subconfig_comment): (String, String)| {
+        #[cfg(feature = "parallel")]
+        if options.parallel {
+            self.prefetch(
+                sub_configs
+                    .iter()
+                    .map(|(path, _)| {
+                        strings::parse_data_directory(&cfg_file_path, path.clone())
+                            .join("openmw.cfg")
+                    })
+                    .collect(),
+            );
+        }
+
+        sub_configs.into_iter().zip(sub_config_lines).try_for_each(
+            |((subconfig_path, mut subconfig_comment), (line_number, inline_comment)): ((String, String), (usize, &str))| {
                 let mut comment = std::mem::take(&mut subconfig_comment);
 
-                let setting: DirectorySetting = DirectorySetting::new(subconfig_path.clone(), cfg_file_path.clone(), &mut comment);
+                let mut setting: DirectorySetting = DirectorySetting::with_queued_comment(subconfig_path.clone(), cfg_file_path.clone(), &mut comment);
+                setting.meta.line = Some(line_number);
+                setting.meta.origin = SettingOrigin::File;
+                setting.meta.set_inline_comment(inline_comment);
                 let subconfig_path = setting.parsed().join("openmw.cfg");
 
-                if std::fs::metadata(&subconfig_path).is_ok() {
-                    self.settings.push(SettingValue::SubConfiguration(setting));
-                    self.load(Path::new(&subconfig_path))
+                if self.single_file {
+                    if let Some(document) = self.documents.get_mut(&document_path) {
+                        document.attach(line_number - 1, setting.meta.handle);
+                    }
+                    self.push_setting(SettingValue::SubConfiguration(setting));
+
+                    Ok(())
+                } else if self.fs.exists(&subconfig_path) {
+                    if let Some(document) = self.documents.get_mut(&document_path) {
+                        document.attach(line_number - 1, setting.meta.handle);
+                    }
+                    self.push_setting(SettingValue::SubConfiguration(setting));
+
+                    match self.load(Path::new(&subconfig_path), options) {
+                        Err(
+                            error @ (ConfigError::ConfigCycle { .. }
+                            | ConfigError::ConfigChainTooDeep { .. }),
+                        ) => options.recover(error),
+                        result => result,
+                    }
                 } else {
-                    util::debug_log(format!(
+                    if let Some(cache) = &mut self.cache {
+                        cache.missing(&subconfig_path);
+                    }
+                    util::debug_log(format_args!(
                         "Skipping parsing of {} As this directory does not actually contain an openmw.cfg!",
                         cfg_file_path.display(),
                     ));
@@ -889,22 +1698,282 @@ impl OpenMWConfiguration {
         Ok(())
     }
 
+    /// The decoded contents of one openmw.cfg, straight from `load_cached`'s cache if it's still current
+    fn config_text(&mut self, path: &Path, options: &ParseOptions) -> Result<String, ConfigError> {
+        let fs = self.fs.clone();
+
+        if let Some(text) = self.cache.as_mut().and_then(|cache| cache.hit(&*fs, path)) {
+            return Ok(text);
+        }
+
+        let stamp = self.cache.as_ref().and(cache::FileStamp::of(&*fs, path));
+
+        #[cfg(feature = "parallel")]
+        let bytes = self
+            .take_prefetched(path)
+            .unwrap_or_else(|| fs.read(path))?;
+        #[cfg(not(feature = "parallel"))]
+        let bytes = fs.read(path)?;
+        let text = self.read_config_file(path, bytes, &options.encoding)?;
+
+        if let Some(cache) = &mut self.cache {
+            cache.store(path, stamp, &text);
+        }
+
+        Ok(text)
+    }
+
+    fn read_config_file(
+        &self,
+        path: &Path,
+        bytes: Vec<u8>,
+        encoding: &FileEncoding,
+    ) -> Result<String, ConfigError> {
+        let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+
+        #[cfg(feature = "encoding")]
+        if let FileEncoding::Forced(forced) = encoding {
+            return Ok(forced
+                .codec()
+                .decode_without_bom_handling(bytes)
+                .0
+                .into_owned());
+        }
+
+        let invalid = match std::str::from_utf8(bytes) {
+            Ok(text) => return Ok(text.to_string()),
+            Err(invalid) => invalid,
+        };
+
+        match encoding {
+            FileEncoding::Utf8Lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            #[cfg(feature = "encoding")]
+            FileEncoding::Detect => {
+                let legacy = self
+                    .encoding()
+                    .map(|setting| setting.encoding_type().codec())
+                    .unwrap_or(encoding_rs::WINDOWS_1252);
+
+                Ok(legacy.decode_without_bom_handling(bytes).0.into_owned())
+            }
+            _ => {
+                let line = bytes[..invalid.valid_up_to()]
+                    .iter()
+                    .filter(|byte| **byte == b'\n')
+                    .count()
+                    + 1;
+
+                bail_config!(invalid_utf8, path, line)
+            }
+        }
+    }
+
+    /// Applies a single non-comment line of an openmw.cfg.
+    /// `config=` lines are only queued, since sub-configurations load after their parent has finished
+    fn parse_line(
+        &mut self,
+        trimmed: &str,
+        config_dir: &Path,
+        queued_comment: &mut String,
+        sub_configs: &mut Vec<(String, String)>,
+        options: &ParseOptions,
+    ) -> Result<(), ConfigError> {
+        let Some((key, value)) = trimmed.split_once('=') else {
+            self.push_setting(SettingValue::Flag(FlagSetting::with_queued_comment(
+                trimmed,
+                config_dir,
+                queued_comment,
+            )));
+            return Ok(());
+        };
+
+        let key = key.trim();
+        let value = value.trim().to_string();
+
+        if key.is_empty() {
+            bail_config!(invalid_line, trimmed.into(), config_dir.to_path_buf());
+        }
+
+        match key {
+            "content" => {
+                let is_repeat = |setting: &SettingValue| match setting {
+                    SettingValue::ContentFile(plugin) => {
+                        plugin.is_named(&value, options.ignore_case)
+                    }
+                    _ => false,
+                };
+                let repeat_error =
+                    || config_err!(duplicate_content_file, value.to_owned(), config_dir);
+
+                if self.admit_entry(options.duplicate_content, options, is_repeat, repeat_error)? {
+                    self.push_setting(SettingValue::ContentFile(FileSetting::with_queued_comment(
+                        &value,
+                        config_dir,
+                        queued_comment,
+                    )));
+                }
+            }
+            "groundcover" => {
+                let is_repeat = |setting: &SettingValue| match setting {
+                    SettingValue::Groundcover(plugin) => {
+                        plugin.is_named(&value, options.ignore_case)
+                    }
+                    _ => false,
+                };
+                let repeat_error =
+                    || config_err!(duplicate_groundcover_file, value.to_owned(), config_dir);
+
+                if self.admit_entry(
+                    options.duplicate_groundcover,
+                    options,
+                    is_repeat,
+                    repeat_error,
+                )? {
+                    self.push_setting(SettingValue::Groundcover(FileSetting::with_queued_comment(
+                        &value,
+                        config_dir,
+                        queued_comment,
+                    )));
+                }
+            }
+            "fallback-archive" => {
+                let is_repeat = |setting: &SettingValue| match setting {
+                    SettingValue::BethArchive(archive) => {
+                        archive.is_named(&value, options.ignore_case)
+                    }
+                    _ => false,
+                };
+                let repeat_error =
+                    || config_err!(duplicate_archive_file, value.to_owned(), config_dir);
+
+                if self.admit_entry(options.duplicate_archives, options, is_repeat, repeat_error)? {
+                    self.push_setting(SettingValue::BethArchive(FileSetting::with_queued_comment(
+                        &value,
+                        config_dir,
+                        queued_comment,
+                    )));
+                }
+            }
+            "fallback" => {
+                self.set_game_setting(&value, Some(config_dir.to_owned()), queued_comment)?;
+            }
+            "encoding" => self.set_encoding(Some(EncodingSetting::try_from((
+                value,
+                config_dir,
+                queued_comment,
+            ))?)),
+            "config" => {
+                sub_configs.push((value, std::mem::take(queued_comment)));
+            }
+            "data" => {
+                insert_dir_setting!(self, DataDirectory, &value, &config_dir, queued_comment)
+            }
+            "resources" => {
+                insert_dir_setting!(self, Resources, &value, &config_dir, queued_comment)
+            }
+            "user-data" => {
+                insert_dir_setting!(self, UserData, &value, &config_dir, queued_comment)
+            }
+            "data-local" => {
+                insert_dir_setting!(self, DataLocal, &value, &config_dir, queued_comment)
+            }
+            "replace" => self.apply_replace(&value)?,
+            _ if value.is_empty() => {
+                let setting =
+                    FlagSetting::with_queued_comment(key, config_dir, queued_comment).with_equals();
+                self.push_setting(SettingValue::Flag(setting));
+            }
+            _ => {
+                let setting =
+                    GenericSetting::with_queued_comment(key, &value, config_dir, queued_comment);
+                self.push_setting(SettingValue::Generic(setting));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The entry on an `#omwcfg:disabled` line, which goes into the list as disabled.
+    /// Unlike enabled entries, repeats aren't checked, since they don't load.
+    fn parse_disabled_line(
+        &mut self,
+        trimmed: &str,
+        config_dir: &Path,
+        queued_comment: &mut String,
+    ) -> Result<(), ConfigError> {
+        let Some((key, value)) = trimmed.split_once('=') else {
+            return Ok(());
+        };
+        let value = value.trim();
+
+        let setting = match key.trim() {
+            "content" => SettingValue::ContentFile(FileSetting::with_queued_comment(
+                value,
+                config_dir,
+                queued_comment,
+            )),
+            "groundcover" => SettingValue::Groundcover(FileSetting::with_queued_comment(
+                value,
+                config_dir,
+                queued_comment,
+            )),
+            "fallback-archive" => SettingValue::BethArchive(FileSetting::with_queued_comment(
+                value,
+                config_dir,
+                queued_comment,
+            )),
+            "data" => {
+                let mut dir = dir_setting!(self, value, &config_dir, queued_comment);
+                if self.resolve_symlinks {
+                    dir.resolve_symlinks(&*self.fs);
+                }
+                SettingValue::DataDirectory(dir)
+            }
+            _ => return Ok(()),
+        };
+
+        self.push_setting(SettingValue::Disabled(Box::new(setting)));
+        Ok(())
+    }
+
+    /// Handles the value side of a `replace=` line, dropping everything which came before it
+    fn apply_replace(&mut self, value: &str) -> Result<(), ConfigError> {
+        match value.to_lowercase().as_str() {
+            "content" => self.set_content_files(None),
+            "data" => self.set_data_directories(None),
+            "fallback" => self.set_game_settings(None)?,
+            "fallback-archives" => self.set_fallback_archives(None),
+            "data-local" => self.set_data_local(None),
+            "resources" => self.set_resources(None),
+            "user-data" => self.set_userdata(None),
+            "config" => self.clear_matching(|_| true),
+            _ => {
+                // eprintln!("Warning: Unrecognized replacement option: {value}")
+            }
+        }
+
+        Ok(())
+    }
+
     fn write_config<P: AsRef<Path> + std::fmt::Debug>(
         &self,
         config_string: String,
         path: &P,
     ) -> Result<(), String> {
-        use std::io::Write;
+        if self.read_only {
+            return Err(config_err!(read_only, path.as_ref()).to_string());
+        }
 
-        let mut file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(&path)
-            .map_err(|e| format!("Failed to open {:?} for writing: {}", path, e))?;
+        self.fs
+            .write(path.as_ref(), config_string.as_bytes())
+            .map_err(|e| format!("Failed to write to {:?}: {}", path, e))
+    }
 
-        file.write_all(config_string.as_bytes())
-            .map_err(|e| format!("Failed to write to {:?}: {}", path, e))?;
+    /// Writes every setting which came from `cfg_path` back to it
+    fn write_source(&self, cfg_path: &Path) -> Result<(), String> {
+        self.write_config(self.source_text(cfg_path), &cfg_path)?;
+        self.dirty.clear(cfg_path);
+        self.notify(ConfigNotification::Saved(cfg_path));
 
         Ok(())
     }
@@ -918,26 +1987,19 @@ impl OpenMWConfiguration {
         let target_dir = self.user_config_path();
 
         // Check if target_dir is a writable directory
-        if !target_dir.is_dir() {
+        if !self.fs.is_dir(&target_dir) {
             return Err(format!("Target path {:?} is not a directory.", target_dir));
         }
 
         // Try to open a file for writing to check writability
-        if !util::can_write_to_dir(&target_dir) {
+        if !self.fs.can_write_to_dir(&target_dir) {
             return Err(format!("Directory {:?} is not writable!", target_dir));
         };
 
         // Write the config to openmw.cfg in the target directory
         let cfg_path = target_dir.join("openmw.cfg");
 
-        let mut user_settings_string = String::new();
-
-        self.settings_matching(|setting| setting.meta().source_config == cfg_path)
-            .for_each(|user_setting| user_settings_string.push_str(&user_setting.to_string()));
-
-        self.write_config(user_settings_string, &cfg_path)?;
-
-        Ok(())
+        self.write_source(&cfg_path)
     }
 
     /// Save the openmw.cfg to an arbitrary path, instead of the (safe) user configuration.
@@ -945,17 +2007,14 @@ impl OpenMWConfiguration {
     /// So you should exercise caution when writing an openmw.cfg and be very sure you know it is going where you think it is.
     pub fn save_subconfig(&self, target_dir: PathBuf) -> Result<(), String> {
         // Check if target_dir is a writable directory
-        if !target_dir.is_dir() {
+        if !self.fs.is_dir(&target_dir) {
             return Err(format!("Target path {:?} is not a directory.", target_dir));
-        } else if !util::can_write_to_dir(&target_dir) {
+        } else if !self.fs.can_write_to_dir(&target_dir) {
             return Err(format!("Directory {:?} is not writable!", target_dir));
         };
 
         let subconfig_is_loaded = self.settings.iter().any(|setting| match setting {
-            SettingValue::SubConfiguration(subconfig) => {
-                subconfig.parsed() == &target_dir
-                    || subconfig.original() == &target_dir.to_string_lossy().to_string()
-            }
+            SettingValue::SubConfiguration(subconfig) => subconfig.refers_to(&target_dir),
             _ => false,
         });
 
@@ -968,16 +2027,7 @@ impl OpenMWConfiguration {
 
         let cfg_path = target_dir.join("openmw.cfg");
 
-        let mut subconfig_settings_string = String::new();
-
-        self.settings_matching(|setting| setting.meta().source_config == cfg_path)
-            .for_each(|subconfig_setting| {
-                subconfig_settings_string.push_str(&subconfig_setting.to_string())
-            });
-
-        self.write_config(subconfig_settings_string, &cfg_path)?;
-
-        Ok(())
+        self.write_source(&cfg_path)
     }
 }
 
@@ -994,16 +2044,774 @@ impl OpenMWConfiguration {
 /// Comments are also preserved.
 impl fmt::Display for OpenMWConfiguration {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.settings
-            .iter()
-            .try_for_each(|setting| write!(f, "{}", setting))?;
+        f.write_str(&self.render(&render::RenderOptions::default()))
+    }
+}
 
-        writeln!(
-            f,
-            "# OpenMW-Config Serializer Version: {}",
-            std::env::var("CARGO_PKG_VERSON").expect("")
-        )?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::temp_config;
+
+    #[test]
+    fn test_remove_setting_by_handle_leaves_duplicates() {
+        let dir = temp_config("remove_by_handle", "data=Mods\ndata=Mods\ndata=Other\n");
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+
+        let handles: Vec<SettingHandle> = config
+            .settings_with_handles()
+            .filter(|(_, setting)| matches!(setting, SettingValue::DataDirectory(_)))
+            .map(|(handle, _)| handle)
+            .collect();
+
+        assert_eq!(handles.len(), 3);
+
+        let removed = config.remove_setting(&handles[1]).unwrap();
+        assert_eq!(removed.handle(), handles[1]);
+        assert!(config.get_setting(&handles[1]).is_none());
+        assert!(config.remove_setting(&handles[1]).is_none());
+
+        assert_eq!(
+            config.data_directories(),
+            vec![&dir.join("Mods"), &dir.join("Other")]
+        );
+    }
 
-        Ok(())
+    #[test]
+    fn test_update_setting_by_handle() {
+        let dir = temp_config(
+            "update_by_handle",
+            "content=Morrowind.esm\ncontent=Tribunal.esm\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let tribunal = config.content_files_iter().nth(1).unwrap().handle();
+
+        config.add_content_file("Bloodmoon.esm").unwrap();
+        let updated = config
+            .update_setting(&tribunal, |setting| {
+                *setting = SettingValue::ContentFile(FileSetting::new(
+                    "Tribunal_Patched.esm",
+                    Path::new("openmw.cfg"),
+                ))
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.handle(), tribunal);
+
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal_Patched.esm", "Bloodmoon.esm"]
+        );
+        let morrowind = config.content_files_iter().next().unwrap().handle();
+        let rename = |name: &str| {
+            let name = name.to_string();
+            move |setting: &mut SettingValue| {
+                *setting =
+                    SettingValue::ContentFile(FileSetting::new(&name, Path::new("openmw.cfg")))
+            }
+        };
+        assert!(matches!(
+            config.update_setting(&morrowind, rename("Bloodmoon.esm")),
+            Err(ConfigError::DuplicateContentFile { .. })
+        ));
+        assert!(matches!(
+            config.update_setting(&morrowind, |setting| {
+                *setting = SettingValue::Groundcover(FileSetting::new(
+                    "Morrowind.esm",
+                    Path::new("openmw.cfg"),
+                ))
+            }),
+            Err(ConfigError::SettingKindChanged { .. })
+        ));
+        assert!(
+            config
+                .update_setting(&morrowind, rename("Morrowind.esm"))
+                .is_ok()
+        );
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal_Patched.esm", "Bloodmoon.esm"]
+        );
+
+        assert!(config.remove_setting(&tribunal).is_some());
+        assert!(config.update_setting(&tribunal, |_| ()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_load_order_survives_interleaved_mutations() {
+        let dir = temp_config(
+            "load_order",
+            "content=Morrowind.esm\ndata=Mods\ncontent=Tribunal.esm\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        config.add_content_file("Bloodmoon.esm").unwrap();
+        config.add_data_directory(PathBuf::from("/more/mods"));
+        config.insert_content_file(1, "Patch.esp").unwrap();
+        config.remove_content_file("Tribunal.esm");
+        config.add_content_file("Last.esp").unwrap();
+        config.insert_content_file(0, "First.esm").unwrap();
+        config.insert_content_file(99, "Appended.esp").unwrap();
+
+        let enumerated: Vec<(usize, &String)> = config
+            .content_files_enumerated()
+            .map(|(index, plugin)| (index, plugin.value()))
+            .collect();
+
+        assert_eq!(
+            enumerated,
+            vec![
+                (0, &"First.esm".to_string()),
+                (1, &"Morrowind.esm".to_string()),
+                (2, &"Patch.esp".to_string()),
+                (3, &"Bloodmoon.esm".to_string()),
+                (4, &"Last.esp".to_string()),
+                (5, &"Appended.esp".to_string()),
+            ]
+        );
+
+        assert!(config.insert_content_file(0, "Patch.esp").is_err());
+        assert_eq!(config.content_files().len(), 6);
+    }
+
+    #[test]
+    fn test_remove_data_directory_from_source() {
+        let child = temp_config("remove_from_child", "data=/shared/mods\n");
+        let root = temp_config(
+            "remove_from_root",
+            &format!("data=/shared/mods\nconfig={}\n", child.display()),
+        );
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        config
+            .remove_data_directory_from(&PathBuf::from("/shared/mods"), &child.join("openmw.cfg"));
+
+        let remaining: Vec<&DirectorySetting> = config.data_directories_iter().collect();
+        assert_eq!(remaining.len(), 1);
+        assert!(remaining[0].meta().is_from(&root));
+    }
+
+    #[test]
+    fn test_lenient_parsing_skips_broken_lines() {
+        let contents =
+            "content=Morrowind.esm\n=not a setting\ncontent=Morrowind.esm\ncontent=Tribunal.esm\n";
+        let dir = temp_config("lenient_parsing", contents);
+
+        assert!(OpenMWConfiguration::new(Some(dir.clone())).is_err());
+
+        let warnings = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = warnings.clone();
+        let options = ParseOptions::lenient()
+            .with_warning_handler(move |error| sink.borrow_mut().push(error.to_string()));
+
+        let config = OpenMWConfiguration::new_with_options(Some(dir), options).unwrap();
+
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal.esm"]
+        );
+        assert_eq!(warnings.borrow().len(), 2);
+        assert!(warnings.borrow()[0].contains("not a setting"));
+    }
+
+    #[test]
+    fn test_case_insensitive_lookups() {
+        let dir = temp_config(
+            "case_insensitive",
+            "content=Morrowind.esm\ncontent=ÄRGERNIS.esp\nfallback-archive=Morrowind.bsa\n",
+        );
+
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert!(!config.has_content_file("morrowind.esm"));
+        assert!(config.add_content_file("MORROWIND.ESM").is_ok());
+
+        let mut config = OpenMWConfiguration::new_with_options(
+            Some(dir.clone()),
+            ParseOptions::strict().ignoring_case(),
+        )
+        .unwrap();
+        assert!(config.has_content_file("morrowind.esm"));
+        assert!(config.has_content_file("ärgernis.ESP"));
+        assert!(config.has_archive_file("MORROWIND.BSA"));
+        assert!(config.add_content_file("MORROWIND.ESM").is_err());
+
+        config.remove_content_file("ärgernis.esp");
+        assert_eq!(config.content_files(), vec!["Morrowind.esm"]);
+
+        std::fs::write(
+            dir.join("openmw.cfg"),
+            "content=Morrowind.esm\ncontent=morrowind.ESM\n",
+        )
+        .unwrap();
+        assert!(OpenMWConfiguration::new(Some(dir.clone())).is_ok());
+        assert!(
+            OpenMWConfiguration::new_with_options(
+                Some(dir.clone()),
+                ParseOptions::strict().ignoring_case()
+            )
+            .is_err()
+        );
+
+        let options = ParseOptions::strict().allowing_duplicates().ignoring_case();
+        let config = OpenMWConfiguration::new_with_options(Some(dir), options).unwrap();
+        assert_eq!(config.duplicates().len(), 1);
+        assert_eq!(config.duplicates()[0].name, "morrowind.ESM");
+    }
+
+    #[test]
+    fn test_duplicate_policies() {
+        let dir = temp_config(
+            "duplicate_policies",
+            "content=Morrowind.esm\ncontent=Mod.esp\ncontent=Morrowind.esm\ngroundcover=Grass.esp\ngroundcover=Grass.esp\n",
+        );
+        let load = |options: ParseOptions| {
+            OpenMWConfiguration::new_with_options(Some(dir.clone()), options)
+        };
+
+        assert!(matches!(
+            load(ParseOptions::strict().with_groundcover_duplicates(DuplicatePolicy::KeepFirst)),
+            Err(ConfigError::DuplicateContentFile { .. })
+        ));
+
+        let config = load(
+            ParseOptions::strict()
+                .with_content_duplicates(DuplicatePolicy::KeepLast)
+                .with_groundcover_duplicates(DuplicatePolicy::KeepFirst),
+        )
+        .unwrap();
+        assert_eq!(config.content_files(), vec!["Mod.esp", "Morrowind.esm"]);
+        assert_eq!(config.groundcover_iter().count(), 1);
+
+        let config =
+            load(ParseOptions::strict().with_duplicate_policy(DuplicatePolicy::KeepFirst)).unwrap();
+        assert_eq!(config.content_files(), vec!["Morrowind.esm", "Mod.esp"]);
+
+        let warnings = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = warnings.clone();
+        let config = load(
+            ParseOptions::strict()
+                .with_duplicate_policy(DuplicatePolicy::Warn)
+                .with_warning_handler(move |_| counter.set(counter.get() + 1)),
+        )
+        .unwrap();
+        assert_eq!(config.content_files().len(), 3);
+        assert_eq!(warnings.get(), 2);
+    }
+
+    #[test]
+    fn test_system_defaults_prefer_local_config() {
+        let user = temp_config("system_defaults_user", "content=Tribunal.esm\n");
+        let local = temp_config(
+            "system_defaults_local",
+            &format!("content=Morrowind.esm\nconfig={}\n", user.display()),
+        );
+
+        let config = OpenMWConfiguration::from_system_defaults_in(Some(&local)).unwrap();
+        assert_eq!(config.root_config_file(), &local.join("openmw.cfg"));
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal.esm"]
+        );
+        assert_eq!(config.user_config_path(), user);
+    }
+
+    #[test]
+    fn test_invalid_utf8_reports_line() {
+        let dir = temp_config("invalid_utf8", "");
+        std::fs::write(
+            dir.join("openmw.cfg"),
+            b"content=Morrowind.esm\ncontent=Caf\xE9.esp\n",
+        )
+        .unwrap();
+
+        match OpenMWConfiguration::new(Some(dir.clone())) {
+            Err(ConfigError::InvalidUtf8 { line, .. }) => assert_eq!(line, 2),
+            other => panic!("expected InvalidUtf8, got {other:?}"),
+        }
+
+        let options = ParseOptions::strict().with_encoding(FileEncoding::Utf8Lossy);
+        let config = OpenMWConfiguration::new_with_options(Some(dir.clone()), options).unwrap();
+        assert_eq!(config.content_files()[1], "Caf\u{FFFD}.esp");
+
+        #[cfg(feature = "encoding")]
+        {
+            let options = ParseOptions::strict().with_encoding(FileEncoding::Detect);
+            let config = OpenMWConfiguration::new_with_options(Some(dir), options).unwrap();
+            assert_eq!(config.content_files()[1], "Café.esp");
+        }
+    }
+
+    #[test]
+    fn test_flags_round_trip() {
+        let dir = temp_config(
+            "flags",
+            "no case folding\nskip-intro=\ncontent=Morrowind.esm\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        assert!(config.has_flag("no case folding"));
+        assert!(config.has_flag("skip-intro"));
+
+        let written: Vec<String> = config.flags().map(ToString::to_string).collect();
+        assert_eq!(written, vec!["no case folding", "skip-intro="]);
+
+        config.set_flag("skip-intro", false);
+        config.set_flag("strict", true);
+        assert_eq!(
+            config
+                .flags()
+                .map(|flag| flag.key().as_str())
+                .collect::<Vec<_>>(),
+            vec!["no case folding", "strict"]
+        );
+    }
+
+    #[test]
+    fn test_generic_settings() {
+        let dir = temp_config(
+            "generic",
+            "script-blacklist=a\nlua-debug=false\nscript-blacklist=b\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+
+        assert_eq!(config.get_generic("script-blacklist"), vec!["a", "b"]);
+
+        config.append_generic("script-blacklist", "c");
+        assert_eq!(
+            config
+                .remove_generic_at("script-blacklist", 0)
+                .unwrap()
+                .value(),
+            "a"
+        );
+        assert!(config.remove_generic_at("script-blacklist", 2).is_none());
+        assert_eq!(
+            config
+                .generic_values("script-blacklist")
+                .map(|generic| generic.value().as_str())
+                .collect::<Vec<_>>(),
+            vec!["b", "c"]
+        );
+
+        config.set_generic("lua-debug", Some(vec!["true".into()]));
+        config.set_generic("script-blacklist", None);
+
+        assert_eq!(config.get_generic("lua-debug"), vec!["true"]);
+        assert!(config.get_generic("script-blacklist").is_empty());
+        assert!(
+            config
+                .settings_matching(|setting| setting.meta().is_from(&dir))
+                .any(|setting| matches!(setting, SettingValue::Generic(generic) if generic.key() == "lua-debug"))
+        );
+    }
+
+    #[test]
+    fn test_serializer_footer_round_trip() {
+        let dir = temp_config("serializer_footer", "content=Morrowind.esm\n");
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert!(config.serializer_info().is_empty());
+
+        let written = config.to_string();
+        assert!(written.ends_with(&format!("Version: {}\n", env!("CARGO_PKG_VERSION"))));
+        std::fs::write(dir.join("openmw.cfg"), &written).unwrap();
+
+        let reloaded = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert_eq!(
+            reloaded.serializer_info()[0].version,
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(reloaded.serializer_info()[0].config, dir.join("openmw.cfg"));
+        assert_eq!(reloaded.to_string(), written);
+
+        config.set_serializer_footer(false);
+        assert!(!config.to_string().contains(comments::SERIALIZER_FOOTER));
+    }
+
+    #[test]
+    fn test_set_game_setting_value_keeps_comment() {
+        let dir = temp_config(
+            "set_game_setting",
+            "fallback=iMaxSpeed,10\n# faster\nfallback=iMaxSpeed,20\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        config.set_game_setting_value("iMaxSpeed", "30").unwrap();
+        let setting = config.get_game_setting("iMaxSpeed").unwrap();
+        assert_eq!(setting.to_string(), "# faster\nfallback=iMaxSpeed,30");
+
+        assert!(matches!(
+            config.set_game_setting_value("iMaxSpeed", "fast"),
+            Err(ConfigError::GameSettingTypeMismatch { .. })
+        ));
+        assert!(matches!(
+            config.set_game_setting_value("iMissing", "1"),
+            Err(ConfigError::UnknownGameSetting(_))
+        ));
+    }
+
+    #[test]
+    fn test_whole_float_survives_save() {
+        let dir = temp_config(
+            "whole_float",
+            "fallback=fJumpHeight,1.75
+",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        config.set_game_setting_value("fJumpHeight", "2").unwrap();
+        config.save_user().unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let setting = config.get_game_setting("fJumpHeight").unwrap();
+        assert_eq!(setting.type_name(), "float");
+        assert_eq!(setting.value(), "2.0");
+
+        config.set_game_setting_value("fJumpHeight", "2.5").unwrap();
+        assert_eq!(config.game_setting_float("fJumpHeight").unwrap(), Some(2.5));
+    }
+
+    #[test]
+    fn test_typed_game_settings() {
+        let dir = temp_config(
+            "typed_game_settings",
+            "fallback=iMaxSpeed,10
+fallback=fGravity,9.8
+fallback=fWhole,2
+fallback=FontColor_color_normal,202,165,96
+fallback=sName,Vivec
+",
+        );
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        assert_eq!(config.game_setting_int("iMaxSpeed").unwrap(), Some(10));
+        assert_eq!(config.game_setting_float("fGravity").unwrap(), Some(9.8));
+        assert_eq!(config.game_setting_float("fWhole").unwrap(), Some(2.0));
+        assert_eq!(
+            config.game_setting_color("FontColor_color_normal").unwrap(),
+            Some(ColorValue::rgb(202, 165, 96))
+        );
+        assert_eq!(
+            config.game_setting_string("sName").as_deref(),
+            Some("Vivec")
+        );
+        assert_eq!(
+            config.game_setting_string("iMaxSpeed").as_deref(),
+            Some("10")
+        );
+        assert_eq!(config.game_setting_int("iMissing").unwrap(), None);
+
+        assert!(matches!(
+            config.game_setting_int("fGravity"),
+            Err(ConfigError::GameSettingWrongType {
+                actual: "float",
+                requested: "int",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_game_setting_sections() {
+        let dir = temp_config(
+            "game_setting_sections",
+            "fallback=Weather_Clear_Cloud_Texture,Tx_Sky_Clear.dds\nfallback=Weather_Hours_Between_Weather_Changes,20\nfallback=LevelUp_Level2,You realize\nfallback=iMaxSpeed,10\n",
+        );
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        assert_eq!(config.sections(), vec!["LevelUp", "Weather"]);
+
+        let mut weather: Vec<&String> = config
+            .game_settings_in_section("Weather")
+            .map(|setting| setting.key())
+            .collect();
+        weather.sort();
+        assert_eq!(
+            weather,
+            vec![
+                "Weather_Clear_Cloud_Texture",
+                "Weather_Hours_Between_Weather_Changes"
+            ]
+        );
+        assert_eq!(config.game_settings_in_section("Fonts").count(), 0);
+    }
+
+    #[test]
+    fn test_self_referencing_chain() {
+        let dir = temp_config("self_referencing", "content=Morrowind.esm\n");
+        let child = dir.join("child");
+        std::fs::create_dir_all(&child).unwrap();
+        std::fs::write(
+            dir.join("openmw.cfg"),
+            format!("content=Morrowind.esm\nconfig={}\n", child.display()),
+        )
+        .unwrap();
+        std::fs::write(
+            child.join("openmw.cfg"),
+            format!("config={}\n", dir.display()),
+        )
+        .unwrap();
+
+        let Err(ConfigError::ConfigCycle { chain }) = OpenMWConfiguration::new(Some(dir.clone()))
+        else {
+            panic!("expected a cycle");
+        };
+        assert_eq!(
+            chain,
+            vec![
+                dir.join("openmw.cfg"),
+                child.join("openmw.cfg"),
+                dir.join("openmw.cfg")
+            ]
+        );
+
+        let warnings = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = warnings.clone();
+        let options =
+            ParseOptions::lenient().with_warning_handler(move |_| counter.set(counter.get() + 1));
+
+        let config = OpenMWConfiguration::new_with_options(Some(dir), options).unwrap();
+        assert_eq!(warnings.get(), 1);
+        assert_eq!(config.content_files(), vec!["Morrowind.esm"]);
+    }
+
+    #[test]
+    fn test_chain_depth_limit() {
+        let root = temp_config("chain_depth", "");
+        let dirs: Vec<PathBuf> = (0..=MAX_CONFIG_DEPTH)
+            .map(|depth| root.join(format!("level{depth}")))
+            .collect();
+
+        std::fs::write(
+            root.join("openmw.cfg"),
+            format!("config={}\n", dirs[0].display()),
+        )
+        .unwrap();
+        for (dir, next) in dirs.iter().zip(dirs.iter().skip(1)) {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(
+                dir.join("openmw.cfg"),
+                format!("config={}\n", next.display()),
+            )
+            .unwrap();
+        }
+        std::fs::create_dir_all(dirs.last().unwrap()).unwrap();
+        std::fs::write(dirs.last().unwrap().join("openmw.cfg"), "").unwrap();
+
+        assert!(matches!(
+            OpenMWConfiguration::new(Some(root)),
+            Err(ConfigError::ConfigChainTooDeep {
+                max: MAX_CONFIG_DEPTH,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        let dir = temp_config(
+            "line_numbers",
+            "# comment\ncontent=Morrowind.esm\n\nencoding=win1252\nencoding=win1250\n=broken\n",
+        );
+
+        let Err(error) = OpenMWConfiguration::new(Some(dir.clone())) else {
+            panic!("expected the broken line to fail");
+        };
+        assert_eq!(error.line(), Some(6));
+        assert!(error.to_string().contains("openmw.cfg:6"));
+
+        let config =
+            OpenMWConfiguration::new_with_options(Some(dir), ParseOptions::lenient()).unwrap();
+        let lines: Vec<Option<usize>> = config
+            .settings_matching(|_| true)
+            .map(|setting| setting.meta().line())
+            .collect();
+
+        // The encoding singleton was replaced in place by the one on line 5
+        assert!(lines.starts_with(&[Some(2), Some(5)]));
+        assert_eq!(config.encoding().unwrap().meta().line(), Some(5));
+    }
+
+    #[test]
+    fn test_engine_resource_dirs_are_opt_in() {
+        let dir = temp_config("engine_resource_dirs", "resources=Resources\ndata=Mods\n");
+
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert_eq!(config.data_directories(), vec![&dir.join("Mods")]);
+
+        let config = config
+            .with_engine_resource_dirs()
+            .with_engine_resource_dirs();
+        assert_eq!(
+            config.data_directories(),
+            vec![
+                &dir.join("Resources/vfs"),
+                &dir.join("Resources/vfs-mw"),
+                &dir.join("Mods")
+            ]
+        );
+
+        config.save_user().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("openmw.cfg")).unwrap(),
+            "resources=Resources\ndata=Mods\n"
+        );
+    }
+
+    #[test]
+    fn test_setting_origins() {
+        let dir = temp_config(
+            "setting_origins",
+            "data-local=Local\ncontent=Morrowind.esm\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        config.set_serializer_footer(false);
+
+        config.add_content_file("Tribunal.esm").unwrap();
+        config
+            .apply_cli_args(&["--content", "Bloodmoon.esm"])
+            .unwrap();
+
+        let origins: Vec<(String, SettingOrigin)> = config
+            .settings_matching(|_| true)
+            .map(|setting| (setting.to_string(), setting.meta().origin()))
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                ("data-local=Local\n".into(), SettingOrigin::File),
+                ("content=Morrowind.esm\n".into(), SettingOrigin::File),
+                ("data=Local\n".into(), SettingOrigin::Synthetic),
+                ("content=Tribunal.esm\n".into(), SettingOrigin::Api),
+                ("content=Bloodmoon.esm\n".into(), SettingOrigin::Cli),
+            ]
+        );
+
+        assert!(!config.to_string().contains("data=Local"));
+        assert!(
+            config
+                .render(&render::RenderOptions {
+                    include_synthetic: true,
+                    ..Default::default()
+                })
+                .contains("data=Local")
+        );
+    }
+
+    #[test]
+    fn test_read_only_open() {
+        let dir = temp_config("read_only", "data-local=Local\ncontent=Morrowind.esm\n");
+
+        let mut config = OpenMWConfiguration::new_with_options(
+            Some(dir.clone()),
+            ParseOptions::default().read_only(),
+        )
+        .unwrap();
+        assert!(config.is_read_only());
+        assert!(!dir.join("Local").exists());
+
+        config.add_content_file("Tribunal.esm").unwrap();
+        assert!(config.save_user().unwrap_err().contains("read-only"));
+        assert!(matches!(
+            config.create_subconfig(&dir.join("fragment"), Vec::new(), false),
+            Err(ConfigError::ReadOnly(_))
+        ));
+        assert_eq!(
+            std::fs::read_to_string(dir.join("openmw.cfg")).unwrap(),
+            "data-local=Local\ncontent=Morrowind.esm\n"
+        );
+
+        OpenMWConfiguration::new_with_options(
+            Some(dir.clone()),
+            ParseOptions::default().creating_missing_dirs(false),
+        )
+        .unwrap();
+        assert!(!dir.join("Local").exists());
+
+        OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert!(dir.join("Local").exists());
+    }
+
+    #[test]
+    fn test_inline_comments() {
+        let contents = "content=Rebirth.esp # the good one\ndata=\"Mods # Misc\" # quoted\nfallback=Key,a=b # tweak\nencoding=win1252 # cyrillic later\n";
+        let dir = temp_config("inline_comments", contents);
+
+        // Without the option the engine's reading applies, which can't make sense of the encoding line
+        let plain =
+            OpenMWConfiguration::new_with_options(Some(dir.clone()), ParseOptions::lenient())
+                .unwrap();
+        assert!(plain.has_content_file("Rebirth.esp # the good one"));
+
+        let mut config = OpenMWConfiguration::new_with_options(
+            Some(dir.clone()),
+            ParseOptions::strict().with_inline_comments(),
+        )
+        .unwrap();
+        config.set_serializer_footer(false);
+
+        assert!(config.has_content_file("Rebirth.esp"));
+        assert_eq!(config.data_directories(), vec![&dir.join("Mods # Misc")]);
+        assert_eq!(config.get_game_setting("Key").unwrap().value(), "a=b");
+        assert_eq!(
+            config.encoding().unwrap().meta().inline_comment(),
+            "# cyrillic later"
+        );
+
+        let written = config.to_string();
+        assert!(written.starts_with(
+            "content=Rebirth.esp # the good one\ndata=\"Mods # Misc\" # quoted\nfallback=Key,a=b # tweak\n"
+        ));
+        assert!(written.contains("encoding=win1252 # cyrillic later\n"));
+    }
+
+    #[test]
+    fn test_hash_in_data_directory_round_trip() {
+        let dir = temp_config("hash_data_directory", "");
+        let mods = dir.join("Mods # Misc");
+
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        config.add_data_directory(mods.clone());
+        config.save_user().unwrap();
+
+        let config = OpenMWConfiguration::new_with_options(
+            Some(dir),
+            ParseOptions::default().with_inline_comments(),
+        )
+        .unwrap();
+        assert_eq!(config.data_directories(), [&mods]);
+    }
+
+    #[test]
+    fn test_content_kinds() {
+        let dir = temp_config(
+            "content_kinds",
+            "content=Starwind.omwgame\ncontent=Tribunal.ESM\ncontent=Patch.esp\ncontent=Tweaks.omwaddon\ncontent=Hud.omwscripts\ncontent=README\ngroundcover=Grass.esp\n",
+        );
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        let names = |files: Vec<&FileSetting>| {
+            files
+                .into_iter()
+                .map(|file| file.value().clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(config.game_files().collect()),
+            vec!["Starwind.omwgame", "Tribunal.ESM"]
+        );
+        assert_eq!(
+            names(config.addon_files().collect()),
+            vec!["Patch.esp", "Tweaks.omwaddon"]
+        );
+        assert_eq!(
+            names(config.content_files_of_kind(ContentKind::Unknown).collect()),
+            vec!["README"]
+        );
+
+        let kinds: Vec<ContentKind> = config
+            .settings_matching(|_| true)
+            .filter_map(SettingValue::content_kind)
+            .collect();
+        assert_eq!(kinds.last(), Some(&ContentKind::Groundcover));
+        assert!(kinds.contains(&ContentKind::OmwScripts));
     }
 }
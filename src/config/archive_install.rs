@@ -0,0 +1,218 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Installing a mod straight from the zip or 7z it was downloaded as.
+//! Mod authors pack things every which way, so the data root is whatever folder inside actually has something the engine loads.
+
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    ConfigError,
+    config::{
+        OpenMWConfiguration,
+        install::{InstallStrategy, ModInstall},
+        modcontents::scan_mod_contents,
+    },
+    config_err,
+};
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const SEVEN_ZIP_MAGIC: &[u8] = b"7z\xBC\xAF\x27\x1C";
+
+/// Folders archivers leave behind, which are never part of a mod
+fn is_junk(name: &str) -> bool {
+    name.starts_with('.') || name.eq_ignore_ascii_case("__MACOSX")
+}
+
+/// The folder inside an extracted mod which should become its data directory.
+/// Wrapper folders are stepped into as long as there's only one of them, as is `Data Files` when nothing usable sits beside it.
+/// Falls back to `dir` itself when nothing better turns up, like for FOMOD installers with several options.
+pub fn find_data_root(dir: &Path) -> PathBuf {
+    let mut dir = dir.to_path_buf();
+
+    loop {
+        let contents = scan_mod_contents(&dir);
+        if contents.is_usable() {
+            return dir;
+        }
+        if let Some(nested) = contents.nested_data_files {
+            dir = nested;
+            continue;
+        }
+
+        let subdirs: Vec<PathBuf> = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| !is_junk(&entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect();
+
+        match subdirs.as_slice() {
+            [only] if only.is_dir() => dir = only.clone(),
+            _ => return dir,
+        }
+    }
+}
+
+fn extract_zip(archive: &Path, dest: &Path) -> Result<(), ConfigError> {
+    let invalid = |err: zip::result::ZipError| config_err!(invalid_archive, archive, err);
+
+    // `extract` fails the whole archive if any entry would land outside of `dest`
+    zip::ZipArchive::new(File::open(archive)?)
+        .map_err(invalid)?
+        .extract(dest)
+        .map_err(invalid)
+}
+
+fn extract_7z(archive: &Path, dest: &Path) -> Result<(), ConfigError> {
+    sevenz_rust::decompress_file_with_extract_fn(archive, dest, |entry, reader, _| {
+        let name = entry.name().replace('\\', "/");
+        let relative = Path::new(&name);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(sevenz_rust::Error::other(format!(
+                "{name} would be extracted outside of the mod"
+            )));
+        }
+
+        sevenz_rust::default_entry_extract_fn(entry, reader, &dest.join(relative))
+    })
+    .map_err(|err| config_err!(invalid_archive, archive, err))
+}
+
+impl OpenMWConfiguration {
+    /// Extracts a downloaded mod, finds its data root with `find_data_root`, and installs that with `install_mod`.
+    /// The installed folder is named after the archive, and the extracted copy is cleaned up afterwards either way.
+    /// Zip and 7z are told apart by their contents rather than their extension. RAR isn't supported.
+    pub fn install_mod_archive(&mut self, archive: &Path) -> Result<ModInstall, ConfigError> {
+        let mut magic = [0; 6];
+        File::open(archive)?.read_exact(&mut magic).ok();
+
+        let Some(name) = archive.file_stem() else {
+            return Err(config_err!(invalid_archive, archive, "no file name"));
+        };
+        let staging = self
+            .mods_path()
+            .join(format!(".extracting-{}", name.to_string_lossy()));
+        let _ = fs::remove_dir_all(&staging);
+
+        let extracted = match &magic {
+            header if header.starts_with(ZIP_MAGIC) => extract_zip(archive, &staging),
+            header if header.starts_with(SEVEN_ZIP_MAGIC) => extract_7z(archive, &staging),
+            _ => Err(config_err!(
+                invalid_archive,
+                archive,
+                "not a zip or 7z archive"
+            )),
+        };
+
+        let installed = extracted.and_then(|()| {
+            let root = find_data_root(&staging);
+            self.install_mod_as(&root, name, InstallStrategy::Copy)
+        });
+
+        let _ = fs::remove_dir_all(&staging);
+        installed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_install_mod_archive() {
+        let root = temp_config("install_mod_archive", "user-data=Profile\n");
+        let archive = root.join("Cool Mod-1234-1-0.zip");
+
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (name, contents) in [
+            ("Cool Mod/readme.txt", "read me"),
+            ("Cool Mod/Data Files/cool.esp", ""),
+            ("Cool Mod/Data Files/Textures/cool.dds", "dds"),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        let installed = config.install_mod_archive(&archive).unwrap();
+
+        assert_eq!(installed.path, root.join("Profile/mods/Cool Mod-1234-1-0"));
+        assert_eq!(installed.plugins_added, ["cool.esp"]);
+        assert!(installed.path.join("Textures/cool.dds").is_file());
+        assert!(!installed.path.join("readme.txt").exists());
+        assert_eq!(fs::read_dir(config.mods_path()).unwrap().count(), 1);
+
+        fs::write(root.join("broken.zip"), "not really").unwrap();
+        assert!(matches!(
+            config.install_mod_archive(&root.join("broken.zip")),
+            Err(ConfigError::InvalidArchive { .. })
+        ));
+    }
+
+    #[test]
+    fn test_zip_path_traversal_is_rejected() {
+        let root = temp_config("install_zip_traversal", "user-data=Profile\n");
+        let archive = root.join("Evil.zip");
+
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for name in ["Data Files/evil.esp", "../evil.txt"] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(b"evil").unwrap();
+        }
+        zip.finish().unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        assert!(matches!(
+            config.install_mod_archive(&archive),
+            Err(ConfigError::InvalidArchive { .. })
+        ));
+        assert!(!config.mods_path().join("evil.txt").exists());
+        assert!(!config.mods_path().join("Evil").exists());
+        assert!(config.content_files().is_empty());
+    }
+
+    #[test]
+    fn test_7z_path_traversal_is_rejected() {
+        let root = temp_config("install_7z_traversal", "user-data=Profile\n");
+        let archive = root.join("Evil.7z");
+
+        let mut writer = sevenz_rust::SevenZWriter::create(&archive).unwrap();
+        for name in ["Data Files/evil.esp", "../evil.txt"] {
+            let mut entry = sevenz_rust::SevenZArchiveEntry::new();
+            entry.name = name.into();
+            entry.has_stream = true;
+            writer
+                .push_archive_entry(entry, Some(&b"evil"[..]))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        assert!(matches!(
+            config.install_mod_archive(&archive),
+            Err(ConfigError::InvalidArchive { .. })
+        ));
+        assert!(!config.mods_path().join("evil.txt").exists());
+        assert!(!config.mods_path().join("Evil").exists());
+        assert!(config.content_files().is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! The openmw.cfg an installer writes on first run, so the engine can start without the launcher's wizard.
+
+use std::path::Path;
+
+use crate::{
+    ConfigError, bail_config,
+    config::{OpenMWConfiguration, discovery::child_ignoring_case, encodingsetting::EncodingType},
+};
+
+/// The expansions are only listed when they're installed
+const GAME_FILES: [(&str, &str, bool); 3] = [
+    ("Morrowind.esm", "Morrowind.bsa", true),
+    ("Tribunal.esm", "Tribunal.bsa", false),
+    ("Bloodmoon.esm", "Bloodmoon.bsa", false),
+];
+
+impl OpenMWConfiguration {
+    /// Writes a user openmw.cfg for a fresh install and loads it.
+    /// `game_data_dir` is Morrowind's `Data Files`, and the Morrowind.ini next to it, if there is one, provides the fallbacks.
+    /// An openmw.cfg which already exists in the user config directory is never overwritten.
+    pub fn bootstrap(game_data_dir: &Path) -> Result<Self, ConfigError> {
+        let config_dir = crate::default_config_path();
+        let cfg_path = config_dir.join("openmw.cfg");
+        if cfg_path.exists() {
+            bail_config!(sub_config_exists, config_dir);
+        }
+
+        std::fs::create_dir_all(&config_dir)?;
+        std::fs::write(&cfg_path, "")?;
+
+        // Whatever goes wrong from here, a half-written file would stop the next attempt
+        Self::fill_bootstrapped(&config_dir, game_data_dir).inspect_err(|_| {
+            let _ = std::fs::remove_file(&cfg_path);
+        })
+    }
+
+    fn fill_bootstrapped(config_dir: &Path, game_data_dir: &Path) -> Result<Self, ConfigError> {
+        let mut config = Self::new(Some(config_dir.to_path_buf()))?;
+
+        let installed: Vec<_> = GAME_FILES
+            .into_iter()
+            .filter(|(plugin, _, required)| {
+                *required || child_ignoring_case(game_data_dir, plugin).is_some()
+            })
+            .collect();
+
+        config.add_data_directory(game_data_dir.to_path_buf());
+        for (plugin, ..) in &installed {
+            config.add_content_file(plugin)?;
+        }
+        for (_, archive, _) in &installed {
+            config.add_archive_file(archive)?;
+        }
+        config.set_encoding_type(Some(EncodingType::WIN1252));
+
+        if let Some(ini) = game_data_dir
+            .parent()
+            .and_then(|game_dir| child_ignoring_case(game_dir, "Morrowind.ini"))
+        {
+            config.import_ini_fallbacks(&ini)?;
+        }
+
+        config
+            .save_user()
+            .map_err(|reason| ConfigError::Io(std::io::Error::other(reason)))?;
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::util::temp_config,
+        paths::{FixedPaths, with_paths_provider},
+    };
+
+    #[test]
+    fn test_bootstrap() {
+        let root = temp_config("bootstrap", "");
+        let data_files = root.join("Morrowind").join("Data Files");
+        std::fs::create_dir_all(&data_files).unwrap();
+        std::fs::write(data_files.join("tribunal.esm"), "").unwrap();
+        std::fs::write(
+            root.join("Morrowind").join("Morrowind.ini"),
+            "[Weather Clear]\nCloud Texture=Tx_Sky_Clear.dds\n",
+        )
+        .unwrap();
+
+        let fixed = FixedPaths::under(&root);
+        with_paths_provider(Arc::new(fixed.clone()), || {
+            let config = OpenMWConfiguration::bootstrap(&data_files).unwrap();
+
+            assert_eq!(config.data_directories(), vec![&data_files]);
+            assert_eq!(
+                config.content_files(),
+                vec!["Morrowind.esm", "Tribunal.esm"]
+            );
+            assert_eq!(
+                config.fallback_archives(),
+                vec!["Morrowind.bsa", "Tribunal.bsa"]
+            );
+            assert_eq!(config.encoding_type(), Some(&EncodingType::WIN1252));
+
+            let reloaded = OpenMWConfiguration::new(None).unwrap();
+            assert_eq!(reloaded.content_files(), config.content_files());
+            assert_eq!(
+                reloaded
+                    .get_game_setting("Weather_Clear_Cloud_Texture")
+                    .unwrap()
+                    .value(),
+                "Tx_Sky_Clear.dds"
+            );
+
+            assert!(matches!(
+                OpenMWConfiguration::bootstrap(&data_files),
+                Err(ConfigError::SubConfigExists(_))
+            ));
+        });
+    }
+}
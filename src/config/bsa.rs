@@ -0,0 +1,406 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Just enough of the Bethesda archive formats to list what's inside them.
+//! File contents are never read, only the directory of names.
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ConfigError, SettingHandle,
+    config::{OpenMWConfiguration, strings},
+};
+
+const TES3_VERSION: u32 = 0x100;
+const TES4_MAGIC: &[u8; 4] = b"BSA\0";
+const TES4_INCLUDE_DIRECTORY_NAMES: u32 = 0x1;
+const TES4_INCLUDE_FILE_NAMES: u32 = 0x2;
+
+/// Archive formats OpenMW is able to load
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// Morrowind
+    Tes3,
+    /// Oblivion, Fallout 3/NV and Skyrim (versions 103, 104 and 105)
+    Tes4(u32),
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    path: &'a Path,
+}
+
+impl<'a> Reader<'a> {
+    fn invalid(&self, reason: &str) -> ConfigError {
+        crate::config_err!(invalid_archive, self.path, reason)
+    }
+
+    /// Offsets worked out from counts in the file, which a broken archive can make overflow
+    fn offset(&self, offset: Option<usize>) -> Result<usize, ConfigError> {
+        offset.ok_or_else(|| self.invalid("offset out of range"))
+    }
+
+    fn slice(&self, offset: usize, len: usize) -> Result<&'a [u8], ConfigError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| self.invalid("unexpected end of file"))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, ConfigError> {
+        let bytes = self.slice(offset, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Null-terminated string starting at offset, and the offset just past its terminator
+    fn cstr(&self, offset: usize) -> Result<(String, usize), ConfigError> {
+        let rest = self
+            .bytes
+            .get(offset..)
+            .ok_or_else(|| self.invalid("unexpected end of file"))?;
+        let len = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| self.invalid("unterminated file name"))?;
+
+        Ok((
+            String::from_utf8_lossy(&rest[..len]).into_owned(),
+            offset + len + 1,
+        ))
+    }
+}
+
+/// Determines which kind of archive this is, if any kind OpenMW supports. Only the header is read.
+pub fn archive_format(path: &Path) -> Result<ArchiveFormat, ConfigError> {
+    let mut header = Vec::with_capacity(8);
+    std::fs::File::open(path)?
+        .take(8)
+        .read_to_end(&mut header)?;
+
+    detect_format(&Reader {
+        bytes: &header,
+        path,
+    })
+}
+
+/// How one `fallback-archive=` entry fared in `validate_archives`
+#[derive(Debug)]
+pub struct ArchiveCheck {
+    pub handle: SettingHandle,
+    pub name: String,
+    /// The copy the engine opens, from the highest priority data directory which has one
+    pub path: Option<PathBuf>,
+    /// Its format, or why the engine can't use it
+    pub result: Result<ArchiveFormat, ConfigError>,
+}
+
+impl ArchiveCheck {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Looks up every archive in the data directories and checks its header, in load order.
+    /// Names are matched ignoring case in any language, so `ÄRGERNIS.BSA` finds `ärgernis.bsa`.
+    pub fn validate_archives(&self) -> Vec<ArchiveCheck> {
+        let mut files: HashMap<String, PathBuf> = HashMap::new();
+        for dir in self.data_directories() {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+
+            // Later directories are searched first by the engine, so they replace what earlier ones had
+            for entry in entries.flatten().filter(|entry| entry.path().is_file()) {
+                files.insert(
+                    strings::fold_case(&entry.file_name().to_string_lossy()),
+                    entry.path(),
+                );
+            }
+        }
+
+        self.fallback_archives_iter()
+            .map(|archive| {
+                let path = files.get(&strings::fold_case(archive.value())).cloned();
+                let result = match &path {
+                    Some(path) => archive_format(path),
+                    None => Err(crate::config_err!(archive_not_found, archive.value())),
+                };
+
+                ArchiveCheck {
+                    handle: archive.handle(),
+                    name: archive.value().clone(),
+                    path,
+                    result,
+                }
+            })
+            .collect()
+    }
+}
+
+fn detect_format(reader: &Reader) -> Result<ArchiveFormat, ConfigError> {
+    if reader.slice(0, 4)? == TES4_MAGIC {
+        match reader.u32(4)? {
+            version @ (103..=105) => Ok(ArchiveFormat::Tes4(version)),
+            _ => Err(reader.invalid("unsupported BSA version")),
+        }
+    } else if reader.u32(0)? == TES3_VERSION {
+        Ok(ArchiveFormat::Tes3)
+    } else {
+        Err(reader.invalid("not a BSA archive"))
+    }
+}
+
+/// Every file path stored in the archive, as written in it (usually with backslashes)
+pub fn read_file_list(path: &Path) -> Result<Vec<String>, ConfigError> {
+    let bytes = std::fs::read(path)?;
+    let reader = Reader {
+        bytes: &bytes,
+        path,
+    };
+
+    match detect_format(&reader)? {
+        ArchiveFormat::Tes3 => read_tes3(&reader),
+        ArchiveFormat::Tes4(version) => read_tes4(&reader, version),
+    }
+}
+
+/// Header, then (size, offset) per file, then name offsets, then the names themselves
+fn read_tes3(reader: &Reader) -> Result<Vec<String>, ConfigError> {
+    let count = reader.u32(8)? as usize;
+    let name_offsets_start =
+        reader.offset(count.checked_mul(8).and_then(|len| len.checked_add(12)))?;
+    let names_start = reader.offset(
+        count
+            .checked_mul(4)
+            .and_then(|len| len.checked_add(name_offsets_start)),
+    )?;
+
+    (0..count)
+        .map(|index| {
+            let name_offset = reader.u32(name_offsets_start + index * 4)? as usize;
+            Ok(reader
+                .cstr(reader.offset(names_start.checked_add(name_offset))?)?
+                .0)
+        })
+        .collect()
+}
+
+/// Folder records, then per folder a name and its file records, then one block of every file name
+fn read_tes4(reader: &Reader, version: u32) -> Result<Vec<String>, ConfigError> {
+    let folder_records_start = reader.u32(8)? as usize;
+    let flags = reader.u32(12)?;
+    let folder_count = reader.u32(16)? as usize;
+
+    if flags & TES4_INCLUDE_FILE_NAMES == 0 {
+        return Err(reader.invalid("archive does not store file names"));
+    }
+
+    let folder_record_size = if version == 105 { 24 } else { 16 };
+    let mut offset = reader.offset(
+        folder_count
+            .checked_mul(folder_record_size)
+            .and_then(|len| len.checked_add(folder_records_start)),
+    )?;
+    // Counts come straight from the file, so nothing is reserved up front on their say-so
+    let mut folders = Vec::new();
+
+    for index in 0..folder_count {
+        let files_in_folder = reader.u32(folder_records_start + index * folder_record_size + 8)?;
+
+        let name = if flags & TES4_INCLUDE_DIRECTORY_NAMES != 0 {
+            let len = *reader.slice(offset, 1)?.first().unwrap_or(&0) as usize;
+            let name = reader.slice(offset + 1, len)?;
+            offset += 1 + len;
+            String::from_utf8_lossy(name)
+                .trim_end_matches('\0')
+                .to_string()
+        } else {
+            String::new()
+        };
+
+        offset = reader.offset(
+            (files_in_folder as usize)
+                .checked_mul(16)
+                .and_then(|len| len.checked_add(offset)),
+        )?;
+        folders.push((name, files_in_folder));
+    }
+
+    let mut names = Vec::new();
+    for (folder, files_in_folder) in folders {
+        for _ in 0..files_in_folder {
+            let (name, next) = reader.cstr(offset)?;
+            offset = next;
+
+            names.push(match folder.is_empty() {
+                true => name,
+                false => format!("{folder}\\{name}"),
+            });
+        }
+    }
+
+    Ok(names)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a minimal Morrowind archive containing empty files with the given names
+    pub fn tes3_archive(names: &[&str]) -> Vec<u8> {
+        let mut name_block = Vec::new();
+        let mut name_offsets = Vec::new();
+
+        for name in names {
+            name_offsets.push(name_block.len() as u32);
+            name_block.extend_from_slice(name.as_bytes());
+            name_block.push(0);
+        }
+
+        let count = names.len() as u32;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&TES3_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(count * 12 + name_block.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        names.iter().for_each(|_| bytes.extend_from_slice(&[0; 8]));
+        name_offsets
+            .iter()
+            .for_each(|offset| bytes.extend_from_slice(&offset.to_le_bytes()));
+        bytes.extend_from_slice(&name_block);
+        names.iter().for_each(|_| bytes.extend_from_slice(&[0; 8]));
+
+        bytes
+    }
+
+    fn tes4_archive(folders: &[(&str, &[&str])]) -> Vec<u8> {
+        let file_count: usize = folders.iter().map(|(_, files)| files.len()).sum();
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(TES4_MAGIC);
+        for value in [
+            104,
+            36,
+            TES4_INCLUDE_DIRECTORY_NAMES | TES4_INCLUDE_FILE_NAMES,
+            folders.len() as u32,
+            file_count as u32,
+            0,
+            0,
+            0,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        for (_, files) in folders {
+            bytes.extend_from_slice(&[0; 8]);
+            bytes.extend_from_slice(&(files.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&[0; 4]);
+        }
+
+        for (folder, files) in folders {
+            bytes.push(folder.len() as u8 + 1);
+            bytes.extend_from_slice(folder.as_bytes());
+            bytes.push(0);
+            files.iter().for_each(|_| bytes.extend_from_slice(&[0; 16]));
+        }
+
+        for (_, files) in folders {
+            for file in *files {
+                bytes.extend_from_slice(file.as_bytes());
+                bytes.push(0);
+            }
+        }
+
+        bytes
+    }
+
+    fn write_temp(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("openmw_config_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_tes3_file_list() {
+        let path = write_temp(
+            "tes3.bsa",
+            &tes3_archive(&["meshes\\a.nif", "textures\\b.dds"]),
+        );
+
+        assert_eq!(archive_format(&path).unwrap(), ArchiveFormat::Tes3);
+        assert_eq!(
+            read_file_list(&path).unwrap(),
+            vec!["meshes\\a.nif", "textures\\b.dds"]
+        );
+    }
+
+    #[test]
+    fn test_tes4_file_list() {
+        let path = write_temp(
+            "tes4.bsa",
+            &tes4_archive(&[("meshes", &["a.nif", "b.nif"]), ("textures", &["c.dds"])]),
+        );
+
+        assert_eq!(archive_format(&path).unwrap(), ArchiveFormat::Tes4(104));
+        assert_eq!(
+            read_file_list(&path).unwrap(),
+            vec!["meshes\\a.nif", "meshes\\b.nif", "textures\\c.dds"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        let mut huge_tes3 = tes3_archive(&["a.nif"]);
+        huge_tes3[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut huge_tes4 = tes4_archive(&[("meshes", &["a.nif"])]);
+        huge_tes4[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        huge_tes4[44..48].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        for (name, bytes) in [
+            ("garbage.bsa", b"definitely not an archive".to_vec()),
+            ("huge_tes3.bsa", huge_tes3),
+            ("huge_tes4.bsa", huge_tes4),
+        ] {
+            assert!(matches!(
+                read_file_list(&write_temp(name, &bytes)),
+                Err(ConfigError::InvalidArchive { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate_archives() {
+        let dir = crate::config::util::temp_config(
+            "validate_archives",
+            "data=Base\ndata=Patch\nfallback-archive=ÄRGERNIS.BSA\nfallback-archive=Broken.bsa\nfallback-archive=Gone.bsa\n",
+        );
+        std::fs::create_dir_all(dir.join("Base")).unwrap();
+        std::fs::create_dir_all(dir.join("Patch")).unwrap();
+        std::fs::write(dir.join("Base/ärgernis.bsa"), b"not an archive").unwrap();
+        std::fs::write(dir.join("Patch/Ärgernis.bsa"), tes3_archive(&["a.nif"])).unwrap();
+        std::fs::write(dir.join("Base/broken.bsa"), b"BSA\0\x64\0\0\0").unwrap();
+
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let checks = config.validate_archives();
+
+        assert_eq!(checks[0].path, Some(dir.join("Patch/Ärgernis.bsa")));
+        assert_eq!(checks[0].result.as_ref().unwrap(), &ArchiveFormat::Tes3);
+        assert!(matches!(
+            checks[1].result,
+            Err(ConfigError::InvalidArchive { .. })
+        ));
+        assert!(matches!(
+            &checks[2].result,
+            Err(ConfigError::ArchiveNotFound(name)) if name == "Gone.bsa"
+        ));
+        assert_eq!(checks.iter().filter(|check| check.is_ok()).count(), 1);
+    }
+}
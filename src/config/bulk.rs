@@ -0,0 +1,195 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Adding whole folders of mods at once, the way mod managers do.
+//! Patterns only understand `*` and `?`, which covers every layout people actually use.
+
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+use crate::{ConfigError, config::OpenMWConfiguration};
+
+fn has_wildcard(part: &str) -> bool {
+    part.contains(['*', '?'])
+}
+
+/// Matches `name` against a pattern where `*` is any run of characters and `?` is exactly one
+pub fn wildcard_match(pattern: &str, name: &str, ignore_case: bool) -> bool {
+    let fold = |c: char| match ignore_case {
+        true => c.to_lowercase().next().unwrap_or(c),
+        false => c,
+    };
+    let pattern: Vec<char> = pattern.chars().map(fold).collect();
+    let name: Vec<char> = name.chars().map(fold).collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and how much of the name it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Entries of `dir` matching `pattern`, sorted by name. Hidden entries only match patterns which start with a dot.
+fn matching_entries(dir: &Path, pattern: &str, ignore_case: bool) -> Vec<PathBuf> {
+    let listed = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+    let Ok(entries) = read_dir(listed) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.starts_with('.') || pattern.starts_with('.'))
+        .filter(|name| wildcard_match(pattern, name, ignore_case))
+        .collect();
+
+    names.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b)));
+    names.into_iter().map(|name| dir.join(name)).collect()
+}
+
+impl OpenMWConfiguration {
+    /// Adds every directory matching `pattern` (like `~/mods/*/`) as a data directory, in alphabetical order.
+    /// Directories which are already in the configuration are skipped. Returns the ones which were added.
+    pub fn add_data_directories_glob(&mut self, pattern: &str) -> Vec<PathBuf> {
+        let expanded = PathBuf::from(shellexpand::tilde(pattern).into_owned());
+        let mut matches = vec![PathBuf::new()];
+
+        for component in expanded.components() {
+            let part = component.as_os_str().to_string_lossy();
+
+            matches = match has_wildcard(&part) {
+                true => matches
+                    .iter()
+                    .flat_map(|dir| matching_entries(dir, &part, false))
+                    .collect(),
+                false => matches.into_iter().map(|dir| dir.join(component)).collect(),
+            };
+        }
+
+        let mut added = Vec::new();
+        for dir in matches.into_iter().filter(|dir| dir.is_dir()) {
+            if !self.data_directories().contains(&&dir) {
+                self.add_data_directory(dir.clone());
+                added.push(dir);
+            }
+        }
+
+        added
+    }
+
+    /// Enables every file in `data_dir` whose name matches `pattern` (like `*.omwaddon`), in alphabetical order.
+    /// Names are matched case-insensitively since plenty of mods ship `.ESP` files, and already enabled plugins are skipped.
+    /// Nothing is reordered by masters, so check `validate()` afterwards when that matters.
+    pub fn add_content_files_matching(
+        &mut self,
+        data_dir: &Path,
+        pattern: &str,
+    ) -> Result<Vec<String>, ConfigError> {
+        read_dir(data_dir)?;
+
+        let mut added = Vec::new();
+        for path in matching_entries(data_dir, pattern, true) {
+            if !path.is_file() {
+                continue;
+            }
+
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if !self.has_content_file(&name) {
+                self.add_content_file(&name)?;
+                added.push(name.into_owned());
+            }
+        }
+
+        Ok(added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.omwaddon", "Foo.omwaddon", false));
+        assert!(wildcard_match("*.esp", "Patch.ESP", true));
+        assert!(!wildcard_match("*.esp", "Patch.ESP", false));
+        assert!(wildcard_match("TR_?*.esm", "TR_Mainland.esm", false));
+        assert!(!wildcard_match("TR_?*.esm", "TR_.esm", false));
+        assert!(wildcard_match("*a*b*", "xxaxxbxx", false));
+        assert!(!wildcard_match("*a*b", "xxbxxa", false));
+    }
+
+    #[test]
+    fn test_bulk_additions() {
+        let dir = temp_config("bulk", "content=Morrowind.esm\n");
+        let mods = dir.join("mods");
+        for name in ["b_mod", "A_mod", ".hidden"] {
+            std::fs::create_dir_all(mods.join(name)).unwrap();
+        }
+        std::fs::write(mods.join("readme.txt"), "").unwrap();
+        for name in [
+            "Zeta.omwaddon",
+            "alpha.OMWADDON",
+            "Morrowind.esm",
+            "notes.txt",
+        ] {
+            std::fs::write(mods.join("A_mod").join(name), "").unwrap();
+        }
+
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let pattern = format!("{}/*/", mods.display());
+
+        assert_eq!(
+            config.add_data_directories_glob(&pattern),
+            vec![mods.join("A_mod"), mods.join("b_mod")]
+        );
+        assert!(config.add_data_directories_glob(&pattern).is_empty());
+
+        assert_eq!(
+            config
+                .add_content_files_matching(&mods.join("A_mod"), "*.omwaddon")
+                .unwrap(),
+            vec!["alpha.OMWADDON", "Zeta.omwaddon"]
+        );
+        assert!(
+            config
+                .add_content_files_matching(&mods.join("A_mod"), "*.esm")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            config
+                .add_content_files_matching(&mods.join("missing"), "*")
+                .is_err()
+        );
+    }
+}
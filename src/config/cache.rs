@@ -0,0 +1,758 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! On-disk cache of an openmw.cfg chain, for tools which load the same setup over and over.
+//!
+//! It holds the settings the chain was parsed into and the layout of every file, along with the mtime and size of each file
+//! That was read and every `config=` directory which had no openmw.cfg in it. While none of those change,
+//! Loading puts the settings back together from the cache without parsing anything. Path tokens like `?userdata?`
+//! Are still resolved when that happens, since they depend on the environment rather than on the files.
+//!
+//! Once something did change the whole chain is parsed again, because `replace=` lets one file wipe out settings from another.
+//! Files which didn't change are still taken from the cache then, rather than read and decoded again.
+//!
+//! The layout is a header line followed by one record per line, with the fields separated by tabs.
+//! Tabs, newlines and backslashes inside a field are escaped with a backslash.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    ConfigError, GameSettingMeta, SettingHandle, SettingOrigin,
+    config::{
+        OpenMWConfiguration, ParseOptions, SerializerInfo, SettingValue,
+        directorysetting::DirectorySetting,
+        document::{Document, DocumentLine, Documents},
+        encodingsetting::EncodingSetting,
+        filesetting::FileSetting,
+        flagsetting::FlagSetting,
+        fs::ConfigFs,
+        gamesetting::{ColorFormat, ColorValue, GameSettingType, TypedValue},
+        genericsetting::GenericSetting,
+        util,
+    },
+};
+
+const HEADER: &str = concat!("openmw-config-cache ", env!("CARGO_PKG_VERSION"));
+
+/// Enough to tell whether a file changed since it was cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+    modified: u128,
+    len: u64,
+}
+
+impl FileStamp {
+    pub(crate) fn of(fs: &dyn ConfigFs, path: &Path) -> Option<Self> {
+        let meta = fs.metadata(path).ok()?;
+        let modified = meta.modified?.duration_since(UNIX_EPOCH).ok()?;
+
+        Some(Self {
+            modified: modified.as_nanos(),
+            len: meta.len,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CachedFile {
+    path: PathBuf,
+    stamp: FileStamp,
+    text: String,
+}
+
+/// What a chain was parsed into, as the unescaped fields of each record
+#[derive(Debug, Default)]
+struct CachedChain {
+    root: PathBuf,
+    /// `config=` directories which didn't have an openmw.cfg, and would change the chain by getting one
+    missing: Vec<PathBuf>,
+    records: Vec<Vec<String>>,
+}
+
+/// Everything loading would have produced, put back together from a `CachedChain`
+struct ParsedChain {
+    settings: Vec<SettingValue>,
+    documents: Documents,
+    serializer_info: Vec<SerializerInfo>,
+}
+
+/// The cache file as it was read, plus whatever the current load ends up using
+#[derive(Debug, Default)]
+pub(crate) struct ConfigCache {
+    previous: HashMap<PathBuf, CachedFile>,
+    chain: Option<CachedChain>,
+    loaded: Vec<CachedFile>,
+    missing: Vec<PathBuf>,
+    /// A file in the current load couldn't be stat'd, so there'd be no telling whether its chain is still current
+    unstamped: bool,
+    /// Something had to come from disk, so the cache file needs rewriting
+    stale: bool,
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for character in text.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            character => escaped.push(character),
+        }
+    }
+
+    escaped
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut characters = text.chars();
+
+    while let Some(character) = characters.next() {
+        unescaped.push(match character {
+            '\\' => match characters.next()? {
+                '\\' => '\\',
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            character => character,
+        });
+    }
+
+    Some(unescaped)
+}
+
+/// Paths which aren't valid UTF-8 can't be stored, so a chain with one isn't cached
+fn path_text(path: &Path) -> Option<String> {
+    path.to_str().map(str::to_string)
+}
+
+fn origin_name(origin: SettingOrigin) -> &'static str {
+    match origin {
+        SettingOrigin::File => "file",
+        SettingOrigin::Synthetic => "synthetic",
+        SettingOrigin::Api => "api",
+        SettingOrigin::Cli => "cli",
+    }
+}
+
+fn parse_origin(name: &str) -> Option<SettingOrigin> {
+    match name {
+        "file" => Some(SettingOrigin::File),
+        "synthetic" => Some(SettingOrigin::Synthetic),
+        "api" => Some(SettingOrigin::Api),
+        "cli" => Some(SettingOrigin::Cli),
+        _ => None,
+    }
+}
+
+fn typed_fields(value: TypedValue) -> [String; 2] {
+    match value {
+        TypedValue::Color(color) => {
+            let format = match color.format {
+                ColorFormat::Bytes => "bytes",
+                ColorFormat::Float => "float",
+            };
+            let components: Vec<String> = color
+                .rgb
+                .iter()
+                .chain(color.alpha.iter())
+                .map(f64::to_string)
+                .collect();
+
+            [format!("color-{format}"), components.join(",")]
+        }
+        TypedValue::String(value) => ["string".into(), value],
+        TypedValue::Float(value) => ["float".into(), value.to_string()],
+        TypedValue::Int(value) => ["int".into(), value.to_string()],
+    }
+}
+
+fn parse_typed(kind: &str, value: &str) -> Option<TypedValue> {
+    let color = |format| {
+        let components = value
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<f64>, _>>()
+            .ok()?;
+
+        match components.as_slice() {
+            [r, g, b] | [r, g, b, _] => Some(TypedValue::Color(ColorValue {
+                rgb: [*r, *g, *b],
+                alpha: components.get(3).copied(),
+                format,
+            })),
+            _ => None,
+        }
+    };
+
+    match kind {
+        "color-bytes" => color(ColorFormat::Bytes),
+        "color-float" => color(ColorFormat::Float),
+        "string" => Some(TypedValue::String(value.to_string())),
+        "float" => value.parse().ok().map(TypedValue::Float),
+        "int" => value.parse().ok().map(TypedValue::Int),
+        _ => None,
+    }
+}
+
+fn setting_record(setting: &SettingValue) -> Option<Vec<String>> {
+    let meta = setting.meta();
+    let mut fields = vec![
+        "setting".to_string(),
+        path_text(meta.source_config())?,
+        origin_name(meta.origin()).to_string(),
+        meta.line().map(|line| line.to_string()).unwrap_or_default(),
+        meta.comment().to_string(),
+        meta.inline_comment().to_string(),
+    ];
+
+    let (state, setting) = match setting {
+        SettingValue::Disabled(inner) => ("disabled", &**inner),
+        setting => ("enabled", setting),
+    };
+    fields.push(state.to_string());
+
+    let directory = |kind: &str, dir: &DirectorySetting| {
+        Some(vec![
+            kind.to_string(),
+            dir.original().clone(),
+            path_text(dir.base())?,
+        ])
+    };
+    let file = |kind: &str, file: &FileSetting| vec![kind.to_string(), file.value().clone()];
+
+    fields.extend(match setting {
+        SettingValue::DataDirectory(dir) => directory("data", dir)?,
+        SettingValue::UserData(dir) => directory("user-data", dir)?,
+        SettingValue::DataLocal(dir) => directory("data-local", dir)?,
+        SettingValue::Resources(dir) => directory("resources", dir)?,
+        SettingValue::SubConfiguration(dir) => directory("config", dir)?,
+        SettingValue::Encoding(encoding) => vec![
+            "encoding".to_string(),
+            encoding.encoding_type().as_str().to_string(),
+        ],
+        SettingValue::ContentFile(plugin) => file("content", plugin),
+        SettingValue::BethArchive(archive) => file("fallback-archive", archive),
+        SettingValue::Groundcover(grass) => file("groundcover", grass),
+        SettingValue::Generic(generic) => vec![
+            "generic".to_string(),
+            generic.key().clone(),
+            generic.value().clone(),
+        ],
+        SettingValue::Flag(flag) => vec![
+            "flag".to_string(),
+            flag.key().clone(),
+            if flag.has_equals() { "=" } else { "" }.to_string(),
+        ],
+        SettingValue::GameSetting(game_setting) => {
+            let [kind, value] = typed_fields(game_setting.typed_value());
+            vec![
+                "fallback".to_string(),
+                game_setting.key().clone(),
+                kind,
+                value,
+            ]
+        }
+        SettingValue::Disabled(_) => return None,
+    });
+
+    Some(fields)
+}
+
+fn read_setting(fields: &[String]) -> Option<SettingValue> {
+    let [
+        source,
+        origin,
+        line,
+        comment,
+        inline_comment,
+        state,
+        kind,
+        payload @ ..,
+    ] = fields
+    else {
+        return None;
+    };
+
+    let source = PathBuf::from(source);
+    let meta = GameSettingMeta {
+        source_config: source.clone(),
+        origin: parse_origin(origin)?,
+        comment: comment.clone(),
+        inline_comment: inline_comment.clone(),
+        handle: SettingHandle::next(),
+        line: match line.as_str() {
+            "" => None,
+            line => Some(line.parse().ok()?),
+        },
+    };
+
+    // Goes through the constructor so path tokens resolve under the current paths provider
+    let directory = |original: &String, base: &String| {
+        DirectorySetting::with_queued_comment(
+            original.clone(),
+            PathBuf::from(base),
+            &mut String::new(),
+        )
+    };
+
+    let mut setting = match (kind.as_str(), payload) {
+        ("data", [original, base]) => SettingValue::DataDirectory(directory(original, base)),
+        ("user-data", [original, base]) => SettingValue::UserData(directory(original, base)),
+        ("data-local", [original, base]) => SettingValue::DataLocal(directory(original, base)),
+        ("resources", [original, base]) => SettingValue::Resources(directory(original, base)),
+        ("config", [original, base]) => SettingValue::SubConfiguration(directory(original, base)),
+        ("encoding", [encoding]) => {
+            SettingValue::Encoding(EncodingSetting::new(encoding.parse().ok()?))
+        }
+        ("content", [name]) => SettingValue::ContentFile(FileSetting::new(name, &source)),
+        ("fallback-archive", [name]) => SettingValue::BethArchive(FileSetting::new(name, &source)),
+        ("groundcover", [name]) => SettingValue::Groundcover(FileSetting::new(name, &source)),
+        ("generic", [key, value]) => {
+            SettingValue::Generic(GenericSetting::new(key, value, &source))
+        }
+        ("flag", [key, equals]) => {
+            let flag = FlagSetting::new(key, &source);
+            SettingValue::Flag(match equals.as_str() {
+                "=" => flag.with_equals(),
+                _ => flag,
+            })
+        }
+        ("fallback", [key, kind, value]) => SettingValue::GameSetting(GameSettingType::from_typed(
+            key.clone(),
+            parse_typed(kind, value)?,
+            meta.clone(),
+        )),
+        _ => return None,
+    };
+    *setting.meta_mut() = meta;
+
+    match state.as_str() {
+        "enabled" => Some(setting),
+        "disabled" => Some(SettingValue::Disabled(Box::new(setting))),
+        _ => None,
+    }
+}
+
+/// Settings first, since document lines refer back to them by index
+fn chain_records(
+    settings: &[SettingValue],
+    documents: &Documents,
+    serializer_info: &[SerializerInfo],
+) -> Option<Vec<Vec<String>>> {
+    let mut records = settings
+        .iter()
+        .map(setting_record)
+        .collect::<Option<Vec<_>>>()?;
+
+    for info in serializer_info {
+        records.push(vec![
+            "footer".to_string(),
+            path_text(&info.config)?,
+            info.version.clone(),
+        ]);
+    }
+
+    let positions: HashMap<SettingHandle, usize> = settings
+        .iter()
+        .enumerate()
+        .map(|(index, setting)| (setting.handle(), index))
+        .collect();
+
+    for (path, document) in documents.iter() {
+        records.push(vec![
+            "document".to_string(),
+            path_text(path)?,
+            document.is_crlf().to_string(),
+            document.has_final_newline().to_string(),
+        ]);
+
+        for line in document.lines() {
+            records.push(match line {
+                DocumentLine::Blank(raw) => vec!["blank".to_string(), raw.clone()],
+                DocumentLine::Comment(raw) => vec!["comment".to_string(), raw.clone()],
+                DocumentLine::Other(raw) => vec!["other".to_string(), raw.clone()],
+                DocumentLine::Setting { handle, raw, .. } => vec![
+                    "line".to_string(),
+                    positions.get(handle)?.to_string(),
+                    raw.clone(),
+                ],
+            });
+        }
+    }
+
+    Some(records)
+}
+
+fn read_chain(records: &[Vec<String>]) -> Option<ParsedChain> {
+    let mut settings = Vec::new();
+    let mut serializer_info = Vec::new();
+    let mut documents = Documents::default();
+    let mut document: Option<(PathBuf, Vec<DocumentLine>, bool, bool)> = None;
+
+    let mut finish_document = |document: Option<(PathBuf, Vec<DocumentLine>, bool, bool)>| {
+        if let Some((path, lines, crlf, final_newline)) = document {
+            documents.insert(path, Document::with_lines(lines, crlf, final_newline));
+        }
+    };
+
+    for record in records {
+        let (kind, fields) = record.split_first()?;
+
+        match (kind.as_str(), fields) {
+            ("setting", fields) => settings.push(read_setting(fields)?),
+            ("footer", [config, version]) => serializer_info.push(SerializerInfo {
+                config: PathBuf::from(config),
+                version: version.clone(),
+            }),
+            ("document", [path, crlf, final_newline]) => finish_document(document.replace((
+                PathBuf::from(path),
+                Vec::new(),
+                crlf.parse().ok()?,
+                final_newline.parse().ok()?,
+            ))),
+            (line_kind, fields) => {
+                let (_, lines, _, _) = document.as_mut()?;
+                lines.push(match (line_kind, fields) {
+                    ("blank", [raw]) => DocumentLine::Blank(raw.clone()),
+                    ("comment", [raw]) => DocumentLine::Comment(raw.clone()),
+                    ("other", [raw]) => DocumentLine::Other(raw.clone()),
+                    ("line", [index, raw]) => DocumentLine::Setting {
+                        handle: settings
+                            .get(index.parse::<usize>().ok()?)
+                            .map(SettingValue::handle)?,
+                        raw: raw.clone(),
+                        rendered: String::new(),
+                    },
+                    _ => return None,
+                });
+            }
+        }
+    }
+    finish_document(document);
+
+    Some(ParsedChain {
+        settings,
+        documents,
+        serializer_info,
+    })
+}
+
+impl ConfigCache {
+    /// A missing or unreadable cache just means starting from scratch
+    fn read(cache_path: &Path) -> Self {
+        let (files, chain) = std::fs::read_to_string(cache_path)
+            .ok()
+            .and_then(|contents| Self::parse(&contents))
+            .unwrap_or_default();
+
+        Self {
+            previous: files
+                .into_iter()
+                .map(|file| (file.path.clone(), file))
+                .collect(),
+            chain,
+            ..Default::default()
+        }
+    }
+
+    fn parse(contents: &str) -> Option<(Vec<CachedFile>, Option<CachedChain>)> {
+        let mut lines = contents.lines();
+        if lines.next()? != HEADER {
+            return None;
+        }
+
+        let mut files = Vec::new();
+        let mut chain: Option<CachedChain> = None;
+
+        for line in lines {
+            let fields = line
+                .split('\t')
+                .map(unescape)
+                .collect::<Option<Vec<String>>>()?;
+
+            match fields.as_slice() {
+                [kind, path, modified, len, text] if kind == "file" => files.push(CachedFile {
+                    path: PathBuf::from(path),
+                    stamp: FileStamp {
+                        modified: modified.parse().ok()?,
+                        len: len.parse().ok()?,
+                    },
+                    text: text.clone(),
+                }),
+                [kind, root] if kind == "root" => {
+                    chain = Some(CachedChain {
+                        root: PathBuf::from(root),
+                        ..Default::default()
+                    })
+                }
+                [kind, path] if kind == "missing" => chain.as_mut()?.missing.push(path.into()),
+                _ => chain.as_mut()?.records.push(fields),
+            }
+        }
+
+        Some((files, chain))
+    }
+
+    /// The cached text for this file, as long as it hasn't changed on disk since
+    pub(crate) fn hit(&mut self, fs: &dyn ConfigFs, path: &Path) -> Option<String> {
+        let cached = self.previous.remove(path)?;
+
+        if FileStamp::of(fs, path) != Some(cached.stamp) {
+            return None;
+        }
+
+        let text = cached.text.clone();
+        self.loaded.push(cached);
+        Some(text)
+    }
+
+    /// Files which don't have a stamp, ie couldn't be stat'd, aren't worth caching
+    pub(crate) fn store(&mut self, path: &Path, stamp: Option<FileStamp>, text: &str) {
+        self.stale = true;
+
+        match stamp {
+            Some(stamp) => self.loaded.push(CachedFile {
+                path: path.to_path_buf(),
+                stamp,
+                text: text.to_string(),
+            }),
+            None => self.unstamped = true,
+        }
+    }
+
+    /// A `config=` line pointed at a directory without an openmw.cfg, which is only skipped while it stays that way
+    pub(crate) fn missing(&mut self, cfg_path: &Path) {
+        self.missing.push(cfg_path.to_path_buf());
+    }
+
+    /// The cached chain, if it starts at `root` and none of the files it was parsed from changed since
+    fn restore(&mut self, fs: &dyn ConfigFs, root: &Path) -> Option<ParsedChain> {
+        let chain = self.chain.as_ref()?;
+        let current = chain.root == root
+            && !self.previous.is_empty()
+            && self
+                .previous
+                .values()
+                .all(|file| FileStamp::of(fs, &file.path) == Some(file.stamp))
+            && chain.missing.iter().all(|path| !fs.exists(path));
+
+        if !current {
+            return None;
+        }
+
+        let parsed = read_chain(&chain.records)?;
+        self.loaded
+            .extend(self.previous.drain().map(|(_, file)| file));
+        Some(parsed)
+    }
+
+    fn keep_chain(
+        &mut self,
+        root: &Path,
+        settings: &[SettingValue],
+        documents: &Documents,
+        serializer_info: &[SerializerInfo],
+    ) {
+        self.stale = true;
+        self.chain = chain_records(settings, documents, serializer_info)
+            .filter(|_| !self.unstamped)
+            .map(|records| CachedChain {
+                root: root.to_path_buf(),
+                missing: std::mem::take(&mut self.missing),
+                records,
+            });
+    }
+
+    /// Leftover entries belong to files which dropped out of the chain
+    fn needs_writing(&self) -> bool {
+        self.stale || !self.previous.is_empty()
+    }
+
+    fn write(&self, cache_path: &Path) -> std::io::Result<()> {
+        let mut contents = format!("{HEADER}\n");
+        let mut push_record = |fields: &[String]| {
+            let fields: Vec<String> = fields.iter().map(|field| escape(field)).collect();
+            contents.push_str(&fields.join("\t"));
+            contents.push('\n');
+        };
+
+        for file in &self.loaded {
+            push_record(&[
+                "file".to_string(),
+                file.path.to_string_lossy().into_owned(),
+                file.stamp.modified.to_string(),
+                file.stamp.len.to_string(),
+                file.text.clone(),
+            ]);
+        }
+
+        if let Some(chain) = &self.chain {
+            push_record(&[
+                "root".to_string(),
+                chain.root.to_string_lossy().into_owned(),
+            ]);
+            for path in &chain.missing {
+                push_record(&["missing".to_string(), path.to_string_lossy().into_owned()]);
+            }
+            chain.records.iter().for_each(|record| push_record(record));
+        }
+
+        if let Some(parent) = cache_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(cache_path, contents)
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Puts back the settings `load_cached` stored last time, if none of the files they came from changed since
+    pub(crate) fn restore_cached_chain(&mut self) -> bool {
+        let fs = self.fs.clone();
+        let Some(parsed) = self
+            .cache
+            .as_mut()
+            .and_then(|cache| cache.restore(&*fs, &self.root_config))
+        else {
+            return false;
+        };
+
+        self.settings = Arc::new(parsed.settings);
+        self.documents = parsed.documents;
+        self.documents.finish(&self.settings);
+        self.serializer_info = parsed.serializer_info;
+        true
+    }
+
+    /// Keeps what the chain was just parsed into, for the cache file to hold
+    pub(crate) fn cache_loaded_chain(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            cache.keep_chain(
+                &self.root_config,
+                &self.settings,
+                &self.documents,
+                &self.serializer_info,
+            );
+        }
+    }
+
+    /// Like `new`, but keeps the parsed chain at `cache_path`, so the next call can skip parsing if none of its files changed.
+    /// Meant for tools that get run over and over on the same setup, like mod managers or scripts.
+    /// The cache is best-effort: if it can't be read the chain is loaded normally, and if it can't be written that's only logged.
+    pub fn load_cached(
+        path: Option<PathBuf>,
+        cache_path: impl AsRef<Path>,
+    ) -> Result<Self, ConfigError> {
+        let cache_path = cache_path.as_ref();
+        let mut config = Self::open(
+            path,
+            ParseOptions::default(),
+            Some(ConfigCache::read(cache_path)),
+        )?;
+
+        if let Some(cache) = config.cache.take()
+            && cache.needs_writing()
+            && let Err(error) = cache.write(cache_path)
+        {
+            util::debug_log(format_args!(
+                "WARNING: Failed to write config cache at {cache_path:?}: {error}"
+            ));
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{OpenMWConfiguration, document::DocumentLine, util::temp_config};
+
+    #[test]
+    fn test_cached_load_skips_parsing_unchanged_chain() {
+        let root = temp_config("cached_load", "");
+        let child = root.join("child");
+        let later = root.join("later");
+        std::fs::create_dir_all(&child).unwrap();
+        std::fs::create_dir_all(&later).unwrap();
+        std::fs::write(
+            child.join("openmw.cfg"),
+            "# mine\ncontent=Child.esp\nfallback=Water_Alpha,1.0\nfallback=Sky,10,20,30\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("openmw.cfg"),
+            format!(
+                "content=Morrowind.esm\r\n#omwcfg:disabled content=Old.esp\r\nconfig={}\r\nconfig={}\r\n",
+                child.display(),
+                later.display()
+            ),
+        )
+        .unwrap();
+        let cache_path = root.join("cache").join("chain.cache");
+
+        let fresh = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        let first = OpenMWConfiguration::load_cached(Some(root.clone()), &cache_path).unwrap();
+        let second = OpenMWConfiguration::load_cached(Some(root.clone()), &cache_path).unwrap();
+        let raw_lines = |config: &OpenMWConfiguration| {
+            config
+                .document(&root.join("openmw.cfg"))
+                .unwrap()
+                .lines()
+                .iter()
+                .map(DocumentLine::raw)
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        for cached in [&first, &second] {
+            assert_eq!(cached.to_string(), fresh.to_string());
+            assert_eq!(raw_lines(cached), raw_lines(&fresh));
+            assert_eq!(
+                cached.source_text(&root.join("openmw.cfg")),
+                fresh.source_text(&root.join("openmw.cfg"))
+            );
+        }
+        assert_eq!(second.game_setting_float("Water_Alpha").unwrap(), Some(1.0));
+
+        // Doctoring only the stored settings proves the third load never parsed the files
+        let cached = std::fs::read_to_string(&cache_path).unwrap();
+        let doctored: Vec<String> = cached
+            .lines()
+            .map(|line| match line.starts_with("setting\t") {
+                true => line.replace("Child.esp", "Cache.esp"),
+                false => line.to_string(),
+            })
+            .collect();
+        std::fs::write(&cache_path, doctored.join("\n")).unwrap();
+        let third = OpenMWConfiguration::load_cached(Some(root.clone()), &cache_path).unwrap();
+        assert_eq!(third.content_files(), vec!["Morrowind.esm", "Cache.esp"]);
+
+        // Whereas a `config=` directory gaining an openmw.cfg gets the chain parsed again
+        std::fs::write(later.join("openmw.cfg"), "content=Later.esp\n").unwrap();
+        let fourth = OpenMWConfiguration::load_cached(Some(root.clone()), &cache_path).unwrap();
+        assert_eq!(
+            fourth.content_files(),
+            vec!["Morrowind.esm", "Child.esp", "Later.esp"]
+        );
+
+        // As does a file which changed size
+        std::fs::write(child.join("openmw.cfg"), "content=Changed.esp\n").unwrap();
+        let fifth = OpenMWConfiguration::load_cached(Some(root), &cache_path).unwrap();
+        assert_eq!(
+            fifth.content_files(),
+            vec!["Morrowind.esm", "Changed.esp", "Later.esp"]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ConfigError, SettingHandle, SettingOrigin, bail_config,
+    config::{
+        DirectorySetting, EncodingSetting, FileSetting, GenericSetting, OpenMWConfiguration,
+        ParseOptions, SettingValue, strings, util,
+    },
+};
+
+/// Settings which came from `apply_cli_args` report this as their source config,
+/// Which also guarantees they are never written back out by `save_user` or `save_subconfig`.
+pub const CLI_SOURCE: &str = "<command line>";
+
+impl OpenMWConfiguration {
+    /// Emulates the engine's command line, which may contain the same options as openmw.cfg.
+    /// Both `--key value` and `--key=value` forms are accepted, and options may be given several values, eg:
+    /// `--data /mods/One /mods/Two --content One.esp Two.esp --replace content`
+    ///
+    /// Arguments are applied in order, on top of everything which was loaded from disk,
+    /// So they take priority over every openmw.cfg in the chain.
+    /// Relative paths are resolved against the current working directory, as the engine would.
+    /// Valueless switches such as `--skip-menu` become generic settings with the value `true`.
+    pub fn apply_cli_args<S: AsRef<str>>(&mut self, args: &[S]) -> Result<(), ConfigError> {
+        let mut options: Vec<(String, Vec<String>)> = Vec::new();
+
+        for arg in args {
+            let arg = arg.as_ref();
+
+            if let Some(option) = arg.strip_prefix("--") {
+                match option.split_once('=') {
+                    Some((key, value)) => options.push((key.to_string(), vec![value.to_string()])),
+                    None => options.push((option.to_string(), Vec::new())),
+                }
+            } else {
+                match options.last_mut() {
+                    Some((_, values)) => values.push(arg.to_string()),
+                    None => bail_config!(invalid_cli_argument, arg),
+                }
+            }
+        }
+
+        let cwd = std::env::current_dir()?;
+        let source = PathBuf::from(CLI_SOURCE);
+        let first_new = SettingHandle::upcoming();
+
+        for (key, values) in options {
+            if values.is_empty() {
+                self.push_setting(SettingValue::Generic(GenericSetting::new(
+                    &key, "true", &source,
+                )));
+                continue;
+            }
+
+            for value in values {
+                self.apply_cli_value(&key, value, &cwd, &source)?;
+            }
+        }
+
+        // Files pulled in with --config keep their own origin
+        self.settings_mut()
+            .iter_mut()
+            .filter(|setting| {
+                setting.handle() >= first_new && setting.meta().origin() == SettingOrigin::Api
+            })
+            .for_each(|setting| setting.meta_mut().origin = SettingOrigin::Cli);
+
+        Ok(())
+    }
+
+    fn apply_cli_value(
+        &mut self,
+        key: &str,
+        value: String,
+        cwd: &Path,
+        source: &Path,
+    ) -> Result<(), ConfigError> {
+        let cli_dir = |value: String| {
+            let mut setting = DirectorySetting::new(value, cwd.to_path_buf());
+            setting.meta.source_config = source.to_path_buf();
+            setting
+        };
+
+        match key {
+            "data" => self.push_setting(SettingValue::DataDirectory(cli_dir(value))),
+            "data-local" => self.set_data_local(Some(cli_dir(value))),
+            "resources" => self.set_resources(Some(cli_dir(value))),
+            "user-data" => self.set_userdata(Some(cli_dir(value))),
+            "content" => {
+                if self.has_content_file(&value) {
+                    bail_config!(duplicate_content_file, value, source)
+                }
+
+                self.push_setting(SettingValue::ContentFile(FileSetting::new(&value, source)))
+            }
+            "groundcover" => {
+                if self.has_groundcover_file(&value) {
+                    bail_config!(duplicate_groundcover_file, value, source)
+                }
+
+                self.push_setting(SettingValue::Groundcover(FileSetting::new(&value, source)))
+            }
+            "fallback-archive" => {
+                if self.has_archive_file(&value) {
+                    bail_config!(duplicate_archive_file, value, source)
+                }
+
+                self.push_setting(SettingValue::BethArchive(FileSetting::new(&value, source)))
+            }
+            "fallback" => {
+                self.set_game_setting(&value, Some(source.to_path_buf()), &mut String::new())?
+            }
+            "encoding" => self.set_encoding(Some(EncodingSetting::try_from((
+                value,
+                source,
+                &mut String::new(),
+            ))?)),
+            "replace" => self.apply_replace(&value)?,
+            "config" => {
+                let config_path = util::input_config_path(
+                    &*self.fs,
+                    &strings::parse_data_directory(&cwd, value.clone()),
+                )?;
+
+                self.push_setting(SettingValue::SubConfiguration(cli_dir(value)));
+                self.load(&config_path, &ParseOptions::default())?;
+            }
+            _ => self.push_setting(SettingValue::Generic(GenericSetting::new(
+                key, &value, source,
+            ))),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameSetting, config::util::temp_config};
+
+    #[test]
+    fn test_cli_args_layer_on_top() {
+        let dir = temp_config("cli_layering", "data=Base\ncontent=Morrowind.esm\n");
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+
+        config
+            .apply_cli_args(&[
+                "--data=/cli/One".to_string(),
+                "--content".to_string(),
+                "One.esp".to_string(),
+                "Two.esp".to_string(),
+                "--skip-menu".to_string(),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "One.esp", "Two.esp"]
+        );
+        assert_eq!(
+            config.data_directories().last(),
+            Some(&&PathBuf::from("/cli/One"))
+        );
+
+        let cli_plugin = config.content_files_iter().last().unwrap();
+        assert_eq!(cli_plugin.meta().source_config(), Path::new(CLI_SOURCE));
+
+        assert!(config.settings_matching(|setting| matches!(setting, SettingValue::Generic(generic) if generic.to_string() == "skip-menu=true")).next().is_some());
+    }
+
+    #[test]
+    fn test_cli_replace_drops_file_entries() {
+        let dir = temp_config(
+            "cli_replace",
+            "content=Morrowind.esm\ncontent=Tribunal.esm\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        config
+            .apply_cli_args(&["--replace", "content", "--content", "Other.esp"])
+            .unwrap();
+
+        assert_eq!(config.content_files(), vec!["Other.esp"]);
+    }
+
+    #[test]
+    fn test_cli_rejects_dangling_value() {
+        let dir = temp_config("cli_dangling", "");
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        assert!(matches!(
+            config.apply_cli_args(&["Morrowind.esm"]),
+            Err(ConfigError::InvalidCliArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_cli_duplicate_content_is_rejected() {
+        let dir = temp_config("cli_duplicate", "content=Morrowind.esm\n");
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        assert!(matches!(
+            config.apply_cli_args(&["--content=Morrowind.esm"]),
+            Err(ConfigError::DuplicateContentFile { .. })
+        ));
+    }
+}
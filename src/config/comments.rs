@@ -0,0 +1,203 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+    SettingHandle,
+    config::{OpenMWConfiguration, events::ConfigNotification},
+};
+
+/// Prefix of every marker comment this crate writes
+pub const OMWCFG_MARKER: &str = "#omwcfg:";
+
+/// Starts the comment a disabled entry is written as, followed by the line it would be otherwise
+pub const DISABLED_MARKER: &str = "#omwcfg:disabled";
+
+/// Tools which want their comments recognized as generated should start them with this
+pub const MANAGED_BY_MARKER: &str = "# managed-by:";
+
+/// Footer written after the composite configuration, followed by the crate version
+pub const SERIALIZER_FOOTER: &str = "# OpenMW-Config Serializer Version:";
+
+/// Lines which are known to be written by something other than a person
+const GENERATED_LINES: [&str; 3] = [
+    SERIALIZER_FOOTER,
+    "# This is the user openmw.cfg.",
+    "# This is the global openmw.cfg.",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentAuthor {
+    User,
+    Tool,
+}
+
+impl CommentAuthor {
+    /// Classifies a single comment line.
+    /// Anything which isn't clearly generated is assumed to belong to the user.
+    pub fn of_line(line: &str) -> Self {
+        let line = line.trim();
+        let lowercase = line.to_lowercase();
+
+        if line.starts_with(OMWCFG_MARKER)
+            || lowercase.starts_with(MANAGED_BY_MARKER)
+            || lowercase.starts_with("# managed by")
+            || GENERATED_LINES
+                .iter()
+                .any(|generated| line.starts_with(generated))
+        {
+            CommentAuthor::Tool
+        } else {
+            CommentAuthor::User
+        }
+    }
+}
+
+/// A run of comment lines without blank lines in between
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommentParagraph<'a> {
+    pub author: CommentAuthor,
+    pub text: &'a str,
+}
+
+/// Splits a setting's comment into paragraphs and classifies each of them.
+/// A paragraph counts as tool-generated if any of its lines carries a marker,
+/// Since tools typically put their marker at the top of a block they own.
+pub fn classify_comment(comment: &str) -> Vec<CommentParagraph<'_>> {
+    let mut paragraphs = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+
+    for line in comment.split_inclusive('\n') {
+        let is_blank = line.trim().is_empty();
+
+        match (start, is_blank) {
+            (None, false) => start = Some(offset),
+            (Some(begin), true) => {
+                paragraphs.push(&comment[begin..offset]);
+                start = None;
+            }
+            _ => {}
+        }
+
+        offset += line.len();
+    }
+
+    if let Some(begin) = start {
+        paragraphs.push(&comment[begin..]);
+    }
+
+    paragraphs
+        .into_iter()
+        .map(|text| CommentParagraph {
+            author: match text
+                .lines()
+                .any(|line| CommentAuthor::of_line(line) == CommentAuthor::Tool)
+            {
+                true => CommentAuthor::Tool,
+                false => CommentAuthor::User,
+            },
+            text,
+        })
+        .collect()
+}
+
+impl OpenMWConfiguration {
+    /// The classified comment preceding each setting which has one
+    pub fn comment_blocks(
+        &self,
+    ) -> impl Iterator<Item = (SettingHandle, Vec<CommentParagraph<'_>>)> {
+        self.settings
+            .iter()
+            .map(|setting| (setting.handle(), classify_comment(&setting.meta().comment)))
+            .filter(|(_, paragraphs)| !paragraphs.is_empty())
+    }
+
+    /// Removes every tool-generated paragraph from every comment, leaving user prose and blank lines where they were.
+    /// Note this also drops markers the crate relies on, such as the Morrowind.ini origin of imported fallbacks.
+    pub fn strip_tool_comments(&mut self) {
+        let mut changed = Vec::new();
+
+        for (index, setting) in self.settings_mut().iter_mut().enumerate() {
+            let comment = &setting.meta().comment;
+            let mut stripped = comment.clone();
+
+            for paragraph in classify_comment(comment).iter().rev() {
+                if paragraph.author == CommentAuthor::Tool {
+                    // Paragraphs are always subslices of the comment they came from
+                    let start = paragraph.text.as_ptr() as usize - comment.as_ptr() as usize;
+                    stripped.replace_range(start..start + paragraph.text.len(), "");
+                }
+            }
+
+            if stripped != *comment {
+                setting.meta_mut().comment = stripped;
+                changed.push(index);
+            }
+        }
+
+        for index in changed {
+            self.notify(ConfigNotification::SettingChanged(&self.settings[index]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_classify_paragraphs() {
+        let comment = "# My favourite mods\n\n# managed-by: some-tool\n# generated list\n\n";
+        let paragraphs = classify_comment(comment);
+
+        assert_eq!(
+            paragraphs,
+            vec![
+                CommentParagraph {
+                    author: CommentAuthor::User,
+                    text: "# My favourite mods\n"
+                },
+                CommentParagraph {
+                    author: CommentAuthor::Tool,
+                    text: "# managed-by: some-tool\n# generated list\n"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_marker_lines() {
+        assert_eq!(
+            CommentAuthor::of_line("#omwcfg:ini-origin [General] Key=1"),
+            CommentAuthor::Tool
+        );
+        assert_eq!(
+            CommentAuthor::of_line("# Managed by OpenMW-Launcher"),
+            CommentAuthor::Tool
+        );
+        assert_eq!(
+            CommentAuthor::of_line("# I manage these by hand"),
+            CommentAuthor::User
+        );
+    }
+
+    #[test]
+    fn test_strip_tool_comments_keeps_user_prose() {
+        let dir = temp_config(
+            "strip_tool_comments",
+            "# keep me\n\n# managed-by: installer\ndata=Mods\n# also mine\ncontent=Foo.esp\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        config.strip_tool_comments();
+
+        let comments: Vec<&str> = config
+            .settings_with_handles()
+            .map(|(_, setting)| setting.meta().comment())
+            .collect();
+
+        assert_eq!(comments, vec!["# keep me\n\n", "# also mine\n"]);
+    }
+}
@@ -0,0 +1,219 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Which loose files in the data directories hide each other, for a mod manager's conflicts tab.
+//! Paths are compared the way the engine looks them up, ignoring case and separators,
+//! And a later data directory always wins over an earlier one.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::config::OpenMWConfiguration;
+
+/// One virtual path provided by more than one data directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileConflict {
+    /// Lowercase, with forward slashes, eg `meshes/xbase_anim.nif`
+    pub virtual_path: String,
+    /// The copy the engine loads
+    pub winner: PathBuf,
+    /// Every other copy, in priority order
+    pub overridden: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryConflicts {
+    pub directory: PathBuf,
+    /// Files from here which replace ones from earlier directories
+    pub overrides: usize,
+    /// Files from here which later directories replace, so they never load
+    pub overridden: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConflictReport {
+    /// Sorted by virtual path
+    pub files: Vec<FileConflict>,
+    /// Every data directory in priority order, conflicting or not
+    pub directories: Vec<DirectoryConflicts>,
+}
+
+impl ConflictReport {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn for_directory(&self, directory: &Path) -> Option<&DirectoryConflicts> {
+        self.directories
+            .iter()
+            .find(|conflicts| conflicts.directory == directory)
+    }
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_files(root, &path, files);
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let virtual_path = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().to_lowercase())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            files.push((virtual_path, path));
+        }
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Every loose file that more than one data directory provides.
+    /// A directory listed more than once only counts at its last, highest priority position.
+    pub fn conflicts(&self) -> ConflictReport {
+        self.conflicts_matching(|_| true)
+    }
+
+    /// Same as `conflicts`, limited to files with one of the given extensions, eg `["nif", "dds"]`
+    pub fn conflicts_filtered(&self, extensions: &[&str]) -> ConflictReport {
+        self.conflicts_matching(|virtual_path| {
+            Path::new(virtual_path)
+                .extension()
+                .is_some_and(|extension| {
+                    extensions
+                        .iter()
+                        .any(|wanted| extension.eq_ignore_ascii_case(wanted))
+                })
+        })
+    }
+
+    fn conflicts_matching(&self, include: impl Fn(&str) -> bool) -> ConflictReport {
+        let mut seen = HashSet::new();
+        let mut directories: Vec<&PathBuf> = self
+            .data_directories()
+            .into_iter()
+            .rev()
+            .filter(|dir| seen.insert(*dir))
+            .collect();
+        directories.reverse();
+
+        // Each virtual path with the directories providing it, lowest priority first
+        let mut providers: BTreeMap<String, Vec<(usize, PathBuf)>> = BTreeMap::new();
+        for (index, dir) in directories.iter().enumerate() {
+            let mut files = Vec::new();
+            collect_files(dir, dir, &mut files);
+
+            files
+                .into_iter()
+                .filter(|(virtual_path, _)| include(virtual_path))
+                .for_each(|(virtual_path, path)| {
+                    providers
+                        .entry(virtual_path)
+                        .or_default()
+                        .push((index, path))
+                });
+        }
+
+        let mut report = ConflictReport {
+            files: Vec::new(),
+            directories: directories
+                .iter()
+                .map(|dir| DirectoryConflicts {
+                    directory: dir.to_path_buf(),
+                    overrides: 0,
+                    overridden: 0,
+                })
+                .collect(),
+        };
+
+        for (virtual_path, mut copies) in providers {
+            // Directories with names differing only by case can put two copies in one directory
+            copies.dedup_by_key(|(index, _)| *index);
+            if copies.len() < 2 {
+                continue;
+            }
+
+            let (winner_index, winner) = copies.pop().expect("at least two copies");
+            report.directories[winner_index].overrides += 1;
+            copies
+                .iter()
+                .for_each(|(index, _)| report.directories[*index].overridden += 1);
+
+            report.files.push(FileConflict {
+                virtual_path,
+                winner,
+                overridden: copies.into_iter().rev().map(|(_, path)| path).collect(),
+            });
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_conflict_report() {
+        let dir = temp_config("conflicts", "data=Base\ndata=Retexture\ndata=Patch\n");
+
+        for (data_dir, file) in [
+            ("Base", "Meshes/Foo.nif"),
+            ("Base", "Textures/Foo.dds"),
+            ("Base", "Textures/Bar.dds"),
+            ("Retexture", "textures/foo.DDS"),
+            ("Patch", "textures/Foo.dds"),
+            ("Patch", "meshes/foo.nif"),
+        ] {
+            let path = dir.join(data_dir).join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let report = config.conflicts();
+
+        assert_eq!(
+            report.files,
+            vec![
+                FileConflict {
+                    virtual_path: "meshes/foo.nif".into(),
+                    winner: dir.join("Patch/meshes/foo.nif"),
+                    overridden: vec![dir.join("Base/Meshes/Foo.nif")],
+                },
+                FileConflict {
+                    virtual_path: "textures/foo.dds".into(),
+                    winner: dir.join("Patch/textures/Foo.dds"),
+                    overridden: vec![
+                        dir.join("Retexture/textures/foo.DDS"),
+                        dir.join("Base/Textures/Foo.dds"),
+                    ],
+                },
+            ]
+        );
+
+        let retexture = report.for_directory(&dir.join("Retexture")).unwrap();
+        assert_eq!((retexture.overrides, retexture.overridden), (0, 1));
+        let base = report.for_directory(&dir.join("Base")).unwrap();
+        assert_eq!((base.overrides, base.overridden), (0, 2));
+
+        let textures = config.conflicts_filtered(&["DDS"]);
+        assert_eq!(textures.files.len(), 1);
+        assert_eq!(
+            textures
+                .for_directory(&dir.join("Patch"))
+                .unwrap()
+                .overrides,
+            1
+        );
+    }
+}
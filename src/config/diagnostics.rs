@@ -0,0 +1,284 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Errors and validation issues rendered as annotated snippets of the openmw.cfg they're about, like rustc does.
+
+use std::{
+    fmt,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ConfigError, GameSetting,
+    config::{
+        OpenMWConfiguration, SettingValue,
+        fs::{ConfigFs, StdFs},
+        messages::{Message, MessageId},
+        validation::{Issue, Severity},
+    },
+};
+
+/// Every key the parser gives special meaning to. Anything else is kept as a generic setting.
+pub const KNOWN_KEYS: &[&str] = &[
+    "config",
+    "content",
+    "data",
+    "data-local",
+    "encoding",
+    "fallback",
+    "fallback-archive",
+    "groundcover",
+    "replace",
+    "resources",
+    "user-data",
+];
+
+/// Where a diagnostic points to in its openmw.cfg
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub path: PathBuf,
+    /// Counting from 1
+    pub line: usize,
+    /// The whole line, as it currently is on disk
+    pub text: String,
+    /// Which bytes of `text` to underline
+    pub span: Range<usize>,
+}
+
+impl SourceLocation {
+    /// Reads the line back from the file, since nothing keeps the raw text around after parsing.
+    /// Directory settings record the directory rather than the file, so that's accepted too.
+    fn read(fs: &dyn ConfigFs, path: &Path, line: usize) -> Option<Self> {
+        let path = match fs.is_dir(path) {
+            true => path.join("openmw.cfg"),
+            false => path.to_path_buf(),
+        };
+
+        let contents = fs.read(&path).ok()?;
+        let text = String::from_utf8_lossy(&contents)
+            .lines()
+            .nth(line.checked_sub(1)?)?
+            .trim_end()
+            .to_string();
+
+        let start = text.len() - text.trim_start().len();
+        let span = start..text.len().max(start + 1);
+
+        Some(Self {
+            path,
+            line,
+            text,
+            span,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: Message,
+    pub location: Option<SourceLocation>,
+    pub help: Option<Message>,
+}
+
+impl Diagnostic {
+    pub fn from_error(error: &ConfigError) -> Self {
+        let path = match error {
+            ConfigError::InvalidGameSetting { config_path, .. }
+            | ConfigError::BadEncoding { config_path, .. }
+            | ConfigError::InvalidLine { config_path, .. } => Some(config_path),
+            ConfigError::InvalidUtf8 { path, .. } => Some(path),
+            _ => None,
+        };
+
+        Self {
+            severity: Severity::Error,
+            message: error.message(),
+            location: path
+                .zip(error.line())
+                .and_then(|(path, line)| SourceLocation::read(&StdFs, path, line)),
+            help: None,
+        }
+    }
+
+    /// Validation issues point at a setting, which is looked up to find its line
+    pub fn from_issue(issue: &Issue, config: &OpenMWConfiguration) -> Self {
+        let location = issue
+            .setting
+            .and_then(|handle| config.get_setting(&handle))
+            .and_then(|setting| {
+                let meta = setting.meta();
+                SourceLocation::read(&*config.fs, meta.source_config(), meta.line()?)
+            });
+
+        Self {
+            severity: issue.severity,
+            message: issue.message.clone(),
+            location,
+            help: issue.fix.as_ref().map(|fix| fix.description.clone()),
+        }
+    }
+
+    /// The diagnostic as plain text, with the offending line underlined
+    pub fn render(&self) -> String {
+        let mut out = format!("{}: {}\n", self.severity.as_str(), self.message);
+
+        if let Some(location) = &self.location {
+            let number = location.line.to_string();
+            let gutter = " ".repeat(number.len());
+            let underline_width = location.span.end.saturating_sub(location.span.start);
+
+            out.push_str(&format!(
+                "{gutter}--> {}:{}\n",
+                location.path.display(),
+                location.line
+            ));
+            out.push_str(&format!("{gutter} |\n"));
+            out.push_str(&format!("{number} | {}\n", location.text));
+            out.push_str(&format!(
+                "{gutter} | {}{}\n",
+                " ".repeat(location.span.start),
+                "^".repeat(underline_width.max(1))
+            ));
+        }
+
+        if let Some(help) = &self.help {
+            let gutter = " ".repeat(
+                self.location
+                    .as_ref()
+                    .map_or(0, |l| l.line.to_string().len()),
+            );
+            out.push_str(&format!("{gutter} = help: {help}\n"));
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+/// The known key `key` was most likely meant to be, if it's a near miss
+pub fn suggest_key(key: &str) -> Option<&'static str> {
+    KNOWN_KEYS
+        .iter()
+        .map(|known| (*known, edit_distance(key, known)))
+        .filter(|(_, distance)| (1..=2).contains(distance))
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(known, _)| known)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+impl OpenMWConfiguration {
+    /// Every validation issue, plus generic settings whose key looks like a typo of one OpenMW knows
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = self
+            .validate()
+            .iter()
+            .map(|issue| Diagnostic::from_issue(issue, self))
+            .collect();
+
+        for setting in self.settings.iter() {
+            let SettingValue::Generic(generic) = setting else {
+                continue;
+            };
+            let Some(suggestion) = suggest_key(generic.key()) else {
+                continue;
+            };
+
+            let location = generic.meta().line().and_then(|line| {
+                let mut location =
+                    SourceLocation::read(&*self.fs, generic.meta().source_config(), line)?;
+                let key_start = location.span.start;
+                location.span = key_start..key_start + generic.key().len();
+                Some(location)
+            });
+
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                message: Message::new(MessageId::UnknownKey).arg("key", generic.key()),
+                location,
+                help: Some(Message::new(MessageId::DidYouMean).arg("suggestion", suggestion)),
+            });
+        }
+
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ParseOptions, util::temp_config};
+
+    #[test]
+    fn test_suggest_key() {
+        assert_eq!(suggest_key("fallback-archives"), Some("fallback-archive"));
+        assert_eq!(suggest_key("contnet"), Some("content"));
+        assert_eq!(suggest_key("content"), None);
+        assert_eq!(suggest_key("script-blacklist"), None);
+    }
+
+    #[test]
+    fn test_render_typo() {
+        let dir = temp_config(
+            "diagnostics_typo",
+            "content=Morrowind.esm\n  fallback-archives=Morrowind.bsa\n",
+        );
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+
+        let rendered: Vec<String> = config
+            .diagnostics()
+            .iter()
+            .map(Diagnostic::render)
+            .collect();
+        let typo = rendered
+            .iter()
+            .find(|text| text.contains("fallback-archives"))
+            .unwrap();
+
+        assert_eq!(
+            *typo,
+            format!(
+                "warning: fallback-archives is not a setting OpenMW knows\n --> {}:2\n  |\n2 |   fallback-archives=Morrowind.bsa\n  |   ^^^^^^^^^^^^^^^^^\n  = help: did you mean fallback-archive?\n",
+                dir.join("openmw.cfg").display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_error() {
+        let dir = temp_config("diagnostics_error", "content=Morrowind.esm\n=broken\n");
+        let error =
+            OpenMWConfiguration::new_with_options(Some(dir), ParseOptions::strict()).unwrap_err();
+
+        let diagnostic = Diagnostic::from_error(&error);
+        let location = diagnostic.location.as_ref().unwrap();
+        assert_eq!(location.line, 2);
+        assert_eq!(location.text, "=broken");
+        assert!(diagnostic.render().contains("2 | =broken\n  | ^^^^^^^\n"));
+    }
+}
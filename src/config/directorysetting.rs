@@ -3,14 +3,27 @@
 // Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
 
-use crate::config::strings;
-use std::{fmt, path::PathBuf};
+use crate::config::{fs::ConfigFs, strings};
+use std::{
+    ffi::OsStr,
+    fmt,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
 #[derive(Debug, Clone)]
 pub struct DirectorySetting {
     pub meta: crate::GameSettingMeta,
     original: String,
-    parsed: PathBuf,
+    /// The path handed to `from_path`, untouched.
+    /// `original` is what gets written to openmw.cfg, which can't hold every path the OS can.
+    path: Option<PathBuf>,
+    /// Directory relative paths resolve against. Kept apart from `meta.source_config`
+    /// because callers like the CLI overlay relabel the source after construction.
+    base: PathBuf,
+    /// Resolved on the first `parsed()` call - big mod lists shouldn't pay for
+    /// normalizing hundreds of paths nobody looks at. Paths starting with a token are the exception, see `resolve_tokens_now`.
+    parsed: OnceLock<PathBuf>,
 }
 
 /// This is tricky.
@@ -31,25 +44,67 @@ impl crate::GameSetting for DirectorySetting {
     fn meta(&self) -> &crate::GameSettingMeta {
         &self.meta
     }
+
+    fn meta_mut(&mut self) -> &mut crate::GameSettingMeta {
+        &mut self.meta
+    }
 }
 
 /// Refactor to clone less shit
-/// Use std::mem::take for the comment and change parse_data_directory to accept &str
+/// Change parse_data_directory to accept &str
 impl DirectorySetting {
-    pub fn new<S: Into<String>>(value: S, source_config: PathBuf, comment: &mut String) -> Self {
+    pub fn handle(&self) -> crate::SettingHandle {
+        self.meta.handle()
+    }
+
+    /// `value` is written the way openmw.cfg would have it, and relative paths resolve against `source_config`
+    pub fn new<S: Into<String>>(value: S, source_config: PathBuf) -> Self {
+        Self::with_queued_comment(value, source_config, &mut String::new())
+    }
+
+    /// Comment lines to write above it, with `#` added where it's missing. `None` leaves it without one.
+    pub fn with_comment(mut self, comment: Option<&str>) -> Self {
+        self.meta.set_comment(comment);
+        self
+    }
+
+    /// Takes the comment the parser queued up, leaving it empty for the next setting
+    pub(crate) fn with_queued_comment<S: Into<String>>(
+        value: S,
+        source_config: PathBuf,
+        comment: &mut String,
+    ) -> Self {
         let original = value.into();
-        let parsed = strings::parse_data_directory(&source_config, original.clone());
+        let meta = crate::GameSettingMeta::new(source_config.clone(), std::mem::take(comment));
 
-        let meta = crate::GameSettingMeta {
-            source_config: source_config,
-            comment: comment.clone(),
+        let setting = Self {
+            original,
+            path: None,
+            base: source_config,
+            parsed: OnceLock::new(),
+            meta,
         };
-        comment.clear();
+        setting.resolve_tokens_now();
+        setting
+    }
 
+    /// Tokens stand for whatever the paths provider says at the time, and `with_paths_provider` only lasts for its closure.
+    /// So those get resolved up front, while the provider they were loaded under is still in charge.
+    fn resolve_tokens_now(&self) {
+        if strings::split_path_token(&strings::unquote_data_directory(&self.original)).is_some() {
+            self.parsed();
+        }
+    }
+
+    /// For paths coming from code rather than from an openmw.cfg, quoted and escaped as needed so they're read back unchanged
+    /// Paths which aren't valid UTF-8 still resolve to exactly what was given, only the text written out is lossy.
+    pub fn from_path(path: &Path, source_config: PathBuf) -> Self {
         Self {
-            original,
-            parsed,
-            meta,
+            path: Some(path.to_path_buf()),
+            ..Self::new(
+                strings::serialize_data_directory(&path.to_string_lossy()),
+                source_config,
+            )
         }
     }
 
@@ -57,8 +112,59 @@ impl DirectorySetting {
         &self.original
     }
 
+    /// Where relative paths resolve from
+    pub(crate) fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// The path this was created from with `from_path`, with nothing lost to UTF-8 conversion
+    pub fn original_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// `original_path` if there is one, otherwise the text from openmw.cfg
+    pub fn original_os_str(&self) -> &OsStr {
+        match &self.path {
+            Some(path) => path.as_os_str(),
+            None => OsStr::new(&self.original),
+        }
+    }
+
+    /// Whether saving would write out a different path than this one holds, since openmw.cfg is text
+    pub fn is_lossy(&self) -> bool {
+        self.path
+            .as_ref()
+            .is_some_and(|path| path.to_str().is_none())
+    }
+
+    /// Matches either the resolved path or the original one, without going through a lossy string
+    pub fn refers_to(&self, path: &Path) -> bool {
+        self.parsed() == path || self.original_os_str() == path.as_os_str()
+    }
+
+    /// Points `parsed()` wherever the symlinks along the way lead, if the directory exists.
+    /// What gets written to openmw.cfg stays the same.
+    pub(crate) fn resolve_symlinks(&mut self, fs: &dyn ConfigFs) {
+        if let Ok(resolved) = fs.canonicalize(self.parsed()) {
+            let resolved = match cfg!(windows) {
+                true => PathBuf::from(strings::strip_verbatim_prefix(&resolved.to_string_lossy())),
+                false => resolved,
+            };
+            self.parsed = OnceLock::from(resolved);
+        }
+    }
+
+    /// Goes back to resolving the path as written
+    pub(crate) fn forget_resolved(&mut self) {
+        self.parsed = OnceLock::new();
+        self.resolve_tokens_now();
+    }
+
     pub fn parsed(&self) -> &PathBuf {
-        &self.parsed
+        self.parsed.get_or_init(|| match &self.path {
+            Some(path) if path.to_str().is_none() => self.base.join(path),
+            _ => strings::parse_data_directory(&self.base, self.original.clone()),
+        })
     }
 }
 
@@ -72,10 +178,11 @@ mod tests {
         let config_path = PathBuf::from("/my/config");
         let mut comment = "some comment".to_string();
 
-        let setting = DirectorySetting::new("data", config_path.clone(), &mut comment);
+        let setting =
+            DirectorySetting::with_queued_comment("data", config_path.clone(), &mut comment);
 
         assert_eq!(setting.original, "data");
-        assert_eq!(setting.parsed, config_path.join("data"));
+        assert_eq!(setting.parsed(), &config_path.join("data"));
         assert_eq!(setting.meta.source_config, config_path);
         assert_eq!(setting.meta.comment, "some comment");
         assert!(comment.is_empty()); // Should have been cleared
@@ -86,11 +193,12 @@ mod tests {
         let config_path = PathBuf::from("/irrelevant");
         let mut comment = String::new();
 
-        let setting = DirectorySetting::new("?userdata?/foo", config_path, &mut comment);
+        let setting =
+            DirectorySetting::with_queued_comment("?userdata?/foo", config_path, &mut comment);
 
         let expected_prefix = crate::default_userdata_path();
-        assert!(setting.parsed.starts_with(expected_prefix));
-        assert!(setting.parsed.ends_with("foo/"));
+        assert!(setting.parsed().starts_with(expected_prefix));
+        assert!(setting.parsed().ends_with("foo/"));
     }
 
     #[test]
@@ -98,12 +206,13 @@ mod tests {
         let config_path = PathBuf::from("/config/dir");
         let mut comment = String::new();
 
-        let setting = DirectorySetting::new("?userconfig?/bar", config_path, &mut comment);
+        let setting =
+            DirectorySetting::with_queued_comment("?userconfig?/bar", config_path, &mut comment);
         dbg!(setting.parsed());
 
         let expected_prefix = crate::default_config_path();
-        assert!(setting.parsed.starts_with(expected_prefix));
-        assert!(setting.parsed.ends_with("bar"));
+        assert!(setting.parsed().starts_with(expected_prefix));
+        assert!(setting.parsed().ends_with("bar"));
     }
 
     #[test]
@@ -111,11 +220,17 @@ mod tests {
         let config_path = PathBuf::from("/my/config");
         let mut comment = String::new();
 
-        let setting =
-            DirectorySetting::new("\"path/with spaces\"", config_path.clone(), &mut comment);
+        let setting = DirectorySetting::with_queued_comment(
+            "\"path/with spaces\"",
+            config_path.clone(),
+            &mut comment,
+        );
 
         assert_eq!(setting.original, "\"path/with spaces\"");
-        assert_eq!(setting.parsed, config_path.join("path").join("with spaces"));
+        assert_eq!(
+            setting.parsed(),
+            &config_path.join("path").join("with spaces")
+        );
     }
 
     #[test]
@@ -123,10 +238,67 @@ mod tests {
         let config_path = PathBuf::from("/my/config");
         let mut comment = String::new();
 
-        let setting = DirectorySetting::new("subdir\\nested", config_path.clone(), &mut comment);
+        let setting = DirectorySetting::with_queued_comment(
+            "subdir\\nested",
+            config_path.clone(),
+            &mut comment,
+        );
 
         let expected = config_path.join("subdir").join("nested");
-        assert_eq!(setting.parsed, expected);
+        assert_eq!(setting.parsed(), &expected);
+    }
+
+    #[test]
+    fn test_paths_are_escaped_on_write() {
+        let config = mock_path("/my/config");
+
+        for path in [
+            "/mods/Tom & Jerry",
+            "/mods/\"Quoted\" Name",
+            " /padded ",
+            "/plain/path",
+        ] {
+            let setting = DirectorySetting::from_path(Path::new(path), config.clone());
+            let written = setting.to_string();
+            let reread = DirectorySetting::new(written.trim_end_matches('\n'), config.clone());
+
+            assert_eq!(setting.parsed(), reread.parsed(), "{written}");
+        }
+
+        let plain = DirectorySetting::from_path(Path::new("/plain/path"), config);
+        assert_eq!(plain.original(), "/plain/path");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths_survive() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/mods/Caf\xE9"));
+        let setting = DirectorySetting::from_path(path, mock_path("/my/config"));
+
+        assert!(setting.is_lossy());
+        assert_eq!(setting.parsed(), path);
+        assert_eq!(setting.original_path(), Some(path));
+        assert!(setting.refers_to(path));
+        assert!(!setting.refers_to(Path::new("/mods/Caf\u{FFFD}")));
+
+        let plain = DirectorySetting::from_path(Path::new("/mods/Cafe"), mock_path("/my/config"));
+        assert!(!plain.is_lossy());
+        assert_eq!(plain.original_os_str(), "/mods/Cafe");
+    }
+
+    #[test]
+    fn test_with_comment() {
+        let setting = DirectorySetting::new("mods", mock_path("/my/config"))
+            .with_comment(Some("# Added by hand\nkeep this last\n\n"));
+        assert_eq!(
+            setting.meta.comment(),
+            "# Added by hand\n# keep this last\n\n"
+        );
+
+        let setting = setting.with_comment(None);
+        assert_eq!(setting.to_string(), "mods\n");
     }
 
     fn mock_path(path: &str) -> PathBuf {
@@ -137,7 +309,7 @@ mod tests {
     fn test_dot_component_is_removed() {
         let config = mock_path("/etc/openmw");
         let mut comment = String::from("comment");
-        let setting = DirectorySetting::new("./data", config.clone(), &mut comment);
+        let setting = DirectorySetting::with_queued_comment("./data", config.clone(), &mut comment);
         assert_eq!(setting.parsed(), &config.join("data"));
     }
 
@@ -145,7 +317,8 @@ mod tests {
     fn test_double_dot_component_removes_parent() {
         let config = mock_path("/home/user/.config/openmw");
         let mut comment = String::from("comment");
-        let setting = DirectorySetting::new("../common", config.clone(), &mut comment);
+        let setting =
+            DirectorySetting::with_queued_comment("../common", config.clone(), &mut comment);
         let expected = config.parent().unwrap().join("common");
         assert_eq!(setting.parsed(), &expected);
     }
@@ -154,8 +327,21 @@ mod tests {
     fn test_nested_dots_normalize_correctly() {
         let config = mock_path("/opt/game/config");
         let mut comment = String::new();
-        let setting = DirectorySetting::new("foo/./bar/../baz", config.clone(), &mut comment);
+        let setting =
+            DirectorySetting::with_queued_comment("foo/./bar/../baz", config.clone(), &mut comment);
         let expected = config.join("foo/baz");
         assert_eq!(setting.parsed(), &expected);
     }
+
+    #[test]
+    fn test_path_is_resolved_lazily_and_memoized() {
+        let config = mock_path("/my/config");
+        let mut setting = DirectorySetting::new("mods/a", config.clone());
+        assert!(setting.parsed.get().is_none());
+
+        // Relabeling the source afterwards doesn't change what the path resolves against
+        setting.meta.source_config = PathBuf::from("<command line>");
+        assert_eq!(setting.parsed(), &config.join("mods/a"));
+        assert!(setting.parsed.get().is_some());
+    }
 }
@@ -0,0 +1,133 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::config::{OpenMWConfiguration, SettingValue};
+
+/// openmw.cfg files with settings that changed since they were loaded or saved.
+/// Saving only needs `&self`, hence the mutex.
+#[derive(Debug, Default)]
+pub(crate) struct DirtySources(Mutex<BTreeSet<PathBuf>>);
+
+impl DirtySources {
+    fn lock(&self) -> MutexGuard<'_, BTreeSet<PathBuf>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn mark(&self, source: &Path) {
+        self.lock().insert(source.to_path_buf());
+    }
+
+    pub(crate) fn clear(&self, source: &Path) {
+        self.lock().remove(source);
+    }
+
+    pub(crate) fn take(&self) -> BTreeSet<PathBuf> {
+        std::mem::take(&mut self.lock())
+    }
+
+    pub(crate) fn restore(&self, sources: BTreeSet<PathBuf>) {
+        *self.lock() = sources;
+    }
+}
+
+impl OpenMWConfiguration {
+    /// The root openmw.cfg followed by every one it pulled in with `config=`
+    pub(crate) fn chain_files(&self) -> Vec<PathBuf> {
+        if self.single_file {
+            return vec![self.root_config_file().to_path_buf()];
+        }
+
+        std::iter::once(self.root_config_file().to_path_buf())
+            .chain(
+                self.sub_configs()
+                    .map(|setting| setting.parsed().join("openmw.cfg")),
+            )
+            .collect()
+    }
+
+    /// Settings from the command line or a project folder have nowhere to be saved, so they're never tracked
+    pub(crate) fn mark_dirty(&self, setting: &SettingValue) {
+        if let Some(file) = self
+            .chain_files()
+            .into_iter()
+            .find(|file| setting.meta().is_from(file))
+        {
+            self.dirty.mark(&file);
+        }
+    }
+
+    /// Whether anything changed which hasn't been saved yet
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.lock().is_empty()
+    }
+
+    /// Every openmw.cfg which would be written by `save_all`
+    pub fn dirty_sources(&self) -> Vec<PathBuf> {
+        self.dirty.lock().iter().cloned().collect()
+    }
+
+    /// Writes every openmw.cfg which has changed settings, whichever part of the chain it belongs to, and leaves the rest untouched.
+    /// Returns the files which were written.
+    /// The same caveats as `save_user` apply, only to more files!
+    pub fn save_all(&self) -> Result<Vec<PathBuf>, String> {
+        let dirty = self.dirty_sources();
+
+        for cfg_path in &dirty {
+            match cfg_path.parent() {
+                Some(dir) if self.fs.can_write_to_dir(dir) => {}
+                _ => return Err(format!("Directory for {:?} is not writable!", cfg_path)),
+            }
+        }
+
+        for cfg_path in &dirty {
+            self.write_source(cfg_path)?;
+        }
+
+        Ok(dirty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        GameSetting,
+        config::{OpenMWConfiguration, util::temp_config},
+    };
+
+    #[test]
+    fn test_only_changed_files_are_saved() {
+        let root = temp_config("dirty_root", "content=Morrowind.esm\nconfig=child\n");
+        let child = root.join("child");
+        std::fs::create_dir_all(&child).unwrap();
+        std::fs::write(child.join("openmw.cfg"), "fallback=iMaxSpeed,10\n").unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        assert!(!config.is_dirty());
+
+        config.set_game_setting_value("iMaxSpeed", "20").unwrap();
+        let child_cfg = config.get_game_setting("iMaxSpeed").unwrap();
+        let child_cfg = child_cfg.meta().source_config.clone();
+        assert_eq!(config.dirty_sources(), vec![child_cfg.clone()]);
+
+        std::fs::write(root.join("openmw.cfg"), "# untouched\n").unwrap();
+        assert_eq!(config.save_all().unwrap(), vec![child_cfg.clone()]);
+
+        assert!(!config.is_dirty());
+        assert_eq!(
+            std::fs::read_to_string(&child_cfg).unwrap(),
+            "fallback=iMaxSpeed,20\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("openmw.cfg")).unwrap(),
+            "# untouched\n"
+        );
+    }
+}
@@ -0,0 +1,273 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Switching entries off without losing them, the way mod managers toggle mods.
+//! A disabled entry keeps its place and comments, and is saved as `#omwcfg:disabled content=Foo.esp` so the engine skips it
+//! While loading it again brings it back as disabled.
+
+use std::path::Path;
+
+use crate::{
+    ConfigError, SettingHandle, SettingOrigin,
+    config::{OpenMWConfiguration, SettingValue, install::file_names},
+    config_err,
+};
+
+/// Keys of the entries which can be disabled
+const KEYS: [&str; 4] = ["content", "groundcover", "fallback-archive", "data"];
+
+/// Whether the text after a disabled marker is an entry this crate knows how to bring back
+pub(crate) fn can_disable_line(line: &str) -> bool {
+    line.split_once('=')
+        .is_some_and(|(key, _)| KEYS.contains(&key.trim()))
+}
+
+impl SettingValue {
+    pub fn is_disabled(&self) -> bool {
+        matches!(self, SettingValue::Disabled(_))
+    }
+
+    /// The entry itself, looking through the disabled marker if there is one
+    pub(crate) fn enabled_form(&self) -> &SettingValue {
+        match self {
+            SettingValue::Disabled(setting) => setting,
+            setting => setting,
+        }
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Entries which were disabled rather than removed, in order, as they'd be if enabled again
+    pub fn disabled_settings(&self) -> impl Iterator<Item = &SettingValue> {
+        self.settings.iter().filter_map(|setting| match setting {
+            SettingValue::Disabled(setting) => Some(setting.as_ref()),
+            _ => None,
+        })
+    }
+
+    /// Disables a `content=`, `groundcover=`, `fallback-archive=` or `data=` entry, which keeps its handle and place.
+    /// Returns false for any other kind of setting, or one which is already disabled or doesn't belong to this configuration.
+    pub fn disable_setting(&mut self, handle: &SettingHandle) -> bool {
+        let Some(index) = self.settings.iter().position(|setting| {
+            setting.handle() == *handle
+                && setting.meta().origin() != SettingOrigin::Synthetic
+                && matches!(
+                    setting,
+                    SettingValue::ContentFile(_)
+                        | SettingValue::Groundcover(_)
+                        | SettingValue::BethArchive(_)
+                        | SettingValue::DataDirectory(_)
+                )
+        }) else {
+            return false;
+        };
+
+        let disabled = SettingValue::Disabled(Box::new(self.settings[index].clone()));
+        self.replace_setting_at(index, disabled);
+        true
+    }
+
+    /// Enables the first disabled entry `is_wanted` picks, returning whether there was one
+    pub(crate) fn enable_first_disabled(
+        &mut self,
+        is_wanted: impl Fn(&SettingValue) -> bool,
+    ) -> bool {
+        let Some(index) = self
+            .settings
+            .iter()
+            .position(|setting| setting.is_disabled() && is_wanted(setting.enabled_form()))
+        else {
+            return false;
+        };
+
+        let enabled = self.settings[index].enabled_form().clone();
+        self.replace_setting_at(index, enabled);
+        true
+    }
+
+    /// Enables a disabled entry again, right where it was. Returns false if the handle isn't a disabled entry.
+    /// Fails rather than enabling a plugin or archive which is also enabled elsewhere, since the engine won't load a list like that.
+    pub fn enable_setting(&mut self, handle: &SettingHandle) -> Result<bool, ConfigError> {
+        let Some(index) = self
+            .settings
+            .iter()
+            .position(|setting| setting.is_disabled() && setting.handle() == *handle)
+        else {
+            return Ok(false);
+        };
+
+        let SettingValue::Disabled(setting) = self.settings[index].clone() else {
+            unreachable!("the setting was just checked to be disabled");
+        };
+        let source = setting.meta().source_config().to_path_buf();
+
+        match setting.as_ref() {
+            SettingValue::ContentFile(plugin) if self.has_content_file(plugin.value()) => {
+                return Err(config_err!(
+                    duplicate_content_file,
+                    plugin.value().clone(),
+                    source
+                ));
+            }
+            SettingValue::Groundcover(plugin) if self.has_groundcover_file(plugin.value()) => {
+                return Err(config_err!(
+                    duplicate_groundcover_file,
+                    plugin.value().clone(),
+                    source
+                ));
+            }
+            SettingValue::BethArchive(archive) if self.has_archive_file(archive.value()) => {
+                return Err(config_err!(
+                    duplicate_archive_file,
+                    archive.value().clone(),
+                    source
+                ));
+            }
+            _ => {}
+        }
+
+        self.replace_setting_at(index, *setting);
+        Ok(true)
+    }
+
+    /// Toggles a whole mod: its `data=` entry, and the content and groundcover entries whose files only it provides.
+    /// Disabling leaves alone plugins which another enabled data directory also has, and enabling skips any which are already enabled.
+    /// Returns the handles of every entry which was switched.
+    pub fn set_mod_enabled(&mut self, data_dir: &Path, enabled: bool) -> Vec<SettingHandle> {
+        let Some((handle, path)) = self.settings.iter().find_map(|setting| {
+            match (setting, enabled) {
+                (SettingValue::DataDirectory(dir), false) => Some(dir),
+                (SettingValue::Disabled(disabled), true) => match disabled.as_ref() {
+                    SettingValue::DataDirectory(dir) => Some(dir),
+                    _ => None,
+                },
+                _ => None,
+            }
+            .filter(|dir| dir.refers_to(data_dir))
+            .map(|dir| (dir.handle(), dir.parsed().clone()))
+        }) else {
+            return Vec::new();
+        };
+
+        let provided = file_names(&path);
+        let mut switched = vec![handle];
+
+        match enabled {
+            true => {
+                let _ = self.enable_setting(&handle);
+
+                let plugins: Vec<SettingHandle> = self
+                    .disabled_settings()
+                    .filter_map(|setting| match setting {
+                        SettingValue::ContentFile(plugin) | SettingValue::Groundcover(plugin) => {
+                            Some((setting.handle(), plugin.value()))
+                        }
+                        _ => None,
+                    })
+                    .filter(|(_, name)| provided.contains(&name.to_ascii_lowercase()))
+                    .map(|(handle, _)| handle)
+                    .collect();
+
+                for plugin in plugins {
+                    if self.enable_setting(&plugin).unwrap_or(false) {
+                        switched.push(plugin);
+                    }
+                }
+            }
+            false => {
+                self.disable_setting(&handle);
+
+                let remaining = self.data_file_index();
+                let plugins: Vec<SettingHandle> = self
+                    .settings
+                    .iter()
+                    .filter_map(|setting| match setting {
+                        SettingValue::ContentFile(plugin) | SettingValue::Groundcover(plugin) => {
+                            Some((setting.handle(), plugin.value().to_ascii_lowercase()))
+                        }
+                        _ => None,
+                    })
+                    .filter(|(_, name)| provided.contains(name) && !remaining.contains_key(name))
+                    .map(|(handle, _)| handle)
+                    .collect();
+
+                for plugin in plugins {
+                    self.disable_setting(&plugin);
+                    switched.push(plugin);
+                }
+            }
+        }
+
+        switched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_soft_disable_round_trip() {
+        let dir = temp_config(
+            "soft_disable",
+            "data=Base\n# armor\ndata=Armor\ncontent=Base.esm\ncontent=Armor.esp\n#omwcfg:disabled content=Old.esp\n",
+        );
+        for (data, plugin) in [("Base", "Base.esm"), ("Armor", "Armor.esp")] {
+            std::fs::create_dir_all(dir.join(data)).unwrap();
+            std::fs::write(dir.join(data).join(plugin), "").unwrap();
+        }
+
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let old: Vec<&SettingValue> = config.disabled_settings().collect();
+        assert_eq!(old.len(), 1);
+        assert_eq!(old[0].key(), "content");
+        assert_eq!(config.content_files(), ["Base.esm", "Armor.esp"]);
+
+        let switched = config.set_mod_enabled(&dir.join("Armor"), false);
+        assert_eq!(switched.len(), 2);
+        assert_eq!(config.data_directories(), [&dir.join("Base")]);
+        assert_eq!(config.content_files(), ["Base.esm"]);
+
+        let cfg_path = dir.join("openmw.cfg");
+        assert_eq!(
+            config.source_text(&cfg_path),
+            "data=Base\n# armor\n#omwcfg:disabled data=Armor\ncontent=Base.esm\n#omwcfg:disabled content=Armor.esp\n#omwcfg:disabled content=Old.esp\n"
+        );
+
+        config.save_user().unwrap();
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert_eq!(config.disabled_settings().count(), 3);
+
+        config.set_mod_enabled(&dir.join("Armor"), true);
+        assert_eq!(config.content_files(), ["Base.esm", "Armor.esp"]);
+        assert_eq!(
+            config.source_text(&cfg_path),
+            "data=Base\n# armor\ndata=Armor\ncontent=Base.esm\ncontent=Armor.esp\n#omwcfg:disabled content=Old.esp\n"
+        );
+    }
+
+    #[test]
+    fn test_add_and_remove_disabled_content() {
+        let dir = temp_config(
+            "disabled_content",
+            "content=Base.esm\n#omwcfg:disabled content=Old.esp\ncontent=Last.esp\n#omwcfg:disabled content=Gone.esp\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        assert!(!config.has_content_file("Old.esp"));
+
+        config.add_content_file("Old.esp").unwrap();
+        assert_eq!(config.content_files(), ["Base.esm", "Old.esp", "Last.esp"]);
+        assert!(config.add_content_file("Old.esp").is_err());
+
+        config.remove_content_file("Gone.esp");
+        assert_eq!(config.disabled_settings().count(), 0);
+        config.add_content_file("Gone.esp").unwrap();
+        assert_eq!(
+            config.content_files(),
+            ["Base.esm", "Old.esp", "Last.esp", "Gone.esp"]
+        );
+    }
+}
@@ -0,0 +1,372 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Compares what the data directories actually contain with what the configuration references,
+//! And finds Morrowind installs in Steam libraries, including ones inside Proton prefixes on Linux and the Steam Deck.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::config::{OpenMWConfiguration, project::PLUGIN_EXTENSIONS, strings};
+
+/// BA2 archives are only read by newer engine versions, but they're still worth reporting
+const ARCHIVE_EXTENSIONS: [&str; 2] = ["bsa", "ba2"];
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|extension| {
+        extensions
+            .iter()
+            .any(|known| extension.eq_ignore_ascii_case(known))
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredFile {
+    pub name: String,
+    /// The copy the engine would use, when more than one data directory has it
+    pub path: PathBuf,
+}
+
+/// Everything `discover_content` found. Each list is sorted by name, except the missing ones, which keep load order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentDiscovery {
+    /// Plugins which are neither `content=` nor `groundcover=` entries
+    pub unreferenced_plugins: Vec<DiscoveredFile>,
+    /// Archives which aren't `fallback-archive=` entries
+    pub unreferenced_archives: Vec<DiscoveredFile>,
+    pub missing_content: Vec<String>,
+    pub missing_groundcover: Vec<String>,
+    pub missing_archives: Vec<String>,
+}
+
+impl ContentDiscovery {
+    pub fn is_empty(&self) -> bool {
+        self.unreferenced_plugins.is_empty()
+            && self.unreferenced_archives.is_empty()
+            && self.missing_content.is_empty()
+            && self.missing_groundcover.is_empty()
+            && self.missing_archives.is_empty()
+    }
+}
+
+/// Steam runs it through Proton from `compatdata/22320`, though the game itself stays in `common/Morrowind`
+pub const MORROWIND_STEAM_APP_ID: u32 = 22320;
+
+/// Where Morrowind usually ends up inside a Wine or Proton `drive_c`, from Steam, the original discs or GOG
+const PREFIX_INSTALL_DIRS: [&str; 7] = [
+    "Program Files (x86)/Steam/steamapps/common/Morrowind",
+    "Program Files/Steam/steamapps/common/Morrowind",
+    "Program Files (x86)/Bethesda Softworks/Morrowind",
+    "Program Files/Bethesda Softworks/Morrowind",
+    "Program Files (x86)/GOG Galaxy/Games/Morrowind",
+    "GOG Games/Morrowind",
+    "Games/Morrowind",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MorrowindInstall {
+    /// The folder holding Morrowind.exe and `Data Files`
+    pub path: PathBuf,
+    pub data_files: PathBuf,
+    /// The compatdata ID of the Proton prefix it's in, or `None` for a plain Steam install
+    pub proton_app_id: Option<String>,
+}
+
+impl MorrowindInstall {
+    /// A `data=` line for `Data Files`, quoted like the OpenMW wizard writes it, since these paths always have spaces in them
+    pub fn data_entry(&self) -> String {
+        format!(
+            "data={}",
+            strings::quote_data_directory(&self.data_files.to_string_lossy())
+        )
+    }
+}
+
+/// `dir/name`, or whatever differs from it only by case, since Windows installs rarely agree on it
+pub(crate) fn child_ignoring_case(dir: &Path, name: &str) -> Option<PathBuf> {
+    let exact = dir.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(name)
+        })
+        .map(|entry| entry.path())
+}
+
+fn path_ignoring_case(base: &Path, relative: &str) -> Option<PathBuf> {
+    relative
+        .split('/')
+        .try_fold(base.to_path_buf(), |dir, name| {
+            child_ignoring_case(&dir, name)
+        })
+}
+
+/// Checks for `Data Files/Morrowind.esm`, which every install has
+fn morrowind_install_at(path: PathBuf, proton_app_id: Option<String>) -> Option<MorrowindInstall> {
+    let data_files = child_ignoring_case(&path, "Data Files")?;
+    child_ignoring_case(&data_files, "Morrowind.esm")?;
+
+    Some(MorrowindInstall {
+        path,
+        data_files,
+        proton_app_id,
+    })
+}
+
+/// The `"path"` values of a libraryfolders.vdf
+fn vdf_library_paths(vdf: &str) -> Vec<PathBuf> {
+    vdf.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('"').skip(1).step_by(2);
+            match (fields.next(), fields.next()) {
+                (Some("path"), Some(path)) => Some(PathBuf::from(path.replace("\\\\", "\\"))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Every Steam library under `home`: the default ones, Flatpak Steam's,
+/// And whatever libraryfolders.vdf adds, such as a Steam Deck's SD card
+pub fn steam_libraries(home: &Path) -> Vec<PathBuf> {
+    let roots = [
+        home.join(".local/share/Steam"),
+        home.join(".steam/steam"),
+        home.join(".steam/root"),
+        home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+    ];
+
+    let mut seen = HashSet::new();
+    let mut libraries = Vec::new();
+
+    for root in roots
+        .into_iter()
+        .filter(|root| root.join("steamapps").is_dir())
+    {
+        let extra = std::fs::read_to_string(root.join("steamapps/libraryfolders.vdf"))
+            .map(|vdf| vdf_library_paths(&vdf))
+            .unwrap_or_default();
+
+        for library in std::iter::once(root).chain(extra) {
+            let canonical = library.canonicalize().unwrap_or_else(|_| library.clone());
+            if library.join("steamapps").is_dir() && seen.insert(canonical) {
+                libraries.push(library);
+            }
+        }
+    }
+
+    libraries
+}
+
+/// Morrowind installs in the given Steam libraries, both native Steam ones and any inside a Proton prefix
+pub fn find_morrowind_installs_in(libraries: &[PathBuf]) -> Vec<MorrowindInstall> {
+    let mut installs = Vec::new();
+
+    for steamapps in libraries.iter().map(|library| library.join("steamapps")) {
+        installs.extend(
+            path_ignoring_case(&steamapps, "common/Morrowind")
+                .and_then(|path| morrowind_install_at(path, None)),
+        );
+
+        let mut prefixes: Vec<_> = std::fs::read_dir(steamapps.join("compatdata"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+        prefixes.sort();
+
+        for prefix in prefixes {
+            let app_id = prefix
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            let drive_c = prefix.join("pfx/drive_c");
+
+            installs.extend(PREFIX_INSTALL_DIRS.iter().filter_map(|dir| {
+                path_ignoring_case(&drive_c, dir)
+                    .and_then(|path| morrowind_install_at(path, app_id.clone()))
+            }));
+        }
+    }
+
+    installs
+}
+
+/// `find_morrowind_installs_in` for every Steam library of the current user
+pub fn find_morrowind_installs() -> Vec<MorrowindInstall> {
+    dirs::home_dir()
+        .map(|home| find_morrowind_installs_in(&steam_libraries(&home)))
+        .unwrap_or_default()
+}
+
+impl OpenMWConfiguration {
+    /// Lists plugins and archives sitting in the data directories which nothing references,
+    /// And entries which reference files that aren't in any data directory. Names are compared case-insensitively, like the engine does.
+    pub fn discover_content(&self) -> ContentDiscovery {
+        let index = self.data_file_index();
+        let mut discovery = ContentDiscovery::default();
+        let mut referenced = HashSet::new();
+
+        for (entries, missing) in [
+            (self.content_files(), &mut discovery.missing_content),
+            (self.groundcover(), &mut discovery.missing_groundcover),
+            (self.fallback_archives(), &mut discovery.missing_archives),
+        ] {
+            for name in entries {
+                let key = name.to_ascii_lowercase();
+                if !index.contains_key(&key) {
+                    missing.push(name.clone());
+                }
+                referenced.insert(key);
+            }
+        }
+
+        let mut found: Vec<(&String, &PathBuf)> = index
+            .iter()
+            .filter(|(key, _)| !referenced.contains(*key))
+            .collect();
+        found.sort();
+
+        for (_, path) in found {
+            let file = DiscoveredFile {
+                name: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                path: path.clone(),
+            };
+
+            if has_extension(path, &PLUGIN_EXTENSIONS) {
+                discovery.unreferenced_plugins.push(file);
+            } else if has_extension(path, &ARCHIVE_EXTENSIONS) {
+                discovery.unreferenced_archives.push(file);
+            }
+        }
+
+        discovery
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_find_proton_installs() {
+        let home = temp_config("proton_home", "");
+        let steam = home.join(".local/share/Steam");
+        let sd_card = home.join("sdcard");
+
+        std::fs::create_dir_all(steam.join("steamapps")).unwrap();
+        std::fs::write(
+            steam.join("steamapps/libraryfolders.vdf"),
+            format!(
+                "\"libraryfolders\"\n{{\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                sd_card.display()
+            ),
+        )
+        .unwrap();
+
+        let native = steam.join("steamapps/common/Morrowind");
+        let proton =
+            sd_card.join("steamapps/compatdata/3021823340/pfx/drive_c/GOG Games/Morrowind");
+        for install in [&native, &proton] {
+            std::fs::create_dir_all(install.join("Data Files")).unwrap();
+            std::fs::write(install.join("Data Files/Morrowind.esm"), "").unwrap();
+        }
+        // A prefix without the game in it
+        std::fs::create_dir_all(sd_card.join("steamapps/compatdata/1/pfx/drive_c/GOG Games"))
+            .unwrap();
+
+        let libraries = steam_libraries(&home);
+        assert_eq!(libraries, vec![steam.clone(), sd_card]);
+
+        let installs = find_morrowind_installs_in(&libraries);
+        assert_eq!(
+            installs,
+            vec![
+                MorrowindInstall {
+                    path: native.clone(),
+                    data_files: native.join("Data Files"),
+                    proton_app_id: None,
+                },
+                MorrowindInstall {
+                    path: proton.clone(),
+                    data_files: proton.join("Data Files"),
+                    proton_app_id: Some("3021823340".into()),
+                },
+            ]
+        );
+        assert_eq!(
+            installs[1].data_entry(),
+            format!("data=\"{}\"", proton.join("Data Files").display())
+        );
+    }
+
+    #[test]
+    fn test_discover_content() {
+        let dir = temp_config("discovery", "");
+        let base = dir.join("base");
+        let overlay = dir.join("overlay");
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::create_dir_all(&overlay).unwrap();
+
+        for name in [
+            "Morrowind.esm",
+            "Morrowind.bsa",
+            "Grass.esp",
+            "Unused.ESP",
+            "readme.txt",
+        ] {
+            std::fs::write(base.join(name), "").unwrap();
+        }
+        std::fs::write(overlay.join("unused.esp"), "").unwrap();
+        std::fs::write(overlay.join("Extra.bsa"), "").unwrap();
+
+        std::fs::write(
+            dir.join("openmw.cfg"),
+            format!(
+                "data={}\ndata={}\ncontent=morrowind.esm\ncontent=Gone.esp\ngroundcover=Grass.esp\nfallback-archive=Morrowind.bsa\n",
+                base.display(),
+                overlay.display()
+            ),
+        )
+        .unwrap();
+
+        let discovery = OpenMWConfiguration::new(Some(dir))
+            .unwrap()
+            .discover_content();
+
+        assert_eq!(
+            discovery.unreferenced_plugins,
+            vec![DiscoveredFile {
+                name: "unused.esp".into(),
+                path: overlay.join("unused.esp"),
+            }]
+        );
+        assert_eq!(
+            discovery.unreferenced_archives,
+            vec![DiscoveredFile {
+                name: "Extra.bsa".into(),
+                path: overlay.join("Extra.bsa"),
+            }]
+        );
+        assert_eq!(discovery.missing_content, vec!["Gone.esp"]);
+        assert!(discovery.missing_groundcover.is_empty());
+        assert!(discovery.missing_archives.is_empty());
+    }
+}
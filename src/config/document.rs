@@ -0,0 +1,372 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! The layout of every openmw.cfg as it was read: each blank line, comment and setting, in order.
+//! Saving goes through it, so a file nobody touched comes back byte for byte,
+//! And an edited one only changes where the edits are.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    SettingHandle, SettingOrigin,
+    config::{OpenMWConfiguration, SettingValue},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocumentLine {
+    Blank(String),
+    Comment(String),
+    /// A line which became a setting.
+    /// `rendered` is how that setting wrote itself once loading finished, to tell whether it was edited since.
+    Setting {
+        handle: SettingHandle,
+        raw: String,
+        rendered: String,
+    },
+    /// Lines that don't stand for any setting: `replace=`, serializer footers, lines lenient parsing skipped,
+    /// And settings something later in the chain overrode
+    Other(String),
+}
+
+impl DocumentLine {
+    /// The line exactly as it was in the file
+    pub fn raw(&self) -> &str {
+        match self {
+            DocumentLine::Blank(raw) | DocumentLine::Comment(raw) | DocumentLine::Other(raw) => raw,
+            DocumentLine::Setting { raw, .. } => raw,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    lines: Vec<DocumentLine>,
+    crlf: bool,
+    final_newline: bool,
+    finished: bool,
+}
+
+impl Document {
+    pub(crate) fn new(text: &str) -> Self {
+        Self {
+            lines: Vec::new(),
+            crlf: text.contains("\r\n"),
+            final_newline: text.is_empty() || text.ends_with('\n'),
+            finished: false,
+        }
+    }
+
+    /// A document laid out the way one was read before, with `finish` still to fill in how its settings render
+    pub(crate) fn with_lines(lines: Vec<DocumentLine>, crlf: bool, final_newline: bool) -> Self {
+        Self {
+            lines,
+            crlf,
+            final_newline,
+            finished: false,
+        }
+    }
+
+    pub fn lines(&self) -> &[DocumentLine] {
+        &self.lines
+    }
+
+    pub(crate) fn is_crlf(&self) -> bool {
+        self.crlf
+    }
+
+    pub(crate) fn has_final_newline(&self) -> bool {
+        self.final_newline
+    }
+
+    pub(crate) fn push(&mut self, line: DocumentLine) {
+        self.lines.push(line);
+    }
+
+    /// Turns a placeholder into the setting it ended up as, for `config=` lines which only load after the rest of the file
+    pub(crate) fn attach(&mut self, index: usize, handle: SettingHandle) {
+        if let Some(line) = self.lines.get_mut(index) {
+            *line = DocumentLine::Setting {
+                handle,
+                raw: line.raw().to_string(),
+                rendered: String::new(),
+            };
+        }
+    }
+
+    /// Blank lines and comments after the last setting
+    fn trailing(&self) -> &[DocumentLine] {
+        let start = self
+            .lines
+            .iter()
+            .rposition(|line| matches!(line, DocumentLine::Setting { .. } | DocumentLine::Other(_)))
+            .map_or(0, |index| index + 1);
+
+        &self.lines[start..]
+    }
+
+    /// The file as it should be written now that `settings` are the ones it holds, in order.
+    /// Settings which were removed take their comments with them, and new ones go after the setting before them.
+    /// If surviving settings were reordered the whole file is written from the settings instead, keeping only the trailing comments.
+    /// `config=` lines always load after the rest of their file, so where they sit among the settings says nothing and they stay on their own lines.
+    pub(crate) fn render(&self, settings: &[&SettingValue]) -> String {
+        let positions: HashMap<SettingHandle, usize> = settings
+            .iter()
+            .enumerate()
+            .map(|(index, setting)| (setting.handle(), index))
+            .collect();
+
+        let in_document: HashSet<SettingHandle> = self
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                DocumentLine::Setting { handle, .. } => Some(*handle),
+                _ => None,
+            })
+            .collect();
+
+        let is_sub_config =
+            |index: usize| matches!(settings[index], SettingValue::SubConfiguration(_));
+
+        let kept: Vec<usize> = self
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                DocumentLine::Setting { handle, .. } => positions.get(handle).copied(),
+                _ => None,
+            })
+            .filter(|&index| !is_sub_config(index))
+            .collect();
+
+        let mut output = String::new();
+
+        if kept.windows(2).any(|pair| pair[0] > pair[1]) {
+            settings
+                .iter()
+                .for_each(|setting| output.push_str(&setting.to_string()));
+            self.trailing().iter().for_each(|line| {
+                output.push_str(line.raw());
+                output.push('\n');
+            });
+
+            return self.finish_output(output);
+        }
+
+        // Anything in the document gets written at its own line
+        let added = |output: &mut String, settings: &[&SettingValue]| {
+            settings
+                .iter()
+                .filter(|setting| !in_document.contains(&setting.handle()))
+                .for_each(|setting| output.push_str(&setting.to_string()));
+        };
+
+        let mut pending: Vec<&str> = Vec::new();
+        let mut next = 0;
+
+        let flush = |output: &mut String, pending: &mut Vec<&str>| {
+            pending.drain(..).for_each(|raw| {
+                output.push_str(raw);
+                output.push('\n');
+            });
+        };
+
+        for line in &self.lines {
+            match line {
+                DocumentLine::Blank(raw) | DocumentLine::Comment(raw) => pending.push(raw),
+                DocumentLine::Other(raw) => {
+                    flush(&mut output, &mut pending);
+                    output.push_str(raw);
+                    output.push('\n');
+                }
+                DocumentLine::Setting {
+                    handle,
+                    raw,
+                    rendered,
+                } => {
+                    let Some(&index) = positions.get(handle) else {
+                        pending.clear();
+                        continue;
+                    };
+
+                    if !is_sub_config(index) {
+                        added(&mut output, &settings[next..index]);
+                    }
+                    flush(&mut output, &mut pending);
+
+                    let setting = settings[index];
+                    let text = setting.to_string();
+                    match text == *rendered {
+                        true => {
+                            output.push_str(raw);
+                            output.push('\n');
+                        }
+                        // The comment lines above were already written from the document
+                        false => output
+                            .push_str(text.strip_prefix(setting.meta().comment()).unwrap_or(&text)),
+                    }
+
+                    if !is_sub_config(index) {
+                        next = index + 1;
+                    }
+                }
+            }
+        }
+
+        added(&mut output, &settings[next.min(settings.len())..]);
+        flush(&mut output, &mut pending);
+
+        self.finish_output(output)
+    }
+
+    fn finish_output(&self, mut output: String) -> String {
+        if !self.final_newline && output.ends_with('\n') {
+            output.pop();
+        }
+
+        match self.crlf {
+            true => output.replace('\n', "\r\n"),
+            false => output,
+        }
+    }
+}
+
+/// Every document loaded so far, by the path of its openmw.cfg
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Documents {
+    files: HashMap<PathBuf, Document>,
+}
+
+impl Documents {
+    pub(crate) fn insert(&mut self, path: PathBuf, document: Document) {
+        self.files.insert(path, document);
+    }
+
+    pub(crate) fn get_mut(&mut self, path: &Path) -> Option<&mut Document> {
+        self.files.get_mut(path)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&PathBuf, &Document)> {
+        self.files.iter()
+    }
+
+    /// Settings a later line or file replaced during loading leave their lines behind as they were,
+    /// And everything still around counts as unedited from here on
+    pub(crate) fn finish(&mut self, settings: &[SettingValue]) {
+        let current: HashMap<SettingHandle, &SettingValue> = settings
+            .iter()
+            .map(|setting| (setting.handle(), setting))
+            .collect();
+
+        for document in self
+            .files
+            .values_mut()
+            .filter(|document| !document.finished)
+        {
+            for line in &mut document.lines {
+                if let DocumentLine::Setting {
+                    handle,
+                    raw,
+                    rendered,
+                } = line
+                {
+                    match current.get(handle) {
+                        Some(setting) => *rendered = setting.to_string(),
+                        None => *line = DocumentLine::Other(std::mem::take(raw)),
+                    }
+                }
+            }
+
+            document.finished = true;
+        }
+    }
+}
+
+impl OpenMWConfiguration {
+    /// How an openmw.cfg in the chain was laid out when it was read
+    pub fn document(&self, cfg_path: &Path) -> Option<&Document> {
+        self.documents.files.get(cfg_path)
+    }
+
+    /// What saving `cfg_path` would write
+    pub(crate) fn source_text(&self, cfg_path: &Path) -> String {
+        let settings: Vec<&SettingValue> = self
+            .settings_matching(|setting| {
+                setting.meta().is_from(cfg_path)
+                    && setting.meta().origin() != SettingOrigin::Synthetic
+            })
+            .collect();
+
+        match self.document(cfg_path) {
+            Some(document) => document.render(&settings),
+            None => settings.iter().map(|setting| setting.to_string()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    const LAYOUT: &str = "# my setup\r\n\r\n  content = Morrowind.esm\r\ncontent=Tribunal.esm\r\n\r\ndata-local=Local\r\nresources=Resources\r\nreplace=fallback\r\nencoding=win1250\r\nencoding=win1252\r\n\r\n# the end\r\n#content=Old.esp";
+
+    #[test]
+    fn test_unedited_save_is_byte_identical() {
+        let dir = temp_config("document_identical", LAYOUT);
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let cfg_path = dir.join("openmw.cfg");
+
+        assert!(matches!(
+            config.document(&cfg_path).unwrap().lines()[0],
+            DocumentLine::Comment(_)
+        ));
+
+        config.save_user().unwrap();
+        assert_eq!(std::fs::read_to_string(&cfg_path).unwrap(), LAYOUT);
+    }
+
+    #[test]
+    fn test_config_lines_stay_in_place() {
+        let user = temp_config("document_user", "content=Tribunal.esm\n");
+        let layout = format!(
+            "config={}\n\n# base game\ncontent=Morrowind.esm\n",
+            user.display()
+        );
+        let dir = temp_config("document_config_lines", &layout);
+
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let cfg_path = dir.join("openmw.cfg");
+        std::fs::write(&cfg_path, config.source_text(&cfg_path)).unwrap();
+        assert_eq!(std::fs::read_to_string(&cfg_path).unwrap(), layout);
+
+        let morrowind = config
+            .settings_matching(|setting| setting.meta().is_from(&cfg_path))
+            .find(|setting| matches!(setting, SettingValue::ContentFile(_)))
+            .unwrap()
+            .handle();
+        config.remove_setting(&morrowind);
+        assert_eq!(
+            config.source_text(&cfg_path),
+            format!("config={}\n", user.display())
+        );
+    }
+
+    #[test]
+    fn test_edits_keep_the_layout() {
+        let dir = temp_config("document_edited", LAYOUT);
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+
+        config.remove_content_file("Tribunal.esm");
+        config.add_content_file("Bloodmoon.esm").unwrap();
+        config.save_user().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.join("openmw.cfg")).unwrap(),
+            "# my setup\r\n\r\n  content = Morrowind.esm\r\n\r\ndata-local=Local\r\nresources=Resources\r\nreplace=fallback\r\nencoding=win1250\r\nencoding=win1252\r\ncontent=Bloodmoon.esm\r\n\r\n# the end\r\n#content=Old.esp"
+        );
+    }
+}
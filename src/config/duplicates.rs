@@ -0,0 +1,135 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    GameSetting, SettingHandle,
+    config::{FileSetting, OpenMWConfiguration, SettingValue, strings},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DuplicateKind {
+    ContentFile,
+    Groundcover,
+    FallbackArchive,
+}
+
+impl DuplicateKind {
+    fn of(setting: &SettingValue) -> Option<(Self, &FileSetting)> {
+        match setting {
+            SettingValue::ContentFile(file) => Some((DuplicateKind::ContentFile, file)),
+            SettingValue::Groundcover(file) => Some((DuplicateKind::Groundcover, file)),
+            SettingValue::BethArchive(file) => Some((DuplicateKind::FallbackArchive, file)),
+            _ => None,
+        }
+    }
+}
+
+/// A repeated list entry, which is only possible when loading with `ParseOptions::allowing_duplicates`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateEntry {
+    pub kind: DuplicateKind,
+    pub name: String,
+    /// Config which defined the entry that takes effect
+    pub first_source: PathBuf,
+    /// Config which repeated it
+    pub duplicate_source: PathBuf,
+    /// Handle of the repeated entry
+    pub handle: SettingHandle,
+}
+
+impl OpenMWConfiguration {
+    /// Every repeated content, groundcover or archive entry, in load order
+    pub fn duplicates(&self) -> Vec<DuplicateEntry> {
+        let mut first_seen: HashMap<(DuplicateKind, Cow<str>), &Path> = HashMap::new();
+        let mut duplicates = Vec::new();
+
+        for setting in self.settings.iter() {
+            let Some((kind, file)) = DuplicateKind::of(setting) else {
+                continue;
+            };
+
+            let source = file.meta().source_config();
+            let name = match self.ignore_case {
+                true => Cow::Owned(strings::fold_case(file.value())),
+                false => Cow::Borrowed(file.value().as_str()),
+            };
+
+            match first_seen.get(&(kind, name.clone())) {
+                Some(first_source) => duplicates.push(DuplicateEntry {
+                    kind,
+                    name: file.value().clone(),
+                    first_source: first_source.to_path_buf(),
+                    duplicate_source: source.to_path_buf(),
+                    handle: file.meta().handle(),
+                }),
+                None => {
+                    first_seen.insert((kind, name), source);
+                }
+            }
+        }
+
+        duplicates
+    }
+
+    /// Drops every repeated entry, keeping the first occurrence like the engine does
+    pub fn dedupe(&mut self) -> Vec<DuplicateEntry> {
+        let duplicates = self.duplicates();
+
+        self.clear_matching(|setting| {
+            duplicates
+                .iter()
+                .any(|duplicate| duplicate.handle == setting.handle())
+        });
+
+        duplicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, config::util::temp_config};
+
+    #[test]
+    fn test_dedupe_keeps_first_occurrence() {
+        let user_dir = temp_config(
+            "duplicates_user",
+            "content=Tribunal.esm\ncontent=Morrowind.esm\nfallback-archive=Morrowind.bsa\n",
+        );
+        let root_dir = temp_config(
+            "duplicates_root",
+            &format!(
+                "content=Morrowind.esm\nfallback-archive=Morrowind.bsa\nconfig={}\n",
+                user_dir.display()
+            ),
+        );
+
+        assert!(OpenMWConfiguration::new(Some(root_dir.clone())).is_err());
+
+        let options = ParseOptions::strict().allowing_duplicates();
+        let mut config =
+            OpenMWConfiguration::new_with_options(Some(root_dir.clone()), options).unwrap();
+
+        let duplicates = config.duplicates();
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].kind, DuplicateKind::ContentFile);
+        assert_eq!(duplicates[0].first_source, root_dir.join("openmw.cfg"));
+        assert_eq!(duplicates[0].duplicate_source, user_dir.join("openmw.cfg"));
+
+        assert_eq!(config.dedupe(), duplicates);
+        assert!(config.duplicates().is_empty());
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal.esm"]
+        );
+        assert_eq!(config.fallback_archives(), vec!["Morrowind.bsa"]);
+    }
+}
@@ -3,9 +3,9 @@
 // Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
 
-use std::fmt;
+use std::{fmt, path::PathBuf, str::FromStr};
 
-use crate::{ConfigError, GameSetting, GameSettingMeta, bail_config};
+use crate::{ConfigError, GameSetting, GameSettingMeta, bail_config, config_err};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum EncodingType {
@@ -14,15 +14,37 @@ pub enum EncodingType {
     WIN1252,
 }
 
-impl std::fmt::Display for EncodingType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let value = match self {
+impl EncodingType {
+    pub const ALL: [EncodingType; 3] = [
+        EncodingType::WIN1250,
+        EncodingType::WIN1251,
+        EncodingType::WIN1252,
+    ];
+
+    /// The name openmw.cfg uses
+    pub fn as_str(&self) -> &'static str {
+        match self {
             EncodingType::WIN1250 => "win1250",
             EncodingType::WIN1251 => "win1251",
             EncodingType::WIN1252 => "win1252",
-        };
+        }
+    }
+}
+
+impl FromStr for EncodingType {
+    type Err = ConfigError;
 
-        writeln!(f, "{value}")
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|encoding| encoding.as_str() == value)
+            .ok_or_else(|| config_err!(unknown_encoding, value))
+    }
+}
+
+impl std::fmt::Display for EncodingType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.as_str())
     }
 }
 
@@ -42,18 +64,19 @@ impl GameSetting for EncodingSetting {
     fn meta(&self) -> &GameSettingMeta {
         &self.meta
     }
+
+    fn meta_mut(&mut self) -> &mut GameSettingMeta {
+        &mut self.meta
+    }
 }
 
 impl fmt::Display for EncodingSetting {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            format!("{}encoding={}", self.meta.comment, self.encoding)
-        )
+        write!(f, "{}encoding={}", self.meta.comment, self.encoding)
     }
 }
 
+/// What the loader uses, taking the queued comment along. Everything else should go through `FromStr` or `SettingContext::parse`.
 impl<P: AsRef<std::path::Path>> TryFrom<(String, P, &mut String)> for EncodingSetting {
     type Error = ConfigError;
 
@@ -62,23 +85,66 @@ impl<P: AsRef<std::path::Path>> TryFrom<(String, P, &mut String)> for EncodingSe
     ) -> Result<Self, Self::Error> {
         let source_config = source_config.as_ref().to_path_buf();
 
-        let encoding = match value.as_str() {
-            "win1250" => EncodingType::WIN1250,
-            "win1251" => EncodingType::WIN1251,
-            "win1252" => EncodingType::WIN1252,
-            _ => bail_config!(bad_encoding, value, source_config),
+        let Ok(encoding) = value.parse() else {
+            bail_config!(bad_encoding, value, source_config)
         };
 
-        let meta = GameSettingMeta {
-            source_config,
-            comment: comment.to_owned(),
-        };
+        let meta = GameSettingMeta::new(source_config, comment.to_owned());
         comment.clear();
 
         Ok(EncodingSetting { encoding, meta })
     }
 }
 
+impl EncodingSetting {
+    pub fn handle(&self) -> crate::SettingHandle {
+        self.meta.handle()
+    }
+
+    /// Not from any openmw.cfg yet. `OpenMWConfiguration::set_encoding_type` puts it in the user's.
+    pub fn new(encoding: EncodingType) -> Self {
+        Self {
+            meta: GameSettingMeta::new(PathBuf::new(), String::new()),
+            encoding,
+        }
+    }
+
+    pub fn encoding_type(&self) -> &EncodingType {
+        &self.encoding
+    }
+
+    #[cfg(feature = "encoding")]
+    pub fn decoder(&self) -> encoding_rs::Decoder {
+        self.encoding.decoder()
+    }
+}
+
+/// Conversions for the text inside plugins and saves, which is in the game's codepage rather than UTF-8
+#[cfg(feature = "encoding")]
+impl EncodingType {
+    pub fn codec(&self) -> &'static encoding_rs::Encoding {
+        match self {
+            EncodingType::WIN1250 => encoding_rs::WINDOWS_1250,
+            EncodingType::WIN1251 => encoding_rs::WINDOWS_1251,
+            EncodingType::WIN1252 => encoding_rs::WINDOWS_1252,
+        }
+    }
+
+    /// For text arriving in pieces. Morrowind's strings never start with a BOM, so none is looked for.
+    pub fn decoder(&self) -> encoding_rs::Decoder {
+        self.codec().new_decoder_without_bom_handling()
+    }
+
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> std::borrow::Cow<'a, str> {
+        self.codec().decode_without_bom_handling(bytes).0
+    }
+
+    /// Characters the codepage doesn't have become HTML numeric references, which is what encoding_rs does
+    pub fn encode<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, [u8]> {
+        self.codec().encode(text).0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -109,6 +175,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encoding_names() {
+        for encoding in EncodingType::ALL {
+            assert_eq!(encoding.as_str().parse::<EncodingType>().unwrap(), encoding);
+        }
+
+        assert!(matches!(
+            "utf8".parse::<EncodingType>(),
+            Err(ConfigError::UnknownEncoding(_))
+        ));
+        assert_eq!(
+            EncodingSetting::new(EncodingType::WIN1251).to_string(),
+            "encoding=win1251\n"
+        );
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn test_decode_codepages() {
+        assert_eq!(EncodingType::WIN1251.decode(b"\xc4\xe0"), "Да");
+        assert_eq!(EncodingType::WIN1252.decode(b"Caf\xe9"), "Café");
+        assert_eq!(&*EncodingType::WIN1250.encode("Łódź"), b"\xa3\xf3d\x9f");
+    }
+
     #[test]
     fn test_invalid_encoding() {
         let err =
@@ -0,0 +1,144 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Comparing configurations by what the engine would end up with, rather than by how the files happen to be written.
+//! Comments, `config=` lines, which file set what, and repeated fallbacks which get overridden anyway all drop out.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    hash::{Hash, Hasher},
+};
+
+use crate::config::{OpenMWConfiguration, SettingValue};
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Feeds `bytes` into an FNV-1a hash, which starts out as `FNV_OFFSET`
+pub(crate) fn fnv1a(hash: u64, bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+impl OpenMWConfiguration {
+    /// One line per effective value, in a fixed order
+    fn effective_lines(&self) -> Vec<String> {
+        let mut singletons = BTreeMap::new();
+        let mut lists = Vec::new();
+        let mut fallbacks = BTreeMap::new();
+        let mut flags = BTreeSet::new();
+
+        for setting in self.settings.iter() {
+            match setting {
+                SettingValue::UserData(dir)
+                | SettingValue::DataLocal(dir)
+                | SettingValue::Resources(dir) => {
+                    singletons.insert(setting.key(), dir.parsed().display().to_string());
+                }
+                SettingValue::Encoding(encoding) => {
+                    let encoding = encoding.encoding_type().to_string();
+                    singletons.insert(setting.key(), encoding.trim_end().to_string());
+                }
+                SettingValue::DataDirectory(dir) => {
+                    lists.push(format!("data={}", dir.parsed().display()));
+                }
+                SettingValue::ContentFile(file)
+                | SettingValue::BethArchive(file)
+                | SettingValue::Groundcover(file) => {
+                    lists.push(format!("{}={}", setting.key(), file.value()));
+                }
+                SettingValue::Generic(generic) => {
+                    lists.push(format!("{}={}", generic.key(), generic.value()));
+                }
+                SettingValue::GameSetting(fallback) => {
+                    fallbacks.insert(fallback.key().clone(), fallback.value());
+                }
+                SettingValue::Flag(flag) => {
+                    flags.insert(flag.key().clone());
+                }
+                SettingValue::SubConfiguration(_) | SettingValue::Disabled(_) => {}
+            }
+        }
+
+        singletons
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .chain(lists)
+            .chain(
+                fallbacks
+                    .into_iter()
+                    .map(|(key, value)| format!("fallback={key},{value}")),
+            )
+            .chain(flags)
+            .collect()
+    }
+
+    /// A hash of the effective configuration which stays the same across runs, platforms and crate versions,
+    /// So it can be stored to tell whether anything changed since the last launch.
+    pub fn content_hash(&self) -> u64 {
+        fnv1a(
+            FNV_OFFSET,
+            self.effective_lines()
+                .iter()
+                .flat_map(|line| line.bytes().chain(std::iter::once(b'\n'))),
+        )
+    }
+
+    /// Stricter than `==`: comments, `config=` lines and the order everything was written in have to match as well
+    pub fn eq_including_comments(&self, other: &Self) -> bool {
+        self.settings.len() == other.settings.len()
+            && self
+                .settings
+                .iter()
+                .zip(other.settings.iter())
+                .all(|(ours, theirs)| ours.to_string() == theirs.to_string())
+    }
+}
+
+/// Two configurations are equal when the engine would load the same thing from both
+impl PartialEq for OpenMWConfiguration {
+    fn eq(&self, other: &Self) -> bool {
+        self.effective_lines() == other.effective_lines()
+    }
+}
+
+impl Eq for OpenMWConfiguration {}
+
+impl Hash for OpenMWConfiguration {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.effective_lines().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_semantic_equality() {
+        let first = temp_config(
+            "equality_first",
+            "# my setup\ncontent=Morrowind.esm\nfallback=iMaxSpeed,5\nfallback=iMaxSpeed,10\nfallback=fScale,1.0\n",
+        );
+        let second = temp_config(
+            "equality_second",
+            "fallback=fScale,1.0\ncontent=Morrowind.esm\n\n# tweaked\nfallback=iMaxSpeed,10\n",
+        );
+
+        let first = OpenMWConfiguration::new(Some(first)).unwrap();
+        let mut second = OpenMWConfiguration::new(Some(second)).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.content_hash(), second.content_hash());
+        assert!(!first.eq_including_comments(&second));
+        assert!(first.eq_including_comments(&first));
+
+        second.add_content_file("Tribunal.esm").unwrap();
+        assert_ne!(first, second);
+        assert_ne!(first.content_hash(), second.content_hash());
+    }
+}
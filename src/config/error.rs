@@ -5,6 +5,8 @@
 
 use std::{fmt, path::PathBuf};
 
+use crate::config::messages::{Message, MessageId};
+
 #[macro_export]
 macro_rules! config_err {
     // InvalidGameSetting: value, path
@@ -12,6 +14,7 @@ macro_rules! config_err {
         $crate::ConfigError::InvalidGameSetting {
             value: $value.to_string(),
             config_path: $path.to_path_buf(),
+            line: None,
         }
     };
 
@@ -69,6 +72,7 @@ macro_rules! config_err {
         $crate::ConfigError::BadEncoding {
             value: $encoding,
             config_path: $config_path,
+            line: None,
         }
     };
 
@@ -76,9 +80,155 @@ macro_rules! config_err {
         $crate::ConfigError::InvalidLine {
             value: $value,
             config_path: $config_path,
+            line: None,
+        }
+    };
+
+    (invalid_cli_argument, $value:expr) => {
+        $crate::ConfigError::InvalidCliArgument($value.to_string())
+    };
+
+    (invalid_merge_resolution, $key:expr, $value:expr) => {
+        $crate::ConfigError::InvalidMergeResolution {
+            key: $key.to_string(),
+            value: $value.to_string(),
+        }
+    };
+
+    (archive_not_found, $archive:expr) => {
+        $crate::ConfigError::ArchiveNotFound($archive.to_string())
+    };
+
+    (invalid_archive, $path:expr, $reason:expr) => {
+        $crate::ConfigError::InvalidArchive {
+            path: $path.to_path_buf(),
+            reason: $reason.to_string(),
+        }
+    };
+
+    (fix_not_applicable, $fix:expr) => {
+        $crate::ConfigError::FixNotApplicable($fix.to_string())
+    };
+
+    (invalid_localization, $reason:expr) => {
+        $crate::ConfigError::InvalidLocalization($reason.to_string())
+    };
+
+    (invalid_utf8, $path:expr, $line:expr) => {
+        $crate::ConfigError::InvalidUtf8 {
+            path: $path.to_path_buf(),
+            line: $line,
+        }
+    };
+
+    (plugin_not_found, $plugin:expr) => {
+        $crate::ConfigError::PluginNotFound($plugin.to_string())
+    };
+
+    (invalid_plugin, $path:expr, $reason:expr) => {
+        $crate::ConfigError::InvalidPlugin {
+            path: $path.to_path_buf(),
+            reason: $reason.to_string(),
+        }
+    };
+
+    (game_setting_type_mismatch, $key:expr, $expected:expr, $value:expr) => {
+        $crate::ConfigError::GameSettingTypeMismatch {
+            key: $key.to_string(),
+            expected: $expected,
+            value: $value.to_string(),
+        }
+    };
+
+    (game_setting_wrong_type, $key:expr, $actual:expr, $requested:expr) => {
+        $crate::ConfigError::GameSettingWrongType {
+            key: $key.to_string(),
+            actual: $actual,
+            requested: $requested,
+        }
+    };
+
+    (unknown_game_setting, $key:expr) => {
+        $crate::ConfigError::UnknownGameSetting($key.to_string())
+    };
+
+    (sub_config_exists, $path:expr) => {
+        $crate::ConfigError::SubConfigExists($path.to_path_buf())
+    };
+
+    (config_cycle, $chain:expr) => {
+        $crate::ConfigError::ConfigCycle { chain: $chain }
+    };
+
+    (config_chain_too_deep, $max:expr, $path:expr) => {
+        $crate::ConfigError::ConfigChainTooDeep {
+            max: $max,
+            path: $path.to_path_buf(),
+        }
+    };
+
+    (invalid_manifest, $path:expr, $reason:expr) => {
+        $crate::ConfigError::InvalidManifest {
+            path: $path.to_path_buf(),
+            reason: $reason.to_string(),
+        }
+    };
+
+    (read_only, $path:expr) => {
+        $crate::ConfigError::ReadOnly($path.to_path_buf())
+    };
+
+    (invalid_save, $path:expr, $reason:expr) => {
+        $crate::ConfigError::InvalidSave {
+            path: $path.to_path_buf(),
+            reason: $reason.to_string(),
+        }
+    };
+
+    (invalid_input_bindings, $path:expr, $reason:expr) => {
+        $crate::ConfigError::InvalidInputBindings {
+            path: $path.to_path_buf(),
+            reason: $reason.to_string(),
+        }
+    };
+
+    (invalid_launcher_cfg, $path:expr, $reason:expr) => {
+        $crate::ConfigError::InvalidLauncherCfg {
+            path: $path.to_path_buf(),
+            reason: $reason.to_string(),
+        }
+    };
+
+    (not_a_game_file, $file:expr) => {
+        $crate::ConfigError::NotAGameFile($file.to_string())
+    };
+
+    (multiple_game_files, $files:expr) => {
+        $crate::ConfigError::MultipleGameFiles($files)
+    };
+
+    (game_file_in_use, $game_file:expr, $dependents:expr) => {
+        $crate::ConfigError::GameFileInUse {
+            game_file: $game_file.to_string(),
+            dependents: $dependents,
+        }
+    };
+
+    (setting_kind_changed, $from:expr, $to:expr) => {
+        $crate::ConfigError::SettingKindChanged {
+            from: $from.to_string(),
+            to: $to.to_string(),
         }
     };
 
+    (outside_mods_path, $path:expr) => {
+        $crate::ConfigError::OutsideModsPath($path.to_path_buf())
+    };
+
+    (unknown_encoding, $value:expr) => {
+        $crate::ConfigError::UnknownEncoding($value.to_string())
+    };
+
     // Wrap std::io::Error
     (io, $err:expr) => {
         $crate::ConfigError::Io($err)
@@ -96,121 +246,334 @@ macro_rules! bail_config {
 
 #[derive(Debug)]
 pub enum ConfigError {
-    DuplicateContentFile { file: String, config_path: PathBuf },
-    DuplicateArchiveFile { file: String, config_path: PathBuf },
-    CannotAddContentFile { file: String, config_path: PathBuf },
-    CannotAddArchiveFile { file: String, config_path: PathBuf },
-    DuplicateGroundcoverFile { file: String, config_path: PathBuf },
-    CannotAddGroundcoverFile { file: String, config_path: PathBuf },
-    InvalidGameSetting { value: String, config_path: PathBuf },
-    BadEncoding { value: String, config_path: PathBuf },
-    InvalidLine { value: String, config_path: PathBuf },
+    DuplicateContentFile {
+        file: String,
+        config_path: PathBuf,
+    },
+    DuplicateArchiveFile {
+        file: String,
+        config_path: PathBuf,
+    },
+    CannotAddContentFile {
+        file: String,
+        config_path: PathBuf,
+    },
+    CannotAddArchiveFile {
+        file: String,
+        config_path: PathBuf,
+    },
+    DuplicateGroundcoverFile {
+        file: String,
+        config_path: PathBuf,
+    },
+    CannotAddGroundcoverFile {
+        file: String,
+        config_path: PathBuf,
+    },
+    /// `line` is filled in when the error comes from reading a file, counting from 1
+    InvalidGameSetting {
+        value: String,
+        config_path: PathBuf,
+        line: Option<usize>,
+    },
+    BadEncoding {
+        value: String,
+        config_path: PathBuf,
+        line: Option<usize>,
+    },
+    InvalidLine {
+        value: String,
+        config_path: PathBuf,
+        line: Option<usize>,
+    },
+    InvalidCliArgument(String),
+    InvalidMergeResolution {
+        key: String,
+        value: String,
+    },
+    ArchiveNotFound(String),
+    InvalidArchive {
+        path: PathBuf,
+        reason: String,
+    },
+    FixNotApplicable(String),
+    InvalidLocalization(String),
+    InvalidUtf8 {
+        path: PathBuf,
+        line: usize,
+    },
+    PluginNotFound(String),
+    InvalidPlugin {
+        path: PathBuf,
+        reason: String,
+    },
+    GameSettingTypeMismatch {
+        key: String,
+        expected: &'static str,
+        value: String,
+    },
+    GameSettingWrongType {
+        key: String,
+        actual: &'static str,
+        requested: &'static str,
+    },
+    UnknownGameSetting(String),
+    SubConfigExists(PathBuf),
+    ConfigCycle {
+        chain: Vec<PathBuf>,
+    },
+    ConfigChainTooDeep {
+        max: usize,
+        path: PathBuf,
+    },
+    InvalidManifest {
+        path: PathBuf,
+        reason: String,
+    },
+    ReadOnly(PathBuf),
+    InvalidSave {
+        path: PathBuf,
+        reason: String,
+    },
+    InvalidInputBindings {
+        path: PathBuf,
+        reason: String,
+    },
+    InvalidLauncherCfg {
+        path: PathBuf,
+        reason: String,
+    },
+    NotAGameFile(String),
+    MultipleGameFiles(Vec<String>),
+    GameFileInUse {
+        game_file: String,
+        dependents: Vec<String>,
+    },
+    UnknownEncoding(String),
+    OutsideModsPath(PathBuf),
+    SettingKindChanged {
+        from: String,
+        to: String,
+    },
     Io(std::io::Error),
     NotFileOrDirectory(PathBuf),
     CannotFind(PathBuf),
 }
 
-impl fmt::Display for ConfigError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl ConfigError {
+    /// The error as a catalog message, for displaying it in languages other than English
+    pub fn message(&self) -> Message {
         match self {
-            ConfigError::InvalidGameSetting { value, config_path } => {
-                write!(
-                    f,
-                    "Invalid fallback setting '{}' in config file '{}'",
-                    value,
-                    config_path.display()
-                )
-            }
-            ConfigError::Io(e) => write!(f, "IO error: {}", e),
-            ConfigError::NotFileOrDirectory(config_path) => write!(
-                f,
-                "{}",
-                format!(
-                    "Unable to determine whether {} was a file or directory, refusing to read.",
-                    config_path.display()
-                )
-            ),
+            ConfigError::InvalidGameSetting {
+                value,
+                config_path,
+                line,
+            } => Message::new(MessageId::InvalidGameSetting)
+                .arg("value", value)
+                .arg("config_path", location(config_path, *line)),
+            ConfigError::Io(e) => Message::new(MessageId::Io).arg("error", e),
+            ConfigError::NotFileOrDirectory(config_path) => {
+                Message::new(MessageId::NotFileOrDirectory)
+                    .arg("config_path", config_path.display())
+            }
             ConfigError::CannotFind(config_path) => {
-                write!(
-                    f,
-                    "{}",
-                    format!("An openmw.cfg does not exist at: {}", config_path.display())
-                )
-            }
-            ConfigError::DuplicateContentFile { file, config_path } => write!(
-                f,
-                "{}",
-                format!(
-                    "{file} has appeared in the content files list twice. Its second occurence was in: {}",
-                    config_path.display()
-                ),
-            ),
-            ConfigError::CannotAddContentFile { file, config_path } => write!(
-                f,
-                "{}",
-                format!(
-                    "{file} cannot be added to the configuration map as a content file because it was already defined by: {}",
-                    config_path.display()
-                ),
-            ),
-            ConfigError::DuplicateGroundcoverFile { file, config_path } => write!(
-                f,
-                "{}",
-                format!(
-                    "{file} has appeared in the groundcover list twice. Its second occurence was in: {}",
-                    config_path.display()
-                ),
-            ),
-            ConfigError::CannotAddGroundcoverFile { file, config_path } => write!(
-                f,
-                "{}",
-                format!(
-                    "{file} cannot be added to the configuration map as a groundcover plugin because it was already defined by: {}",
-                    config_path.display()
-                ),
-            ),
-            ConfigError::DuplicateArchiveFile { file, config_path } => write!(
-                f,
-                "{}",
-                format!(
-                    "{file} has appeared in the BSA/Archive list twice. Its second occurence was in: {}",
-                    config_path.display()
-                ),
-            ),
-            ConfigError::CannotAddArchiveFile { file, config_path } => write!(
-                f,
-                "{}",
-                format!(
-                    "{file} cannot be added to the configuration map as a fallback-archive because it was already defined by: {}",
-                    config_path.display()
-                ),
+                Message::new(MessageId::CannotFind).arg("config_path", config_path.display())
+            }
+            ConfigError::DuplicateContentFile { file, config_path } => {
+                Message::new(MessageId::DuplicateContentFile)
+                    .arg("file", file)
+                    .arg("config_path", config_path.display())
+            }
+            ConfigError::CannotAddContentFile { file, config_path } => {
+                Message::new(MessageId::CannotAddContentFile)
+                    .arg("file", file)
+                    .arg("config_path", config_path.display())
+            }
+            ConfigError::DuplicateGroundcoverFile { file, config_path } => {
+                Message::new(MessageId::DuplicateGroundcoverFile)
+                    .arg("file", file)
+                    .arg("config_path", config_path.display())
+            }
+            ConfigError::CannotAddGroundcoverFile { file, config_path } => {
+                Message::new(MessageId::CannotAddGroundcoverFile)
+                    .arg("file", file)
+                    .arg("config_path", config_path.display())
+            }
+            ConfigError::DuplicateArchiveFile { file, config_path } => {
+                Message::new(MessageId::DuplicateArchiveFile)
+                    .arg("file", file)
+                    .arg("config_path", config_path.display())
+            }
+            ConfigError::CannotAddArchiveFile { file, config_path } => {
+                Message::new(MessageId::CannotAddArchiveFile)
+                    .arg("file", file)
+                    .arg("config_path", config_path.display())
+            }
+            ConfigError::BadEncoding {
+                value,
+                config_path,
+                line,
+            } => Message::new(MessageId::BadEncoding)
+                .arg("value", value)
+                .arg("config_path", location(config_path, *line)),
+            ConfigError::InvalidCliArgument(value) => {
+                Message::new(MessageId::InvalidCliArgument).arg("value", value)
+            }
+            ConfigError::InvalidMergeResolution { key, value } => {
+                Message::new(MessageId::InvalidMergeResolution)
+                    .arg("key", key)
+                    .arg("value", value)
+            }
+            ConfigError::ArchiveNotFound(archive) => {
+                Message::new(MessageId::ArchiveNotFound).arg("archive", archive)
+            }
+            ConfigError::InvalidArchive { path, reason } => Message::new(MessageId::InvalidArchive)
+                .arg("path", path.display())
+                .arg("reason", reason),
+            ConfigError::FixNotApplicable(fix) => {
+                Message::new(MessageId::FixNotApplicable).arg("fix", fix)
+            }
+            ConfigError::InvalidLocalization(reason) => {
+                Message::new(MessageId::InvalidLocalization).arg("reason", reason)
+            }
+            ConfigError::InvalidUtf8 { path, line } => Message::new(MessageId::InvalidUtf8)
+                .arg("path", path.display())
+                .arg("line", line),
+            ConfigError::PluginNotFound(plugin) => {
+                Message::new(MessageId::PluginNotFound).arg("plugin", plugin)
+            }
+            ConfigError::InvalidPlugin { path, reason } => Message::new(MessageId::InvalidPlugin)
+                .arg("path", path.display())
+                .arg("reason", reason),
+            ConfigError::GameSettingTypeMismatch {
+                key,
+                expected,
+                value,
+            } => Message::new(MessageId::GameSettingTypeMismatch)
+                .arg("key", key)
+                .arg("expected", expected)
+                .arg("value", value),
+            ConfigError::GameSettingWrongType {
+                key,
+                actual,
+                requested,
+            } => Message::new(MessageId::GameSettingWrongType)
+                .arg("key", key)
+                .arg("actual", actual)
+                .arg("requested", requested),
+            ConfigError::UnknownGameSetting(key) => {
+                Message::new(MessageId::UnknownGameSetting).arg("key", key)
+            }
+            ConfigError::SubConfigExists(path) => {
+                Message::new(MessageId::SubConfigExists).arg("path", path.display())
+            }
+            ConfigError::ConfigCycle { chain } => Message::new(MessageId::ConfigCycle).arg(
+                "chain",
+                chain
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> "),
             ),
-            ConfigError::BadEncoding { value, config_path } => {
-                write!(
-                    f,
-                    "{}",
-                    format!(
-                        "Invalid encoding type: {value} in config file {}",
-                        config_path.display()
-                    ),
-                )
-            }
-            ConfigError::InvalidLine { value, config_path } => {
-                write!(
-                    f,
-                    "{}",
-                    format!(
-                        "Invalid pair in openmw.cfg {value} was defined by {}",
-                        config_path.display()
-                    )
-                )
+            ConfigError::ConfigChainTooDeep { max, path } => {
+                Message::new(MessageId::ConfigChainTooDeep)
+                    .arg("max", max)
+                    .arg("path", path.display())
+            }
+            ConfigError::InvalidManifest { path, reason } => {
+                Message::new(MessageId::InvalidManifest)
+                    .arg("path", path.display())
+                    .arg("reason", reason)
+            }
+            ConfigError::ReadOnly(path) => {
+                Message::new(MessageId::ReadOnly).arg("path", path.display())
+            }
+            ConfigError::InvalidSave { path, reason } => Message::new(MessageId::InvalidSave)
+                .arg("path", path.display())
+                .arg("reason", reason),
+            ConfigError::InvalidInputBindings { path, reason } => {
+                Message::new(MessageId::InvalidInputBindings)
+                    .arg("path", path.display())
+                    .arg("reason", reason)
+            }
+            ConfigError::InvalidLauncherCfg { path, reason } => {
+                Message::new(MessageId::InvalidLauncherCfg)
+                    .arg("path", path.display())
+                    .arg("reason", reason)
+            }
+            ConfigError::NotAGameFile(file) => {
+                Message::new(MessageId::NotAGameFile).arg("file", file)
             }
+            ConfigError::MultipleGameFiles(files) => {
+                Message::new(MessageId::MultipleGameFiles).arg("files", files.join(", "))
+            }
+            ConfigError::GameFileInUse {
+                game_file,
+                dependents,
+            } => Message::new(MessageId::GameFileInUse)
+                .arg("game_file", game_file)
+                .arg("dependents", dependents.join(", ")),
+            ConfigError::UnknownEncoding(value) => {
+                Message::new(MessageId::UnknownEncoding).arg("value", value)
+            }
+            ConfigError::SettingKindChanged { from, to } => {
+                Message::new(MessageId::SettingKindChanged)
+                    .arg("from", from)
+                    .arg("to", to)
+            }
+            ConfigError::OutsideModsPath(path) => {
+                Message::new(MessageId::OutsideModsPath).arg("path", path.display())
+            }
+            ConfigError::InvalidLine {
+                value,
+                config_path,
+                line,
+            } => Message::new(MessageId::InvalidLine)
+                .arg("value", value)
+                .arg("config_path", location(config_path, *line)),
         }
     }
+
+    /// The line of the openmw.cfg this error was found on, if it came from one
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ConfigError::InvalidGameSetting { line, .. }
+            | ConfigError::BadEncoding { line, .. }
+            | ConfigError::InvalidLine { line, .. } => *line,
+            ConfigError::InvalidUtf8 { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
+    /// Records which line the error was found on, for the variants which can carry one
+    pub(crate) fn at_line(mut self, number: usize) -> Self {
+        if let ConfigError::InvalidGameSetting { line, .. }
+        | ConfigError::BadEncoding { line, .. }
+        | ConfigError::InvalidLine { line, .. } = &mut self
+        {
+            *line = Some(number);
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
 }
 
 impl std::error::Error for ConfigError {}
 
+/// `path:line`, the way editors and compilers point at a location
+fn location(path: &std::path::Path, line: Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{line}", path.display()),
+        None => path.display().to_string(),
+    }
+}
+
 impl From<std::io::Error> for ConfigError {
     fn from(err: std::io::Error) -> Self {
         ConfigError::Io(err)
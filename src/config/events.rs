@@ -0,0 +1,376 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! One place for an application to hear about everything the crate reports:
+//! Parse warnings, changes to the settings list, saves and (with the `watch` feature) changes on disk.
+//!
+//! UIs which only care about what changed can use `on_change` instead, which hands out owned `ChangeEvent`s.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    ConfigError, SettingHandle,
+    config::{OpenMWConfiguration, SettingValue},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigNotification<'a> {
+    /// A line which lenient parsing skipped
+    ParseWarning(&'a ConfigError),
+    SettingAdded(&'a SettingValue),
+    SettingRemoved(&'a SettingValue),
+    /// Same setting (and handle), with a new value or comment
+    SettingChanged(&'a SettingValue),
+    /// A content file moved to a different load order position
+    SettingMoved(&'a SettingValue),
+    /// An openmw.cfg was written, with the path written to
+    Saved(&'a Path),
+    #[cfg(feature = "watch")]
+    FileChanged(&'a crate::config::watch::ConfigEvent),
+}
+
+/// Implemented by applications that want every notification in one place.
+/// Notifications are delivered synchronously, from whichever thread caused them.
+pub trait ConfigEventSink: Send + Sync {
+    fn notify(&self, notification: ConfigNotification<'_>);
+}
+
+impl<F> ConfigEventSink for F
+where
+    F: Fn(ConfigNotification<'_>) + Send + Sync,
+{
+    fn notify(&self, notification: ConfigNotification<'_>) {
+        self(notification)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct EventSinkSlot(Option<Arc<dyn ConfigEventSink>>);
+
+impl EventSinkSlot {
+    pub(crate) fn new(sink: Option<Arc<dyn ConfigEventSink>>) -> Self {
+        Self(sink)
+    }
+
+    pub(crate) fn get(&self) -> Option<&Arc<dyn ConfigEventSink>> {
+        self.0.as_ref()
+    }
+
+    pub(crate) fn notify(&self, notification: ConfigNotification<'_>) {
+        if let Some(sink) = &self.0 {
+            sink.notify(notification);
+        }
+    }
+}
+
+impl fmt::Debug for EventSinkSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EventSinkSlot({})", self.0.is_some())
+    }
+}
+
+/// A change to the settings list, in terms of what a UI would show.
+/// Content and archive names are exactly as written in openmw.cfg.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent {
+    ContentAdded(String),
+    ContentRemoved(String),
+    ContentMoved(String),
+    GroundcoverAdded(String),
+    GroundcoverRemoved(String),
+    ArchiveAdded(String),
+    ArchiveRemoved(String),
+    DataDirAdded(PathBuf),
+    DataDirRemoved(PathBuf),
+    /// `user-data`, `data-local`, `resources` or `encoding`, with `None` once it's removed
+    SingletonChanged {
+        key: String,
+        value: Option<String>,
+    },
+    /// Anything else: fallbacks, sub-configurations, flags, unknown keys, and comment edits on list entries
+    SettingChanged {
+        key: String,
+        handle: SettingHandle,
+    },
+}
+
+impl ChangeEvent {
+    fn of(notification: &ConfigNotification<'_>) -> Option<Self> {
+        use ConfigNotification::{SettingAdded, SettingChanged, SettingMoved, SettingRemoved};
+
+        let event = match *notification {
+            SettingAdded(SettingValue::ContentFile(plugin)) => {
+                ChangeEvent::ContentAdded(plugin.value().clone())
+            }
+            SettingRemoved(SettingValue::ContentFile(plugin)) => {
+                ChangeEvent::ContentRemoved(plugin.value().clone())
+            }
+            SettingMoved(SettingValue::ContentFile(plugin)) => {
+                ChangeEvent::ContentMoved(plugin.value().clone())
+            }
+            SettingAdded(SettingValue::Groundcover(grass)) => {
+                ChangeEvent::GroundcoverAdded(grass.value().clone())
+            }
+            SettingRemoved(SettingValue::Groundcover(grass)) => {
+                ChangeEvent::GroundcoverRemoved(grass.value().clone())
+            }
+            SettingAdded(SettingValue::BethArchive(archive)) => {
+                ChangeEvent::ArchiveAdded(archive.value().clone())
+            }
+            SettingRemoved(SettingValue::BethArchive(archive)) => {
+                ChangeEvent::ArchiveRemoved(archive.value().clone())
+            }
+            SettingAdded(SettingValue::DataDirectory(dir)) => {
+                ChangeEvent::DataDirAdded(dir.parsed().to_path_buf())
+            }
+            SettingRemoved(SettingValue::DataDirectory(dir)) => {
+                ChangeEvent::DataDirRemoved(dir.parsed().to_path_buf())
+            }
+            SettingAdded(setting) | SettingChanged(setting) | SettingRemoved(setting)
+                if singleton_value(setting).is_some() =>
+            {
+                ChangeEvent::SingletonChanged {
+                    key: setting.key().to_string(),
+                    value: match notification {
+                        SettingRemoved(_) => None,
+                        _ => singleton_value(setting),
+                    },
+                }
+            }
+            SettingAdded(setting)
+            | SettingChanged(setting)
+            | SettingRemoved(setting)
+            | SettingMoved(setting) => ChangeEvent::SettingChanged {
+                key: setting.key().to_string(),
+                handle: setting.handle(),
+            },
+            _ => return None,
+        };
+
+        Some(event)
+    }
+}
+
+fn singleton_value(setting: &SettingValue) -> Option<String> {
+    match setting {
+        SettingValue::UserData(dir)
+        | SettingValue::DataLocal(dir)
+        | SettingValue::Resources(dir) => Some(dir.original().clone()),
+        SettingValue::Encoding(encoding) => {
+            Some(encoding.encoding_type().to_string().trim_end().to_string())
+        }
+        _ => None,
+    }
+}
+
+pub type ChangeObserver = Arc<dyn Fn(&ChangeEvent) + Send + Sync>;
+
+/// Returned by `on_change`, to stop observing later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+#[derive(Clone, Default)]
+pub(crate) struct Observers {
+    next_id: u64,
+    observers: Vec<(ObserverId, ChangeObserver)>,
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.observers.len())
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Calls `observer` after every change to the settings list, until `remove_observer` is called with the returned id.
+    /// Works alongside the event sink rather than replacing it.
+    pub fn on_change<F>(&mut self, observer: F) -> ObserverId
+    where
+        F: Fn(&ChangeEvent) + Send + Sync + 'static,
+    {
+        let id = ObserverId(self.observers.next_id);
+        self.observers.next_id += 1;
+        self.observers.observers.push((id, Arc::new(observer)));
+        id
+    }
+
+    /// Returns false if there was no such observer
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        let before = self.observers.observers.len();
+        self.observers
+            .observers
+            .retain(|(observer_id, _)| *observer_id != id);
+        self.observers.observers.len() != before
+    }
+
+    /// Loading happens before a sink can be attached, so lines read from disk never show up as additions.
+    /// Use `ParseOptions::with_event_sink` to receive parse warnings as well.
+    pub fn set_event_sink(&mut self, sink: Option<Arc<dyn ConfigEventSink>>) {
+        self.event_sink = EventSinkSlot::new(sink);
+    }
+
+    pub fn event_sink(&self) -> Option<&Arc<dyn ConfigEventSink>> {
+        self.event_sink.get()
+    }
+
+    /// Also where changed settings mark their file as needing a save
+    pub(crate) fn notify(&self, notification: ConfigNotification<'_>) {
+        match notification {
+            ConfigNotification::SettingAdded(setting)
+            | ConfigNotification::SettingRemoved(setting)
+            | ConfigNotification::SettingChanged(setting)
+            | ConfigNotification::SettingMoved(setting) => self.mark_dirty(setting),
+            _ => {}
+        }
+
+        if !self.observers.observers.is_empty()
+            && let Some(event) = ChangeEvent::of(&notification)
+        {
+            self.observers
+                .observers
+                .iter()
+                .for_each(|(_, observer)| observer(&event));
+        }
+
+        self.event_sink.notify(notification);
+    }
+
+    // Everything which adds, removes or replaces a setting after loading should go through these,
+    // So the sink hears about it.
+
+    pub(crate) fn push_setting(&mut self, mut setting: SettingValue) {
+        if self.resolve_symlinks
+            && let SettingValue::DataDirectory(dir) = &mut setting
+        {
+            dir.resolve_symlinks(&*self.fs);
+        }
+
+        self.settings_mut().push(setting);
+        self.notify(ConfigNotification::SettingAdded(
+            self.settings.last().expect("a setting was just pushed"),
+        ));
+    }
+
+    pub(crate) fn remove_setting_at(&mut self, index: usize) -> SettingValue {
+        let removed = self.settings_mut().remove(index);
+        self.notify(ConfigNotification::SettingRemoved(&removed));
+        removed
+    }
+
+    pub(crate) fn replace_setting_at(&mut self, index: usize, setting: SettingValue) {
+        let replaced = std::mem::replace(&mut self.settings_mut()[index], setting);
+        self.notify(ConfigNotification::SettingRemoved(&replaced));
+        self.notify(ConfigNotification::SettingAdded(&self.settings[index]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{ParseOptions, config::util::temp_config};
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl ConfigEventSink for Recorder {
+        fn notify(&self, notification: ConfigNotification<'_>) {
+            let entry = match notification {
+                ConfigNotification::ParseWarning(_) => "warning".to_string(),
+                ConfigNotification::SettingAdded(setting) => {
+                    format!("added {}", setting.to_string().trim())
+                }
+                ConfigNotification::SettingRemoved(setting) => {
+                    format!("removed {}", setting.to_string().trim())
+                }
+                ConfigNotification::SettingChanged(setting) => {
+                    format!("changed {}", setting.to_string().trim())
+                }
+                ConfigNotification::SettingMoved(setting) => {
+                    format!("moved {}", setting.to_string().trim())
+                }
+                ConfigNotification::Saved(_) => "saved".to_string(),
+                #[cfg(feature = "watch")]
+                ConfigNotification::FileChanged(_) => "file".to_string(),
+            };
+
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    #[test]
+    fn test_change_observers() {
+        let dir = temp_config(
+            "change_observers",
+            "content=Morrowind.esm\ndata=Mods\nencoding=win1252\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let id = config.on_change(move |event| seen.lock().unwrap().push(event.clone()));
+
+        config.add_content_file("Tribunal.esm").unwrap();
+        config.remove_data_directory(&dir.join("Mods"));
+        let encoding = crate::EncodingSetting::try_from((
+            "win1251".to_string(),
+            dir.join("openmw.cfg"),
+            &mut String::new(),
+        ))
+        .unwrap();
+        config.set_encoding(Some(encoding));
+
+        assert!(config.remove_observer(id));
+        assert!(!config.remove_observer(id));
+        config.remove_content_file("Tribunal.esm");
+
+        let events = events.lock().unwrap();
+        assert_eq!(events[0], ChangeEvent::ContentAdded("Tribunal.esm".into()));
+        assert_eq!(events[1], ChangeEvent::DataDirRemoved(dir.join("Mods")));
+        assert_eq!(
+            events.last(),
+            Some(&ChangeEvent::SingletonChanged {
+                key: "encoding".into(),
+                value: Some("win1251".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_sink_receives_everything() {
+        let dir = temp_config(
+            "event_sink",
+            "content=Morrowind.esm\n=broken\nfallback=iMaxSpeed,1\n",
+        );
+        let recorder = Arc::new(Recorder::default());
+
+        let options = ParseOptions::lenient().with_event_sink(recorder.clone());
+        let mut config = OpenMWConfiguration::new_with_options(Some(dir), options).unwrap();
+
+        config.add_content_file("Tribunal.esm").unwrap();
+        config.insert_content_file(0, "Bloodmoon.esm").unwrap();
+        config.remove_content_file("Morrowind.esm");
+        config.set_game_setting_value("iMaxSpeed", "2").unwrap();
+        config.save_user().unwrap();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                "warning",
+                "added content=Tribunal.esm",
+                "added content=Bloodmoon.esm",
+                "moved content=Bloodmoon.esm",
+                "removed content=Morrowind.esm",
+                "changed fallback=iMaxSpeed,2",
+                "saved",
+            ]
+        );
+    }
+}
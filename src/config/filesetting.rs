@@ -6,6 +6,49 @@
 use crate::{GameSetting, GameSettingMeta};
 use std::fmt;
 
+/// What kind of file a content entry is, going by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentKind {
+    Esm,
+    Esp,
+    Omwaddon,
+    /// A standalone game, which the engine wants exactly one of, first in the load order
+    Omwgame,
+    /// A list of Lua scripts rather than a plugin
+    OmwScripts,
+    /// Anything listed under `groundcover=`, whatever its extension.
+    /// `FileSetting::kind` can't tell on its own, see `SettingValue::content_kind`.
+    Groundcover,
+    Unknown,
+}
+
+impl ContentKind {
+    pub fn from_file_name(name: &str) -> Self {
+        let extension = std::path::Path::new(name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("esm") => ContentKind::Esm,
+            Some("esp") => ContentKind::Esp,
+            Some("omwaddon") => ContentKind::Omwaddon,
+            Some("omwgame") => ContentKind::Omwgame,
+            Some("omwscripts") => ContentKind::OmwScripts,
+            _ => ContentKind::Unknown,
+        }
+    }
+
+    /// `.omwgame` and `.esm` files, which can start a load order
+    pub fn is_game_file(self) -> bool {
+        matches!(self, ContentKind::Omwgame | ContentKind::Esm)
+    }
+
+    /// `.esp` and `.omwaddon` files, which build on a game file
+    pub fn is_addon(self) -> bool {
+        matches!(self, ContentKind::Esp | ContentKind::Omwaddon)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FileSetting {
     meta: GameSettingMeta,
@@ -40,6 +83,10 @@ impl GameSetting for FileSetting {
     fn meta(&self) -> &GameSettingMeta {
         &self.meta
     }
+
+    fn meta_mut(&mut self) -> &mut GameSettingMeta {
+        &mut self.meta
+    }
 }
 
 impl fmt::Display for FileSetting {
@@ -49,12 +96,30 @@ impl fmt::Display for FileSetting {
 }
 
 impl FileSetting {
-    pub fn new(value: &str, source_config: &std::path::Path, comment: &mut String) -> Self {
+    /// Refers back to this entry through `OpenMWConfiguration::get_setting` and the like,
+    /// Even after the list around it has changed
+    pub fn handle(&self) -> crate::SettingHandle {
+        self.meta.handle()
+    }
+
+    pub fn new(value: &str, source_config: &std::path::Path) -> Self {
+        Self::with_queued_comment(value, source_config, &mut String::new())
+    }
+
+    /// Comment lines to write above it, with `#` added where it's missing. `None` leaves it without one.
+    pub fn with_comment(mut self, comment: Option<&str>) -> Self {
+        self.meta.set_comment(comment);
+        self
+    }
+
+    /// Takes the comment the parser queued up, leaving it empty for the next setting
+    pub(crate) fn with_queued_comment(
+        value: &str,
+        source_config: &std::path::Path,
+        comment: &mut String,
+    ) -> Self {
         Self {
-            meta: GameSettingMeta {
-                source_config: source_config.to_path_buf(),
-                comment: std::mem::take(comment),
-            },
+            meta: GameSettingMeta::new(source_config.to_path_buf(), std::mem::take(comment)),
             value: value.to_string(),
         }
     }
@@ -62,4 +127,21 @@ impl FileSetting {
     pub fn value(&self) -> &String {
         &self.value
     }
+
+    /// `.omwscripts` entries sit in the content list like plugins do, but name a list of Lua scripts instead
+    pub fn is_lua_scripts(&self) -> bool {
+        self.kind() == ContentKind::OmwScripts
+    }
+
+    pub fn kind(&self) -> ContentKind {
+        ContentKind::from_file_name(&self.value)
+    }
+
+    /// Whether this is `name`, optionally ignoring case like the engine's file lookups do
+    pub fn is_named(&self, name: &str, ignore_case: bool) -> bool {
+        match ignore_case {
+            true => crate::config::strings::eq_fold_case(&self.value, name),
+            false => self.value == name,
+        }
+    }
 }
@@ -0,0 +1,87 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{GameSetting, GameSettingMeta};
+use std::fmt;
+
+/// A valueless entry, written either as a bare `key` or as `key=`.
+/// Its presence is what matters, so it's written back out in whichever style it was read in.
+#[derive(Debug, Clone)]
+pub struct FlagSetting {
+    meta: GameSettingMeta,
+    key: String,
+    with_equals: bool,
+}
+
+impl PartialEq for FlagSetting {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl GameSetting for FlagSetting {
+    fn meta(&self) -> &GameSettingMeta {
+        &self.meta
+    }
+
+    fn meta_mut(&mut self) -> &mut GameSettingMeta {
+        &mut self.meta
+    }
+}
+
+impl fmt::Display for FlagSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.meta.comment, self.key)?;
+
+        if self.with_equals {
+            write!(f, "=")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FlagSetting {
+    pub fn handle(&self) -> crate::SettingHandle {
+        self.meta.handle()
+    }
+
+    pub fn new(key: &str, source_config: &std::path::Path) -> Self {
+        Self::with_queued_comment(key, source_config, &mut String::new())
+    }
+
+    /// Comment lines to write above it, with `#` added where it's missing. `None` leaves it without one.
+    pub fn with_comment(mut self, comment: Option<&str>) -> Self {
+        self.meta.set_comment(comment);
+        self
+    }
+
+    /// Takes the comment the parser queued up, leaving it empty for the next setting
+    pub(crate) fn with_queued_comment(
+        key: &str,
+        source_config: &std::path::Path,
+        comment: &mut String,
+    ) -> Self {
+        Self {
+            meta: GameSettingMeta::new(source_config.to_path_buf(), std::mem::take(comment)),
+            key: key.to_string(),
+            with_equals: false,
+        }
+    }
+
+    pub(crate) fn with_equals(mut self) -> Self {
+        self.with_equals = true;
+        self
+    }
+
+    pub fn key(&self) -> &String {
+        &self.key
+    }
+
+    /// Whether it was written as `key=` rather than just `key`
+    pub(crate) fn has_equals(&self) -> bool {
+        self.with_equals
+    }
+}
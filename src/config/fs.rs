@@ -0,0 +1,250 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Where openmw.cfg files are read from and written to.
+//!
+//! Everything the loader and the save functions touch goes through `ConfigFs`, so a browser or Electron tool
+//! (or a wasm32 build with no real filesystem at all) can hand over file contents itself.
+//! Game data - plugin headers, archives, the VFS overlay and watching for changes - still reads straight from disk.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
+};
+
+/// The parts of `std::fs::Metadata` the crate cares about, which a virtual filesystem can actually fill in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsMetadata {
+    pub is_dir: bool,
+    pub is_file: bool,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+pub trait ConfigFs: fmt::Debug + Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata>;
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Only used to spot the same file reached through different paths, so returning it as-is is fine
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|meta| meta.is_dir)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|meta| meta.is_file)
+    }
+
+    /// Checked before saving, so nothing gets half-written into a directory that refuses it
+    fn can_write_to_dir(&self, path: &Path) -> bool {
+        self.is_dir(path)
+    }
+}
+
+/// The real filesystem, and the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFs;
+
+impl ConfigFs for StdFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        let meta = std::fs::metadata(path)?;
+
+        Ok(FsMetadata {
+            is_dir: meta.is_dir(),
+            is_file: meta.is_file(),
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+
+    fn can_write_to_dir(&self, path: &Path) -> bool {
+        self.is_dir(path) && crate::config::util::can_write_to_dir(&path)
+    }
+}
+
+/// Files held in memory, for tools which get their openmw.cfg contents from somewhere other than a disk.
+/// Directories exist as soon as something is written inside them.
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+    dirs: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) -> Self {
+        let path = path.into();
+        self.add_parents(&path);
+        self.lock_files().insert(path, contents.into());
+        self
+    }
+
+    /// Whatever is currently stored at `path`, such as an openmw.cfg after saving
+    pub fn contents(&self, path: &Path) -> Option<Vec<u8>> {
+        self.lock_files().get(path).cloned()
+    }
+
+    fn lock_files(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Vec<u8>>> {
+        self.files.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn add_parents(&self, path: &Path) {
+        let mut dirs = self.dirs.lock().unwrap_or_else(PoisonError::into_inner);
+        dirs.extend(path.ancestors().skip(1).map(Path::to_path_buf));
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", path.display()),
+        )
+    }
+}
+
+impl ConfigFs for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.contents(path).ok_or_else(|| Self::not_found(path))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        if let Some(contents) = self.lock_files().get(path) {
+            return Ok(FsMetadata {
+                is_dir: false,
+                is_file: true,
+                len: contents.len() as u64,
+                modified: None,
+            });
+        }
+
+        match self
+            .dirs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(path)
+        {
+            true => Ok(FsMetadata {
+                is_dir: true,
+                is_file: false,
+                len: 0,
+                modified: None,
+            }),
+            false => Err(Self::not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.add_parents(path);
+        self.lock_files()
+            .insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.add_parents(&path.join("_"));
+        Ok(())
+    }
+}
+
+/// The filesystem a configuration was loaded through, so saving ends up in the same place
+#[derive(Debug, Clone)]
+pub(crate) struct FsSlot(pub(crate) Arc<dyn ConfigFs>);
+
+impl Default for FsSlot {
+    fn default() -> Self {
+        Self(Arc::new(StdFs))
+    }
+}
+
+impl std::ops::Deref for FsSlot {
+    type Target = dyn ConfigFs;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use super::{ConfigFs, MemoryFs};
+    use crate::config::{OpenMWConfiguration, ParseOptions};
+
+    #[test]
+    fn test_load_and_save_in_memory() {
+        let fs = Arc::new(
+            MemoryFs::new()
+                .with_file(
+                    "/virtual/openmw.cfg",
+                    "content=Morrowind.esm\ndata=Data Files\nconfig=user\n",
+                )
+                .with_file("/virtual/user/openmw.cfg", "content=Tribunal.esm\n"),
+        );
+
+        let mut config = OpenMWConfiguration::new_with_options(
+            Some("/virtual".into()),
+            ParseOptions::default().with_fs(fs.clone()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal.esm"]
+        );
+        assert_eq!(
+            config.data_directories(),
+            vec![Path::new("/virtual/Data Files")]
+        );
+
+        config.add_content_file("Bloodmoon.esm").unwrap();
+        config.save_user().unwrap();
+
+        assert_eq!(
+            fs.read_to_string(Path::new("/virtual/user/openmw.cfg"))
+                .unwrap(),
+            "content=Tribunal.esm\ncontent=Bloodmoon.esm\n"
+        );
+        assert!(!Path::new("/virtual").exists());
+    }
+}
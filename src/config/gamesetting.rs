@@ -7,20 +7,102 @@ use std::fmt;
 
 use crate::{ConfigError, GameSetting, GameSettingMeta, bail_config};
 
+/// How a color's components are written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorFormat {
+    /// Integers from 0 to 255, like Morrowind.ini uses
+    Bytes,
+    /// Decimals, normally from 0.0 to 1.0
+    Float,
+}
+
+/// A fallback color with three or four components, kept in the format it was written in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorValue {
+    pub rgb: [f64; 3],
+    pub alpha: Option<f64>,
+    pub format: ColorFormat,
+}
+
+impl ColorValue {
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self {
+            rgb: [r.into(), g.into(), b.into()],
+            alpha: None,
+            format: ColorFormat::Bytes,
+        }
+    }
+
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self {
+            alpha: Some(a.into()),
+            ..Self::rgb(r, g, b)
+        }
+    }
+
+    /// The same color with its components converted, so it serializes in the other format
+    pub fn to_format(self, format: ColorFormat) -> Self {
+        let convert = |component: f64| match (self.format, format) {
+            (ColorFormat::Bytes, ColorFormat::Float) => component / 255.0,
+            (ColorFormat::Float, ColorFormat::Bytes) => {
+                (component * 255.0).round().clamp(0.0, 255.0)
+            }
+            _ => component,
+        };
+
+        Self {
+            rgb: self.rgb.map(convert),
+            alpha: self.alpha.map(convert),
+            format,
+        }
+    }
+
+    pub fn to_rgb8(self) -> (u8, u8, u8) {
+        let [r, g, b] = self.to_format(ColorFormat::Bytes).rgb;
+        (r as u8, g as u8, b as u8)
+    }
+
+    /// Colors without an alpha component are opaque
+    pub fn to_rgba8(self) -> (u8, u8, u8, u8) {
+        let (r, g, b) = self.to_rgb8();
+        let alpha = self.to_format(ColorFormat::Bytes).alpha.unwrap_or(255.0);
+        (r, g, b, alpha as u8)
+    }
+}
+
+impl fmt::Display for ColorValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let components = self.rgb.iter().chain(self.alpha.iter());
+
+        for (index, component) in components.enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+
+            // Whole floats keep their decimal point, or they'd read back as bytes
+            match self.format {
+                ColorFormat::Float if component.fract() == 0.0 => write!(f, "{component:.1}")?,
+                _ => write!(f, "{component}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ColorGameSetting {
     meta: GameSettingMeta,
     key: String,
-    value: (u8, u8, u8),
+    value: ColorValue,
 }
 
 impl std::fmt::Display for ColorGameSetting {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let (r, g, b) = self.value;
         write!(
             f,
-            "{}",
-            format!("{}fallback={},{r},{g},{b}", self.meta.comment, self.key)
+            "{}fallback={},{}",
+            self.meta.comment, self.key, self.value
         )
     }
 }
@@ -36,8 +118,8 @@ impl std::fmt::Display for StringGameSetting {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}",
-            format!("{}fallback={},{}", self.meta.comment, self.key, self.value)
+            "{}fallback={},{}",
+            self.meta.comment, self.key, self.value
         )
     }
 }
@@ -49,12 +131,22 @@ pub struct FloatGameSetting {
     value: f64,
 }
 
+/// Whole floats keep their decimal point, or they'd be loaded back as ints
+fn float_text(value: f64) -> String {
+    match value.fract() == 0.0 {
+        true => format!("{value:.1}"),
+        false => value.to_string(),
+    }
+}
+
 impl std::fmt::Display for FloatGameSetting {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}",
-            format!("{}fallback={},{}", self.meta.comment, self.key, self.value)
+            "{}fallback={},{}",
+            self.meta.comment,
+            self.key,
+            float_text(self.value)
         )
     }
 }
@@ -70,12 +162,21 @@ impl std::fmt::Display for IntGameSetting {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}",
-            format!("{}fallback={},{}", self.meta.comment, self.key, self.value)
+            "{}fallback={},{}",
+            self.meta.comment, self.key, self.value
         )
     }
 }
 
+/// A fallback's value along with the type it was loaded as
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TypedValue {
+    Color(ColorValue),
+    String(String),
+    Float(f64),
+    Int(i64),
+}
+
 #[derive(Debug, Clone)]
 pub enum GameSettingType {
     Color(ColorGameSetting),
@@ -85,26 +186,130 @@ pub enum GameSettingType {
 }
 
 impl GameSettingType {
+    pub fn handle(&self) -> crate::SettingHandle {
+        self.meta().handle()
+    }
+
     pub fn key(&self) -> &String {
-        match &self {
-            &GameSettingType::Color(setting) => &setting.key,
-            &GameSettingType::String(setting) => &setting.key,
-            &GameSettingType::Float(setting) => &setting.key,
-            &GameSettingType::Int(setting) => &setting.key,
+        match self {
+            GameSettingType::Color(setting) => &setting.key,
+            GameSettingType::String(setting) => &setting.key,
+            GameSettingType::Float(setting) => &setting.key,
+            GameSettingType::Int(setting) => &setting.key,
         }
     }
 
+    /// Morrowind.ini fallbacks are named `Section_Key`, such as `Weather_Clear_Cloud_Texture`.
+    /// Keys without an underscore don't belong to any section.
+    pub fn section(&self) -> Option<&str> {
+        self.key().split_once('_').map(|(section, _)| section)
+    }
+
     pub fn value(&self) -> String {
-        match &self {
-            &GameSettingType::Color(setting) => {
-                let (r, g, b) = setting.value;
-                format!("{r},{g},{b}")
+        match self {
+            GameSettingType::Color(setting) => setting.value.to_string(),
+            GameSettingType::String(setting) => setting.value.clone(),
+            GameSettingType::Float(setting) => float_text(setting.value),
+            GameSettingType::Int(setting) => setting.value.to_string(),
+        }
+    }
+
+    pub(crate) fn typed_value(&self) -> TypedValue {
+        match self {
+            GameSettingType::Color(setting) => TypedValue::Color(setting.value),
+            GameSettingType::String(setting) => TypedValue::String(setting.value.clone()),
+            GameSettingType::Float(setting) => TypedValue::Float(setting.value),
+            GameSettingType::Int(setting) => TypedValue::Int(setting.value),
+        }
+    }
+
+    /// The reverse of `typed_value`, for settings which don't come from a line of text
+    pub(crate) fn from_typed(key: String, value: TypedValue, meta: GameSettingMeta) -> Self {
+        match value {
+            TypedValue::Color(value) => {
+                GameSettingType::Color(ColorGameSetting { meta, key, value })
+            }
+            TypedValue::String(value) => {
+                GameSettingType::String(StringGameSetting { meta, key, value })
             }
-            &GameSettingType::String(setting) => setting.value.clone(),
-            &GameSettingType::Float(setting) => setting.value.to_string(),
-            &GameSettingType::Int(setting) => setting.value.to_string(),
+            TypedValue::Float(value) => {
+                GameSettingType::Float(FloatGameSetting { meta, key, value })
+            }
+            TypedValue::Int(value) => GameSettingType::Int(IntGameSetting { meta, key, value }),
         }
     }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            GameSettingType::Int(setting) => Some(setting.value),
+            _ => None,
+        }
+    }
+
+    /// Integers count as floats too, since `fallback=fFoo,1` is loaded as one
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            GameSettingType::Float(setting) => Some(setting.value),
+            GameSettingType::Int(setting) => Some(setting.value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_color(&self) -> Option<ColorValue> {
+        match self {
+            GameSettingType::Color(setting) => Some(setting.value),
+            _ => None,
+        }
+    }
+
+    /// Rewrites a color's components in another format, e.g. `255,128,0` as `1.0,0.5019607843137255,0.0`.
+    /// Anything other than a color is left alone.
+    pub fn set_color_format(&mut self, format: ColorFormat) {
+        if let GameSettingType::Color(setting) = self {
+            setting.value = setting.value.to_format(format);
+        }
+    }
+
+    /// Name of the value's type, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            GameSettingType::Color(_) => "color",
+            GameSettingType::String(_) => "string",
+            GameSettingType::Float(_) => "float",
+            GameSettingType::Int(_) => "int",
+        }
+    }
+
+    /// Replaces the value while keeping the type it was loaded as.
+    /// Integers are acceptable for floats, but not the other way around, and anything goes for strings.
+    pub fn set_value(&mut self, value: &str) -> Result<(), ConfigError> {
+        let value = value.trim();
+        let mismatch = crate::config_err!(
+            game_setting_type_mismatch,
+            self.key(),
+            self.type_name(),
+            value
+        );
+
+        match self {
+            GameSettingType::Color(setting) => {
+                setting.value = parse_color_value(value).ok_or(mismatch)?
+            }
+            GameSettingType::Float(setting) => {
+                setting.value = value.parse().map_err(|_| mismatch)?
+            }
+            GameSettingType::Int(setting) => setting.value = value.parse().map_err(|_| mismatch)?,
+            GameSettingType::String(setting) => setting.value = value.to_string(),
+        }
+
+        Ok(())
+    }
+
+    /// If this entry was imported from Morrowind.ini, where it came from and the value it was imported with.
+    /// Comparing that value against the current one tells whether the user has modified it since.
+    pub fn ini_origin(&self) -> Option<crate::config::ini::IniOrigin> {
+        crate::config::ini::IniOrigin::from_comment(&self.meta().comment)
+    }
 }
 
 impl std::fmt::Display for GameSettingType {
@@ -127,6 +332,15 @@ impl GameSetting for GameSettingType {
             GameSettingType::Int(s) => &s.meta,
         }
     }
+
+    fn meta_mut(&mut self) -> &mut GameSettingMeta {
+        match self {
+            GameSettingType::Color(s) => &mut s.meta,
+            GameSettingType::String(s) => &mut s.meta,
+            GameSettingType::Float(s) => &mut s.meta,
+            GameSettingType::Int(s) => &mut s.meta,
+        }
+    }
 }
 
 impl PartialEq for GameSettingType {
@@ -159,6 +373,7 @@ impl PartialEq<&str> for GameSettingType {
 
 impl Eq for GameSettingType {}
 
+/// What the loader uses, taking the queued comment along. Everything else should go through `FromStr` or `SettingContext::parse`.
 impl TryFrom<(String, std::path::PathBuf, &mut String)> for GameSettingType {
     type Error = ConfigError;
 
@@ -174,10 +389,7 @@ impl TryFrom<(String, std::path::PathBuf, &mut String)> for GameSettingType {
         let key = tokens[0].to_string();
         let value = tokens[1].to_string();
 
-        let meta = GameSettingMeta {
-            source_config,
-            comment: queued_comment.clone(),
-        };
+        let meta = GameSettingMeta::new(source_config, queued_comment.clone());
 
         queued_comment.clear();
 
@@ -189,14 +401,14 @@ impl TryFrom<(String, std::path::PathBuf, &mut String)> for GameSettingType {
             }));
         }
 
-        if value.contains('.') {
-            if let Ok(f) = value.parse::<f64>() {
-                return Ok(GameSettingType::Float(FloatGameSetting {
-                    meta,
-                    key,
-                    value: f,
-                }));
-            }
+        if value.contains('.')
+            && let Ok(f) = value.parse::<f64>()
+        {
+            return Ok(GameSettingType::Float(FloatGameSetting {
+                meta,
+                key,
+                value: f,
+            }));
         }
 
         if let Ok(i) = value.parse::<i64>() {
@@ -215,17 +427,34 @@ impl TryFrom<(String, std::path::PathBuf, &mut String)> for GameSettingType {
     }
 }
 
-fn parse_color_value(value: &str) -> Option<(u8, u8, u8)> {
-    let parts: Vec<_> = value
-        .split(',')
-        .map(str::trim)
-        .filter_map(|s| s.parse::<u8>().ok())
-        .collect();
+/// Three or four components, either all bytes or decimals.
+/// A single component with a decimal point makes the whole color a float one.
+fn parse_color_value(value: &str) -> Option<ColorValue> {
+    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
 
-    match parts.as_slice() {
-        [r, g, b] => Some((*r, *g, *b)),
-        _ => None,
+    if !(3..=4).contains(&parts.len()) {
+        return None;
     }
+
+    let format = if parts.iter().all(|part| part.parse::<u8>().is_ok()) {
+        ColorFormat::Bytes
+    } else if parts.iter().any(|part| part.contains('.'))
+        && parts
+            .iter()
+            .all(|part| part.parse::<f64>().is_ok_and(f64::is_finite))
+    {
+        ColorFormat::Float
+    } else {
+        return None;
+    };
+
+    let components: Vec<f64> = parts.iter().filter_map(|part| part.parse().ok()).collect();
+
+    Some(ColorValue {
+        rgb: [components[0], components[1], components[2]],
+        alpha: components.get(3).copied(),
+        format,
+    })
 }
 
 #[cfg(test)]
@@ -235,10 +464,7 @@ mod tests {
     use super::*;
 
     fn default_meta() -> GameSettingMeta {
-        GameSettingMeta {
-            source_config: PathBuf::default(),
-            comment: String::default(),
-        }
+        GameSettingMeta::new(PathBuf::default(), String::default())
     }
 
     #[test]
@@ -279,7 +505,7 @@ mod tests {
         let setting = GameSettingType::Color(ColorGameSetting {
             meta: default_meta(),
             key: "hud_color".into(),
-            value: (255, 128, 64),
+            value: ColorValue::rgb(255, 128, 64),
         });
 
         assert_eq!(setting.value(), "255,128,64");
@@ -323,20 +549,84 @@ mod tests {
         let setting = GameSettingType::Color(ColorGameSetting {
             meta: default_meta(),
             key: "iHUDColor".into(),
-            value: (128, 64, 255),
+            value: ColorValue::rgb(128, 64, 255),
         });
 
         assert_eq!(setting.to_string(), "fallback=iHUDColor,128,64,255");
     }
 
+    #[test]
+    fn test_set_value_keeps_type() {
+        let mut setting = GameSettingType::Float(FloatGameSetting {
+            meta: default_meta(),
+            key: "fJumpHeight".into(),
+            value: 1.75,
+        });
+
+        setting.set_value("2").unwrap();
+        assert_eq!(setting.to_string(), "fallback=fJumpHeight,2.0");
+
+        assert!(matches!(
+            setting.set_value("high"),
+            Err(ConfigError::GameSettingTypeMismatch {
+                expected: "float",
+                ..
+            })
+        ));
+
+        let mut color = GameSettingType::Color(ColorGameSetting {
+            meta: default_meta(),
+            key: "iHUDColor".into(),
+            value: ColorValue::rgb(128, 64, 255),
+        });
+
+        assert!(color.set_value("1.5").is_err());
+        color.set_value("1, 2, 3").unwrap();
+        assert_eq!(color.value(), "1,2,3");
+    }
+
+    #[test]
+    fn test_rgba_and_float_colors() {
+        let parse = |value: &str| {
+            GameSettingType::try_from((
+                format!("FontColor_color_normal,{value}"),
+                PathBuf::default(),
+                &mut String::new(),
+            ))
+            .unwrap()
+        };
+
+        let rgba = parse("202,165,96,128");
+        assert_eq!(rgba.as_color(), Some(ColorValue::rgba(202, 165, 96, 128)));
+        assert_eq!(rgba.value(), "202,165,96,128");
+
+        let mut float = parse("1.0, 0.5, 0");
+        let color = float.as_color().unwrap();
+        assert_eq!(color.format, ColorFormat::Float);
+        assert_eq!(color.to_rgba8(), (255, 128, 0, 255));
+        assert_eq!(float.value(), "1.0,0.5,0.0");
+
+        float.set_color_format(ColorFormat::Bytes);
+        assert_eq!(float.value(), "255,128,0");
+
+        assert!(matches!(parse("1,2,3,4,5"), GameSettingType::String(_)));
+        assert!(matches!(parse("a,1,2,3"), GameSettingType::String(_)));
+    }
+
     #[test]
     fn test_commented_string() {
         let setting = GameSettingType::Color(ColorGameSetting {
-            meta: GameSettingMeta { source_config: PathBuf::from("$HOME/.config/openmw/openmw.cfg"), comment: String::from("#Monochrome UI Settings\n#\n#\n#\n#######\n##\n##\n##\n") },
+            meta: GameSettingMeta::new(
+                PathBuf::from("$HOME/.config/openmw/openmw.cfg"),
+                String::from("#Monochrome UI Settings\n#\n#\n#\n#######\n##\n##\n##\n"),
+            ),
             key: "iHUDColor".into(),
-            value: (128, 64, 255),
+            value: ColorValue::rgb(128, 64, 255),
         });
 
-        assert_eq!(setting.to_string(), "#Monochrome UI Settings\n#\n#\n#\n#######\n##\n##\n##\nfallback=iHUDColor,128,64,255");
+        assert_eq!(
+            setting.to_string(),
+            "#Monochrome UI Settings\n#\n#\n#\n#######\n##\n##\n##\nfallback=iHUDColor,128,64,255"
+        );
     }
 }
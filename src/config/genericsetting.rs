@@ -17,6 +17,10 @@ impl GameSetting for GenericSetting {
     fn meta(&self) -> &GameSettingMeta {
         &self.meta
     }
+
+    fn meta_mut(&mut self) -> &mut GameSettingMeta {
+        &mut self.meta
+    }
 }
 
 impl fmt::Display for GenericSetting {
@@ -26,19 +30,43 @@ impl fmt::Display for GenericSetting {
 }
 
 impl GenericSetting {
-    pub fn new(
+    pub fn handle(&self) -> crate::SettingHandle {
+        self.meta.handle()
+    }
+
+    pub fn new(key: &str, value: &str, source_config: &std::path::Path) -> Self {
+        Self::with_queued_comment(key, value, source_config, &mut String::new())
+    }
+
+    /// Comment lines to write above it, with `#` added where it's missing. `None` leaves it without one.
+    pub fn with_comment(mut self, comment: Option<&str>) -> Self {
+        self.meta.set_comment(comment);
+        self
+    }
+
+    /// Takes the comment the parser queued up, leaving it empty for the next setting
+    pub(crate) fn with_queued_comment(
         key: &str,
         value: &str,
         source_config: &std::path::Path,
         comment: &mut String,
     ) -> Self {
         Self {
-            meta: GameSettingMeta {
-                source_config: source_config.to_path_buf(),
-                comment: std::mem::take(comment),
-            },
+            meta: GameSettingMeta::new(source_config.to_path_buf(), std::mem::take(comment)),
             key: key.to_string(),
             value: value.to_string(),
         }
     }
+
+    pub fn key(&self) -> &String {
+        &self.key
+    }
+
+    pub fn value(&self) -> &String {
+        &self.value
+    }
+
+    pub fn set_value(&mut self, value: &str) {
+        self.value = value.to_string();
+    }
 }
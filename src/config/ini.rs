@@ -0,0 +1,325 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ConfigError, GameSetting,
+    config::{GameSettingType, OpenMWConfiguration, SettingValue},
+};
+
+/// Comment marker written above every fallback which came out of Morrowind.ini
+const INI_ORIGIN_MARKER: &str = "#omwcfg:ini-origin ";
+
+/// Morrowind.ini sections which hold fallback values.
+/// Sections are matched exactly or as a prefix followed by a space, so `Weather` covers `[Weather Clear]` and friends.
+const FALLBACK_SECTIONS: [&str; 10] = [
+    "Fonts",
+    "General",
+    "Moons",
+    "Weather",
+    "Water",
+    "Level Up",
+    "Movies",
+    "Blood",
+    "Inventory",
+    "Map",
+];
+
+/// Where in Morrowind.ini a fallback= entry was imported from, and what value it had at the time.
+/// This is stored as a marker comment above the entry, so it survives being saved and loaded again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IniOrigin {
+    pub section: String,
+    pub key: String,
+    pub imported_value: String,
+}
+
+impl IniOrigin {
+    /// The openmw.cfg fallback key for this entry, eg `[Weather Clear] Cloud Texture` => `Weather_Clear_Cloud_Texture`
+    pub fn fallback_key(&self) -> String {
+        format!("{}_{}", self.section, self.key).replace(' ', "_")
+    }
+
+    pub(crate) fn from_comment(comment: &str) -> Option<Self> {
+        comment.lines().rev().find_map(|line| {
+            let marker = line.trim().strip_prefix(INI_ORIGIN_MARKER)?;
+            let (section, rest) = marker.strip_prefix('[')?.split_once("] ")?;
+            let (key, imported_value) = rest.split_once('=')?;
+
+            Some(Self {
+                section: section.to_string(),
+                key: key.to_string(),
+                imported_value: imported_value.to_string(),
+            })
+        })
+    }
+
+    /// Replaces any existing marker in the comment with this one
+    fn write_to_comment(&self, comment: &str) -> String {
+        let mut comment: String = comment
+            .lines()
+            .filter(|line| !line.trim().starts_with(INI_ORIGIN_MARKER))
+            .map(|line| format!("{line}\n"))
+            .collect();
+
+        comment.push_str(&format!(
+            "{INI_ORIGIN_MARKER}[{}] {}={}\n",
+            self.section, self.key, self.imported_value
+        ));
+
+        comment
+    }
+}
+
+/// Outcome of `import_ini_fallbacks`, listing fallback keys by what happened to them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IniImportReport {
+    /// Not previously present in the configuration
+    pub added: Vec<String>,
+    /// Previously imported, unmodified by the user, and changed in Morrowind.ini since
+    pub updated: Vec<String>,
+    /// Left alone because the user changed or defined them by hand
+    pub preserved: Vec<String>,
+}
+
+/// Splits Morrowind.ini into (section, key, value) triples, in file order.
+/// The file is usually windows-1252, so anything which isn't valid UTF-8 is replaced rather than rejected.
+pub fn parse_ini(contents: &str) -> Vec<(String, String, String)> {
+    let mut section = String::new();
+    let mut entries = Vec::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        } else if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            entries.push((
+                section.clone(),
+                key.trim().to_string(),
+                value.trim().to_string(),
+            ));
+        }
+    }
+
+    entries
+}
+
+/// A value the way it reads back once loaded, so `0.50` matches `0.5` and `255,115,081` matches `255,115,81`
+fn loaded_form(key: &str, value: &str) -> String {
+    GameSettingType::try_from((format!("{key},{value}"), PathBuf::new(), &mut String::new()))
+        .map(|setting| setting.value())
+        .unwrap_or_else(|_| value.to_string())
+}
+
+fn is_fallback_section(section: &str) -> bool {
+    FALLBACK_SECTIONS.iter().any(|fallback| {
+        section == *fallback
+            || section
+                .strip_prefix(fallback)
+                .is_some_and(|rest| rest.starts_with(' '))
+    })
+}
+
+impl OpenMWConfiguration {
+    /// Imports the fallback values from a Morrowind.ini into the user configuration, as `openmw-iniimporter` does.
+    /// Each imported entry remembers its origin (see `GameSettingType::ini_origin`), which makes importing again safe:
+    /// Entries the user has since modified, or defined without the importer, are never overwritten.
+    pub fn import_ini_fallbacks(
+        &mut self,
+        ini_path: &Path,
+    ) -> Result<IniImportReport, ConfigError> {
+        let contents = String::from_utf8_lossy(&self.fs.read(ini_path)?).into_owned();
+        self.import_ini_text(&contents)
+    }
+
+    /// `import_ini_fallbacks`, for a Morrowind.ini which was already read and decoded
+    pub(crate) fn import_ini_text(
+        &mut self,
+        contents: &str,
+    ) -> Result<IniImportReport, ConfigError> {
+        let user_config = self.user_config_path().join("openmw.cfg");
+        let mut report = IniImportReport::default();
+
+        for (section, key, value) in parse_ini(contents) {
+            if !is_fallback_section(&section) {
+                continue;
+            }
+
+            let origin = IniOrigin {
+                section,
+                key,
+                imported_value: value,
+            };
+            let fallback_key = origin.fallback_key();
+
+            let existing = self.settings.iter().rposition(|setting| {
+                matches!(setting, SettingValue::GameSetting(game_setting) if game_setting == &fallback_key.as_str())
+            });
+
+            match existing {
+                None => {
+                    self.set_game_setting(
+                        &format!("{fallback_key},{}", origin.imported_value),
+                        Some(user_config.clone()),
+                        &mut origin.write_to_comment(""),
+                    )?;
+                    report.added.push(fallback_key);
+                }
+                Some(index) => {
+                    let SettingValue::GameSetting(current) = &self.settings[index] else {
+                        unreachable!()
+                    };
+
+                    let untouched = current.ini_origin().is_some_and(|previous| {
+                        loaded_form(&fallback_key, &previous.imported_value) == current.value()
+                    });
+
+                    if !untouched {
+                        report.preserved.push(fallback_key);
+                    } else if current.value() != loaded_form(&fallback_key, &origin.imported_value)
+                    {
+                        let replacement = GameSettingType::try_from((
+                            format!("{fallback_key},{}", origin.imported_value),
+                            current.meta().source_config.clone(),
+                            &mut origin.write_to_comment(&current.meta().comment),
+                        ))?;
+
+                        self.replace_setting_at(index, SettingValue::GameSetting(replacement));
+                        report.updated.push(fallback_key);
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    const INI: &str = "[Game Files]\nGameFile0=Morrowind.esm\n\n[Weather Clear]\nCloud Texture=Tx_Sky_Clear.dds\n; comment\n[Level Up]\nLevel2=You realize that all your life you have been coasting along.\n[General]\nWerewolf FOV=100\n";
+
+    #[test]
+    fn test_parse_ini_sections() {
+        let entries = parse_ini(INI);
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[1],
+            (
+                "Weather Clear".to_string(),
+                "Cloud Texture".to_string(),
+                "Tx_Sky_Clear.dds".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_origin_round_trips_through_comment() {
+        let origin = IniOrigin {
+            section: "Weather Clear".into(),
+            key: "Cloud Texture".into(),
+            imported_value: "Tx_Sky_Clear.dds".into(),
+        };
+
+        let comment = origin.write_to_comment("# user comment\n");
+        assert!(comment.starts_with("# user comment\n"));
+        assert_eq!(IniOrigin::from_comment(&comment), Some(origin.clone()));
+        assert_eq!(origin.fallback_key(), "Weather_Clear_Cloud_Texture");
+    }
+
+    #[test]
+    fn test_reimport_preserves_user_overrides() {
+        let dir = temp_config("ini_reimport", "fallback=General_Werewolf_FOV,90\n");
+        let ini_path = dir.join("Morrowind.ini");
+        std::fs::write(&ini_path, INI).unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let report = config.import_ini_fallbacks(&ini_path).unwrap();
+
+        assert_eq!(report.added.len(), 2);
+        assert_eq!(report.preserved, vec!["General_Werewolf_FOV"]);
+
+        let imported = config
+            .get_game_setting("Weather_Clear_Cloud_Texture")
+            .unwrap();
+        assert_eq!(imported.ini_origin().unwrap().section, "Weather Clear");
+
+        // The user edits the level up message, and the ini changes the cloud texture
+        config
+            .set_game_setting(
+                "Level_Up_Level2,Custom message",
+                None,
+                &mut IniOrigin {
+                    section: "Level Up".into(),
+                    key: "Level2".into(),
+                    imported_value: "You realize that all your life you have been coasting along."
+                        .into(),
+                }
+                .write_to_comment(""),
+            )
+            .unwrap();
+        std::fs::write(&ini_path, INI.replace("Tx_Sky_Clear", "Tx_Sky_New")).unwrap();
+
+        let report = config.import_ini_fallbacks(&ini_path).unwrap();
+
+        assert_eq!(report.updated, vec!["Weather_Clear_Cloud_Texture"]);
+        assert!(report.preserved.contains(&"Level_Up_Level2".to_string()));
+        assert_eq!(
+            config
+                .get_game_setting("Weather_Clear_Cloud_Texture")
+                .unwrap()
+                .value(),
+            "Tx_Sky_New.dds"
+        );
+        assert_eq!(
+            config.get_game_setting("Level_Up_Level2").unwrap().value(),
+            "Custom message"
+        );
+    }
+
+    #[test]
+    fn test_reimport_compares_loaded_values() {
+        let dir = temp_config("ini_reimport_typed", "");
+        let ini_path = dir.join("Morrowind.ini");
+        let ini = "[Weather Clear]\nSky Sunrise Color=255,115,081\nWind Speed=0.10\n";
+        std::fs::write(&ini_path, ini).unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        assert_eq!(
+            config.import_ini_fallbacks(&ini_path).unwrap().added.len(),
+            2
+        );
+
+        // Written back differently than the ini has them, but still the values that were imported
+        let report = config.import_ini_fallbacks(&ini_path).unwrap();
+        assert!(report.updated.is_empty() && report.preserved.is_empty());
+
+        std::fs::write(&ini_path, ini.replace("081", "082").replace("0.10", "0.20")).unwrap();
+        let report = config.import_ini_fallbacks(&ini_path).unwrap();
+        assert_eq!(
+            report.updated,
+            [
+                "Weather_Clear_Sky_Sunrise_Color",
+                "Weather_Clear_Wind_Speed"
+            ]
+        );
+        assert!(report.preserved.is_empty());
+        assert_eq!(
+            config
+                .get_game_setting("Weather_Clear_Wind_Speed")
+                .unwrap()
+                .as_float(),
+            Some(0.2)
+        );
+    }
+}
@@ -0,0 +1,482 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! The engine's input bindings, which live in input_v3.xml next to the user openmw.cfg.
+//!
+//! ```xml
+//! <Controller>
+//!     <Control name="MoveForward" persistent="false" initialValue="0.000000">
+//!         <KeyBinding key="26" direction="INCREASE" />
+//!         <Channel number="0" direction="DIRECT" />
+//!     </Control>
+//! </Controller>
+//! ```
+//!
+//! The whole file is kept as an element tree, so attributes and elements this module doesn't know about survive a save.
+//! Comments don't, since the engine drops them the next time it writes the file anyway.
+
+use std::{
+    fmt::{self, Write as _},
+    path::{Path, PathBuf},
+};
+
+use quick_xml::{
+    escape::escape,
+    events::{BytesStart, Event},
+};
+
+use crate::{
+    ConfigError, bail_config,
+    config::{
+        OpenMWConfiguration,
+        fs::{ConfigFs, StdFs},
+    },
+};
+
+pub const INPUT_BINDINGS_FILE: &str = "input_v3.xml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputElement {
+    pub name: String,
+    /// In the order they were written
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<InputElement>,
+}
+
+impl InputElement {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value in place if the attribute is already there, so the order doesn't shift
+    pub fn set_attribute(&mut self, key: &str, value: impl ToString) {
+        let value = value.to_string();
+
+        match self.attributes.iter_mut().find(|(name, _)| name == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.attributes.push((key.to_string(), value)),
+        }
+    }
+
+    fn with_attribute(mut self, key: &str, value: impl ToString) -> Self {
+        self.set_attribute(key, value);
+        self
+    }
+
+    fn from_start(start: &BytesStart) -> Result<Self, String> {
+        let mut element = Self::new(&String::from_utf8_lossy(start.name().as_ref()));
+
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(|error| error.to_string())?;
+            let value = attribute
+                .unescape_value()
+                .map_err(|error| error.to_string())?;
+
+            element.attributes.push((
+                String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                value.into_owned(),
+            ));
+        }
+
+        Ok(element)
+    }
+
+    fn write_to(&self, output: &mut String, depth: usize) -> fmt::Result {
+        let indent = "\t".repeat(depth);
+        write!(output, "{indent}<{}", self.name)?;
+
+        for (key, value) in &self.attributes {
+            write!(output, " {key}=\"{}\"", escape(value.as_str()))?;
+        }
+
+        if self.children.is_empty() {
+            return writeln!(output, " />");
+        }
+
+        writeln!(output, ">")?;
+        for child in &self.children {
+            child.write_to(output, depth + 1)?;
+        }
+        writeln!(output, "{indent}</{}>", self.name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Increase,
+    Decrease,
+}
+
+impl Direction {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "INCREASE" => Some(Self::Increase),
+            "DECREASE" => Some(Self::Decrease),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Direction::Increase => "INCREASE",
+            Direction::Decrease => "DECREASE",
+        })
+    }
+}
+
+/// One way of triggering a control.
+/// Keys are SDL scancodes and mouse axes are `X`, `Y` or `Z`, as the engine writes them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key { scancode: i32, direction: Direction },
+    MouseButton { button: i32, direction: Direction },
+    MouseAxis { axis: String, direction: Direction },
+    JoystickButton { button: i32, direction: Direction },
+    JoystickAxis { axis: i32, direction: Direction },
+}
+
+impl Binding {
+    pub fn direction(&self) -> Direction {
+        match self {
+            Binding::Key { direction, .. }
+            | Binding::MouseButton { direction, .. }
+            | Binding::MouseAxis { direction, .. }
+            | Binding::JoystickButton { direction, .. }
+            | Binding::JoystickAxis { direction, .. } => *direction,
+        }
+    }
+
+    /// `None` for anything that isn't a binding, or a binding this module can't make sense of
+    pub fn from_element(element: &InputElement) -> Option<Self> {
+        let direction = Direction::parse(element.attribute("direction")?)?;
+        let number = |key: &str| element.attribute(key)?.parse::<i32>().ok();
+
+        Some(match element.name.as_str() {
+            "KeyBinding" => Binding::Key {
+                scancode: number("key")?,
+                direction,
+            },
+            "MouseButtonBinding" => Binding::MouseButton {
+                button: number("button")?,
+                direction,
+            },
+            "MouseBinding" => Binding::MouseAxis {
+                axis: element.attribute("axis")?.to_string(),
+                direction,
+            },
+            "JoystickButtonBinding" => Binding::JoystickButton {
+                button: number("button")?,
+                direction,
+            },
+            "JoystickAxisBinding" => Binding::JoystickAxis {
+                axis: number("axis")?,
+                direction,
+            },
+            _ => return None,
+        })
+    }
+
+    pub fn to_element(&self) -> InputElement {
+        let element = match self {
+            Binding::Key { scancode, .. } => {
+                InputElement::new("KeyBinding").with_attribute("key", scancode)
+            }
+            Binding::MouseButton { button, .. } => {
+                InputElement::new("MouseButtonBinding").with_attribute("button", button)
+            }
+            Binding::MouseAxis { axis, .. } => {
+                InputElement::new("MouseBinding").with_attribute("axis", axis)
+            }
+            Binding::JoystickButton { button, .. } => {
+                InputElement::new("JoystickButtonBinding").with_attribute("button", button)
+            }
+            Binding::JoystickAxis { axis, .. } => {
+                InputElement::new("JoystickAxisBinding").with_attribute("axis", axis)
+            }
+        };
+
+        element.with_attribute("direction", self.direction())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputBindings {
+    declaration: Option<String>,
+    root: InputElement,
+}
+
+impl InputBindings {
+    pub fn from_xml(text: &str) -> Result<Self, ConfigError> {
+        Self::parse(text).map_err(|reason| {
+            crate::config_err!(
+                invalid_input_bindings,
+                Path::new("<input bindings>"),
+                reason
+            )
+        })
+    }
+
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        let text = StdFs.read_to_string(path)?;
+
+        Self::parse(&text)
+            .map_err(|reason| crate::config_err!(invalid_input_bindings, path, reason))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), ConfigError> {
+        Ok(std::fs::write(path, self.to_xml())?)
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut reader = quick_xml::Reader::from_str(text);
+        let mut declaration = None;
+        let mut open: Vec<InputElement> = Vec::new();
+        let mut root = None;
+
+        let mut close = |element: InputElement, open: &mut Vec<InputElement>| match open.last_mut()
+        {
+            Some(parent) => parent.children.push(element),
+            None => root = Some(element),
+        };
+
+        loop {
+            match reader.read_event().map_err(|error| error.to_string())? {
+                Event::Decl(decl) => {
+                    declaration = Some(String::from_utf8_lossy(&decl).into_owned());
+                }
+                Event::Start(start) => open.push(InputElement::from_start(&start)?),
+                Event::Empty(start) => close(InputElement::from_start(&start)?, &mut open),
+                Event::End(_) => {
+                    let element = open.pop().ok_or("unexpected closing tag")?;
+                    close(element, &mut open);
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        if !open.is_empty() {
+            return Err(format!("<{}> is never closed", open[open.len() - 1].name));
+        }
+
+        let root = root.ok_or("there's no root element")?;
+        Ok(Self { declaration, root })
+    }
+
+    /// The file as the engine would read it, tab-indented like the engine writes it
+    pub fn to_xml(&self) -> String {
+        let mut output = String::new();
+
+        if let Some(declaration) = &self.declaration {
+            let _ = writeln!(output, "<?{declaration}?>");
+        }
+        let _ = self.root.write_to(&mut output, 0);
+
+        output
+    }
+
+    /// The `<Controller>` element, for anything the typed helpers don't cover
+    pub fn root(&self) -> &InputElement {
+        &self.root
+    }
+
+    pub fn root_mut(&mut self) -> &mut InputElement {
+        &mut self.root
+    }
+
+    fn control_elements(&self) -> impl Iterator<Item = &InputElement> {
+        self.root
+            .children
+            .iter()
+            .filter(|element| element.name == "Control")
+    }
+
+    fn control_mut(&mut self, control: &str) -> Option<&mut InputElement> {
+        self.root
+            .children
+            .iter_mut()
+            .find(|element| element.name == "Control" && element.attribute("name") == Some(control))
+    }
+
+    /// The name of every control, in file order
+    pub fn controls(&self) -> impl Iterator<Item = &str> {
+        self.control_elements()
+            .filter_map(|element| element.attribute("name"))
+    }
+
+    pub fn bindings(&self, control: &str) -> Vec<Binding> {
+        self.control_elements()
+            .filter(|element| element.attribute("name") == Some(control))
+            .flat_map(|element| &element.children)
+            .filter_map(Binding::from_element)
+            .collect()
+    }
+
+    /// Every control which `binding` triggers, to find conflicts before adding it somewhere else
+    pub fn controls_bound_to(&self, binding: &Binding) -> Vec<&str> {
+        self.control_elements()
+            .filter(|element| {
+                element
+                    .children
+                    .iter()
+                    .any(|child| Binding::from_element(child).as_ref() == Some(binding))
+            })
+            .filter_map(|element| element.attribute("name"))
+            .collect()
+    }
+
+    /// False if the control doesn't exist or already has this binding.
+    /// New bindings go before the control's channels, where the engine puts them.
+    pub fn add_binding(&mut self, control: &str, binding: Binding) -> bool {
+        let Some(element) = self.control_mut(control) else {
+            return false;
+        };
+
+        if element
+            .children
+            .iter()
+            .any(|child| Binding::from_element(child).as_ref() == Some(&binding))
+        {
+            return false;
+        }
+
+        let position = element
+            .children
+            .iter()
+            .position(|child| child.name == "Channel")
+            .unwrap_or(element.children.len());
+        element.children.insert(position, binding.to_element());
+
+        true
+    }
+
+    /// Returns whether the control had it
+    pub fn remove_binding(&mut self, control: &str, binding: &Binding) -> bool {
+        let Some(element) = self.control_mut(control) else {
+            return false;
+        };
+
+        let before = element.children.len();
+        element
+            .children
+            .retain(|child| Binding::from_element(child).as_ref() != Some(binding));
+
+        element.children.len() != before
+    }
+}
+
+impl OpenMWConfiguration {
+    /// input_v3.xml in the user config directory, whether or not it exists yet
+    pub fn input_bindings_path(&self) -> PathBuf {
+        self.user_config_path().join(INPUT_BINDINGS_FILE)
+    }
+
+    /// `None` when there's no input_v3.xml yet, which is the case until the engine has been run once
+    pub fn input_bindings(&self) -> Result<Option<InputBindings>, ConfigError> {
+        let path = self.input_bindings_path();
+
+        match path.is_file() {
+            true => InputBindings::read(&path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    pub fn save_input_bindings(&self, bindings: &InputBindings) -> Result<(), ConfigError> {
+        let path = self.input_bindings_path();
+
+        if self.is_read_only() {
+            bail_config!(read_only, path);
+        }
+
+        bindings.write(&path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    const INPUT: &str = r#"<?xml version="1.0" ?>
+<Controller>
+	<!-- written by the engine -->
+	<Control name="MoveForward" persistent="false" initialValue="0.000000">
+		<KeyBinding key="26" direction="INCREASE" />
+		<JoystickAxisBinding axis="1" direction="DECREASE" />
+		<Channel number="0" direction="DIRECT" />
+	</Control>
+	<Control name="Jump" persistent="false" initialValue="0.000000">
+		<KeyBinding key="8" direction="INCREASE" />
+		<Channel number="1" direction="DIRECT" />
+	</Control>
+	<Channel number="0" />
+</Controller>
+"#;
+
+    #[test]
+    fn test_input_bindings() {
+        let dir = temp_config("input_bindings", "");
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert_eq!(config.input_bindings().unwrap(), None);
+
+        std::fs::write(dir.join(INPUT_BINDINGS_FILE), INPUT).unwrap();
+        let mut bindings = config.input_bindings().unwrap().unwrap();
+
+        assert_eq!(
+            bindings.controls().collect::<Vec<_>>(),
+            vec!["MoveForward", "Jump"]
+        );
+        assert_eq!(
+            bindings.bindings("MoveForward"),
+            vec![
+                Binding::Key {
+                    scancode: 26,
+                    direction: Direction::Increase
+                },
+                Binding::JoystickAxis {
+                    axis: 1,
+                    direction: Direction::Decrease
+                },
+            ]
+        );
+
+        let space = Binding::Key {
+            scancode: 44,
+            direction: Direction::Increase,
+        };
+        let e = Binding::Key {
+            scancode: 8,
+            direction: Direction::Increase,
+        };
+        assert!(bindings.remove_binding("Jump", &e));
+        assert!(bindings.add_binding("Jump", space.clone()));
+        assert!(!bindings.add_binding("Jump", space.clone()));
+        assert_eq!(bindings.controls_bound_to(&space), vec!["Jump"]);
+
+        config.save_input_bindings(&bindings).unwrap();
+        let written = std::fs::read_to_string(config.input_bindings_path()).unwrap();
+        assert!(written.contains(
+            "\t<Control name=\"Jump\" persistent=\"false\" initialValue=\"0.000000\">\n\t\t<KeyBinding key=\"44\" direction=\"INCREASE\" />\n\t\t<Channel number=\"1\" direction=\"DIRECT\" />\n"
+        ));
+        assert_eq!(InputBindings::from_xml(&written).unwrap(), bindings);
+
+        assert!(matches!(
+            InputBindings::from_xml("<Controller><Control>"),
+            Err(ConfigError::InvalidInputBindings { .. })
+        ));
+    }
+}
@@ -0,0 +1,351 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Installing a mod in one call: place it under the managed mods folder, add it as a data directory and enable its plugins.
+//! Only the top level of the mod is looked at for plugins, since that's the only place the engine loads them from.
+//! `uninstall_mod` undoes all of that again, for mods installed this way or by hand.
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ConfigError, bail_config,
+    config::{OpenMWConfiguration, filesetting::ContentKind},
+};
+
+/// How `install_mod` puts a mod's files in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallStrategy {
+    /// A full copy, so the original can be deleted afterwards
+    Copy,
+    /// A link to the whole folder, so edits to the original show up in game
+    Symlink,
+    /// The same folder layout with every file hardlinked. Needs both sides on the same drive.
+    Hardlink,
+}
+
+/// What `install_mod` placed and changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModInstall {
+    /// Where the mod lives now, inside `mods_path`
+    pub path: PathBuf,
+    /// The `data=` entry, unless it was already there
+    pub data_directory_added: bool,
+    /// Masters first, skipping ones which were already enabled
+    pub plugins_added: Vec<String>,
+}
+
+/// One thing `uninstall_mod` did, in the order it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UninstallAction {
+    RemovedDataDirectory(PathBuf),
+    RemovedContent(String),
+    RemovedGroundcover(String),
+    /// Only the link itself for a symlinked mod, never what it points to
+    DeletedDirectory(PathBuf),
+}
+
+fn copy_tree(src: &Path, dest: &Path, strategy: InstallStrategy) -> std::io::Result<()> {
+    fs::create_dir_all(dest)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+
+        match entry.file_type()?.is_dir() {
+            true => copy_tree(&entry.path(), &target, strategy)?,
+            false if strategy == InstallStrategy::Hardlink => fs::hard_link(entry.path(), target)?,
+            false => {
+                fs::copy(entry.path(), target)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn link_dir(src: &Path, dest: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(src, dest);
+
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_dir(src, dest);
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (src, dest);
+        Err(Error::from(ErrorKind::Unsupported))
+    }
+}
+
+/// Lowercased names of everything directly inside `dir`
+pub(crate) fn file_names(dir: &Path) -> HashSet<String> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_ascii_lowercase())
+        .collect()
+}
+
+fn remove_link(link: &Path) -> std::io::Result<()> {
+    // Directory symlinks are directories as far as Windows is concerned
+    match cfg!(windows) {
+        true => fs::remove_dir(link),
+        false => fs::remove_file(link),
+    }
+}
+
+/// Whether `dir` itself sits somewhere under `mods`. Only its parent is canonicalized, so a symlinked mod counts by where the link is.
+fn is_inside(dir: &Path, mods: &Path) -> bool {
+    let (Some(parent), Some(name)) = (dir.parent(), dir.file_name()) else {
+        return false;
+    };
+
+    match (fs::canonicalize(parent), fs::canonicalize(mods)) {
+        (Ok(parent), Ok(mods)) => parent.join(name).starts_with(mods),
+        _ => false,
+    }
+}
+
+/// Plugins directly inside `dir`, game files ahead of everything else and otherwise alphabetical
+fn plugins_in(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut plugins: Vec<(ContentKind, String)> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .map(|name| (ContentKind::from_file_name(&name), name))
+        .filter(|(kind, _)| !matches!(kind, ContentKind::Unknown | ContentKind::Omwgame))
+        .collect();
+
+    plugins.sort_by_key(|(kind, name)| (!kind.is_game_file(), name.to_lowercase()));
+    plugins.into_iter().map(|(_, name)| name).collect()
+}
+
+impl OpenMWConfiguration {
+    /// Where `install_mod` puts mods, `mods` inside the user data directory
+    pub fn mods_path(&self) -> PathBuf {
+        self.userdata_path().join("mods")
+    }
+
+    /// Places the mod in `src_dir` into `mods_path`, under the same folder name.
+    /// It's added as the highest priority data directory, and the plugins at its top level are enabled at the end of the load order.
+    /// Fails without touching anything if a mod by that name is already installed.
+    pub fn install_mod(
+        &mut self,
+        src_dir: &Path,
+        strategy: InstallStrategy,
+    ) -> Result<ModInstall, ConfigError> {
+        if self.is_read_only() {
+            bail_config!(read_only, self.root_config_file());
+        }
+
+        let src_dir = fs::canonicalize(src_dir)?;
+        let Some(name) = src_dir.file_name().filter(|_| src_dir.is_dir()) else {
+            bail_config!(not_file_or_directory, src_dir);
+        };
+
+        self.install_mod_as(&src_dir, name, strategy)
+    }
+
+    /// `install_mod`, with the folder under `mods_path` called `name` instead of whatever `src_dir` is called
+    pub(crate) fn install_mod_as(
+        &mut self,
+        src_dir: &Path,
+        name: &std::ffi::OsStr,
+        strategy: InstallStrategy,
+    ) -> Result<ModInstall, ConfigError> {
+        if self.is_read_only() {
+            bail_config!(read_only, self.root_config_file());
+        }
+
+        let mods = self.mods_path();
+        let path = mods.join(name);
+        if path.symlink_metadata().is_ok() {
+            return Err(ConfigError::Io(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} is already installed", path.display()),
+            )));
+        }
+
+        fs::create_dir_all(&mods)?;
+        let placed = match strategy {
+            InstallStrategy::Symlink => link_dir(src_dir, &path),
+            InstallStrategy::Copy | InstallStrategy::Hardlink => {
+                copy_tree(src_dir, &path, strategy)
+            }
+        };
+        if let Err(err) = placed {
+            let _ = fs::remove_dir_all(&path);
+            return Err(err.into());
+        }
+
+        let data_directory_added = !self.data_directories().contains(&&path);
+        if data_directory_added {
+            self.add_data_directory(path.clone());
+        }
+
+        let mut plugins_added = Vec::new();
+        for plugin in plugins_in(&path) {
+            if !self.has_content_file(&plugin) {
+                self.add_content_file(&plugin)?;
+                plugins_added.push(plugin);
+            }
+        }
+
+        Ok(ModInstall {
+            path,
+            data_directory_added,
+            plugins_added,
+        })
+    }
+
+    /// Removes the `data=` entry for `data_dir`, then removes the content and groundcover entries whose file was only in that directory.
+    /// Plugins which another data directory also provides stay enabled, since the engine will still find them.
+    /// With `delete_files` the directory goes too. Returns everything that was done, which is empty if `data_dir` wasn't configured.
+    /// Only directories inside `mods_path` can be deleted. Asking to delete any other one fails without changing anything.
+    pub fn uninstall_mod(
+        &mut self,
+        data_dir: &Path,
+        delete_files: bool,
+    ) -> Result<Vec<UninstallAction>, ConfigError> {
+        if self.is_read_only() {
+            bail_config!(read_only, self.root_config_file());
+        }
+
+        let Some(path) = self
+            .data_directories_iter()
+            .find(|dir| dir.refers_to(data_dir))
+            .map(|dir| dir.parsed().clone())
+        else {
+            return Ok(Vec::new());
+        };
+
+        if delete_files && !is_inside(&path, &self.mods_path()) {
+            bail_config!(outside_mods_path, path);
+        }
+
+        let provided = file_names(&path);
+
+        self.remove_data_directory(data_dir);
+        let mut actions = vec![UninstallAction::RemovedDataDirectory(path.clone())];
+
+        let remaining = self.data_file_index();
+        let orphaned = |name: &String| {
+            let name = name.to_ascii_lowercase();
+            provided.contains(&name) && !remaining.contains_key(&name)
+        };
+
+        let content: Vec<String> = self
+            .content_files()
+            .into_iter()
+            .filter(|name| orphaned(name))
+            .cloned()
+            .collect();
+        for plugin in content {
+            self.remove_content_file(&plugin);
+            actions.push(UninstallAction::RemovedContent(plugin));
+        }
+
+        let groundcover: Vec<String> = self
+            .groundcover()
+            .into_iter()
+            .filter(|name| orphaned(name))
+            .cloned()
+            .collect();
+        for plugin in groundcover {
+            self.remove_groundcover_file(&plugin);
+            actions.push(UninstallAction::RemovedGroundcover(plugin));
+        }
+
+        if let Some(metadata) = path.symlink_metadata().ok().filter(|_| delete_files) {
+            match metadata.is_symlink() {
+                true => remove_link(&path)?,
+                false => fs::remove_dir_all(&path)?,
+            }
+            actions.push(UninstallAction::DeletedDirectory(path));
+        }
+
+        Ok(actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_install_mod() {
+        let root = temp_config("install_mod", "user-data=Profile\n");
+        let mod_dir = root.join("Better Bodies");
+        fs::create_dir_all(mod_dir.join("meshes")).unwrap();
+        fs::write(mod_dir.join("meshes/body.nif"), b"nif").unwrap();
+        fs::write(mod_dir.join("Better Bodies.esp"), b"").unwrap();
+        fs::write(mod_dir.join("BB_Base.ESM"), b"").unwrap();
+        fs::write(mod_dir.join("readme.txt"), b"").unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+
+        let installed = config.install_mod(&mod_dir, InstallStrategy::Copy).unwrap();
+        assert_eq!(installed.path, root.join("Profile/mods/Better Bodies"));
+        assert!(installed.data_directory_added);
+        assert_eq!(
+            installed.plugins_added,
+            ["BB_Base.ESM", "Better Bodies.esp"]
+        );
+        assert!(installed.path.join("meshes/body.nif").is_file());
+        assert_eq!(config.data_directories().last(), Some(&&installed.path));
+
+        assert!(config.install_mod(&mod_dir, InstallStrategy::Copy).is_err());
+    }
+
+    #[test]
+    fn test_uninstall_mod() {
+        let root = temp_config(
+            "uninstall_mod",
+            "user-data=Profile\ndata=Base\ndata=Profile/mods/Tweaks\ncontent=Shared.esp\ncontent=Tweaks.esp\ngroundcover=Grass.esp\n",
+        );
+        for dir in ["Base", "Profile/mods/Tweaks"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join("Shared.esp"), b"").unwrap();
+        }
+        fs::write(root.join("Profile/mods/Tweaks/Tweaks.esp"), b"").unwrap();
+        fs::write(root.join("Profile/mods/Tweaks/grass.ESP"), b"").unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        let base = root.join("Base");
+        assert!(matches!(
+            config.uninstall_mod(&base, true),
+            Err(ConfigError::OutsideModsPath(_))
+        ));
+        assert!(base.is_dir());
+        assert_eq!(config.data_directories().len(), 2);
+
+        let tweaks = root.join("Profile/mods/Tweaks");
+        let actions = config.uninstall_mod(&tweaks, true).unwrap();
+
+        assert_eq!(
+            actions,
+            [
+                UninstallAction::RemovedDataDirectory(tweaks.clone()),
+                UninstallAction::RemovedContent("Tweaks.esp".into()),
+                UninstallAction::RemovedGroundcover("Grass.esp".into()),
+                UninstallAction::DeletedDirectory(tweaks.clone()),
+            ]
+        );
+        assert_eq!(config.content_files(), ["Shared.esp"]);
+        assert!(!tweaks.exists());
+        assert!(config.uninstall_mod(&tweaks, true).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Importing an existing Morrowind install the way OpenMW's wizard does:
+//! Data Files becomes a data directory, and Morrowind.ini provides the fallbacks, archives, game files and a guess at the encoding.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ConfigError, bail_config,
+    config::{
+        OpenMWConfiguration,
+        discovery::child_ignoring_case,
+        encodingsetting::EncodingType,
+        ini::{IniImportReport, parse_ini},
+    },
+};
+
+/// What `import_installation` found and changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallationImport {
+    pub data_files: PathBuf,
+    pub ini: PathBuf,
+    pub encoding: EncodingType,
+    /// The `data=` entry, unless it was already there
+    pub data_directory_added: bool,
+    /// From `[Game Files]`, in load order, skipping ones which were already enabled
+    pub content_added: Vec<String>,
+    /// Morrowind.bsa and `[Archives]`, skipping ones which were already listed
+    pub archives_added: Vec<String>,
+    pub fallbacks: IniImportReport,
+}
+
+/// Bytes which are common letters in Polish text encoded as windows-1250 (ł, ą, ś, ź), and rare otherwise
+const POLISH_MARKERS: [u8; 4] = [0xB3, 0xB9, 0x9C, 0x9F];
+
+/// Which encoding a Morrowind.ini is probably in, going by the text of its values.
+/// The wizard asks for the game's language instead, so this is only a guess for when nobody can be asked:
+/// Russian releases are nearly all bytes from the upper half of windows-1251, Polish ones have a few letters
+/// Which only windows-1250 uses, and everything else is windows-1252.
+pub fn guess_ini_encoding(ini: &[u8]) -> EncodingType {
+    let values = ini
+        .split(|byte| *byte == b'\n')
+        .filter_map(|line| {
+            let split = line.iter().position(|byte| *byte == b'=')?;
+            Some(&line[split + 1..])
+        })
+        .flatten();
+
+    let (mut upper, mut ascii, mut polish) = (0, 0, 0);
+    for byte in values {
+        match byte {
+            0xC0.. => upper += 1,
+            _ if byte.is_ascii_alphabetic() => ascii += 1,
+            _ if POLISH_MARKERS.contains(byte) => polish += 1,
+            _ => {}
+        }
+    }
+
+    if upper > ascii {
+        EncodingType::WIN1251
+    } else if polish >= 2 {
+        EncodingType::WIN1250
+    } else {
+        EncodingType::WIN1252
+    }
+}
+
+/// `path` can be the install directory or its Data Files
+fn find_installation(path: &Path) -> Result<(PathBuf, PathBuf), ConfigError> {
+    let is_data_files = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().eq_ignore_ascii_case("Data Files"));
+
+    let (install_dir, data_files) = match is_data_files {
+        true => (path.parent().unwrap_or(path), Some(path.to_path_buf())),
+        false => (path, child_ignoring_case(path, "Data Files")),
+    };
+
+    let Some(data_files) = data_files.filter(|dir| dir.is_dir()) else {
+        bail_config!(cannot_find, path.join("Data Files"));
+    };
+    let Some(ini) = child_ignoring_case(install_dir, "Morrowind.ini") else {
+        bail_config!(cannot_find, install_dir.join("Morrowind.ini"));
+    };
+
+    Ok((data_files, ini))
+}
+
+impl OpenMWConfiguration {
+    /// Adds a Morrowind install to the user configuration like OpenMW's install wizard.
+    /// Data Files becomes a data directory, and Morrowind.ini is imported: its fallbacks, the game files and archives it lists,
+    /// With Morrowind.bsa always first, and the encoding, which is guessed from the text with `guess_ini_encoding`.
+    /// Entries which are already present are left alone, so importing the same install twice only refreshes the fallbacks.
+    pub fn import_installation(&mut self, path: &Path) -> Result<InstallationImport, ConfigError> {
+        let (data_files, ini) = find_installation(path)?;
+        let bytes = self.fs.read(&ini)?;
+        let encoding = guess_ini_encoding(&bytes);
+
+        #[cfg(feature = "encoding")]
+        let contents = encoding.decode(&bytes).into_owned();
+        #[cfg(not(feature = "encoding"))]
+        let contents = String::from_utf8_lossy(&bytes).into_owned();
+
+        let entries = parse_ini(&contents);
+        let listed = |section: &str, prefix: &str| -> Vec<String> {
+            let mut listed: Vec<(u32, String)> = entries
+                .iter()
+                .filter(|(in_section, ..)| in_section == section)
+                .filter_map(|(_, key, value)| {
+                    let index = key.strip_prefix(prefix)?.trim().parse().ok()?;
+                    Some((index, value.clone()))
+                })
+                .collect();
+            listed.sort_by_key(|(index, _)| *index);
+            listed.into_iter().map(|(_, value)| value).collect()
+        };
+
+        let data_directory_added = !self.has_data_dir(&data_files.to_string_lossy());
+        if data_directory_added {
+            self.add_data_directory(data_files.clone());
+        }
+
+        let mut content_added = Vec::new();
+        for plugin in listed("Game Files", "GameFile") {
+            if !self.has_content_file(&plugin) {
+                self.add_content_file(&plugin)?;
+                content_added.push(plugin);
+            }
+        }
+
+        let mut archives_added = Vec::new();
+        let archives =
+            std::iter::once("Morrowind.bsa".to_string()).chain(listed("Archives", "Archive "));
+        for archive in archives {
+            if !self.has_archive_file(&archive) {
+                self.add_archive_file(&archive)?;
+                archives_added.push(archive);
+            }
+        }
+
+        self.set_encoding_type(Some(encoding.clone()));
+        let fallbacks = self.import_ini_text(&contents)?;
+
+        Ok(InstallationImport {
+            data_files,
+            ini,
+            encoding,
+            data_directory_added,
+            content_added,
+            archives_added,
+            fallbacks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_guess_ini_encoding() {
+        assert_eq!(
+            guess_ini_encoding(b"[Level Up]\nLevel2=You realize that all your life\n"),
+            EncodingType::WIN1252
+        );
+        assert_eq!(
+            guess_ini_encoding(
+                b"[Level Up]\nLevel2=\xc2\xfb \xef\xee\xed\xe8\xec\xe0\xe5\xf2\xe5\n"
+            ),
+            EncodingType::WIN1251
+        );
+        assert_eq!(
+            guess_ini_encoding(b"[Level Up]\nLevel2=Zda\xb3o ci si\xea, \xbfe ca\xb3y \x9cwiat\n"),
+            EncodingType::WIN1250
+        );
+    }
+
+    #[test]
+    fn test_import_installation() {
+        let dir = temp_config("import_installation", "content=Morrowind.esm\n");
+        let install = dir.join("Morrowind");
+        std::fs::create_dir_all(install.join("Data Files")).unwrap();
+        std::fs::write(
+            install.join("Morrowind.ini"),
+            "[Game Files]\nGameFile1=Tribunal.esm\nGameFile0=Morrowind.esm\n[Archives]\nArchive 0=Tribunal.bsa\n[Weather Clear]\nCloud Texture=Tx_Sky_Clear.dds\n",
+        )
+        .unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let import = config
+            .import_installation(&install.join("Data Files"))
+            .unwrap();
+
+        assert_eq!(import.encoding, EncodingType::WIN1252);
+        assert!(import.data_directory_added);
+        assert_eq!(import.content_added, vec!["Tribunal.esm"]);
+        assert_eq!(import.archives_added, vec!["Morrowind.bsa", "Tribunal.bsa"]);
+        assert_eq!(import.fallbacks.added, vec!["Weather_Clear_Cloud_Texture"]);
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal.esm"]
+        );
+        assert_eq!(config.encoding_type(), Some(&EncodingType::WIN1252));
+
+        let again = config.import_installation(&install).unwrap();
+        assert!(!again.data_directory_added);
+        assert!(again.archives_added.is_empty());
+        assert!(again.fallbacks.added.is_empty());
+
+        assert!(matches!(
+            config.import_installation(&install.join("Data Files").join("Meshes")),
+            Err(ConfigError::CannotFind(_))
+        ));
+    }
+}
@@ -0,0 +1,194 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Load orders from other mod managers, for people moving their setup over to OpenMW.
+
+use std::path::{Path, PathBuf};
+
+use crate::{ConfigError, config::OpenMWConfiguration};
+
+/// Mod Organizer 2 marks separators in the mod list as mods with this suffix
+const MO2_SEPARATOR_SUFFIX: &str = "_separator";
+
+/// A load order translated into OpenMW terms, ready for `apply_import`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedSetup {
+    /// In load order, so later directories win
+    pub data: Vec<PathBuf>,
+    pub content: Vec<String>,
+}
+
+impl ImportedSetup {
+    /// Reads `modlist.txt` and `plugins.txt` (plus `loadorder.txt`, if it's there) from an MO2 profile.
+    /// Mods are looked up as folders in `mods_dir`, which is normally the `mods` folder of the MO2 instance.
+    pub fn from_mo2_profile(profile_dir: &Path, mods_dir: &Path) -> Result<Self, ConfigError> {
+        let modlist = read_list(&profile_dir.join("modlist.txt"))?;
+        let plugins = read_list(&profile_dir.join("plugins.txt"))?;
+        let mut content = parse_mo2_plugins(&plugins);
+
+        if let Ok(load_order) = read_list(&profile_dir.join("loadorder.txt")) {
+            let mut ordered: Vec<String> = parse_mash_list(&load_order)
+                .into_iter()
+                .filter(|plugin| content.contains(plugin))
+                .collect();
+            content.retain(|plugin| !ordered.contains(plugin));
+            ordered.append(&mut content);
+            content = ordered;
+        }
+
+        Ok(Self {
+            data: parse_mo2_modlist(&modlist)
+                .into_iter()
+                .map(|name| mods_dir.join(name))
+                .collect(),
+            content,
+        })
+    }
+
+    /// Reads a plugin list exported from Wrye Mash, or any other one-plugin-per-line load order
+    pub fn from_mash_list(path: &Path) -> Result<Self, ConfigError> {
+        Ok(Self {
+            data: Vec::new(),
+            content: parse_mash_list(&read_list(path)?),
+        })
+    }
+}
+
+/// These are written by Windows tools, so a BOM or the odd non-UTF-8 byte is expected
+fn read_list(path: &Path) -> Result<String, ConfigError> {
+    let bytes = std::fs::read(path)?;
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Enabled mods from an MO2 `modlist.txt`, lowest priority first.
+/// MO2 writes the highest priority mod at the top, and `+`/`-` mark enabled and disabled ones.
+/// Unmanaged entries (`*`) and separators are skipped, since there's no folder behind them.
+pub fn parse_mo2_modlist(contents: &str) -> Vec<String> {
+    let mut mods: Vec<String> = contents
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix('+'))
+        .filter(|name| !name.is_empty() && !name.ends_with(MO2_SEPARATOR_SUFFIX))
+        .map(str::to_string)
+        .collect();
+
+    mods.reverse();
+    mods
+}
+
+/// Enabled plugins from an MO2 `plugins.txt`, in the order they're listed.
+/// Newer profiles mark enabled plugins with `*`, in which case anything without one is disabled.
+pub fn parse_mo2_plugins(contents: &str) -> Vec<String> {
+    let entries = parse_mash_list(contents);
+
+    match entries.iter().any(|entry| entry.starts_with('*')) {
+        true => entries
+            .iter()
+            .filter_map(|entry| entry.strip_prefix('*'))
+            .map(str::to_string)
+            .collect(),
+        false => entries,
+    }
+}
+
+/// One plugin per line, skipping blank lines, `#`/`;` comments and `[section]` headers
+pub fn parse_mash_list(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            !line.is_empty()
+                && !line.starts_with('#')
+                && !line.starts_with(';')
+                && !line.starts_with('[')
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+impl OpenMWConfiguration {
+    /// Appends whatever part of `setup` isn't in the configuration yet to the user openmw.cfg.
+    /// Returns the entries which were actually added.
+    pub fn apply_import(&mut self, setup: &ImportedSetup) -> Result<ImportedSetup, ConfigError> {
+        let mut added = ImportedSetup::default();
+
+        for dir in &setup.data {
+            if !self.data_directories().contains(&dir) {
+                self.add_data_directory(dir.clone());
+                added.data.push(dir.clone());
+            }
+        }
+
+        for plugin in &setup.content {
+            if !self.has_content_file(plugin) {
+                self.add_content_file(plugin)?;
+                added.content.push(plugin.clone());
+            }
+        }
+
+        Ok(added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_parse_mo2_lists() {
+        let modlist = "# This file was automatically generated by Mod Organizer.\n\
+                       +Patch for Purists\n\
+                       -Disabled Mod\n\
+                       +Visuals_separator\n\
+                       *Unmanaged: Tribunal\n\
+                       +Tamriel Data\n";
+        assert_eq!(
+            parse_mo2_modlist(modlist),
+            vec!["Tamriel Data", "Patch for Purists"]
+        );
+
+        let plugins = "# comment\n*Morrowind.esm\nOff.esp\n*Tamriel_Data.esm\n";
+        assert_eq!(
+            parse_mo2_plugins(plugins),
+            vec!["Morrowind.esm", "Tamriel_Data.esm"]
+        );
+        assert_eq!(parse_mo2_plugins("A.esm\nB.esp\n"), vec!["A.esm", "B.esp"]);
+    }
+
+    #[test]
+    fn test_import_mo2_profile() {
+        let dir = temp_config("interop", "content=Morrowind.esm\n");
+        let profile = dir.join("profile");
+        std::fs::create_dir_all(&profile).unwrap();
+        std::fs::write(profile.join("modlist.txt"), "\u{feff}+Second\n+First\n").unwrap();
+        std::fs::write(
+            profile.join("plugins.txt"),
+            "*Morrowind.esm\n*Late.esp\n*Early.esp\n",
+        )
+        .unwrap();
+        std::fs::write(
+            profile.join("loadorder.txt"),
+            "Morrowind.esm\nEarly.esp\nLate.esp\n",
+        )
+        .unwrap();
+
+        let mods = dir.join("mods");
+        let setup = ImportedSetup::from_mo2_profile(&profile, &mods).unwrap();
+        assert_eq!(setup.data, vec![mods.join("First"), mods.join("Second")]);
+        assert_eq!(
+            setup.content,
+            vec!["Morrowind.esm", "Early.esp", "Late.esp"]
+        );
+
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let added = config.apply_import(&setup).unwrap();
+        assert_eq!(added.content, vec!["Early.esp", "Late.esp"]);
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Early.esp", "Late.esp"]
+        );
+    }
+}
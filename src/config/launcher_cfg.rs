@@ -0,0 +1,412 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! The official launcher's own launcher.cfg, which sits next to the user openmw.cfg.
+//!
+//! ```ini
+//! [General]
+//! firstrun=false
+//!
+//! [Profiles]
+//! currentprofile=Default
+//! Default/data=/home/me/mods/Tamriel_Data
+//! Default/content=Morrowind.esm
+//! Default/content=Tamriel_Data.esm
+//! ```
+//!
+//! It's QSettings-style INI where a key may repeat to hold a list, so entries are kept in order instead of in a map.
+//! Comments aren't kept, since the launcher doesn't keep them either.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ConfigError, bail_config,
+    config::{
+        OpenMWConfiguration,
+        fs::{ConfigFs, StdFs},
+    },
+};
+
+pub const LAUNCHER_CFG_FILE: &str = "launcher.cfg";
+
+const GENERAL: &str = "General";
+const PROFILES: &str = "Profiles";
+const CURRENT_PROFILE: &str = "currentprofile";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LauncherSection {
+    pub name: String,
+    pub entries: Vec<(String, String)>,
+}
+
+/// One of the launcher's named setups, as stored under `[Profiles]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LauncherProfile {
+    pub name: String,
+    pub data: Vec<String>,
+    pub content: Vec<String>,
+    pub fallback_archives: Vec<String>,
+    /// Anything else the launcher stored for this profile, like its `timestamp`, keyed without the profile prefix
+    pub other: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LauncherConfig {
+    sections: Vec<LauncherSection>,
+}
+
+/// QSettings quotes values with surrounding whitespace or characters it treats specially
+fn unquote(value: &str) -> String {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return value.to_string();
+    };
+
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+
+    unquoted
+}
+
+fn quote(value: &str) -> String {
+    let needs_quotes = value.trim() != value || value.contains([';', ',', '"', '\\']);
+
+    match needs_quotes {
+        true => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        false => value.to_string(),
+    }
+}
+
+impl LauncherConfig {
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        Self::parse_from(text, Path::new("<launcher.cfg>"))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        Self::parse_from(&StdFs.read_to_string(path)?, path)
+    }
+
+    fn parse_from(text: &str, path: &Path) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with([';', '#']) {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                config.section_mut(name.trim());
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                bail_config!(
+                    invalid_launcher_cfg,
+                    path,
+                    format!(
+                        "line {} is neither a section nor a key=value pair",
+                        index + 1
+                    )
+                );
+            };
+
+            let Some(section) = config.sections.last_mut() else {
+                bail_config!(
+                    invalid_launcher_cfg,
+                    path,
+                    format!("line {} comes before any section", index + 1)
+                );
+            };
+
+            section
+                .entries
+                .push((key.trim().to_string(), unquote(value.trim())));
+        }
+
+        Ok(config)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), ConfigError> {
+        Ok(std::fs::write(path, self.to_string())?)
+    }
+
+    pub fn sections(&self) -> &[LauncherSection] {
+        &self.sections
+    }
+
+    pub fn section(&self, name: &str) -> Option<&LauncherSection> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// Adds the section at the end if it isn't there yet
+    pub fn section_mut(&mut self, name: &str) -> &mut LauncherSection {
+        let index = match self
+            .sections
+            .iter()
+            .position(|section| section.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.sections.push(LauncherSection {
+                    name: name.to_string(),
+                    entries: Vec::new(),
+                });
+                self.sections.len() - 1
+            }
+        };
+
+        &mut self.sections[index]
+    }
+
+    /// Every value of a key, in file order
+    pub fn get(&self, section: &str, key: &str) -> Vec<&str> {
+        self.section(section)
+            .into_iter()
+            .flat_map(|section| &section.entries)
+            .filter(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Replaces every value of a key, keeping its place in the section if it had one
+    pub fn set(&mut self, section: &str, key: &str, values: Vec<String>) {
+        let section = self.section_mut(section);
+        let position = section
+            .entries
+            .iter()
+            .position(|(name, _)| name == key)
+            .unwrap_or(section.entries.len());
+
+        section.entries.retain(|(name, _)| name != key);
+
+        let position = position.min(section.entries.len());
+        section.entries.splice(
+            position..position,
+            values.into_iter().map(|value| (key.to_string(), value)),
+        );
+    }
+
+    /// `None` when the launcher hasn't recorded it, which it treats as a first run
+    pub fn first_run(&self) -> Option<bool> {
+        self.get(GENERAL, "firstrun")
+            .last()
+            .and_then(|value| value.parse().ok())
+    }
+
+    pub fn set_first_run(&mut self, first_run: bool) {
+        self.set(GENERAL, "firstrun", vec![first_run.to_string()]);
+    }
+
+    pub fn current_profile(&self) -> Option<&str> {
+        self.get(PROFILES, CURRENT_PROFILE).last().copied()
+    }
+
+    pub fn set_current_profile(&mut self, name: &str) {
+        self.set(PROFILES, CURRENT_PROFILE, vec![name.to_string()]);
+    }
+
+    /// Profile names in the order they first appear
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+
+        self.section(PROFILES)
+            .into_iter()
+            .flat_map(|section| &section.entries)
+            .filter_map(|(key, _)| key.split_once('/'))
+            .for_each(|(name, _)| {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            });
+
+        names
+    }
+
+    pub fn profile(&self, name: &str) -> Option<LauncherProfile> {
+        let mut profile = LauncherProfile {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut found = false;
+
+        self.section(PROFILES)
+            .into_iter()
+            .flat_map(|section| &section.entries)
+            .filter_map(|(key, value)| Some((key.strip_prefix(name)?.strip_prefix('/')?, value)))
+            .for_each(|(key, value)| {
+                found = true;
+                match key {
+                    "data" => profile.data.push(value.clone()),
+                    "content" => profile.content.push(value.clone()),
+                    "fallback-archive" => profile.fallback_archives.push(value.clone()),
+                    key => profile.other.push((key.to_string(), value.clone())),
+                }
+            });
+
+        found.then_some(profile)
+    }
+
+    pub fn profiles(&self) -> Vec<LauncherProfile> {
+        self.profile_names()
+            .into_iter()
+            .filter_map(|name| self.profile(name))
+            .collect()
+    }
+
+    /// Replaces the profile with the same name, or adds it after the others
+    pub fn set_profile(&mut self, profile: &LauncherProfile) {
+        let prefix = format!("{}/", profile.name);
+        let section = self.section_mut(PROFILES);
+
+        let position = section
+            .entries
+            .iter()
+            .position(|(key, _)| key.starts_with(&prefix))
+            .unwrap_or(section.entries.len());
+        section.entries.retain(|(key, _)| !key.starts_with(&prefix));
+
+        let entries = profile
+            .other
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+            .chain(profile.data.iter().map(|value| ("data", value)))
+            .chain(
+                profile
+                    .fallback_archives
+                    .iter()
+                    .map(|value| ("fallback-archive", value)),
+            )
+            .chain(profile.content.iter().map(|value| ("content", value)))
+            .map(|(key, value)| (format!("{prefix}{key}"), value.clone()));
+
+        let position = position.min(section.entries.len());
+        section.entries.splice(position..position, entries);
+    }
+
+    /// Returns whether there was such a profile
+    pub fn remove_profile(&mut self, name: &str) -> bool {
+        let prefix = format!("{name}/");
+        let Some(section) = self
+            .sections
+            .iter_mut()
+            .find(|section| section.name == PROFILES)
+        else {
+            return false;
+        };
+
+        let before = section.entries.len();
+        section.entries.retain(|(key, _)| !key.starts_with(&prefix));
+
+        section.entries.len() != before
+    }
+}
+
+impl std::fmt::Display for LauncherConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, section) in self.sections.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "[{}]", section.name)?;
+            for (key, value) in &section.entries {
+                writeln!(f, "{key}={}", quote(value))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl OpenMWConfiguration {
+    /// launcher.cfg in the user config directory, whether or not it exists yet
+    pub fn launcher_cfg_path(&self) -> PathBuf {
+        self.user_config_path().join(LAUNCHER_CFG_FILE)
+    }
+
+    /// `None` when the official launcher has never been run
+    pub fn launcher_config(&self) -> Result<Option<LauncherConfig>, ConfigError> {
+        let path = self.launcher_cfg_path();
+
+        match path.is_file() {
+            true => LauncherConfig::read(&path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    pub fn save_launcher_config(&self, launcher: &LauncherConfig) -> Result<(), ConfigError> {
+        let path = self.launcher_cfg_path();
+
+        if self.is_read_only() {
+            bail_config!(read_only, path);
+        }
+
+        launcher.write(&path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    const LAUNCHER: &str = "[General]\nfirstrun=false\n\n[Profiles]\ncurrentprofile=Default\nDefault/timestamp=2024-01-01T00:00:00\nDefault/data=\"/mods/Spaced Out \"\nDefault/content=Morrowind.esm\nDefault/content=Tribunal.esm\nModded/content=Morrowind.esm\n\n[Settings]\nlanguage=English\n";
+
+    #[test]
+    fn test_launcher_profiles() {
+        let dir = temp_config("launcher_cfg", "");
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert_eq!(config.launcher_config().unwrap(), None);
+
+        std::fs::write(dir.join(LAUNCHER_CFG_FILE), LAUNCHER).unwrap();
+        let mut launcher = config.launcher_config().unwrap().unwrap();
+
+        assert_eq!(launcher.first_run(), Some(false));
+        assert_eq!(launcher.current_profile(), Some("Default"));
+        assert_eq!(launcher.profile_names(), vec!["Default", "Modded"]);
+
+        let mut default = launcher.profile("Default").unwrap();
+        assert_eq!(default.data, vec!["/mods/Spaced Out "]);
+        assert_eq!(default.content, vec!["Morrowind.esm", "Tribunal.esm"]);
+        assert_eq!(
+            default.other,
+            vec![("timestamp".to_string(), "2024-01-01T00:00:00".to_string())]
+        );
+
+        config.save_launcher_config(&launcher).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(config.launcher_cfg_path()).unwrap(),
+            LAUNCHER
+        );
+
+        default.content.push("Bloodmoon.esm".into());
+        launcher.set_profile(&default);
+        assert!(launcher.remove_profile("Modded"));
+        launcher.set_first_run(true);
+
+        let reparsed = LauncherConfig::parse(&launcher.to_string()).unwrap();
+        assert_eq!(reparsed.profiles(), vec![default]);
+        assert_eq!(reparsed.first_run(), Some(true));
+        assert_eq!(reparsed.get("Settings", "language"), vec!["English"]);
+
+        assert!(matches!(
+            LauncherConfig::parse("firstrun=true\n"),
+            Err(ConfigError::InvalidLauncherCfg { .. })
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Style problems which `validate` leaves alone: lines that load, but probably not the way whoever wrote them meant.
+//! The issues are the same as `validate`'s, so their fixes go through `apply_fix` too.
+
+use std::{collections::HashSet, path::Path};
+
+use crate::{
+    GameSetting, SettingOrigin,
+    config::{
+        OpenMWConfiguration, SettingValue,
+        directorysetting::DirectorySetting,
+        messages::{Message, MessageId},
+        strings,
+        validation::{Issue, Severity},
+    },
+};
+
+fn directory_setting(setting: &SettingValue) -> Option<&DirectorySetting> {
+    match setting {
+        SettingValue::DataDirectory(dir)
+        | SettingValue::UserData(dir)
+        | SettingValue::DataLocal(dir)
+        | SettingValue::Resources(dir)
+        | SettingValue::SubConfiguration(dir) => Some(dir),
+        _ => None,
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Checks for lines which are probably mistakes even though the engine accepts them, in load order.
+    /// Settings the crate made up itself, like the directory `data-local` implies, are left out.
+    pub fn lint(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        for setting in self.settings.iter() {
+            if setting.meta().origin() == SettingOrigin::Synthetic {
+                continue;
+            }
+
+            self.lint_whitespace(setting, &mut issues);
+            if !cfg!(windows)
+                && let Some(dir) = directory_setting(setting)
+            {
+                lint_windows_path(dir, &mut issues);
+            }
+        }
+
+        self.lint_case_duplicates(&mut issues);
+        self.lint_unused_archives(&mut issues);
+
+        issues
+    }
+
+    fn lint_whitespace(&self, setting: &SettingValue, issues: &mut Vec<Issue>) {
+        let (value, trimmed) = match setting {
+            SettingValue::ContentFile(file)
+            | SettingValue::BethArchive(file)
+            | SettingValue::Groundcover(file) => (file.value().clone(), file.value().trim().into()),
+            _ => match directory_setting(setting) {
+                Some(dir) => {
+                    let path = strings::unquote_data_directory(dir.original());
+                    let trimmed = strings::serialize_data_directory(path.trim());
+                    (path, trimmed)
+                }
+                None => return,
+            },
+        };
+
+        if value.trim() != value {
+            issues.push(
+                Issue::new(
+                    Severity::Warning,
+                    "whitespace-in-value",
+                    Message::new(MessageId::WhitespaceInValue).arg("value", &value),
+                )
+                .replaceable(setting.handle(), MessageId::FixTrimValue, trimmed),
+            );
+        }
+    }
+
+    /// Later directories win, so of two spellings the earlier one is the one to drop
+    fn lint_case_duplicates(&self, issues: &mut Vec<Issue>) {
+        let directories: Vec<&DirectorySetting> = self
+            .data_directories_iter()
+            .filter(|dir| dir.meta().origin() != SettingOrigin::Synthetic)
+            .collect();
+
+        for (index, dir) in directories.iter().enumerate() {
+            let path = dir.parsed().to_string_lossy();
+
+            let other = directories[index + 1..].iter().find(|later| {
+                let later = later.parsed().to_string_lossy();
+                later != path && strings::eq_fold_case(&later, &path)
+            });
+
+            if let Some(other) = other {
+                issues.push(
+                    Issue::new(
+                        Severity::Warning,
+                        "case-duplicate-data-dir",
+                        Message::new(MessageId::CaseDuplicateDataDir)
+                            .arg("path", dir.parsed().display())
+                            .arg("other", other.parsed().display()),
+                    )
+                    .removable(dir.handle(), MessageId::FixRemoveDuplicateDataDir),
+                );
+            }
+        }
+    }
+
+    /// Archives which share their name with an enabled plugin, like Tribunal.bsa and Tribunal.esm, are in use.
+    /// The rest are only flagged as suggestions, since some mods ship an archive on its own.
+    fn lint_unused_archives(&self, issues: &mut Vec<Issue>) {
+        let stem = |name: &str| {
+            Path::new(name)
+                .file_stem()
+                .map(|stem| strings::fold_case(&stem.to_string_lossy()))
+        };
+
+        let plugins: HashSet<String> = self
+            .content_files_iter()
+            .chain(self.groundcover_iter())
+            .filter_map(|plugin| stem(plugin.value()))
+            .collect();
+
+        for archive in self.fallback_archives_iter() {
+            if stem(archive.value()).is_some_and(|name| !plugins.contains(&name)) {
+                issues.push(
+                    Issue::new(
+                        Severity::Suggestion,
+                        "unused-archive",
+                        Message::new(MessageId::UnusedArchive).arg("name", archive.value()),
+                    )
+                    .removable(archive.handle(), MessageId::FixRemoveArchive),
+                );
+            }
+        }
+    }
+}
+
+/// Only called off Windows, where drive letters and backslashes mean nothing to the filesystem
+fn lint_windows_path(dir: &DirectorySetting, issues: &mut Vec<Issue>) {
+    let path = strings::unquote_data_directory(dir.original());
+
+    if strings::drive_letter(&path).is_some() || path.starts_with(r"\\") {
+        issues.push(
+            Issue::new(
+                Severity::Warning,
+                "windows-path",
+                Message::new(MessageId::WindowsPath).arg("path", &path),
+            )
+            .about(dir.handle()),
+        );
+    } else if path.contains('\\') {
+        issues.push(
+            Issue::new(
+                Severity::Suggestion,
+                "backslash-path",
+                Message::new(MessageId::BackslashPath).arg("path", &path),
+            )
+            .replaceable(
+                dir.handle(),
+                MessageId::FixForwardSlashes,
+                strings::serialize_data_directory(&path.replace('\\', "/")),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lint_and_fix() {
+        let dir = temp_config(
+            "lint",
+            "data=\"Mods \"\ndata=C:\\Games\\Morrowind\ndata=Mods\\Textures\ndata=Extra\ndata=EXTRA\ncontent=Morrowind.esm\nfallback-archive=Morrowind.bsa\nfallback-archive=Old Mod.bsa\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        config.add_content_file("Tribunal.esm ").unwrap();
+
+        let codes: Vec<&str> = config.lint().iter().map(|issue| issue.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                "whitespace-in-value",
+                "windows-path",
+                "backslash-path",
+                "whitespace-in-value",
+                "case-duplicate-data-dir",
+                "unused-archive",
+            ]
+        );
+
+        let fixes: Vec<_> = config
+            .lint()
+            .into_iter()
+            .filter_map(|issue| issue.fix)
+            .collect();
+        assert_eq!(fixes.len(), 5);
+        fixes
+            .iter()
+            .for_each(|fix| config.apply_fix(&fix.id).unwrap());
+
+        assert_eq!(
+            config
+                .lint()
+                .iter()
+                .map(|issue| issue.code)
+                .collect::<Vec<_>>(),
+            vec!["windows-path"]
+        );
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal.esm"]
+        );
+        assert!(config.has_data_dir(&dir.join("Mods").to_string_lossy()));
+        assert!(config.to_string().contains("data=Mods/Textures\n"));
+        assert_eq!(config.fallback_archives(), vec!["Morrowind.bsa"]);
+    }
+}
@@ -0,0 +1,83 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! An owned copy of what the engine loads and in which order.
+//! It doesn't borrow the configuration, so it can be handed to a worker thread
+//! While the UI keeps editing the original.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::OpenMWConfiguration;
+
+/// Every list is lowest priority first, the same order the engine loads them in
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LoadOrder {
+    pub content: Vec<String>,
+    pub groundcover: Vec<String>,
+    pub archives: Vec<String>,
+    /// data-local included, as the last one
+    pub data_directories: Vec<PathBuf>,
+}
+
+impl LoadOrder {
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+            && self.groundcover.is_empty()
+            && self.archives.is_empty()
+            && self.data_directories.is_empty()
+    }
+
+    /// Where the plugin sits in the content list, ignoring case like the engine does
+    pub fn content_position(&self, name: &str) -> Option<usize> {
+        self.content
+            .iter()
+            .position(|plugin| plugin.eq_ignore_ascii_case(name))
+    }
+
+    pub fn has_data_directory(&self, dir: &Path) -> bool {
+        self.data_directories.iter().any(|known| known == dir)
+    }
+}
+
+impl OpenMWConfiguration {
+    pub fn to_load_order(&self) -> LoadOrder {
+        let owned = |names: Vec<&String>| names.into_iter().cloned().collect();
+
+        LoadOrder {
+            content: owned(self.content_files()),
+            groundcover: owned(self.groundcover()),
+            archives: owned(self.fallback_archives()),
+            data_directories: self.data_directories().into_iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_load_order_moves_to_threads() {
+        let dir = temp_config(
+            "owned_load_order",
+            "data=Data\ncontent=Morrowind.esm\ncontent=Tribunal.esm\ngroundcover=Grass.esp\nfallback-archive=Morrowind.bsa\n",
+        );
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let order = config.to_load_order();
+
+        let worker = std::thread::spawn(move || {
+            assert_eq!(order.content, vec!["Morrowind.esm", "Tribunal.esm"]);
+            assert_eq!(order.content_position("tribunal.ESM"), Some(1));
+            assert_eq!(order.groundcover, vec!["Grass.esp"]);
+            assert_eq!(order.archives, vec!["Morrowind.bsa"]);
+            assert!(order.has_data_directory(&dir.join("Data")));
+            order
+        });
+
+        assert_eq!(worker.join().unwrap(), config.to_load_order());
+        assert!(LoadOrder::default().is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Which of the engine's configuration locations each openmw.cfg in the chain is.
+//! A portable install keeps a "local" openmw.cfg next to the executable, which the engine loads instead of the global one.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::OpenMWConfiguration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConfigLocation {
+    /// The system-wide install, such as `/etc/openmw`
+    Global,
+    /// Next to the executable, in a portable install
+    Local,
+    /// The last openmw.cfg in the chain, or the platform's user config directory
+    User,
+    /// Anything else pulled in with `config=`
+    Custom,
+}
+
+/// Which platform's rules the default locations follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathConvention {
+    /// `~/.config/openmw` and `~/.local/share/openmw`, or wherever the XDG variables point
+    Xdg,
+    /// `~/Library/Preferences/openmw` for config, `~/Library/Application Support/openmw` for user data
+    MacOs,
+    /// `Documents/My Games/openmw` for both
+    Windows,
+    /// Under `android::android_root()`
+    Android,
+}
+
+impl PathConvention {
+    pub fn current() -> Self {
+        if cfg!(target_os = "android") {
+            PathConvention::Android
+        } else if cfg!(windows) {
+            PathConvention::Windows
+        } else if cfg!(target_os = "macos") {
+            PathConvention::MacOs
+        } else {
+            PathConvention::Xdg
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigSource {
+    pub path: PathBuf,
+    pub location: ConfigLocation,
+    /// Set when the file is at one of the platform's default locations, rather than somewhere a `config=` line pointed
+    pub convention: Option<PathConvention>,
+}
+
+/// The local openmw.cfg of an install, if it's a portable one
+pub fn find_local_config(install_dir: &Path) -> Option<PathBuf> {
+    let cfg = install_dir.join("openmw.cfg");
+    cfg.is_file().then_some(cfg)
+}
+
+/// Compares directories, seeing through symlinks and `..` when both exist
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Where OpenMW is installed, which decides what counts as the local openmw.cfg.
+    /// `from_system_defaults` fills this in, so it only needs setting for configurations loaded some other way.
+    pub fn set_install_dir(&mut self, dir: Option<PathBuf>) {
+        self.install_dir = dir;
+    }
+
+    pub fn install_dir(&self) -> Option<&Path> {
+        self.install_dir.as_deref()
+    }
+
+    /// Whether the chain starts from a local openmw.cfg, meaning the global one is never read
+    pub fn is_portable(&self) -> bool {
+        self.location_of(self.root_config_file()) == ConfigLocation::Local
+    }
+
+    /// Classifies an openmw.cfg (or the directory holding one)
+    pub fn location_of(&self, config: &Path) -> ConfigLocation {
+        let dir = match config.file_name() {
+            Some(name) if name == "openmw.cfg" => config.parent().unwrap_or(config),
+            _ => config,
+        };
+
+        if self
+            .install_dir
+            .as_deref()
+            .is_some_and(|install| same_dir(install, dir))
+        {
+            ConfigLocation::Local
+        } else if crate::default_global_config_path().is_some_and(|global| same_dir(&global, dir)) {
+            ConfigLocation::Global
+        } else if same_dir(&self.user_config_path(), dir)
+            || same_dir(&crate::default_config_path(), dir)
+        {
+            ConfigLocation::User
+        } else {
+            ConfigLocation::Custom
+        }
+    }
+
+    /// Every openmw.cfg in the chain, in load order, with its location
+    pub fn config_sources(&self) -> Vec<ConfigSource> {
+        self.chain_files()
+            .into_iter()
+            .map(|path| {
+                let dir = path.parent().unwrap_or(&path);
+                let is_default = crate::default_global_config_path()
+                    .is_some_and(|global| same_dir(&global, dir))
+                    || same_dir(&crate::default_config_path(), dir);
+
+                ConfigSource {
+                    location: self.location_of(&path),
+                    convention: is_default.then(PathConvention::current),
+                    path,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_portable_install_locations() {
+        let user = temp_config("location_user", "content=Tribunal.esm\n");
+        let fragment = temp_config("location_fragment", "content=Patch.esp\n");
+        let install = temp_config(
+            "location_install",
+            &format!(
+                "content=Morrowind.esm\nconfig={}\nconfig={}\n",
+                fragment.display(),
+                user.display()
+            ),
+        );
+
+        let config = OpenMWConfiguration::from_system_defaults_in(Some(&install)).unwrap();
+        assert!(config.is_portable());
+        assert_eq!(
+            find_local_config(&install),
+            Some(install.join("openmw.cfg"))
+        );
+        assert_eq!(
+            config
+                .config_sources()
+                .into_iter()
+                .map(|source| source.location)
+                .collect::<Vec<_>>(),
+            vec![
+                ConfigLocation::Local,
+                ConfigLocation::Custom,
+                ConfigLocation::User
+            ]
+        );
+
+        let plain = OpenMWConfiguration::new(Some(install)).unwrap();
+        assert!(!plain.is_portable());
+        assert!(
+            plain
+                .config_sources()
+                .iter()
+                .all(|source| source.convention.is_none())
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_macos_default_paths() {
+        let home = dirs::home_dir().unwrap();
+
+        assert_eq!(PathConvention::current(), PathConvention::MacOs);
+        assert_eq!(
+            crate::default_config_path(),
+            home.join("Library/Preferences/openmw")
+        );
+        assert_eq!(
+            crate::default_userdata_path(),
+            home.join("Library/Application Support/openmw")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_default_paths() {
+        assert_eq!(PathConvention::current(), PathConvention::Windows);
+        assert!(crate::default_config_path().ends_with("My Games/openmw"));
+        assert_eq!(crate::default_userdata_path(), crate::default_config_path());
+    }
+
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "android")))]
+    #[test]
+    fn test_xdg_default_paths() {
+        assert_eq!(PathConvention::current(), PathConvention::Xdg);
+        assert_eq!(
+            crate::default_config_path(),
+            dirs::config_dir().unwrap().join("openmw")
+        );
+        assert_eq!(
+            crate::default_userdata_path(),
+            dirs::data_dir().unwrap().join("openmw")
+        );
+    }
+}
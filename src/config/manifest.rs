@@ -0,0 +1,278 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Declarative mod setups, written as TOML (or YAML with `manifest-yaml`) and compiled into an openmw.cfg.
+//!
+//! ```toml
+//! data = ["~/Games/Morrowind/Data Files", "mods/Tamriel_Data"]
+//! content = ["Morrowind.esm", "Tribunal.esm", "Tamriel_Data.esm"]
+//! fallback-archives = ["Morrowind.bsa"]
+//!
+//! [fallback]
+//! Weather_Clear_Sky_Sunrise_Color = "117,141,164"
+//! ```
+//!
+//! Relative paths are relative to the manifest itself, so a modlist can live in a repo next to the mods it lists.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    ConfigError, bail_config,
+    config::{
+        OpenMWConfiguration, ParseOptions,
+        fs::{ConfigFs, FsSlot, MemoryFs, StdFs},
+        strings,
+    },
+};
+
+/// A fallback value. Plain numbers are accepted so manifests don't have to quote everything.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum FallbackValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl fmt::Display for FallbackValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FallbackValue::Int(value) => write!(f, "{value}"),
+            // Whole floats keep their decimal point, or they'd load back as ints
+            FallbackValue::Float(value) if value.fract() == 0.0 => write!(f, "{value:.1}"),
+            FallbackValue::Float(value) => write!(f, "{value}"),
+            FallbackValue::Text(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub data: Vec<String>,
+    #[serde(default)]
+    pub content: Vec<String>,
+    #[serde(default)]
+    pub groundcover: Vec<String>,
+    #[serde(default)]
+    pub fallback_archives: Vec<String>,
+    #[serde(default)]
+    pub fallback: BTreeMap<String, FallbackValue>,
+    pub encoding: Option<String>,
+    pub user_data: Option<String>,
+    pub data_local: Option<String>,
+    pub resources: Option<String>,
+    /// Where relative paths are resolved from, which is the manifest's own directory when it came from `read`
+    #[serde(skip)]
+    pub base_dir: Option<PathBuf>,
+}
+
+impl Manifest {
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(|error| {
+            crate::config_err!(invalid_manifest, Path::new("<manifest>"), error.message())
+        })
+    }
+
+    #[cfg(feature = "manifest-yaml")]
+    pub fn from_yaml(text: &str) -> Result<Self, ConfigError> {
+        serde_yaml::from_str(text)
+            .map_err(|error| crate::config_err!(invalid_manifest, Path::new("<manifest>"), error))
+    }
+
+    /// Picks TOML or YAML from the file extension
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        let text = StdFs.read_to_string(path)?;
+
+        let parsed = match path.extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "manifest-yaml")]
+            Some("yaml" | "yml") => Self::from_yaml(&text),
+            Some("toml") => Self::from_toml(&text),
+            _ => bail_config!(
+                invalid_manifest,
+                path,
+                "manifests have to be .toml files (or .yaml, with the manifest-yaml feature)"
+            ),
+        };
+
+        let mut manifest = parsed.map_err(|error| match error {
+            ConfigError::InvalidManifest { reason, .. } => {
+                crate::config_err!(invalid_manifest, path, reason)
+            }
+            error => error,
+        })?;
+        manifest.base_dir = path.parent().map(Path::to_path_buf);
+
+        Ok(manifest)
+    }
+
+    /// Tokens are left alone, since they only mean something to the engine
+    fn resolve_dir(&self, dir: &str) -> String {
+        if dir.starts_with('?') {
+            return dir.to_string();
+        }
+
+        let expanded = PathBuf::from(shellexpand::tilde(dir).into_owned());
+        match (&self.base_dir, expanded.is_relative()) {
+            (Some(base), true) => base.join(expanded),
+            _ => expanded,
+        }
+        .to_string_lossy()
+        .into_owned()
+    }
+
+    /// The openmw.cfg this manifest describes
+    pub fn to_openmw_cfg(&self) -> String {
+        let mut lines = vec![
+            "# Generated from a manifest by openmw-config, edit the manifest instead".to_string(),
+        ];
+
+        if let Some(encoding) = &self.encoding {
+            lines.push(format!("encoding={encoding}"));
+        }
+
+        for (key, dir) in [
+            ("user-data", &self.user_data),
+            ("data-local", &self.data_local),
+            ("resources", &self.resources),
+        ] {
+            if let Some(dir) = dir {
+                lines.push(format!(
+                    "{key}={}",
+                    strings::quote_data_directory(&self.resolve_dir(dir))
+                ));
+            }
+        }
+
+        lines.extend(self.data.iter().map(|dir| {
+            format!(
+                "data={}",
+                strings::quote_data_directory(&self.resolve_dir(dir))
+            )
+        }));
+        lines.extend(
+            self.fallback_archives
+                .iter()
+                .map(|archive| format!("fallback-archive={archive}")),
+        );
+        lines.extend(
+            self.content
+                .iter()
+                .map(|plugin| format!("content={plugin}")),
+        );
+        lines.extend(
+            self.groundcover
+                .iter()
+                .map(|grass| format!("groundcover={grass}")),
+        );
+        lines.extend(
+            self.fallback
+                .iter()
+                .map(|(key, value)| format!("fallback={key},{value}")),
+        );
+
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    /// Writes the openmw.cfg into `output_dir`, replacing whatever was there, and returns it loaded.
+    /// It's loaded once in memory first, so a manifest with a bad value never clobbers a working openmw.cfg.
+    pub fn compile(&self, output_dir: &Path) -> Result<OpenMWConfiguration, ConfigError> {
+        let cfg_path = output_dir.join("openmw.cfg");
+        let text = self.to_openmw_cfg();
+
+        let memory = Arc::new(MemoryFs::new().with_file(&cfg_path, text.as_bytes()));
+        let mut config = OpenMWConfiguration::new_with_options(
+            Some(output_dir.to_path_buf()),
+            ParseOptions::default().with_fs(memory),
+        )?;
+
+        StdFs.create_dir_all(output_dir)?;
+        StdFs.write(&cfg_path, text.as_bytes())?;
+        config.fs = FsSlot::default();
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    const MANIFEST: &str = r#"
+data = ["Data Files", "mods/Tamriel & Friends"]
+content = ["Morrowind.esm", "Tamriel_Data.esm"]
+fallback-archives = ["Morrowind.bsa"]
+
+[fallback]
+iMaxSpeed = 10
+fScale = 1.0
+Weather_Clear_Sky_Sunrise_Color = "117,141,164"
+"#;
+
+    #[test]
+    fn test_compile_manifest() {
+        let dir = temp_config("manifest", "");
+        std::fs::write(dir.join("modlist.toml"), MANIFEST).unwrap();
+        let output = dir.join("out");
+
+        let manifest = Manifest::read(&dir.join("modlist.toml")).unwrap();
+        let config = manifest.compile(&output).unwrap();
+
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tamriel_Data.esm"]
+        );
+        assert_eq!(
+            config.data_directories(),
+            vec![
+                &dir.join("Data Files"),
+                &dir.join("mods").join("Tamriel & Friends")
+            ]
+        );
+        assert_eq!(config.game_setting_int("iMaxSpeed").unwrap(), Some(10));
+        assert_eq!(config.game_setting_float("fScale").unwrap(), Some(1.0));
+        assert_eq!(
+            OpenMWConfiguration::new(Some(output)).unwrap().to_string(),
+            config.to_string()
+        );
+    }
+
+    #[test]
+    fn test_bad_manifest_leaves_existing_config() {
+        assert!(matches!(
+            Manifest::from_toml("plugins = [\"Morrowind.esm\"]"),
+            Err(ConfigError::InvalidManifest { .. })
+        ));
+
+        let dir = temp_config("manifest_bad", "content=Morrowind.esm\n");
+        let manifest = Manifest::from_toml("content = [\"A.esp\", \"A.esp\"]").unwrap();
+
+        assert!(manifest.compile(&dir).is_err());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("openmw.cfg")).unwrap(),
+            "content=Morrowind.esm\n"
+        );
+    }
+
+    #[cfg(feature = "manifest-yaml")]
+    #[test]
+    fn test_yaml_manifest() {
+        let manifest =
+            Manifest::from_yaml("content:\n  - Morrowind.esm\nfallback:\n  iMaxSpeed: 10\n")
+                .unwrap();
+
+        assert_eq!(manifest.content, vec!["Morrowind.esm"]);
+        assert_eq!(manifest.fallback["iMaxSpeed"], FallbackValue::Int(10));
+    }
+}
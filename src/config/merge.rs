@@ -0,0 +1,653 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+    ConfigError, GameSetting, SettingHandle, bail_config,
+    config::{
+        DirectorySetting, EncodingSetting, GameSettingType, OpenMWConfiguration, SettingValue,
+    },
+};
+
+/// How a single merge conflict should be settled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep what the configuration being merged *into* already has
+    Left,
+    /// Take the value from the configuration being merged in
+    Right,
+    /// Use something else entirely, written as it would appear on the right-hand side of an openmw.cfg line.
+    /// For `ContentOrder` conflicts, this is instead the desired (zero-based) load order position.
+    Custom(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// user-data, data-local, resources, or encoding were set to different values
+    Singleton,
+    /// The same fallback= key with two different values
+    GameSetting,
+    /// A content file enabled by both sides, but in a different order relative to the other plugins both sides enable.
+    /// Positions only count those shared plugins, so plugins which only one side has don't cause conflicts.
+    ContentOrder { left: usize, right: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    kind: ConflictKind,
+    key: String,
+    left: SettingValue,
+    right: SettingValue,
+    resolution: Option<Resolution>,
+}
+
+impl Conflict {
+    pub fn kind(&self) -> &ConflictKind {
+        &self.kind
+    }
+
+    /// The openmw.cfg key for singletons, the fallback name for game settings, or the plugin name for content files
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn left(&self) -> &SettingValue {
+        &self.left
+    }
+
+    pub fn right(&self) -> &SettingValue {
+        &self.right
+    }
+
+    pub fn resolution(&self) -> Option<&Resolution> {
+        self.resolution.as_ref()
+    }
+
+    pub fn resolve(&mut self, resolution: Resolution) {
+        self.resolution = Some(resolution);
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.resolution.is_some()
+    }
+}
+
+/// Everything `merge` could not decide on by itself.
+/// Iterate it, `resolve` each conflict however the user chooses, and then `apply` it back onto the configuration.
+/// Anything left unresolved at that point keeps its left-hand value.
+#[derive(Debug, Clone, Default)]
+pub struct ConflictSet {
+    conflicts: Vec<Conflict>,
+    /// The plugins both sides enable, in the order the right side has them
+    right_content_order: Vec<String>,
+}
+
+impl ConflictSet {
+    pub fn is_empty(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.conflicts.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Conflict> {
+        self.conflicts.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Conflict> {
+        self.conflicts.iter_mut()
+    }
+
+    pub fn unresolved(&self) -> impl Iterator<Item = &Conflict> {
+        self.conflicts
+            .iter()
+            .filter(|conflict| !conflict.is_resolved())
+    }
+
+    /// Settle every conflict which hasn't been dealt with yet the same way
+    pub fn resolve_remaining(&mut self, resolution: Resolution) {
+        self.conflicts
+            .iter_mut()
+            .filter(|conflict| !conflict.is_resolved())
+            .for_each(|conflict| conflict.resolve(resolution.clone()));
+    }
+
+    /// Writes the chosen resolutions into the configuration which produced this set.
+    /// Resolutions are validated one at a time, so an invalid custom value stops the process partway through.
+    /// Load order conflicts are settled together at the end, so the result doesn't depend on which order they were resolved in.
+    pub fn apply(self, config: &mut OpenMWConfiguration) -> Result<(), ConfigError> {
+        let (content_order, conflicts): (Vec<Conflict>, Vec<Conflict>) = self
+            .conflicts
+            .into_iter()
+            .partition(|conflict| matches!(conflict.kind, ConflictKind::ContentOrder { .. }));
+
+        conflicts
+            .into_iter()
+            .try_for_each(|conflict| config.apply_resolution(conflict))?;
+        config.apply_content_order(content_order, &self.right_content_order)
+    }
+}
+
+impl<'a> IntoIterator for &'a ConflictSet {
+    type Item = &'a Conflict;
+    type IntoIter = std::slice::Iter<'a, Conflict>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut ConflictSet {
+    type Item = &'a mut Conflict;
+    type IntoIter = std::slice::IterMut<'a, Conflict>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+fn singleton_key(setting: &SettingValue) -> Option<&'static str> {
+    match setting {
+        SettingValue::UserData(_) => Some("user-data"),
+        SettingValue::DataLocal(_) => Some("data-local"),
+        SettingValue::Resources(_) => Some("resources"),
+        SettingValue::Encoding(_) => Some("encoding"),
+        _ => None,
+    }
+}
+
+fn content_name(setting: &SettingValue) -> Option<&String> {
+    match setting {
+        SettingValue::ContentFile(plugin) => Some(plugin.value()),
+        _ => None,
+    }
+}
+
+/// Indices of a longest increasing run in `values`, which needn't be contiguous
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // `tails[len]` is the index of the smallest value ending an increasing run of `len + 1`
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+
+    for (index, value) in values.iter().enumerate() {
+        let len = tails.partition_point(|&tail| values[tail] < *value);
+        previous[index] = len.checked_sub(1).map(|before| tails[before]);
+
+        match tails.get_mut(len) {
+            Some(tail) => *tail = index,
+            None => tails.push(index),
+        }
+    }
+
+    let mut run = Vec::new();
+    let mut current = tails.last().copied();
+    while let Some(index) = current {
+        run.push(index);
+        current = previous[index];
+    }
+    run.reverse();
+    run
+}
+
+fn same_value(left: &SettingValue, right: &SettingValue) -> bool {
+    match (left, right) {
+        (SettingValue::UserData(l), SettingValue::UserData(r))
+        | (SettingValue::DataLocal(l), SettingValue::DataLocal(r))
+        | (SettingValue::Resources(l), SettingValue::Resources(r)) => l.parsed() == r.parsed(),
+        (SettingValue::Encoding(l), SettingValue::Encoding(r)) => l == r,
+        (SettingValue::GameSetting(l), SettingValue::GameSetting(r)) => l.value() == r.value(),
+        _ => false,
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Folds another configuration into this one.
+    /// Anything the other configuration has which this one doesn't is appended to the user config straight away,
+    /// In the order the other configuration defines it.
+    /// Where both sides disagree (a singleton or fallback set differently, or plugins in a different order),
+    /// Nothing is changed and a `Conflict` is recorded instead, to be settled through the returned `ConflictSet`.
+    /// Only the fewest plugins needed to explain a different load order are reported, keeping the rest where they are.
+    ///
+    /// Sub-configurations of the other configuration are not merged, only the settings they contributed.
+    pub fn merge(&mut self, other: &OpenMWConfiguration) -> ConflictSet {
+        let mut conflicts = Vec::new();
+        let mut right_content_order = Vec::new();
+
+        for setting in other.settings.iter() {
+            let left = match setting {
+                SettingValue::SubConfiguration(_) | SettingValue::Disabled(_) => continue,
+                SettingValue::DataDirectory(dir) => {
+                    if !self
+                        .data_directories_iter()
+                        .any(|existing| existing.parsed() == dir.parsed())
+                    {
+                        self.push_setting(self.adopt(setting));
+                    }
+                    continue;
+                }
+                SettingValue::ContentFile(plugin) => {
+                    let shared = self.content_files_iter().any(|existing| existing == plugin);
+                    match shared {
+                        true => right_content_order.push(setting),
+                        false => self.push_setting(self.adopt(setting)),
+                    }
+                    continue;
+                }
+                SettingValue::Groundcover(grass) => {
+                    if !self.has_groundcover_file(grass.value()) {
+                        self.push_setting(self.adopt(setting));
+                    }
+                    continue;
+                }
+                SettingValue::BethArchive(archive) => {
+                    if !self.has_archive_file(archive.value()) {
+                        self.push_setting(self.adopt(setting));
+                    }
+                    continue;
+                }
+                SettingValue::Generic(generic) => {
+                    let exists = self.settings.iter().any(|existing| match existing {
+                        SettingValue::Generic(existing) => {
+                            existing.key() == generic.key() && existing.value() == generic.value()
+                        }
+                        _ => false,
+                    });
+
+                    if !exists {
+                        self.push_setting(self.adopt(setting));
+                    }
+                    continue;
+                }
+                SettingValue::Flag(flag) => {
+                    if !self.has_flag(flag.key()) {
+                        self.push_setting(self.adopt(setting));
+                    }
+                    continue;
+                }
+                SettingValue::GameSetting(game_setting) => {
+                    // Only the effective definition on the right matters
+                    if other
+                        .get_game_setting(game_setting.key())
+                        .is_some_and(|effective| effective.meta().handle() != setting.handle())
+                    {
+                        continue;
+                    }
+
+                    self.get_game_setting(game_setting.key())
+                        .cloned()
+                        .map(SettingValue::GameSetting)
+                }
+                SettingValue::UserData(_)
+                | SettingValue::DataLocal(_)
+                | SettingValue::Resources(_)
+                | SettingValue::Encoding(_) => {
+                    let effective = |config: &OpenMWConfiguration| {
+                        config
+                            .settings
+                            .iter()
+                            .rev()
+                            .find(|s| std::mem::discriminant(*s) == std::mem::discriminant(setting))
+                            .cloned()
+                    };
+
+                    if effective(other).is_some_and(|s| s.handle() != setting.handle()) {
+                        continue;
+                    }
+
+                    effective(self)
+                }
+            };
+
+            match left {
+                None => {
+                    let adopted = self.adopt(setting);
+                    self.push_setting(adopted);
+                }
+                Some(left) if !same_value(&left, setting) => {
+                    let (kind, key) = match setting {
+                        SettingValue::GameSetting(game_setting) => {
+                            (ConflictKind::GameSetting, game_setting.key().to_owned())
+                        }
+                        _ => (
+                            ConflictKind::Singleton,
+                            singleton_key(setting).unwrap_or_default().to_owned(),
+                        ),
+                    };
+
+                    conflicts.push(Conflict {
+                        kind,
+                        key,
+                        left,
+                        right: setting.clone(),
+                        resolution: None,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        conflicts.extend(self.content_order_conflicts(&right_content_order));
+
+        ConflictSet {
+            conflicts,
+            right_content_order: right_content_order
+                .iter()
+                .filter_map(|setting| content_name(setting))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The plugins both sides enable which would have to move for this side to load them in the same order as the other.
+    /// That's everything outside of the longest run both sides agree on.
+    fn content_order_conflicts(&self, right_order: &[&SettingValue]) -> Vec<Conflict> {
+        let shared: Vec<(&SettingValue, usize)> = self
+            .settings
+            .iter()
+            .filter_map(|setting| {
+                let name = content_name(setting)?;
+                let right = right_order
+                    .iter()
+                    .position(|right| content_name(right) == Some(name))?;
+                Some((setting, right))
+            })
+            .collect();
+
+        let in_order = longest_increasing(
+            &shared
+                .iter()
+                .map(|(_, right)| *right)
+                .collect::<Vec<usize>>(),
+        );
+
+        shared
+            .iter()
+            .enumerate()
+            .filter(|(left, _)| !in_order.contains(left))
+            .map(|(left, (setting, right))| Conflict {
+                kind: ConflictKind::ContentOrder {
+                    left,
+                    right: *right,
+                },
+                key: content_name(setting).cloned().unwrap_or_default(),
+                left: (*setting).clone(),
+                right: right_order[*right].clone(),
+                resolution: None,
+            })
+            .collect()
+    }
+
+    /// Re-homes a setting from another configuration into this one's user config,
+    /// With a fresh handle so it can't be mistaken for the original.
+    /// Directories are re-based on their resolved path, as their original form may have been relative to another openmw.cfg.
+    fn adopt(&self, setting: &SettingValue) -> SettingValue {
+        let user_dir = self.user_config_path();
+        let rebase = |dir: &DirectorySetting| {
+            DirectorySetting::from_path(dir.parsed(), user_dir.clone())
+                .with_comment(Some(&dir.meta().comment))
+        };
+
+        let mut adopted = match setting {
+            SettingValue::DataDirectory(dir) => SettingValue::DataDirectory(rebase(dir)),
+            SettingValue::UserData(dir) => SettingValue::UserData(rebase(dir)),
+            SettingValue::DataLocal(dir) => SettingValue::DataLocal(rebase(dir)),
+            SettingValue::Resources(dir) => SettingValue::Resources(rebase(dir)),
+            SettingValue::SubConfiguration(dir) => SettingValue::SubConfiguration(rebase(dir)),
+            _ => {
+                let mut adopted = setting.clone();
+                adopted.meta_mut().source_config = user_dir.join("openmw.cfg");
+                adopted
+            }
+        };
+
+        adopted.meta_mut().handle = SettingHandle::next();
+        adopted
+    }
+
+    /// Settles every load order conflict at once, so they can't undo each other's moves.
+    /// Plugins resolved `Right` go after whichever plugin comes before them on the right-hand side,
+    /// And plugins given a position are put there once everything else is in place.
+    fn apply_content_order(
+        &mut self,
+        conflicts: Vec<Conflict>,
+        right_order: &[String],
+    ) -> Result<(), ConfigError> {
+        let mut order: Vec<String> = self.content_files().into_iter().cloned().collect();
+        let mut to_right = Vec::new();
+        let mut to_position = Vec::new();
+
+        for conflict in conflicts {
+            // Plugins disabled since the merge have nowhere to go
+            if !order.contains(&conflict.key) {
+                continue;
+            }
+
+            match conflict.resolution {
+                None | Some(Resolution::Left) => {}
+                Some(Resolution::Right) => to_right.push(conflict.key),
+                Some(Resolution::Custom(position)) => match position.parse::<usize>() {
+                    Ok(position) => to_position.push((position, conflict.key)),
+                    Err(_) => bail_config!(invalid_merge_resolution, conflict.key, position),
+                },
+            }
+        }
+
+        order.retain(|plugin| {
+            !to_right.contains(plugin) && !to_position.iter().any(|(_, moved)| moved == plugin)
+        });
+
+        for (right, plugin) in right_order.iter().enumerate() {
+            if !to_right.contains(plugin) {
+                continue;
+            }
+
+            let index = match right_order[..right]
+                .iter()
+                .rev()
+                .find_map(|before| order.iter().position(|placed| placed == before))
+            {
+                Some(before) => before + 1,
+                None => order
+                    .iter()
+                    .position(|placed| right_order.contains(placed))
+                    .unwrap_or(order.len()),
+            };
+            order.insert(index, plugin.clone());
+        }
+
+        to_position.sort_by_key(|(position, _)| *position);
+        for (position, plugin) in to_position {
+            order.insert(position.min(order.len()), plugin);
+        }
+
+        for (position, plugin) in order.iter().enumerate() {
+            if self
+                .content_files_iter()
+                .position(|existing| existing.value() == plugin)
+                == Some(position)
+            {
+                continue;
+            }
+
+            if let Some(index) = self
+                .settings
+                .iter()
+                .position(|setting| content_name(setting) == Some(plugin))
+            {
+                self.move_content_file(index, position);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_resolution(&mut self, conflict: Conflict) -> Result<(), ConfigError> {
+        let Some(index) = self
+            .settings
+            .iter()
+            .position(|setting| setting.handle() == conflict.left.handle())
+        else {
+            // The left side was removed since the merge, so there's nothing to resolve against anymore
+            return Ok(());
+        };
+
+        let mut comment = conflict.left.meta().comment.clone();
+
+        let replacement = match (&conflict.kind, conflict.resolution) {
+            // Settled all together by `apply_content_order`
+            (ConflictKind::ContentOrder { .. }, _) | (_, None | Some(Resolution::Left)) => {
+                return Ok(());
+            }
+            (_, Some(Resolution::Right)) => self.adopt(&conflict.right),
+            (ConflictKind::GameSetting, Some(Resolution::Custom(value))) => {
+                SettingValue::GameSetting(GameSettingType::try_from((
+                    format!("{},{value}", conflict.key),
+                    self.user_config_path().join("openmw.cfg"),
+                    &mut comment,
+                ))?)
+            }
+            (ConflictKind::Singleton, Some(Resolution::Custom(value))) => {
+                let user_dir = self.user_config_path();
+
+                match conflict.left {
+                    SettingValue::Encoding(_) => SettingValue::Encoding(EncodingSetting::try_from(
+                        (value, user_dir.join("openmw.cfg"), &mut comment),
+                    )?),
+                    SettingValue::UserData(_) => SettingValue::UserData(
+                        DirectorySetting::with_queued_comment(value, user_dir, &mut comment),
+                    ),
+                    SettingValue::DataLocal(_) => SettingValue::DataLocal(
+                        DirectorySetting::with_queued_comment(value, user_dir, &mut comment),
+                    ),
+                    SettingValue::Resources(_) => SettingValue::Resources(
+                        DirectorySetting::with_queued_comment(value, user_dir, &mut comment),
+                    ),
+                    _ => bail_config!(invalid_merge_resolution, conflict.key, value),
+                }
+            }
+        };
+
+        self.replace_setting_at(index, replacement);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    fn merge_pair(name: &str, left: &str, right: &str) -> (OpenMWConfiguration, ConflictSet) {
+        let left = temp_config(&format!("{name}_left"), left);
+        let right = temp_config(&format!("{name}_right"), right);
+
+        let mut left = OpenMWConfiguration::new(Some(left)).unwrap();
+        let right = OpenMWConfiguration::new(Some(right)).unwrap();
+
+        let conflicts = left.merge(&right);
+        (left, conflicts)
+    }
+
+    #[test]
+    fn test_merge_without_conflicts_appends() {
+        let (config, conflicts) = merge_pair(
+            "merge_clean",
+            "content=Morrowind.esm\nfallback-archive=Morrowind.bsa\n",
+            "content=Morrowind.esm\ncontent=Extra.esp\nfallback-archive=Extra.bsa\n",
+        );
+
+        assert!(conflicts.is_empty());
+        assert_eq!(config.content_files(), vec!["Morrowind.esm", "Extra.esp"]);
+        assert_eq!(
+            config.fallback_archives(),
+            vec!["Morrowind.bsa", "Extra.bsa"]
+        );
+    }
+
+    #[test]
+    fn test_merge_reports_and_resolves_conflicts() {
+        let (mut config, mut conflicts) = merge_pair(
+            "merge_conflicts",
+            "encoding=win1252\nfallback=Key_A,1\nfallback=Key_B,2\ncontent=One.esp\ncontent=Two.esp\n",
+            "encoding=win1250\nfallback=Key_A,5\nfallback=Key_B,6\ncontent=Two.esp\ncontent=One.esp\n",
+        );
+
+        assert_eq!(conflicts.len(), 4);
+        assert_eq!(
+            conflicts
+                .iter()
+                .filter(|c| matches!(c.kind(), ConflictKind::ContentOrder { .. }))
+                .count(),
+            1
+        );
+
+        for conflict in &mut conflicts {
+            match (conflict.kind(), conflict.key()) {
+                (ConflictKind::Singleton, "encoding") => conflict.resolve(Resolution::Right),
+                (ConflictKind::GameSetting, "Key_A") => {
+                    conflict.resolve(Resolution::Custom("9".into()))
+                }
+                (ConflictKind::ContentOrder { .. }, "One.esp") => {
+                    conflict.resolve(Resolution::Right)
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(conflicts.unresolved().count(), 1);
+        conflicts.apply(&mut config).unwrap();
+
+        assert_eq!(
+            config.encoding().unwrap().to_string().trim(),
+            "encoding=win1250"
+        );
+        assert_eq!(config.get_game_setting("Key_A").unwrap().value(), "9");
+        assert_eq!(config.get_game_setting("Key_B").unwrap().value(), "2");
+        assert_eq!(config.content_files(), vec!["Two.esp", "One.esp"]);
+    }
+
+    #[test]
+    fn test_merge_content_order() {
+        let (config, conflicts) = merge_pair(
+            "merge_inserted",
+            "content=A.esp\ncontent=B.esp\ncontent=C.esp\n",
+            "content=A.esp\ncontent=X.esp\ncontent=B.esp\ncontent=C.esp\n",
+        );
+        assert!(conflicts.is_empty());
+        assert_eq!(config.content_files(), ["A.esp", "B.esp", "C.esp", "X.esp"]);
+
+        let (mut config, mut conflicts) = merge_pair(
+            "merge_reversed",
+            "content=A.esp\ncontent=B.esp\ncontent=Mine.esp\ncontent=C.esp\ncontent=D.esp\n",
+            "content=D.esp\ncontent=C.esp\ncontent=B.esp\ncontent=A.esp\n",
+        );
+        assert_eq!(conflicts.len(), 3);
+
+        // D.esp stays put, so the plugin only this side has stays ahead of it
+        conflicts.resolve_remaining(Resolution::Right);
+        conflicts.apply(&mut config).unwrap();
+        assert_eq!(
+            config.content_files(),
+            ["Mine.esp", "D.esp", "C.esp", "B.esp", "A.esp"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_custom_content_position() {
+        let (mut config, mut conflicts) = merge_pair(
+            "merge_bad_position",
+            "content=One.esp\ncontent=Two.esp\n",
+            "content=Two.esp\ncontent=One.esp\n",
+        );
+
+        conflicts.resolve_remaining(Resolution::Custom("first".into()));
+
+        assert!(matches!(
+            conflicts.apply(&mut config),
+            Err(ConfigError::InvalidMergeResolution { .. })
+        ));
+    }
+}
@@ -0,0 +1,266 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Every user-facing string the crate produces, keyed by a stable message ID.
+//! English templates use Fluent placeable syntax, so `english_ftl` doubles as the reference translation.
+
+use std::fmt;
+
+macro_rules! message_catalog {
+    ($($variant:ident => $id:literal, $template:literal;)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum MessageId {
+            $($variant,)*
+        }
+
+        impl MessageId {
+            pub const ALL: &'static [MessageId] = &[$(MessageId::$variant,)*];
+
+            /// The Fluent message ID, which is stable across releases
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(MessageId::$variant => $id,)*
+                }
+            }
+
+            pub fn english(&self) -> &'static str {
+                match self {
+                    $(MessageId::$variant => $template,)*
+                }
+            }
+        }
+    };
+}
+
+message_catalog! {
+    InvalidGameSetting => "error-invalid-game-setting", "Invalid fallback setting '{ $value }' in config file '{ $config_path }'";
+    Io => "error-io", "IO error: { $error }";
+    NotFileOrDirectory => "error-not-file-or-directory", "Unable to determine whether { $config_path } was a file or directory, refusing to read.";
+    CannotFind => "error-cannot-find", "An openmw.cfg does not exist at: { $config_path }";
+    DuplicateContentFile => "error-duplicate-content-file", "{ $file } has appeared in the content files list twice. Its second occurence was in: { $config_path }";
+    CannotAddContentFile => "error-cannot-add-content-file", "{ $file } cannot be added to the configuration map as a content file because it was already defined by: { $config_path }";
+    DuplicateGroundcoverFile => "error-duplicate-groundcover-file", "{ $file } has appeared in the groundcover list twice. Its second occurence was in: { $config_path }";
+    CannotAddGroundcoverFile => "error-cannot-add-groundcover-file", "{ $file } cannot be added to the configuration map as a groundcover plugin because it was already defined by: { $config_path }";
+    DuplicateArchiveFile => "error-duplicate-archive-file", "{ $file } has appeared in the BSA/Archive list twice. Its second occurence was in: { $config_path }";
+    CannotAddArchiveFile => "error-cannot-add-archive-file", "{ $file } cannot be added to the configuration map as a fallback-archive because it was already defined by: { $config_path }";
+    BadEncoding => "error-bad-encoding", "Invalid encoding type: { $value } in config file { $config_path }";
+    InvalidCliArgument => "error-invalid-cli-argument", "Command line argument { $value } is not attached to any option";
+    InvalidMergeResolution => "error-invalid-merge-resolution", "{ $value } is not a valid way to resolve the merge conflict for { $key }";
+    ArchiveNotFound => "error-archive-not-found", "Archive { $archive } was not found in any data directory";
+    InvalidArchive => "error-invalid-archive", "Unable to read archive { $path }: { $reason }";
+    FixNotApplicable => "error-fix-not-applicable", "Fix { $fix } no longer applies to this configuration";
+    InvalidLocalization => "error-invalid-localization", "Unable to load translations: { $reason }";
+    InvalidUtf8 => "error-invalid-utf8", "{ $path } is not valid UTF-8 (first bad byte on line { $line }). It may have been saved in a legacy Windows encoding";
+    PluginNotFound => "error-plugin-not-found", "Plugin { $plugin } was not found in any data directory";
+    InvalidPlugin => "error-invalid-plugin", "Unable to read the header of plugin { $path }: { $reason }";
+    GameSettingTypeMismatch => "error-game-setting-type-mismatch", "fallback={ $key } holds a { $expected }, which { $value } is not";
+    GameSettingWrongType => "error-game-setting-wrong-type", "fallback={ $key } holds a { $actual }, not a { $requested }";
+    UnknownGameSetting => "error-unknown-game-setting", "There is no fallback={ $key } to change";
+    SubConfigExists => "error-sub-config-exists", "{ $path } already contains an openmw.cfg, refusing to overwrite it";
+    ConfigCycle => "error-config-cycle", "config= entries form a loop: { $chain }";
+    ConfigChainTooDeep => "error-config-chain-too-deep", "Refusing to load { $path }, config= entries are nested more than { $max } deep";
+    InvalidManifest => "error-invalid-manifest", "Invalid manifest { $path }: { $reason }";
+    ReadOnly => "error-read-only", "The configuration was opened read-only, refusing to write { $path }";
+    InvalidSave => "error-invalid-save", "Unable to read the header of save { $path }: { $reason }";
+    InvalidInputBindings => "error-invalid-input-bindings", "Invalid input bindings { $path }: { $reason }";
+    InvalidLauncherCfg => "error-invalid-launcher-cfg", "Invalid launcher.cfg { $path }: { $reason }";
+    NotAGameFile => "error-not-a-game-file", "{ $file } is not a game file, only .omwgame and .esm files can be";
+    MultipleGameFiles => "error-multiple-game-files", "Only one game file can be enabled, but { $files } all are";
+    GameFileInUse => "error-game-file-in-use", "Replacing { $game_file } would break { $dependents }, which need it";
+    UnknownEncoding => "error-unknown-encoding", "Unknown encoding { $value }, expected win1250, win1251 or win1252";
+    SettingKindChanged => "error-setting-kind-changed", "A { $from } entry can't be changed into a { $to } entry in place";
+    OutsideModsPath => "error-outside-mods-path", "{ $path } is not inside the mods folder, refusing to delete it";
+    InvalidLine => "error-invalid-line", "Invalid pair in openmw.cfg { $value } was defined by { $config_path }";
+
+    MissingDataDir => "issue-missing-data-dir", "Data directory { $path } does not exist";
+    DuplicateDataDir => "issue-duplicate-data-dir", "Data directory { $path } is listed more than once";
+    AliasedDataDir => "issue-aliased-data-dir", "Data directory { $path } is the same directory as { $other }";
+    ShadowedFallback => "issue-shadowed-fallback", "fallback={ $key } is overridden by a later definition";
+    MissingLuaScripts => "issue-missing-lua-scripts", "{ $name } is enabled, but no data directory has it";
+    GroundcoverInContent => "issue-groundcover-in-content", "{ $name } is enabled as both content and groundcover, which crashes the engine";
+    MissingGroundcover => "issue-missing-groundcover", "Groundcover { $name } is enabled, but no data directory has it";
+    NoContent => "issue-no-content", "No content files are enabled, so there is no game to load";
+    WhitespaceInValue => "issue-whitespace-in-value", "'{ $value }' has whitespace around it, which becomes part of the name";
+    WindowsPath => "issue-windows-path", "{ $path } is a Windows path, which can't be found on this system";
+    BackslashPath => "issue-backslash-path", "{ $path } uses backslashes, forward slashes work on every system";
+    CaseDuplicateDataDir => "issue-case-duplicate-data-dir", "Data directories { $path } and { $other } only differ in case";
+    UnusedArchive => "issue-unused-archive", "{ $name } doesn't go with any enabled plugin, it may be left over from a removed mod";
+
+    UnknownKey => "issue-unknown-key", "{ $key } is not a setting OpenMW knows";
+    DidYouMean => "help-did-you-mean", "did you mean { $suggestion }?";
+
+    FixRemoveDataDir => "fix-remove-data-dir", "Remove the data directory";
+    FixRemoveDuplicateDataDir => "fix-remove-duplicate-data-dir", "Remove the lower priority duplicate";
+    FixRemoveShadowedFallback => "fix-remove-shadowed-fallback", "Remove the unused definition";
+    FixRemoveLuaScripts => "fix-remove-lua-scripts", "Disable the script list";
+    FixRemoveGroundcoverContent => "fix-remove-groundcover-content", "Keep it as groundcover only";
+    FixRemoveGroundcover => "fix-remove-groundcover", "Disable the groundcover plugin";
+    FixTrimValue => "fix-trim-value", "Remove the surrounding whitespace";
+    FixForwardSlashes => "fix-forward-slashes", "Use forward slashes";
+    FixRemoveArchive => "fix-remove-archive", "Remove the archive";
+}
+
+/// A message ID along with the values for its placeables.
+/// Displaying it renders the English text; use a `Localizer` (with the `fluent` feature) for anything else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    id: MessageId,
+    args: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    pub fn new(id: MessageId) -> Self {
+        Self {
+            id,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg<S: ToString>(mut self, name: &'static str, value: S) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+
+    pub fn id(&self) -> MessageId {
+        self.id
+    }
+
+    pub fn args(&self) -> &[(&'static str, String)] {
+        &self.args
+    }
+
+    /// Substitutes arguments into a template written with Fluent's `{ $name }` syntax
+    pub fn render(&self, template: &str) -> String {
+        self.args
+            .iter()
+            .fold(template.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{ ${name} }}"), value)
+            })
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(self.id.english()))
+    }
+}
+
+/// The whole English catalog as a Fluent resource, for translators to start from
+pub fn english_ftl() -> String {
+    MessageId::ALL
+        .iter()
+        .map(|id| format!("{} = {}\n", id.as_str(), id.english()))
+        .collect()
+}
+
+#[cfg(feature = "fluent")]
+pub use localizer::Localizer;
+
+#[cfg(feature = "fluent")]
+mod localizer {
+    use fluent_bundle::{FluentArgs, FluentBundle, FluentResource};
+
+    use super::{Message, english_ftl};
+    use crate::{ConfigError, bail_config};
+
+    /// Renders messages using a Fluent translation, falling back to English for anything it doesn't cover
+    pub struct Localizer {
+        bundle: FluentBundle<FluentResource>,
+    }
+
+    impl Localizer {
+        /// `locale` is a language tag such as `de-DE`, and `ftl` the contents of a Fluent resource using the IDs from `english_ftl`
+        pub fn new(locale: &str, ftl: String) -> Result<Self, ConfigError> {
+            let Ok(language) = locale.parse() else {
+                bail_config!(
+                    invalid_localization,
+                    format!("{locale} is not a language tag")
+                )
+            };
+
+            let resource = match FluentResource::try_new(ftl) {
+                Ok(resource) => resource,
+                Err((_, errors)) => bail_config!(invalid_localization, format!("{errors:?}")),
+            };
+
+            let mut bundle = FluentBundle::new(vec![language]);
+            bundle.set_use_isolating(false);
+
+            if let Err(errors) = bundle.add_resource(resource) {
+                bail_config!(invalid_localization, format!("{errors:?}"))
+            }
+
+            Ok(Self { bundle })
+        }
+
+        pub fn english() -> Self {
+            Self::new("en-US", english_ftl()).expect("the English catalog is always valid")
+        }
+
+        pub fn format(&self, message: &Message) -> String {
+            let Some(pattern) = self
+                .bundle
+                .get_message(message.id().as_str())
+                .and_then(|translated| translated.value())
+            else {
+                return message.to_string();
+            };
+
+            let mut args = FluentArgs::new();
+            for (name, value) in message.args() {
+                args.set(*name, value.as_str());
+            }
+
+            let mut errors = Vec::new();
+            self.bundle
+                .format_pattern(pattern, Some(&args), &mut errors)
+                .into_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_english_rendering() {
+        let message = Message::new(MessageId::MissingDataDir).arg("path", "/mods/Foo");
+        assert_eq!(
+            message.to_string(),
+            "Data directory /mods/Foo does not exist"
+        );
+    }
+
+    #[test]
+    fn test_ids_are_unique() {
+        let mut ids: Vec<&str> = MessageId::ALL.iter().map(MessageId::as_str).collect();
+        ids.sort();
+        ids.dedup();
+
+        assert_eq!(ids.len(), MessageId::ALL.len());
+        assert_eq!(english_ftl().lines().count(), MessageId::ALL.len());
+    }
+
+    #[cfg(feature = "fluent")]
+    #[test]
+    fn test_localizer_falls_back_to_english() {
+        let localizer = Localizer::new(
+            "de-DE",
+            "issue-missing-data-dir = Datenverzeichnis { $path } existiert nicht\n".into(),
+        )
+        .unwrap();
+
+        let missing = Message::new(MessageId::MissingDataDir).arg("path", "/mods/Foo");
+        assert_eq!(
+            localizer.format(&missing),
+            "Datenverzeichnis /mods/Foo existiert nicht"
+        );
+
+        let untranslated = Message::new(MessageId::NoContent);
+        assert_eq!(localizer.format(&untranslated), untranslated.to_string());
+        assert_eq!(Localizer::english().format(&missing), missing.to_string());
+    }
+}
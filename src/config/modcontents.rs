@@ -0,0 +1,156 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! What a mod folder holds, going by its top level, so tools can tell when it has nothing the engine will load.
+//! MWSE mods are the usual case: they look like any other mod, but everything in them is for the original engine.
+
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+use crate::config::{OpenMWConfiguration, filesetting::ContentKind};
+
+/// Folders the engine looks in for assets, lowercased
+const ASSET_FOLDERS: [&str; 13] = [
+    "bookart", "fonts", "icons", "l10n", "meshes", "music", "scripts", "shaders", "sound",
+    "splash", "textures", "video", "grass",
+];
+
+/// What `scan_mod_contents` found at the top of a data directory.
+/// Names are as they are on disk, sorted ignoring case.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModContents {
+    pub path: PathBuf,
+    /// Content files, including `.omwscripts`
+    pub plugins: Vec<String>,
+    /// `.bsa` files, which still need a `fallback-archive=` entry each
+    pub archives: Vec<String>,
+    /// Folders like `Textures` and `Meshes`
+    pub asset_folders: Vec<String>,
+    /// `.omwfx` files under the shaders folder
+    pub shaders: Vec<String>,
+    /// An `MWSE` folder, which OpenMW ignores
+    pub mwse: bool,
+    /// A `Data Files` folder, meaning the data directory was probably meant to be that instead
+    pub nested_data_files: Option<PathBuf>,
+}
+
+impl ModContents {
+    /// Whether the engine would load anything from here
+    pub fn is_usable(&self) -> bool {
+        !self.plugins.is_empty() || !self.archives.is_empty() || !self.asset_folders.is_empty()
+    }
+
+    /// Only MWSE content and nothing else, the case worth warning about
+    pub fn is_mwse_only(&self) -> bool {
+        self.mwse && !self.is_usable()
+    }
+}
+
+fn sorted_names(dir: &Path, keep: impl Fn(&Path, &str) -> bool) -> Vec<String> {
+    let Ok(entries) = read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|entry| {
+            (
+                entry.path(),
+                entry.file_name().to_string_lossy().into_owned(),
+            )
+        })
+        .filter(|(path, name)| keep(path, name))
+        .map(|(_, name)| name)
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    names
+}
+
+fn has_extension(name: &str, wanted: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(wanted))
+}
+
+/// Looks at the top level of `dir` and the shaders folder, without descending any further
+pub fn scan_mod_contents(dir: &Path) -> ModContents {
+    let plugins = sorted_names(dir, |path, name| {
+        path.is_file() && ContentKind::from_file_name(name) != ContentKind::Unknown
+    });
+    let archives = sorted_names(dir, |path, name| {
+        path.is_file() && has_extension(name, "bsa")
+    });
+    let folders = sorted_names(dir, |path, _| path.is_dir());
+
+    let folder = |wanted: &str| {
+        folders
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(wanted))
+    };
+
+    let shaders = folder("shaders")
+        .map(|shaders| sorted_names(&dir.join(shaders), |_, name| has_extension(name, "omwfx")))
+        .unwrap_or_default();
+
+    ModContents {
+        path: dir.to_path_buf(),
+        plugins,
+        archives,
+        asset_folders: folders
+            .iter()
+            .filter(|name| ASSET_FOLDERS.contains(&name.to_lowercase().as_str()))
+            .cloned()
+            .collect(),
+        shaders,
+        mwse: folder("mwse").is_some(),
+        nested_data_files: folder("data files").map(|name| dir.join(name)),
+    }
+}
+
+impl OpenMWConfiguration {
+    /// `add_data_directory`, which also reports what the directory holds.
+    /// It's added either way, so it's up to the caller to warn about (or undo) one that isn't `is_usable`.
+    pub fn add_data_directory_scanned(&mut self, dir: PathBuf) -> ModContents {
+        let contents = scan_mod_contents(&dir);
+        self.add_data_directory(dir);
+        contents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_scan_mod_contents() {
+        let root = temp_config("scan_mod_contents", "");
+        let textures = root.join("Textures Mod");
+        std::fs::create_dir_all(textures.join("Textures")).unwrap();
+        std::fs::create_dir_all(textures.join("Shaders")).unwrap();
+        std::fs::write(textures.join("Shaders/clouds.omwfx"), "").unwrap();
+        std::fs::write(textures.join("Tex.BSA"), "").unwrap();
+        std::fs::write(textures.join("tex.esp"), "").unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        let contents = config.add_data_directory_scanned(textures.clone());
+        assert_eq!(contents.plugins, ["tex.esp"]);
+        assert_eq!(contents.archives, ["Tex.BSA"]);
+        assert_eq!(contents.asset_folders, ["Shaders", "Textures"]);
+        assert_eq!(contents.shaders, ["clouds.omwfx"]);
+        assert!(contents.is_usable());
+        assert_eq!(config.data_directories(), [&textures]);
+
+        let mwse = root.join("MWSE Mod");
+        std::fs::create_dir_all(mwse.join("MWSE/mods")).unwrap();
+        std::fs::create_dir_all(mwse.join("Data Files")).unwrap();
+
+        let contents = scan_mod_contents(&mwse);
+        assert!(contents.is_mwse_only());
+        assert_eq!(contents.nested_data_files, Some(mwse.join("Data Files")));
+    }
+}
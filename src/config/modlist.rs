@@ -0,0 +1,308 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Modlists for sharing a setup with someone else, who keeps their mods somewhere else entirely.
+//! Unlike a `Manifest`, nothing in one is an absolute path: data directories are named by their folder under the mods root,
+//! And plugins carry hashes so the other side can tell whether they got the same files.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ConfigError, GameSetting,
+    config::{
+        OpenMWConfiguration, SettingValue,
+        encodingsetting::EncodingType,
+        equality::{FNV_OFFSET, fnv1a},
+        filesetting::FileSetting,
+    },
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Modlist {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// Folder names, relative to the mods root where they were under it, in priority order
+    #[serde(default)]
+    pub data: Vec<String>,
+    #[serde(default)]
+    pub content: Vec<String>,
+    #[serde(default)]
+    pub groundcover: Vec<String>,
+    #[serde(default)]
+    pub fallback_archives: Vec<String>,
+    /// Fallbacks set in the user openmw.cfg, on top of whatever the game's Morrowind.ini provides
+    #[serde(default)]
+    pub fallback: BTreeMap<String, String>,
+    /// FNV-1a hashes of the plugin files, by plugin name
+    #[serde(default)]
+    pub hashes: BTreeMap<String, String>,
+}
+
+impl Modlist {
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(|error| {
+            crate::config_err!(invalid_manifest, Path::new("<modlist>"), error.message())
+        })
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("a modlist only holds strings")
+    }
+}
+
+/// What `apply_modlist` couldn't reproduce
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModlistReport {
+    /// Data directories which aren't in the mods root or the configuration, and were left out
+    pub missing_data: Vec<String>,
+    /// Plugins which no data directory has. They're still enabled, like in the modlist.
+    pub missing_plugins: Vec<String>,
+    /// Plugins which were found, but aren't the same file the modlist was made with
+    pub changed_plugins: Vec<String>,
+}
+
+impl ModlistReport {
+    pub fn is_complete(&self) -> bool {
+        self.missing_data.is_empty()
+            && self.missing_plugins.is_empty()
+            && self.changed_plugins.is_empty()
+    }
+}
+
+/// Hashes a file a chunk at a time, since plugins can be hundreds of megabytes
+fn hash_file(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut hash = FNV_OFFSET;
+
+    loop {
+        match file.read(&mut buffer).ok()? {
+            0 => return Some(format!("{hash:016x}")),
+            read => hash = fnv1a(hash, buffer[..read].iter().copied()),
+        }
+    }
+}
+
+fn names<'a>(files: impl Iterator<Item = &'a FileSetting>) -> Vec<String> {
+    files.map(|file| file.value().clone()).collect()
+}
+
+impl OpenMWConfiguration {
+    /// Describes the current setup in a way that works on another machine.
+    /// Data directories inside `mods_path` keep their path under it, and every other one is named by its folder alone.
+    pub fn export_modlist(&self) -> Modlist {
+        let mods = self.mods_path();
+        let user_config = self.user_config_path();
+        let index = self.data_file_index();
+
+        let content = names(self.content_files_iter());
+        let groundcover = names(self.groundcover_iter());
+
+        Modlist {
+            encoding: self
+                .encoding_type()
+                .map(|encoding| encoding.as_str().to_string()),
+            data: self
+                .data_directories()
+                .into_iter()
+                .map(|dir| {
+                    let relative = dir
+                        .strip_prefix(&mods)
+                        .ok()
+                        .filter(|relative| !relative.as_os_str().is_empty())
+                        .or_else(|| dir.file_name().map(Path::new))
+                        .unwrap_or(dir);
+                    relative.to_string_lossy().replace('\\', "/")
+                })
+                .collect(),
+            fallback_archives: names(self.fallback_archives_iter()),
+            fallback: self
+                .game_settings()
+                .filter(|setting| setting.meta().is_from(&user_config))
+                .map(|setting| (setting.key().clone(), setting.value()))
+                .collect(),
+            hashes: content
+                .iter()
+                .chain(&groundcover)
+                .filter_map(|plugin| {
+                    let path = index.get(&plugin.to_ascii_lowercase())?;
+                    Some((plugin.clone(), hash_file(path)?))
+                })
+                .collect(),
+            content,
+            groundcover,
+        }
+    }
+
+    /// Reproduces a modlist, looking for its data directories in `mods_root`.
+    /// Ones which aren't there can still match a data directory already configured with the same folder name, like the game's `Data Files`.
+    /// The data directories, plugins and archives are replaced with the modlist's, and its fallbacks and encoding are set on top.
+    /// Fails without changing anything if a data directory name would lead outside of `mods_root`,
+    /// Or if the modlist can't be applied, like when a fallback has a different type than the configuration's.
+    pub fn apply_modlist(
+        &mut self,
+        modlist: &Modlist,
+        mods_root: &Path,
+    ) -> Result<ModlistReport, ConfigError> {
+        let mut report = ModlistReport::default();
+
+        let configured: Vec<PathBuf> = self.data_directories().into_iter().cloned().collect();
+        let mut data = Vec::new();
+        for name in &modlist.data {
+            if !Path::new(name)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(crate::config_err!(
+                    invalid_manifest,
+                    mods_root,
+                    format!("{name} would be outside of the mods root")
+                ));
+            }
+
+            let in_root = mods_root.join(name);
+            let folder = Path::new(name).file_name().unwrap_or_default();
+            let found = match in_root.is_dir() {
+                true => Some(in_root),
+                false => configured
+                    .iter()
+                    .find(|dir| {
+                        dir.file_name()
+                            .is_some_and(|existing| existing.eq_ignore_ascii_case(folder))
+                    })
+                    .cloned(),
+            };
+
+            match found {
+                Some(dir) => data.push(dir),
+                None => report.missing_data.push(name.clone()),
+            }
+        }
+
+        self.transaction(|config| {
+            if let Some(encoding) = &modlist.encoding {
+                config.set_encoding_type(Some(encoding.parse::<EncodingType>()?));
+            }
+            config.set_data_directories(Some(data));
+            config.set_fallback_archives(Some(modlist.fallback_archives.clone()));
+            config.set_content_files(Some(modlist.content.clone()));
+            config.clear_matching(|setting| matches!(setting, SettingValue::Groundcover(_)));
+            for grass in &modlist.groundcover {
+                config.add_groundcover_file(grass)?;
+            }
+
+            for (key, value) in &modlist.fallback {
+                match config.get_game_setting(key) {
+                    Some(_) => config.set_game_setting_value(key, value)?,
+                    None => config.set_game_setting(
+                        &format!("{key},{value}"),
+                        None,
+                        &mut String::new(),
+                    )?,
+                }
+            }
+
+            Ok::<_, ConfigError>(())
+        })?;
+
+        let index = self.data_file_index();
+        for plugin in modlist.content.iter().chain(&modlist.groundcover) {
+            match index.get(&plugin.to_ascii_lowercase()) {
+                None => report.missing_plugins.push(plugin.clone()),
+                Some(path) => {
+                    if let Some(expected) = modlist.hashes.get(plugin)
+                        && hash_file(path).as_ref() != Some(expected)
+                    {
+                        report.changed_plugins.push(plugin.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_modlist_round_trip() {
+        let source = temp_config(
+            "modlist_source",
+            "user-data=Profile\ndata=\"Data Files\"\ndata=Profile/mods/Armor/Data\ncontent=Base.esm\ncontent=Armor.esp\nfallback=Water_Map_Alpha,0.4\nencoding=win1251\n",
+        );
+        for (dir, plugin) in [
+            ("Data Files", "Base.esm"),
+            ("Profile/mods/Armor/Data", "Armor.esp"),
+        ] {
+            std::fs::create_dir_all(source.join(dir)).unwrap();
+            std::fs::write(source.join(dir).join(plugin), plugin).unwrap();
+        }
+
+        let config = OpenMWConfiguration::new(Some(source.clone())).unwrap();
+        let modlist = config.export_modlist();
+        assert_eq!(modlist.data, ["Data Files", "Armor/Data"]);
+        assert_eq!(modlist.fallback["Water_Map_Alpha"], "0.4");
+        assert_eq!(modlist.hashes.len(), 2);
+        assert_eq!(Modlist::from_toml(&modlist.to_toml()).unwrap(), modlist);
+
+        let target = temp_config("modlist_target", "data=\"Data Files\"\n");
+        let mods = target.join("mods");
+        std::fs::create_dir_all(target.join("Data Files")).unwrap();
+        std::fs::write(target.join("Data Files/Base.esm"), "Base.esm").unwrap();
+        std::fs::create_dir_all(mods.join("Armor/Data")).unwrap();
+        std::fs::write(mods.join("Armor/Data/Armor.esp"), "a different build").unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(target.clone())).unwrap();
+        let report = config.apply_modlist(&modlist, &mods).unwrap();
+
+        assert_eq!(
+            config.data_directories(),
+            [&target.join("Data Files"), &mods.join("Armor/Data")]
+        );
+        assert_eq!(config.content_files(), ["Base.esm", "Armor.esp"]);
+        assert_eq!(
+            config.game_setting_float("Water_Map_Alpha").unwrap(),
+            Some(0.4)
+        );
+        assert_eq!(report.changed_plugins, ["Armor.esp"]);
+        assert!(report.missing_data.is_empty() && report.missing_plugins.is_empty());
+
+        for escaping in ["../Armor", "/etc"] {
+            let modlist = Modlist {
+                data: vec![escaping.into()],
+                ..Default::default()
+            };
+            assert!(matches!(
+                config.apply_modlist(&modlist, &mods),
+                Err(ConfigError::InvalidManifest { .. })
+            ));
+        }
+        assert_eq!(config.content_files(), ["Base.esm", "Armor.esp"]);
+
+        let before = config.to_string();
+        let mismatched = Modlist {
+            content: vec!["Other.esp".into()],
+            fallback: BTreeMap::from([("Water_Map_Alpha".into(), "opaque".into())]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.apply_modlist(&mismatched, &mods),
+            Err(ConfigError::GameSettingTypeMismatch { .. })
+        ));
+        assert_eq!(config.to_string(), before);
+    }
+}
@@ -0,0 +1,84 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Filesystem work which doesn't depend on load order, done on rayon's thread pool.
+//! Applying settings stays sequential, since every line can depend on the ones before it.
+
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::config::OpenMWConfiguration;
+
+impl OpenMWConfiguration {
+    /// Reads every one of these files at once, so `load` finds them ready instead of reading them one by one
+    pub(crate) fn prefetch(&mut self, paths: Vec<PathBuf>) {
+        let fs = &*self.fs;
+        let read: Vec<_> = paths
+            .into_par_iter()
+            .map(|path| {
+                let bytes = fs.read(&path);
+                (path, bytes)
+            })
+            .collect();
+
+        self.prefetched.extend(read);
+    }
+
+    pub(crate) fn take_prefetched(&mut self, path: &Path) -> Option<std::io::Result<Vec<u8>>> {
+        self.prefetched.remove(path)
+    }
+
+    pub(crate) fn directories_exist(&self, paths: &[&PathBuf]) -> Vec<bool> {
+        paths.par_iter().map(|path| self.fs.is_dir(path)).collect()
+    }
+
+    pub(crate) fn directory_sizes(&self, paths: &[&PathBuf]) -> Vec<u64> {
+        paths
+            .par_iter()
+            .map(|path| crate::config::stats::directory_size(path))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{OpenMWConfiguration, ParseOptions, util::temp_config};
+
+    #[test]
+    fn test_parallel_load_matches_sequential() {
+        let root = temp_config("parallel_load", "content=Morrowind.esm\n");
+        let mut root_cfg = String::from("content=Morrowind.esm\n");
+
+        for index in 0..8 {
+            let dir = root.join(format!("sub{index}"));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join("openmw.cfg"),
+                format!("content=Mod{index}.esp\ndata=missing{index}\n"),
+            )
+            .unwrap();
+            root_cfg.push_str(&format!("config={}\n", dir.display()));
+        }
+        std::fs::write(root.join("openmw.cfg"), root_cfg).unwrap();
+
+        let sequential = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        let parallel =
+            OpenMWConfiguration::new_with_options(Some(root), ParseOptions::default().parallel())
+                .unwrap();
+
+        let messages = |config: &OpenMWConfiguration| -> Vec<String> {
+            config
+                .validate()
+                .iter()
+                .map(|issue| issue.message.to_string())
+                .collect()
+        };
+
+        assert_eq!(sequential.to_string(), parallel.to_string());
+        assert_eq!(messages(&sequential), messages(&parallel));
+        assert_eq!(messages(&parallel).len(), 8);
+    }
+}
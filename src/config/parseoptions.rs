@@ -0,0 +1,274 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::{fmt, sync::Arc};
+
+#[cfg(feature = "encoding")]
+use crate::config::encodingsetting::EncodingType;
+use crate::{
+    ConfigError,
+    config::{
+        events::{ConfigEventSink, ConfigNotification},
+        fs::{ConfigFs, StdFs},
+    },
+};
+
+/// How the bytes of each openmw.cfg are turned into text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FileEncoding {
+    /// Anything which isn't UTF-8 is a `ConfigError::InvalidUtf8`
+    #[default]
+    Utf8,
+    /// Invalid sequences become U+FFFD instead of failing
+    Utf8Lossy,
+    /// Falls back to a legacy encoding when a file isn't UTF-8:
+    /// Whatever `encoding=` the chain has set so far, or win1252 if there isn't one
+    #[cfg(feature = "encoding")]
+    Detect,
+    /// Always decode using this encoding, even if the file happens to be valid UTF-8
+    #[cfg(feature = "encoding")]
+    Forced(EncodingType),
+}
+
+pub type WarningHandler = Box<dyn Fn(&ConfigError)>;
+
+/// What to do when a `content=`, `groundcover=` or `fallback-archive=` entry repeats an earlier one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// The repeat is a parse error, so strict parsing fails and lenient parsing skips it
+    #[default]
+    Error,
+    /// Keeps both and reports the repeat through `on_warning`, which is what the engine does
+    Warn,
+    /// Silently drops the repeat
+    KeepFirst,
+    /// Drops the earlier entry, so the file ends up where it was last listed
+    KeepLast,
+}
+
+/// Controls how forgiving `OpenMWConfiguration::new_with_options` is about broken lines.
+///
+/// Strict parsing (the default, and what `new` does) refuses the whole chain over a single bad line.
+/// Lenient parsing behaves like the engine: the offending line is skipped and reported through `on_warning`.
+/// Missing or unreadable config files are always errors.
+///
+/// `duplicate_content`, `duplicate_groundcover` and `duplicate_archives` decide what happens to repeated list entries.
+/// Those kept by `DuplicatePolicy::Warn` can be listed with `duplicates()` and dropped with `dedupe()` afterwards.
+///
+/// `ignore_case` treats `Morrowind.esm` and `morrowind.esm` as the same file, both while loading and afterwards.
+///
+/// `read_only` never touches the disk: no missing directories get created, and saving is refused.
+/// `create_missing_dirs` only covers the former, creating the data-local directory if it doesn't exist yet, which is on by default.
+///
+/// `resolve_symlinks` makes every data directory resolve to its canonical path, symlinks followed,
+/// While openmw.cfg keeps the paths as written. Without it they're used literally.
+///
+/// `inline_comments` splits `key=value # note` into the value and a comment kept alongside the setting.
+/// The engine itself only treats whole lines as comments, so a `#` is part of the value unless this is set.
+pub struct ParseOptions {
+    pub strict: bool,
+    pub duplicate_content: DuplicatePolicy,
+    pub duplicate_groundcover: DuplicatePolicy,
+    pub duplicate_archives: DuplicatePolicy,
+    pub ignore_case: bool,
+    pub inline_comments: bool,
+    pub resolve_symlinks: bool,
+    pub read_only: bool,
+    pub create_missing_dirs: bool,
+    pub encoding: FileEncoding,
+    pub on_warning: Option<WarningHandler>,
+    /// Receives parse warnings, and is attached to the configuration once loading finishes
+    pub event_sink: Option<Arc<dyn ConfigEventSink>>,
+    /// Where every openmw.cfg is read from, and later saved to
+    pub fs: Arc<dyn ConfigFs>,
+    /// Read sub-configurations and check data directories on rayon's thread pool
+    #[cfg(feature = "parallel")]
+    pub parallel: bool,
+}
+
+impl ParseOptions {
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            duplicate_content: DuplicatePolicy::Error,
+            duplicate_groundcover: DuplicatePolicy::Error,
+            duplicate_archives: DuplicatePolicy::Error,
+            ignore_case: false,
+            inline_comments: false,
+            resolve_symlinks: false,
+            read_only: false,
+            create_missing_dirs: true,
+            encoding: FileEncoding::default(),
+            on_warning: None,
+            event_sink: None,
+            fs: Arc::new(StdFs),
+            #[cfg(feature = "parallel")]
+            parallel: false,
+        }
+    }
+
+    pub fn lenient() -> Self {
+        Self {
+            strict: false,
+            duplicate_content: DuplicatePolicy::Error,
+            duplicate_groundcover: DuplicatePolicy::Error,
+            duplicate_archives: DuplicatePolicy::Error,
+            ignore_case: false,
+            inline_comments: false,
+            resolve_symlinks: false,
+            read_only: false,
+            create_missing_dirs: true,
+            encoding: FileEncoding::default(),
+            on_warning: None,
+            event_sink: None,
+            fs: Arc::new(StdFs),
+            #[cfg(feature = "parallel")]
+            parallel: false,
+        }
+    }
+
+    /// Keeps every repeated entry, reporting each one as a warning
+    pub fn allowing_duplicates(self) -> Self {
+        self.with_duplicate_policy(DuplicatePolicy::Warn)
+    }
+
+    /// The same policy for content files, groundcover and archives
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_content = policy;
+        self.duplicate_groundcover = policy;
+        self.duplicate_archives = policy;
+        self
+    }
+
+    pub fn with_content_duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_content = policy;
+        self
+    }
+
+    pub fn with_groundcover_duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_groundcover = policy;
+        self
+    }
+
+    pub fn with_archive_duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_archives = policy;
+        self
+    }
+
+    pub fn ignoring_case(mut self) -> Self {
+        self.ignore_case = true;
+        self
+    }
+
+    pub fn with_inline_comments(mut self) -> Self {
+        self.inline_comments = true;
+        self
+    }
+
+    /// For Flatpak and Steam Deck setups, where the same mods are often reachable through more than one path
+    pub fn resolving_symlinks(mut self) -> Self {
+        self.resolve_symlinks = true;
+        self
+    }
+
+    /// For inspection tools, and configurations on read-only filesystems
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self.create_missing_dirs = false;
+        self
+    }
+
+    pub fn creating_missing_dirs(mut self, create: bool) -> Self {
+        self.create_missing_dirs = create;
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: FileEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn with_warning_handler<F>(mut self, on_warning: F) -> Self
+    where
+        F: Fn(&ConfigError) + 'static,
+    {
+        self.on_warning = Some(Box::new(on_warning));
+        self
+    }
+
+    pub fn with_event_sink(mut self, sink: Arc<dyn ConfigEventSink>) -> Self {
+        self.event_sink = Some(sink);
+        self
+    }
+
+    /// Reads (and saves) through something other than the real filesystem, such as a `MemoryFs`
+    pub fn with_fs(mut self, fs: Arc<dyn ConfigFs>) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    /// Loading still applies every line in order, only the filesystem work happens in parallel.
+    /// Validation of the resulting configuration checks its data directories in parallel as well.
+    #[cfg(feature = "parallel")]
+    pub fn parallel(mut self) -> Self {
+        self.parallel = true;
+        self
+    }
+
+    /// Hands back the error in strict mode, otherwise reports it and lets parsing carry on
+    pub(crate) fn recover(&self, error: ConfigError) -> Result<(), ConfigError> {
+        if self.strict {
+            return Err(error);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::warn!(%error, "skipping invalid line");
+
+        self.warn(&error);
+        Ok(())
+    }
+
+    /// Reports something which doesn't stop parsing, strict or not
+    pub(crate) fn warn(&self, error: &ConfigError) {
+        if let Some(on_warning) = &self.on_warning {
+            on_warning(error);
+        }
+
+        if let Some(sink) = &self.event_sink {
+            sink.notify(ConfigNotification::ParseWarning(error));
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
+
+impl fmt::Debug for ParseOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ParseOptions");
+        debug
+            .field("strict", &self.strict)
+            .field("duplicate_content", &self.duplicate_content)
+            .field("duplicate_groundcover", &self.duplicate_groundcover)
+            .field("duplicate_archives", &self.duplicate_archives)
+            .field("ignore_case", &self.ignore_case)
+            .field("inline_comments", &self.inline_comments)
+            .field("resolve_symlinks", &self.resolve_symlinks)
+            .field("read_only", &self.read_only)
+            .field("create_missing_dirs", &self.create_missing_dirs)
+            .field("encoding", &self.encoding)
+            .field("on_warning", &self.on_warning.is_some())
+            .field("event_sink", &self.event_sink.is_some())
+            .field("fs", &self.fs);
+
+        #[cfg(feature = "parallel")]
+        debug.field("parallel", &self.parallel);
+
+        debug.finish()
+    }
+}
@@ -0,0 +1,572 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Reads the TES3 header at the start of a content file, which is where its masters are declared.
+//! .esm, .esp, .omwgame and .omwaddon all share this format. .omwscripts files are plain text and have no masters.
+
+use std::{
+    collections::HashMap,
+    fs::{File, read_dir},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ConfigError, bail_config,
+    config::{ContentKind, FileSetting, OpenMWConfiguration, SettingValue},
+};
+
+pub(crate) const TES3_MAGIC: &[u8; 4] = b"TES3";
+pub(crate) const RECORD_HEADER_LEN: usize = 16;
+pub(crate) const SUBRECORD_HEADER_LEN: usize = 8;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PluginHeader {
+    pub version: f32,
+    pub author: String,
+    pub description: String,
+    pub record_count: u32,
+    /// In the order the plugin declares them, which is also the order they need to load in
+    pub masters: Vec<String>,
+}
+
+/// Fixed-width, null-padded text field
+pub(crate) fn fixed_str(bytes: &[u8]) -> String {
+    let len = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+pub(crate) fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn is_script_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("omwscripts"))
+}
+
+/// Reads only the header record, so this stays cheap even for very large masters
+pub fn read_plugin_header(path: &Path) -> Result<PluginHeader, ConfigError> {
+    if is_script_file(path) {
+        return Ok(PluginHeader::default());
+    }
+
+    let mut file = File::open(path)?;
+    let mut record_header = [0; RECORD_HEADER_LEN];
+    if file.read_exact(&mut record_header).is_err() || &record_header[..4] != TES3_MAGIC {
+        bail_config!(invalid_plugin, path, "missing TES3 header");
+    }
+
+    // The length is whatever the file claims, so the buffer only grows as far as there's really data
+    let record_len = u32_at(&record_header, 4).unwrap_or_default() as usize;
+    let mut record = Vec::new();
+    file.take(record_len as u64).read_to_end(&mut record)?;
+
+    if record.len() < record_len {
+        bail_config!(invalid_plugin, path, "header record is truncated");
+    }
+
+    let mut header = PluginHeader::default();
+    let mut offset = 0;
+
+    while offset + SUBRECORD_HEADER_LEN <= record.len() {
+        let tag = &record[offset..offset + 4];
+        let len = u32_at(&record, offset + 4).unwrap_or_default() as usize;
+        let start = offset + SUBRECORD_HEADER_LEN;
+
+        let Some(data) = start
+            .checked_add(len)
+            .and_then(|end| record.get(start..end))
+        else {
+            bail_config!(
+                invalid_plugin,
+                path,
+                "subrecord runs past the end of the header"
+            );
+        };
+
+        match tag {
+            b"HEDR" if data.len() >= 300 => {
+                header.version = f32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+                header.author = fixed_str(&data[8..40]);
+                header.description = fixed_str(&data[40..296]);
+                header.record_count = u32_at(data, 296).unwrap_or_default();
+            }
+            b"MAST" => header.masters.push(fixed_str(data)),
+            _ => {}
+        }
+
+        offset = start + len;
+    }
+
+    Ok(header)
+}
+
+impl OpenMWConfiguration {
+    /// Looks up a file by name in the data directories, case-insensitively like the engine.
+    /// Later data directories take priority, so the last match wins.
+    pub fn find_data_file(&self, file_name: &str) -> Option<PathBuf> {
+        self.data_directories().into_iter().rev().find_map(|dir| {
+            read_dir(dir)
+                .ok()?
+                .flatten()
+                .find(|entry| entry.file_name().eq_ignore_ascii_case(file_name))
+                .map(|entry| entry.path())
+        })
+    }
+
+    /// Every enabled content file in load order, paired with the file the engine would load for it.
+    /// Meant for tools like OMWLLF or delta-plugin which need real paths.
+    /// Plugins which aren't in any data directory are reported as `ConfigError::PluginNotFound` instead.
+    pub fn resolved_content_paths(&self) -> (Vec<(String, PathBuf)>, Vec<ConfigError>) {
+        let index = self.data_file_index();
+        let mut resolved = Vec::new();
+        let mut missing = Vec::new();
+
+        for plugin in self.content_files_iter() {
+            match index.get(&plugin.value().to_ascii_lowercase()) {
+                Some(path) => resolved.push((plugin.value().clone(), path.clone())),
+                None => missing.push(crate::config_err!(plugin_not_found, plugin.value())),
+            }
+        }
+
+        (resolved, missing)
+    }
+
+    /// Every file in the data directories by lowercased name, with later directories winning.
+    /// Each directory is only listed once, rather than once per lookup like `find_data_file` would.
+    pub(crate) fn data_file_index(&self) -> HashMap<String, PathBuf> {
+        let mut index = HashMap::new();
+        for dir in self.data_directories() {
+            let Ok(entries) = read_dir(dir) else {
+                continue;
+            };
+
+            for entry in entries.flatten().filter(|entry| entry.path().is_file()) {
+                index.insert(
+                    entry.file_name().to_string_lossy().to_ascii_lowercase(),
+                    entry.path(),
+                );
+            }
+        }
+
+        index
+    }
+
+    fn has_content_file_ignore_case(&self, file_name: &str) -> bool {
+        self.content_files_iter()
+            .any(|plugin| plugin.value().eq_ignore_ascii_case(file_name))
+    }
+
+    /// Enables a plugin along with any of its masters (and their masters) which aren't enabled yet.
+    /// Masters are placed ahead of whatever needs them, and the plugin itself goes at the end of the load order if it wasn't already enabled.
+    /// Returns the entries which were added, in load order.
+    pub fn activate_with_dependencies(
+        &mut self,
+        file_name: &str,
+    ) -> Result<Vec<String>, ConfigError> {
+        let mut plan = Vec::new();
+        self.plan_activation(file_name, &mut Vec::new(), &mut plan)?;
+
+        let mut position = self
+            .content_files_iter()
+            .position(|plugin| plugin.value().eq_ignore_ascii_case(file_name))
+            .unwrap_or(usize::MAX);
+
+        for plugin in &plan {
+            self.insert_content_file(position, plugin)?;
+            position = position.saturating_add(1);
+        }
+
+        Ok(plan)
+    }
+
+    /// Enabled plugins which would break if this one were disabled, in load order.
+    /// That includes plugins depending on it indirectly, through another plugin that would break.
+    /// Plugins which can't be found or read are left out, since there's no way to tell what they need.
+    pub fn deactivation_impact(&self, file_name: &str) -> Vec<String> {
+        let index = self.data_file_index();
+        let mut broken = vec![file_name.to_string()];
+        let mut impacted = Vec::new();
+
+        for plugin in self.content_files_iter() {
+            let Some(header) = index
+                .get(&plugin.value().to_ascii_lowercase())
+                .and_then(|path| read_plugin_header(path).ok())
+            else {
+                continue;
+            };
+
+            let breaks = header.masters.iter().any(|master| {
+                broken
+                    .iter()
+                    .any(|broken| broken.eq_ignore_ascii_case(master))
+            });
+
+            if breaks {
+                broken.push(plugin.value().clone());
+                impacted.push(plugin.value().clone());
+            }
+        }
+
+        impacted
+    }
+
+    /// Game files which don't build on anything else, which is how the launcher tells them apart from `Tribunal.esm` and friends.
+    /// Files which can't be found or read only count if they're `.omwgame`.
+    pub fn base_game_files(&self) -> Vec<&FileSetting> {
+        let index = self.data_file_index();
+
+        self.game_files()
+            .filter(|file| {
+                match index
+                    .get(&file.value().to_ascii_lowercase())
+                    .and_then(|path| read_plugin_header(path).ok())
+                {
+                    Some(header) => header.masters.is_empty(),
+                    None => file.kind() == ContentKind::Omwgame,
+                }
+            })
+            .collect()
+    }
+
+    /// The one game file the load order is built on.
+    /// More than one is `ConfigError::MultipleGameFiles`, since the engine can only start one game.
+    pub fn active_game_file(&self) -> Result<Option<&FileSetting>, ConfigError> {
+        let game_files = self.base_game_files();
+
+        if game_files.len() > 1 {
+            bail_config!(
+                multiple_game_files,
+                game_files.iter().map(|file| file.value().clone()).collect()
+            );
+        }
+
+        Ok(game_files.into_iter().next())
+    }
+
+    /// Makes `file_name` the only game file, first in the load order, like picking one in the launcher does.
+    /// Any other game file is disabled, unless something enabled still needs it,
+    /// In which case nothing changes and `ConfigError::GameFileInUse` lists what would break.
+    pub fn set_game_file(&mut self, file_name: &str) -> Result<(), ConfigError> {
+        if !ContentKind::from_file_name(file_name).is_game_file() {
+            bail_config!(not_a_game_file, file_name);
+        }
+
+        let replaced: Vec<String> = self
+            .base_game_files()
+            .iter()
+            .filter(|file| !file.value().eq_ignore_ascii_case(file_name))
+            .map(|file| file.value().clone())
+            .collect();
+
+        for game_file in &replaced {
+            let dependents = self.deactivation_impact(game_file);
+            if !dependents.is_empty() {
+                bail_config!(game_file_in_use, game_file, dependents);
+            }
+        }
+
+        replaced
+            .iter()
+            .for_each(|game_file| self.remove_content_file(game_file));
+
+        let index = self.settings.iter().position(|setting| {
+            matches!(setting, SettingValue::ContentFile(file) if file.value().eq_ignore_ascii_case(file_name))
+        });
+
+        let first = self
+            .settings
+            .iter()
+            .position(|setting| matches!(setting, SettingValue::ContentFile(_)));
+
+        match index {
+            Some(index) if Some(index) == first => {}
+            Some(index) => self.move_content_file(index, 0),
+            None => self.insert_content_file(0, file_name)?,
+        }
+
+        Ok(())
+    }
+
+    /// Depth-first over the master graph, so masters land in the plan ahead of their dependents
+    fn plan_activation(
+        &self,
+        file_name: &str,
+        visiting: &mut Vec<String>,
+        plan: &mut Vec<String>,
+    ) -> Result<(), ConfigError> {
+        let already_planned = |plan: &Vec<String>| {
+            plan.iter()
+                .any(|planned| planned.eq_ignore_ascii_case(file_name))
+        };
+
+        if already_planned(plan)
+            || visiting
+                .iter()
+                .any(|visited| visited.eq_ignore_ascii_case(file_name))
+        {
+            return Ok(());
+        }
+
+        let Some(path) = self.find_data_file(file_name) else {
+            bail_config!(plugin_not_found, file_name);
+        };
+
+        visiting.push(file_name.to_string());
+
+        for master in read_plugin_header(&path)?.masters {
+            if !self.has_content_file_ignore_case(&master) {
+                self.plan_activation(&master, visiting, plan)?;
+            }
+        }
+
+        visiting.pop();
+
+        if !self.has_content_file_ignore_case(file_name) && !already_planned(plan) {
+            plan.push(path_name(&path, file_name));
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps the on-disk capitalization when adding a file, since that's what other tools will show
+fn path_name(path: &Path, fallback: &str) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    /// Builds the header of a Morrowind plugin declaring the given masters
+    pub fn tes3_plugin(masters: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        data.extend_from_slice(b"HEDR");
+        data.extend_from_slice(&300u32.to_le_bytes());
+        data.extend_from_slice(&1.3f32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        let mut author = [0u8; 32];
+        author[..6].copy_from_slice(b"tester");
+        data.extend_from_slice(&author);
+        data.extend_from_slice(&[0u8; 256]);
+        data.extend_from_slice(&0u32.to_le_bytes());
+
+        for master in masters {
+            let name = format!("{master}\0");
+            data.extend_from_slice(b"MAST");
+            data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(b"DATA");
+            data.extend_from_slice(&8u32.to_le_bytes());
+            data.extend_from_slice(&0u64.to_le_bytes());
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(TES3_MAGIC);
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.extend_from_slice(&data);
+
+        bytes
+    }
+
+    /// A config with one data directory holding the given plugins, each with its masters
+    pub fn plugin_config(
+        name: &str,
+        content: &[&str],
+        plugins: &[(&str, &[&str])],
+    ) -> OpenMWConfiguration {
+        let dir = temp_config(name, "");
+        let data = dir.join("Data Files");
+        std::fs::create_dir_all(&data).unwrap();
+
+        for (plugin, masters) in plugins {
+            std::fs::write(data.join(plugin), tes3_plugin(masters)).unwrap();
+        }
+
+        let mut contents = format!("data={}\n", data.display());
+        content
+            .iter()
+            .for_each(|plugin| contents.push_str(&format!("content={plugin}\n")));
+        std::fs::write(dir.join("openmw.cfg"), contents).unwrap();
+
+        OpenMWConfiguration::new(Some(dir)).unwrap()
+    }
+
+    #[test]
+    fn test_read_plugin_header() {
+        let dir = temp_config("plugin_header", "");
+        let path = dir.join("Patch.esp");
+        std::fs::write(&path, tes3_plugin(&["Morrowind.esm", "Tribunal.esm"])).unwrap();
+
+        let header = read_plugin_header(&path).unwrap();
+        assert_eq!(header.author, "tester");
+        assert_eq!(header.masters, vec!["Morrowind.esm", "Tribunal.esm"]);
+
+        let mut oversized = tes3_plugin(&[]);
+        oversized[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut truncated = tes3_plugin(&["Morrowind.esm"]);
+        truncated.truncate(truncated.len() - 4);
+
+        for bytes in [b"not a plugin".to_vec(), oversized, truncated] {
+            std::fs::write(&path, bytes).unwrap();
+            assert!(matches!(
+                read_plugin_header(&path),
+                Err(ConfigError::InvalidPlugin { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_activate_with_dependencies() {
+        let mut config = plugin_config(
+            "activate_dependencies",
+            &["Morrowind.esm", "Other.esp"],
+            &[
+                ("Morrowind.esm", &[]),
+                ("Tamriel_Data.esm", &["Morrowind.esm"]),
+                ("TR_Mainland.esm", &["Morrowind.esm", "Tamriel_Data.esm"]),
+                ("TR_Patch.esp", &["tr_mainland.esm"]),
+            ],
+        );
+
+        let added = config.activate_with_dependencies("TR_Patch.esp").unwrap();
+
+        assert_eq!(
+            added,
+            vec!["Tamriel_Data.esm", "TR_Mainland.esm", "TR_Patch.esp"]
+        );
+        assert_eq!(
+            config.content_files(),
+            vec![
+                "Morrowind.esm",
+                "Other.esp",
+                "Tamriel_Data.esm",
+                "TR_Mainland.esm",
+                "TR_Patch.esp"
+            ]
+        );
+        assert!(
+            config
+                .activate_with_dependencies("TR_Patch.esp")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(matches!(
+            config.activate_with_dependencies("Missing.esp"),
+            Err(ConfigError::PluginNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_deactivation_impact() {
+        let config = plugin_config(
+            "deactivation_impact",
+            &[
+                "Morrowind.esm",
+                "Tamriel_Data.esm",
+                "Standalone.esp",
+                "TR_Mainland.esm",
+                "TR_Patch.esp",
+            ],
+            &[
+                ("Morrowind.esm", &[]),
+                ("Tamriel_Data.esm", &["Morrowind.esm"]),
+                ("Standalone.esp", &["Morrowind.esm"]),
+                ("TR_Mainland.esm", &["Morrowind.esm", "Tamriel_Data.esm"]),
+                ("TR_Patch.esp", &["TR_Mainland.esm"]),
+            ],
+        );
+
+        assert_eq!(
+            config.deactivation_impact("tamriel_data.esm"),
+            vec!["TR_Mainland.esm", "TR_Patch.esp"]
+        );
+        assert!(config.deactivation_impact("TR_Patch.esp").is_empty());
+    }
+
+    #[test]
+    fn test_resolved_content_paths() {
+        let config = plugin_config(
+            "resolved_content_paths",
+            &["Morrowind.esm", "tamriel_data.esm", "Missing.esp"],
+            &[
+                ("Morrowind.esm", &[]),
+                ("Tamriel_Data.esm", &["Morrowind.esm"]),
+            ],
+        );
+        let data = config.data_directories()[0].clone();
+
+        let (resolved, missing) = config.resolved_content_paths();
+
+        assert_eq!(
+            resolved,
+            vec![
+                ("Morrowind.esm".to_string(), data.join("Morrowind.esm")),
+                (
+                    "tamriel_data.esm".to_string(),
+                    data.join("Tamriel_Data.esm")
+                ),
+            ]
+        );
+        assert!(matches!(
+            missing.as_slice(),
+            [ConfigError::PluginNotFound(name)] if name == "Missing.esp"
+        ));
+    }
+
+    #[test]
+    fn test_game_file_selection() {
+        let mut config = plugin_config(
+            "game_file_selection",
+            &[
+                "Tribunal.esm",
+                "Morrowind.esm",
+                "Starwind.omwgame",
+                "Patch.esp",
+            ],
+            &[
+                ("Morrowind.esm", &[]),
+                ("Tribunal.esm", &["Morrowind.esm"]),
+                ("Starwind.omwgame", &[]),
+                ("Patch.esp", &["Morrowind.esm"]),
+            ],
+        );
+
+        assert!(matches!(
+            config.active_game_file(),
+            Err(ConfigError::MultipleGameFiles(files)) if files == ["Morrowind.esm", "Starwind.omwgame"]
+        ));
+        assert!(matches!(
+            config.set_game_file("Starwind.omwgame"),
+            Err(ConfigError::GameFileInUse { game_file, dependents })
+                if game_file == "Morrowind.esm" && dependents == ["Tribunal.esm", "Patch.esp"]
+        ));
+        assert!(matches!(
+            config.set_game_file("Patch.esp"),
+            Err(ConfigError::NotAGameFile(_))
+        ));
+
+        config.set_game_file("Morrowind.esm").unwrap();
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal.esm", "Patch.esp"]
+        );
+        assert_eq!(
+            config.active_game_file().unwrap().unwrap().value(),
+            "Morrowind.esm"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Post-processing shaders: the `.omwfx` files mods ship under `shaders/`,
+//! And the chain in settings.cfg which decides which of them run, in order.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::config::{OpenMWConfiguration, settings_cfg::SettingsCfg};
+
+const SECTION: &str = "Post Processing";
+
+/// A shader the engine could load, named the way the chain refers to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderFile {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl SettingsCfg {
+    pub fn post_processing_enabled(&self) -> Option<bool> {
+        self.get(SECTION, "enabled")
+            .and_then(|value| value.parse().ok())
+    }
+
+    pub fn set_post_processing_enabled(&mut self, enabled: bool) {
+        self.set(SECTION, "enabled", &enabled.to_string());
+    }
+
+    /// Shader names in the order they run. `None` when settings.cfg leaves it at the engine's default.
+    pub fn post_processing_chain(&self) -> Option<Vec<String>> {
+        self.get(SECTION, "chain").map(|chain| {
+            chain
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+    }
+
+    pub fn set_post_processing_chain(&mut self, chain: &[String]) {
+        self.set(SECTION, "chain", &chain.join(","));
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Every `.omwfx` under `shaders/` in the data directories.
+    /// Later directories override earlier ones with the same shader name, like they do in the VFS,
+    /// And the engine's own shaders only show up if `with_engine_resource_dirs` was used.
+    pub fn available_shaders(&self) -> Vec<ShaderFile> {
+        let mut shaders: Vec<ShaderFile> = Vec::new();
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+
+        for dir in self.data_directories() {
+            let mut found: Vec<PathBuf> = std::fs::read_dir(dir.join("shaders"))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension.eq_ignore_ascii_case("omwfx"))
+                })
+                .collect();
+            found.sort();
+
+            for path in found {
+                let Some(name) = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                else {
+                    continue;
+                };
+
+                match by_name.get(&name.to_lowercase()) {
+                    Some(&index) => shaders[index] = ShaderFile { name, path },
+                    None => {
+                        by_name.insert(name.to_lowercase(), shaders.len());
+                        shaders.push(ShaderFile { name, path });
+                    }
+                }
+            }
+        }
+
+        shaders
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{settings_cfg::SETTINGS_CFG_FILE, util::temp_config};
+
+    #[test]
+    fn test_post_processing_chain() {
+        let dir = temp_config("post_processing", "data=Base\ndata=Override\n");
+        for (data, shader) in [
+            ("Base", "bloom.omwfx"),
+            ("Base", "SSAO.omwfx"),
+            ("Override", "Bloom.OMWFX"),
+            ("Override", "readme.txt"),
+        ] {
+            std::fs::create_dir_all(dir.join(data).join("shaders")).unwrap();
+            std::fs::write(dir.join(data).join("shaders").join(shader), "").unwrap();
+        }
+
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert_eq!(
+            config.available_shaders(),
+            vec![
+                ShaderFile {
+                    name: "SSAO".into(),
+                    path: dir.join("Base/shaders/SSAO.omwfx"),
+                },
+                ShaderFile {
+                    name: "Bloom".into(),
+                    path: dir.join("Override/shaders/Bloom.OMWFX"),
+                },
+            ]
+        );
+
+        let mut settings = config.settings_cfg().unwrap();
+        assert_eq!(settings.post_processing_chain(), None);
+
+        settings.set_post_processing_enabled(true);
+        settings.set_post_processing_chain(&["SSAO".into(), "Bloom".into()]);
+        config.save_settings_cfg(&settings).unwrap();
+
+        let settings = config.settings_cfg().unwrap();
+        assert_eq!(settings.post_processing_enabled(), Some(true));
+        assert_eq!(
+            settings.post_processing_chain(),
+            Some(vec!["SSAO".to_string(), "Bloom".to_string()])
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join(SETTINGS_CFG_FILE)).unwrap(),
+            "[Post Processing]\nenabled = true\nchain = SSAO,Bloom\n"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Scratch configurations for mod developers.
+//! A project folder is layered on top of the normal configuration chain as its last data directory,
+//! With its plugins (and any masters they're missing) appended to the load order.
+//! Everything added this way records the project folder as its source, so it is never written to the user's openmw.cfg.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ConfigError, GameSetting,
+    config::{DirectorySetting, OpenMWConfiguration, SettingValue},
+};
+
+pub(crate) const PLUGIN_EXTENSIONS: [&str; 5] = ["omwgame", "esm", "esp", "omwaddon", "omwscripts"];
+
+/// Masters first, then everything else alphabetically, so the project's own plugins load in a sensible order
+fn plugin_rank(path: &Path) -> Option<usize> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    PLUGIN_EXTENSIONS
+        .iter()
+        .position(|known| *known == extension)
+}
+
+impl OpenMWConfiguration {
+    /// Loads the normal configuration (see `new`) and layers a project folder on top of it
+    pub fn for_project(
+        project_dir: &Path,
+        base_config: Option<PathBuf>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = Self::new(base_config)?;
+        config.layer_project(project_dir)?;
+        Ok(config)
+    }
+
+    /// Adds the project folder as a data directory and enables every plugin directly inside it.
+    /// Returns the content entries which were added, including masters that had to be enabled for them.
+    pub fn layer_project(&mut self, project_dir: &Path) -> Result<Vec<String>, ConfigError> {
+        let project_dir = std::fs::canonicalize(project_dir)?;
+
+        self.push_setting(SettingValue::DataDirectory(DirectorySetting::from_path(
+            &project_dir,
+            project_dir.clone(),
+        )));
+
+        let mut plugins: Vec<(usize, String)> = std::fs::read_dir(&project_dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter_map(|path| {
+                let rank = plugin_rank(&path)?;
+                Some((rank, path.file_name()?.to_string_lossy().into_owned()))
+            })
+            .collect();
+        plugins.sort();
+
+        let mut added = Vec::new();
+        for (_, plugin) in plugins {
+            added.extend(self.activate_with_dependencies(&plugin)?);
+        }
+
+        self.settings_mut().iter_mut().for_each(|setting| {
+            if let SettingValue::ContentFile(plugin) = setting
+                && added.contains(plugin.value())
+            {
+                plugin.meta_mut().source_config = project_dir.clone();
+            }
+        });
+
+        self.project = Some(project_dir);
+
+        Ok(added)
+    }
+
+    pub fn project_dir(&self) -> Option<&PathBuf> {
+        self.project.as_ref()
+    }
+
+    /// Command line arguments which reproduce the project layer when passed to `openmw` alongside the normal configuration
+    pub fn launch_args_for_project(&self) -> Vec<String> {
+        let Some(project) = &self.project else {
+            return Vec::new();
+        };
+
+        let project_settings = || {
+            self.settings
+                .iter()
+                .filter(|setting| setting.meta().source_config() == project)
+        };
+
+        let data = project_settings().filter_map(|setting| match setting {
+            SettingValue::DataDirectory(dir) => {
+                Some(("--data", dir.parsed().to_string_lossy().into_owned()))
+            }
+            _ => None,
+        });
+
+        let content = project_settings().filter_map(|setting| match setting {
+            SettingValue::ContentFile(plugin) => Some(("--content", plugin.value().clone())),
+            _ => None,
+        });
+
+        data.chain(content)
+            .flat_map(|(option, value)| [option.to_string(), value])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        GameSetting,
+        config::plugins::tests::{plugin_config, tes3_plugin},
+    };
+
+    #[test]
+    fn test_project_layer() {
+        let mut config = plugin_config(
+            "project_base",
+            &["Morrowind.esm"],
+            &[
+                ("Morrowind.esm", &[]),
+                ("Tamriel_Data.esm", &["Morrowind.esm"]),
+            ],
+        );
+
+        let project = std::env::temp_dir()
+            .join(format!("openmw_config_test_{}", std::process::id()))
+            .join("project_wip");
+        let _ = std::fs::remove_dir_all(&project);
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(project.join("WIP.esp"), tes3_plugin(&["Tamriel_Data.esm"])).unwrap();
+        std::fs::write(project.join("notes.txt"), "").unwrap();
+
+        let added = config.layer_project(&project).unwrap();
+        assert_eq!(added, vec!["Tamriel_Data.esm", "WIP.esp"]);
+
+        let project = std::fs::canonicalize(project).unwrap();
+        assert_eq!(
+            config.launch_args_for_project(),
+            vec![
+                "--data".to_string(),
+                project.to_string_lossy().into_owned(),
+                "--content".into(),
+                "Tamriel_Data.esm".into(),
+                "--content".into(),
+                "WIP.esp".into(),
+            ]
+        );
+
+        let user_cfg = config.user_config_path().join("openmw.cfg");
+        assert!(
+            config
+                .content_files_iter()
+                .filter(|plugin| plugin.meta().is_from(&user_cfg))
+                .all(|plugin| plugin.value() == "Morrowind.esm")
+        );
+    }
+}
@@ -0,0 +1,190 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Writing out the whole chain as one openmw.cfg, with a choice of how much of the original files shows through.
+//! `Display` is `render` with the default options.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    SettingOrigin,
+    config::{OpenMWConfiguration, SettingValue, comments, strings},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Write `?userdata?`, `?local?` and `?global?` directories as the paths they stand for
+    pub expand_tokens: bool,
+    /// Write every directory as the absolute path the engine resolves it to, tokens included
+    pub absolute_paths: bool,
+    pub include_comments: bool,
+    /// Start each run of settings with a marker comment naming the openmw.cfg they came from
+    pub include_provenance_headers: bool,
+    /// Also write settings the crate made up, like the data directory `data-local` implies
+    pub include_synthetic: bool,
+}
+
+impl Default for RenderOptions {
+    /// The same output as `Display`
+    fn default() -> Self {
+        Self {
+            expand_tokens: false,
+            absolute_paths: false,
+            include_comments: true,
+            include_provenance_headers: false,
+            include_synthetic: false,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// What the engine ends up with and nothing else: absolute paths, no comments
+    pub fn resolved() -> Self {
+        Self {
+            expand_tokens: true,
+            absolute_paths: true,
+            include_comments: false,
+            include_provenance_headers: false,
+            include_synthetic: true,
+        }
+    }
+}
+
+/// The marker comment `include_provenance_headers` writes
+pub fn provenance_header(source: &Path) -> String {
+    format!("{}source={}", comments::OMWCFG_MARKER, source.display())
+}
+
+fn render_setting(setting: &SettingValue, options: &RenderOptions) -> String {
+    let comment = &setting.meta().comment;
+    let inline_comment = setting.meta().inline_comment();
+
+    let line = match options.include_comments || inline_comment.is_empty() {
+        true => setting.to_string(),
+        false => {
+            let mut plain = setting.clone();
+            plain.meta_mut().set_inline_comment("");
+            plain.to_string()
+        }
+    };
+    let line = line.strip_prefix(comment.as_str()).unwrap_or(&line);
+
+    let line = match setting {
+        SettingValue::DataDirectory(dir)
+        | SettingValue::UserData(dir)
+        | SettingValue::DataLocal(dir)
+        | SettingValue::Resources(dir)
+        | SettingValue::SubConfiguration(dir)
+            if options.absolute_paths
+                || (options.expand_tokens && dir.original().starts_with('?')) =>
+        {
+            let inline_comment = match options.include_comments && !inline_comment.is_empty() {
+                true => format!(" {inline_comment}"),
+                false => String::new(),
+            };
+
+            format!(
+                "{}={}{inline_comment}\n",
+                setting.key(),
+                strings::quote_data_directory(&dir.parsed().to_string_lossy())
+            )
+        }
+        _ => line.to_string(),
+    };
+
+    match options.include_comments {
+        true => format!("{comment}{line}"),
+        false => line,
+    }
+}
+
+impl OpenMWConfiguration {
+    /// The composite configuration as openmw.cfg text. The serializer footer follows `set_serializer_footer` either way.
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let mut output = String::new();
+        let mut current_source: Option<&Path> = None;
+        let chain = self.chain_files();
+
+        for setting in self.settings.iter() {
+            let meta = setting.meta();
+
+            if meta.origin() == SettingOrigin::Synthetic && !options.include_synthetic {
+                continue;
+            }
+
+            if options.include_provenance_headers
+                && !current_source.is_some_and(|source| meta.is_from(source))
+            {
+                // Directory settings only know the directory, so name the file when it's one from the chain
+                let source = chain
+                    .iter()
+                    .find(|file| meta.is_from(file))
+                    .map_or(meta.source_config(), PathBuf::as_path);
+
+                output.push_str(&provenance_header(source));
+                output.push('\n');
+                current_source = Some(source);
+            }
+
+            output.push_str(&render_setting(setting, options));
+        }
+
+        if !self.omit_serializer_footer {
+            output.push_str(&format!(
+                "{} {}\n",
+                comments::SERIALIZER_FOOTER,
+                env!("CARGO_PKG_VERSION")
+            ));
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_render_options() {
+        let dir = temp_config(
+            "render",
+            "# base game\ncontent=Morrowind.esm\ndata=Data Files\ndata=?userdata?mods\n",
+        );
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        config.set_serializer_footer(false);
+
+        assert_eq!(config.render(&RenderOptions::default()), config.to_string());
+
+        let tokens = config.render(&RenderOptions {
+            expand_tokens: true,
+            include_comments: false,
+            ..Default::default()
+        });
+        let userdata = crate::default_userdata_path().join("mods");
+        assert_eq!(
+            tokens,
+            format!(
+                "content=Morrowind.esm\ndata=Data Files\ndata=\"{}\"\n",
+                userdata.display()
+            )
+        );
+
+        let resolved = config.render(&RenderOptions {
+            include_provenance_headers: true,
+            ..RenderOptions::resolved()
+        });
+        assert_eq!(
+            resolved,
+            format!(
+                "{}\ncontent=Morrowind.esm\ndata=\"{}\"\ndata=\"{}\"\n",
+                provenance_header(&dir.join("openmw.cfg")),
+                dir.join("Data Files").display(),
+                userdata.display()
+            )
+        );
+    }
+}
@@ -0,0 +1,269 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Listing the games saved under `saves_path()`, for save browsers and backups.
+//! The engine keeps one folder per character, holding that character's `.omwsave` files.
+
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    ConfigError, bail_config,
+    config::{
+        OpenMWConfiguration,
+        plugins::{RECORD_HEADER_LEN, SUBRECORD_HEADER_LEN, TES3_MAGIC, fixed_str, u32_at},
+    },
+};
+
+const SAVE_MAGIC: &[u8; 4] = b"SAVE";
+
+/// What a save says about itself, from the records at the start of the file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveHeader {
+    pub player_name: String,
+    pub player_level: Option<i32>,
+    pub player_class: String,
+    pub player_cell: String,
+    /// Content files which were active when the game was saved
+    pub content_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveGame {
+    pub path: PathBuf,
+    /// When the file was last written, which is when the game was saved
+    pub modified: Option<SystemTime>,
+    /// `None` for files which couldn't be read
+    pub header: Option<SaveHeader>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharacterSaves {
+    /// The folder's name, which the engine derives from the character's
+    pub name: String,
+    pub path: PathBuf,
+    /// Newest first
+    pub saves: Vec<SaveGame>,
+}
+
+impl CharacterSaves {
+    /// The character's name as the newest save has it, falling back to the folder name
+    pub fn display_name(&self) -> &str {
+        self.saves
+            .iter()
+            .find_map(|save| save.header.as_ref())
+            .map(|header| header.player_name.as_str())
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&self.name)
+    }
+
+    pub fn latest(&self) -> Option<&SaveGame> {
+        self.saves.first()
+    }
+}
+
+fn read_record(file: &mut File, path: &Path) -> Result<([u8; 4], Vec<u8>), ConfigError> {
+    let mut record_header = [0; RECORD_HEADER_LEN];
+    if file.read_exact(&mut record_header).is_err() {
+        bail_config!(invalid_save, path, "file ends before the save header");
+    }
+
+    // The length is whatever the file claims, so the buffer only grows as far as there's really data
+    let record_len = u32_at(&record_header, 4).unwrap_or_default() as usize;
+    let mut record = Vec::new();
+    file.take(record_len as u64).read_to_end(&mut record)?;
+
+    if record.len() < record_len {
+        bail_config!(invalid_save, path, "header record is truncated");
+    }
+
+    let mut tag = [0; 4];
+    tag.copy_from_slice(&record_header[..4]);
+
+    Ok((tag, record))
+}
+
+fn subrecords(record: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut offset = 0;
+
+    std::iter::from_fn(move || {
+        let tag = record.get(offset..offset + 4)?;
+        let len = u32_at(record, offset + 4)? as usize;
+        let start = offset + SUBRECORD_HEADER_LEN;
+        let data = record.get(start..start + len)?;
+
+        offset = start + len;
+        Some((tag, data))
+    })
+}
+
+/// Reads the character details from an `.omwsave`, without going through the rest of the file
+pub fn read_save_header(path: &Path) -> Result<SaveHeader, ConfigError> {
+    let mut file = File::open(path)?;
+    let mut header = SaveHeader::default();
+
+    let (tag, record) = read_record(&mut file, path)?;
+    if &tag != TES3_MAGIC {
+        bail_config!(invalid_save, path, "missing TES3 header");
+    }
+
+    for (tag, data) in subrecords(&record) {
+        if tag == b"MAST" {
+            header.content_files.push(fixed_str(data));
+        }
+    }
+
+    let (tag, record) = read_record(&mut file, path)?;
+    if &tag != SAVE_MAGIC {
+        bail_config!(invalid_save, path, "missing SAVE record");
+    }
+
+    for (tag, data) in subrecords(&record) {
+        match tag {
+            b"MAST" => header.content_files.push(fixed_str(data)),
+            b"PLNA" => header.player_name = fixed_str(data),
+            b"PLLE" => {
+                header.player_level = u32_at(data, 0).map(|level| level as i32);
+            }
+            b"PLCN" => header.player_class = fixed_str(data),
+            b"PLCE" => header.player_cell = fixed_str(data),
+            _ => {}
+        }
+    }
+
+    Ok(header)
+}
+
+fn read_character(dir: PathBuf) -> CharacterSaves {
+    let mut saves: Vec<SaveGame> = std::fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("omwsave"))
+        })
+        .map(|path| SaveGame {
+            modified: std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            header: read_save_header(&path).ok(),
+            path,
+        })
+        .collect();
+
+    saves.sort_by_key(|save| std::cmp::Reverse(save.modified));
+
+    CharacterSaves {
+        name: dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path: dir,
+        saves,
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Every character with a folder under `saves_path()`, most recently played first.
+    /// No saves folder yet just means an empty list.
+    pub fn save_games(&self) -> Vec<CharacterSaves> {
+        let mut characters: Vec<CharacterSaves> = std::fs::read_dir(self.saves_path())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .map(read_character)
+            .collect();
+
+        characters.sort_by_key(|character| {
+            std::cmp::Reverse(character.latest().and_then(|save| save.modified))
+        });
+
+        characters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{plugins::tests::tes3_plugin, util::temp_config};
+
+    fn save_file(player: &str, level: i32, cell: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        for (tag, value) in [
+            (b"PLNA", format!("{player}\0").into_bytes()),
+            (b"PLLE", level.to_le_bytes().to_vec()),
+            (b"PLCE", format!("{cell}\0").into_bytes()),
+        ] {
+            data.extend_from_slice(tag);
+            data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            data.extend_from_slice(&value);
+        }
+
+        let mut bytes = tes3_plugin(&["Morrowind.esm"]);
+        bytes.extend_from_slice(SAVE_MAGIC);
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.extend_from_slice(&data);
+
+        bytes
+    }
+
+    #[test]
+    fn test_save_games() {
+        let dir = temp_config("save_games", "user-data=Profile\n");
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert!(config.save_games().is_empty());
+
+        let character = config.saves_path().join("1_Nerevar");
+        std::fs::create_dir_all(&character).unwrap();
+        std::fs::write(
+            character.join("Quicksave.omwsave"),
+            save_file("Nerevar", 5, "Seyda Neen"),
+        )
+        .unwrap();
+        std::fs::write(character.join("Broken.omwsave"), b"not a save").unwrap();
+        std::fs::write(character.join("notes.txt"), b"").unwrap();
+
+        let characters = config.save_games();
+        assert_eq!(characters.len(), 1);
+        assert_eq!(characters[0].name, "1_Nerevar");
+        assert_eq!(characters[0].display_name(), "Nerevar");
+        assert_eq!(characters[0].saves.len(), 2);
+
+        let header = read_save_header(&character.join("Quicksave.omwsave")).unwrap();
+        assert_eq!(
+            header,
+            SaveHeader {
+                player_name: "Nerevar".into(),
+                player_level: Some(5),
+                player_class: String::new(),
+                player_cell: "Seyda Neen".into(),
+                content_files: vec!["Morrowind.esm".into()],
+            }
+        );
+        assert!(matches!(
+            read_save_header(&character.join("Broken.omwsave")),
+            Err(ConfigError::InvalidSave { .. })
+        ));
+
+        let mut oversized = b"TES3".to_vec();
+        oversized.extend_from_slice(&u32::MAX.to_le_bytes());
+        oversized.extend_from_slice(&[0; 8]);
+        std::fs::write(character.join("Oversized.omwsave"), oversized).unwrap();
+        assert!(matches!(
+            read_save_header(&character.join("Oversized.omwsave")),
+            Err(ConfigError::InvalidSave { .. })
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Screenshots under `screenshots_path()`, and the keys deciding how new ones get written.
+//! Those keys have no dedicated setting type, so they're stored as generic `key=value` lines.
+
+use std::{fmt, path::PathBuf, time::SystemTime};
+
+use crate::config::OpenMWConfiguration;
+
+pub const SCREENSHOT_FORMAT_KEY: &str = "screenshot-format";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScreenshotFormat {
+    Png,
+    Jpg,
+    Tga,
+}
+
+impl ScreenshotFormat {
+    pub const ALL: [ScreenshotFormat; 3] = [Self::Png, Self::Jpg, Self::Tga];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "png",
+            ScreenshotFormat::Jpg => "jpg",
+            ScreenshotFormat::Tga => "tga",
+        }
+    }
+
+    /// Case doesn't matter, and `jpeg` counts as `jpg`
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpg),
+            "tga" => Some(Self::Tga),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ScreenshotFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub path: PathBuf,
+    pub format: ScreenshotFormat,
+    pub modified: Option<SystemTime>,
+}
+
+impl OpenMWConfiguration {
+    /// Every image in the screenshots folder, newest first.
+    /// No folder yet just means an empty list.
+    pub fn screenshots(&self) -> Vec<Screenshot> {
+        let mut screenshots: Vec<Screenshot> = std::fs::read_dir(self.screenshots_path())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter_map(|path| {
+                let format = ScreenshotFormat::from_extension(path.extension()?.to_str()?)?;
+
+                Some(Screenshot {
+                    modified: std::fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok(),
+                    format,
+                    path,
+                })
+            })
+            .collect();
+
+        screenshots.sort_by_key(|screenshot| std::cmp::Reverse(screenshot.modified));
+
+        screenshots
+    }
+
+    /// The last `screenshot-format=` in the chain.
+    /// `None` when it's unset or names a format the engine can't write.
+    pub fn screenshot_format(&self) -> Option<ScreenshotFormat> {
+        self.get_generic(SCREENSHOT_FORMAT_KEY)
+            .last()
+            .and_then(|value| ScreenshotFormat::from_extension(value.trim()))
+    }
+
+    /// `None` removes the key, leaving the choice to the engine
+    pub fn set_screenshot_format(&mut self, format: Option<ScreenshotFormat>) {
+        self.set_generic(
+            SCREENSHOT_FORMAT_KEY,
+            format.map(|format| vec![format.to_string()]),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_screenshots() {
+        let dir = temp_config("screenshots", "user-data=Profile\nscreenshot-format=bmp\n");
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        assert!(config.screenshots().is_empty());
+        assert_eq!(config.screenshot_format(), None);
+
+        let folder = config.screenshots_path();
+        std::fs::create_dir_all(&folder).unwrap();
+        for name in ["screenshot000.png", "screenshot001.JPEG", "thumbs.db"] {
+            std::fs::write(folder.join(name), b"").unwrap();
+        }
+
+        let mut formats: Vec<ScreenshotFormat> = config
+            .screenshots()
+            .iter()
+            .map(|screenshot| screenshot.format)
+            .collect();
+        formats.sort_by_key(|format| format.extension());
+        assert_eq!(formats, vec![ScreenshotFormat::Jpg, ScreenshotFormat::Png]);
+
+        config.set_screenshot_format(Some(ScreenshotFormat::Tga));
+        assert_eq!(config.screenshot_format(), Some(ScreenshotFormat::Tga));
+        assert_eq!(config.get_generic(SCREENSHOT_FORMAT_KEY), vec!["tga"]);
+
+        config.set_screenshot_format(None);
+        assert!(config.get_generic(SCREENSHOT_FORMAT_KEY).is_empty());
+    }
+}
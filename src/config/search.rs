@@ -0,0 +1,163 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Finding text anywhere in the settings, for a launcher's search box.
+//! Matching ignores ASCII case, so the ranges it reports line up with the original text.
+
+use std::ops::Range;
+
+use crate::{
+    SettingHandle,
+    config::{OpenMWConfiguration, SettingValue},
+};
+
+/// Which part of a setting the text was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchField {
+    /// The key, or the name of a `fallback=` entry
+    Key,
+    /// The value as written in openmw.cfg
+    Value,
+    /// The comment lines above the setting
+    Comment,
+    /// The comment after the value, on the same line
+    InlineComment,
+    /// Where a directory setting resolves to
+    Path,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub handle: SettingHandle,
+    /// What kind of setting it is, eg `content` or `data`
+    pub key: String,
+    pub field: SearchField,
+    /// All of the field's text
+    pub text: String,
+    /// Where the first match is in `text`, in bytes
+    pub range: Range<usize>,
+}
+
+fn searchable_fields(setting: &SettingValue) -> Vec<(SearchField, String)> {
+    if let SettingValue::Disabled(setting) = setting {
+        return searchable_fields(setting);
+    }
+
+    let mut fields = vec![(SearchField::Key, setting.key().to_string())];
+
+    match setting {
+        SettingValue::DataDirectory(dir)
+        | SettingValue::UserData(dir)
+        | SettingValue::DataLocal(dir)
+        | SettingValue::Resources(dir)
+        | SettingValue::SubConfiguration(dir) => {
+            fields.push((SearchField::Value, dir.original().clone()));
+            fields.push((
+                SearchField::Path,
+                dir.parsed().to_string_lossy().into_owned(),
+            ));
+        }
+        SettingValue::ContentFile(file)
+        | SettingValue::BethArchive(file)
+        | SettingValue::Groundcover(file) => {
+            fields.push((SearchField::Value, file.value().clone()))
+        }
+        SettingValue::GameSetting(fallback) => {
+            fields.push((SearchField::Key, fallback.key().clone()));
+            fields.push((SearchField::Value, fallback.value()));
+        }
+        SettingValue::Encoding(encoding) => fields.push((
+            SearchField::Value,
+            encoding.encoding_type().as_str().to_string(),
+        )),
+        SettingValue::Generic(generic) => {
+            fields.push((SearchField::Value, generic.value().clone()))
+        }
+        SettingValue::Flag(_) | SettingValue::Disabled(_) => {}
+    }
+
+    fields.push((SearchField::Comment, setting.meta().comment().to_string()));
+    fields.push((
+        SearchField::InlineComment,
+        setting.meta().inline_comment().to_string(),
+    ));
+
+    fields
+}
+
+impl OpenMWConfiguration {
+    /// Every field of every setting containing `query`, in load order.
+    /// A setting shows up once for each field that matches. An empty query matches nothing.
+    pub fn search(&self, query: &str) -> Vec<SearchMatch> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let query = query.to_ascii_lowercase();
+
+        self.settings
+            .iter()
+            .flat_map(|setting| {
+                searchable_fields(setting)
+                    .into_iter()
+                    .filter_map(|(field, text)| {
+                        let start = text.to_ascii_lowercase().find(&query)?;
+
+                        Some(SearchMatch {
+                            handle: setting.handle(),
+                            key: setting.key().to_string(),
+                            field,
+                            range: start..start + query.len(),
+                            text,
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, config::util::temp_config};
+
+    #[test]
+    fn test_search_fields() {
+        let dir = temp_config(
+            "search",
+            "# the base game\ncontent=Morrowind.esm\ndata=\"Morrowind Data\" # vanilla\nfallback=Morrowind_Name,Vvardenfell\nfallback=Weather_Sunrise_Time,6\n",
+        );
+        let config = OpenMWConfiguration::new_with_options(
+            Some(dir),
+            ParseOptions::default().with_inline_comments(),
+        )
+        .unwrap();
+
+        let matches = config.search("MORROWIND");
+        let found: Vec<(&str, SearchField)> = matches
+            .iter()
+            .map(|found| (found.key.as_str(), found.field))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("content", SearchField::Value),
+                ("data", SearchField::Value),
+                ("data", SearchField::Path),
+                ("fallback", SearchField::Key),
+            ]
+        );
+
+        let base = &config.search("base game")[0];
+        assert_eq!(base.field, SearchField::Comment);
+        assert_eq!(&base.text[base.range.clone()], "base game");
+        assert_eq!(
+            config.search("vanilla")[0].field,
+            SearchField::InlineComment
+        );
+        assert!(config.search("").is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Building settings from the value part of their line, like `"Morrowind.esm".parse::<FileSetting>()`.
+//! `SettingContext` says which openmw.cfg they belong to and what comment goes above them, when that matters.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    ConfigError, bail_config,
+    config::{
+        directorysetting::DirectorySetting, encodingsetting::EncodingSetting,
+        filesetting::FileSetting, gamesetting::GameSettingType,
+    },
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingContext {
+    source_config: PathBuf,
+    comment: String,
+}
+
+impl SettingContext {
+    /// The openmw.cfg the settings belong to, or the directory holding it.
+    /// Relative `data=` style paths resolve against it, so the directory is what they need.
+    pub fn new(source_config: impl Into<PathBuf>) -> Self {
+        Self {
+            source_config: source_config.into(),
+            comment: String::new(),
+        }
+    }
+
+    /// Comment lines written above the setting, `#` included
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    pub fn source_config(&self) -> &Path {
+        &self.source_config
+    }
+
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    pub fn parse<T: ParseSetting>(&self, value: &str) -> Result<T, ConfigError> {
+        T::parse_with(value, self)
+    }
+}
+
+/// Settings which can be built from the text after the `=`
+pub trait ParseSetting: Sized {
+    fn parse_with(value: &str, context: &SettingContext) -> Result<Self, ConfigError>;
+}
+
+impl ParseSetting for GameSettingType {
+    fn parse_with(value: &str, context: &SettingContext) -> Result<Self, ConfigError> {
+        GameSettingType::try_from((
+            value.to_string(),
+            context.source_config.clone(),
+            &mut context.comment.clone(),
+        ))
+    }
+}
+
+impl ParseSetting for EncodingSetting {
+    fn parse_with(value: &str, context: &SettingContext) -> Result<Self, ConfigError> {
+        EncodingSetting::try_from((
+            value.trim().to_string(),
+            &context.source_config,
+            &mut context.comment.clone(),
+        ))
+    }
+}
+
+impl ParseSetting for FileSetting {
+    fn parse_with(value: &str, context: &SettingContext) -> Result<Self, ConfigError> {
+        let value = value.trim();
+        if value.is_empty() {
+            bail_config!(invalid_line, value.into(), context.source_config.clone());
+        }
+
+        Ok(FileSetting::new(value, &context.source_config).with_comment(Some(&context.comment)))
+    }
+}
+
+impl ParseSetting for DirectorySetting {
+    fn parse_with(value: &str, context: &SettingContext) -> Result<Self, ConfigError> {
+        let value = value.trim();
+        if value.is_empty() {
+            bail_config!(invalid_line, value.into(), context.source_config.clone());
+        }
+
+        Ok(DirectorySetting::new(value, context.source_config.clone())
+            .with_comment(Some(&context.comment)))
+    }
+}
+
+/// `FromStr` and `TryFrom<&str>` with the default context: no source file and no comment
+macro_rules! impl_from_str {
+    ($($setting:ty),* $(,)?) => {
+        $(
+            impl FromStr for $setting {
+                type Err = ConfigError;
+
+                fn from_str(value: &str) -> Result<Self, Self::Err> {
+                    SettingContext::default().parse(value)
+                }
+            }
+
+            impl TryFrom<&str> for $setting {
+                type Error = ConfigError;
+
+                fn try_from(value: &str) -> Result<Self, Self::Error> {
+                    value.parse()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_str!(
+    GameSettingType,
+    EncodingSetting,
+    FileSetting,
+    DirectorySetting
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EncodingType, GameSetting};
+
+    #[test]
+    fn test_parse_settings() {
+        let plugin: FileSetting = "Morrowind.esm".parse().unwrap();
+        assert_eq!(plugin.value(), "Morrowind.esm");
+        assert!(FileSetting::try_from("  ").is_err());
+
+        let encoding = EncodingSetting::try_from("win1251").unwrap();
+        assert_eq!(encoding.encoding_type(), &EncodingType::WIN1251);
+        assert!(matches!(
+            "utf8".parse::<EncodingSetting>(),
+            Err(ConfigError::BadEncoding { .. })
+        ));
+
+        let fallback: GameSettingType = "Weather_Sunrise_Time,6".parse().unwrap();
+        assert_eq!(fallback.key(), "Weather_Sunrise_Time");
+        assert!("Weather_Sunrise_Time".parse::<GameSettingType>().is_err());
+
+        let context = SettingContext::new("/games/openmw").with_comment("# main data\n");
+        let data: DirectorySetting = context.parse("\"Data Files\"").unwrap();
+        assert_eq!(data.parsed(), Path::new("/games/openmw/Data Files"));
+        assert_eq!(data.meta().comment(), "# main data\n");
+        assert_eq!(data.meta().source_config(), Path::new("/games/openmw"));
+    }
+}
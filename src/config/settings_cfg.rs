@@ -0,0 +1,267 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! The user's settings.cfg, which holds everything the engine doesn't read from openmw.cfg.
+//! It only contains what differs from the engine defaults, and people edit it by hand,
+//! So it's kept line by line and edits only touch the lines they're about.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ConfigError, bail_config,
+    config::{OpenMWConfiguration, fs::ConfigFs},
+};
+
+pub const SETTINGS_CFG_FILE: &str = "settings.cfg";
+
+enum Line<'a> {
+    Section(&'a str),
+    Entry(&'a str, &'a str),
+    Other,
+}
+
+fn classify(line: &str) -> Line<'_> {
+    let trimmed = line.trim();
+
+    if trimmed.starts_with('#') {
+        return Line::Other;
+    }
+
+    if let Some(name) = trimmed
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+    {
+        return Line::Section(name.trim());
+    }
+
+    match trimmed.split_once('=') {
+        Some((key, value)) => Line::Entry(key.trim(), value.trim()),
+        None => Line::Other,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingsCfg {
+    lines: Vec<String>,
+    no_final_newline: bool,
+    crlf: bool,
+}
+
+impl SettingsCfg {
+    pub fn parse(text: &str) -> Self {
+        Self {
+            lines: text.lines().map(str::to_string).collect(),
+            no_final_newline: !text.is_empty() && !text.ends_with('\n'),
+            crlf: text.contains("\r\n"),
+        }
+    }
+
+    pub fn read(fs: &dyn ConfigFs, path: &Path) -> Result<Self, ConfigError> {
+        Ok(Self::parse(&fs.read_to_string(path)?))
+    }
+
+    pub fn write(&self, fs: &dyn ConfigFs, path: &Path) -> Result<(), ConfigError> {
+        Ok(fs.write(path, self.to_string().as_bytes())?)
+    }
+
+    /// Each line with the section it's in
+    fn entries(&self) -> impl Iterator<Item = (usize, &str, Line<'_>)> {
+        let mut section = "";
+
+        self.lines.iter().enumerate().map(move |(index, line)| {
+            let line = classify(line);
+            if let Line::Section(name) = line {
+                section = name;
+            }
+
+            (index, section, line)
+        })
+    }
+
+    fn position(&self, section: &str, key: &str) -> Option<usize> {
+        self.entries()
+            .filter(|(_, in_section, line)| {
+                *in_section == section && matches!(line, Line::Entry(name, _) if *name == key)
+            })
+            .map(|(index, ..)| index)
+            .last()
+    }
+
+    /// The value the engine would use, which is the last one if a key is repeated
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries()
+            .filter_map(|(_, in_section, line)| match line {
+                Line::Entry(name, value) if in_section == section && name == key => Some(value),
+                _ => None,
+            })
+            .last()
+    }
+
+    /// Every section name, in file order
+    pub fn sections(&self) -> Vec<&str> {
+        self.entries()
+            .filter_map(|(_, _, line)| match line {
+                Line::Section(name) => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Rewrites the key's line if there is one.
+    /// Otherwise it goes after the last entry of its section, which is added at the end of the file if needed.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let line = format!("{key} = {value}");
+
+        if let Some(index) = self.position(section, key) {
+            self.lines[index] = line;
+            return;
+        }
+
+        let last_in_section = self
+            .entries()
+            .filter(|(_, in_section, line)| {
+                *in_section == section && matches!(line, Line::Section(_) | Line::Entry(..))
+            })
+            .map(|(index, ..)| index)
+            .last();
+
+        match last_in_section {
+            Some(index) => self.lines.insert(index + 1, line),
+            None => {
+                if self
+                    .lines
+                    .last()
+                    .is_some_and(|line| !line.trim().is_empty())
+                {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{section}]"));
+                self.lines.push(line);
+            }
+        }
+    }
+
+    /// Drops every line for the key, so the engine falls back to its default.
+    /// Returns whether there was one.
+    pub fn remove(&mut self, section: &str, key: &str) -> bool {
+        let before = self.lines.len();
+
+        while let Some(index) = self.position(section, key) {
+            self.lines.remove(index);
+        }
+
+        self.lines.len() != before
+    }
+}
+
+impl std::fmt::Display for SettingsCfg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let newline = match self.crlf {
+            true => "\r\n",
+            false => "\n",
+        };
+        let text = self.lines.join(newline);
+
+        match self.no_final_newline || self.lines.is_empty() {
+            true => write!(f, "{text}"),
+            false => write!(f, "{text}{newline}"),
+        }
+    }
+}
+
+impl OpenMWConfiguration {
+    /// settings.cfg in the user config directory, whether or not it exists yet
+    pub fn settings_cfg_path(&self) -> PathBuf {
+        self.user_config_path().join(SETTINGS_CFG_FILE)
+    }
+
+    /// An empty one if the file isn't there, since that just means every setting is at its default
+    pub fn settings_cfg(&self) -> Result<SettingsCfg, ConfigError> {
+        let path = self.settings_cfg_path();
+
+        match self.fs.is_file(&path) {
+            true => SettingsCfg::read(&*self.fs, &path),
+            false => Ok(SettingsCfg::default()),
+        }
+    }
+
+    pub fn save_settings_cfg(&self, settings: &SettingsCfg) -> Result<(), ConfigError> {
+        let path = self.settings_cfg_path();
+
+        if self.is_read_only() {
+            bail_config!(read_only, path);
+        }
+
+        settings.write(&*self.fs, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edits_keep_the_layout() {
+        let mut settings = SettingsCfg::parse(
+            "# tweaked by hand\n[Camera]\nfield of view = 75\n\n[General]\n# default is 1\nanisotropy = 4\n",
+        );
+
+        assert_eq!(settings.get("Camera", "field of view"), Some("75"));
+        assert_eq!(settings.get("General", "field of view"), None);
+
+        settings.set("Camera", "field of view", "90");
+        settings.set("Camera", "viewing distance", "8192");
+        settings.set("Shadows", "enable shadows", "true");
+        assert!(settings.remove("General", "anisotropy"));
+
+        assert_eq!(
+            settings.to_string(),
+            "# tweaked by hand\n[Camera]\nfield of view = 90\nviewing distance = 8192\n\n[General]\n# default is 1\n\n[Shadows]\nenable shadows = true\n"
+        );
+        assert_eq!(settings.sections(), vec!["Camera", "General", "Shadows"]);
+    }
+
+    #[test]
+    fn test_keeps_crlf() {
+        let mut settings = SettingsCfg::parse("[Camera]\r\nfield of view = 75\r\n");
+        settings.set("Camera", "viewing distance", "8192");
+
+        assert_eq!(
+            settings.to_string(),
+            "[Camera]\r\nfield of view = 75\r\nviewing distance = 8192\r\n"
+        );
+    }
+
+    #[test]
+    fn test_goes_through_the_config_fs() {
+        use std::sync::Arc;
+
+        use crate::config::{ParseOptions, fs::MemoryFs};
+
+        let fs = Arc::new(
+            MemoryFs::new()
+                .with_file("/virtual/openmw.cfg", "content=Morrowind.esm\n")
+                .with_file("/virtual/settings.cfg", "[Camera]\nfield of view = 75\n"),
+        );
+        let config = OpenMWConfiguration::new_with_options(
+            Some("/virtual".into()),
+            ParseOptions::default().with_fs(fs.clone()),
+        )
+        .unwrap();
+
+        let mut settings = config.settings_cfg().unwrap();
+        assert_eq!(settings.get("Camera", "field of view"), Some("75"));
+
+        settings.set("Camera", "field of view", "90");
+        config.save_settings_cfg(&settings).unwrap();
+
+        assert_eq!(
+            fs.read_to_string(Path::new("/virtual/settings.cfg"))
+                .unwrap(),
+            "[Camera]\nfield of view = 90\n"
+        );
+        assert!(!Path::new("/virtual").exists());
+    }
+}
@@ -0,0 +1,106 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! One openmw.cfg on its own, such as a fragment a mod ships to be pulled in with `config=`.
+//! None of the rest of the chain has to exist: `config=` lines are kept as settings but never followed,
+//! And nothing is added on top of what the file says, so there's no data-local directory or engine VFS entries.
+
+use std::{
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use crate::{
+    ConfigError,
+    config::{OpenMWConfiguration, fs::FsSlot, parseoptions::ParseOptions, util},
+};
+
+/// Derefs to `OpenMWConfiguration`, so everything it can do works here too,
+/// With new settings landing in this file since it's the only one there is
+#[derive(Debug)]
+pub struct SingleConfig(OpenMWConfiguration);
+
+impl SingleConfig {
+    /// `path` is an openmw.cfg, or the directory holding one
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::open_with_options(path, ParseOptions::default())
+    }
+
+    pub fn open_with_options(
+        path: impl AsRef<Path>,
+        options: ParseOptions,
+    ) -> Result<Self, ConfigError> {
+        let mut config = OpenMWConfiguration {
+            fs: FsSlot(options.fs.clone()),
+            ignore_case: options.ignore_case,
+            read_only: options.read_only,
+            single_file: true,
+            ..Default::default()
+        };
+        config.root_config = util::input_config_path(&*config.fs, path.as_ref())?;
+
+        config.load(&config.root_config.to_owned(), &options)?;
+        config.dirty.take();
+        config.set_event_sink(options.event_sink);
+
+        Ok(Self(config))
+    }
+
+    pub fn path(&self) -> &Path {
+        self.0.root_config_file()
+    }
+
+    /// Writes the file back, and nothing else
+    pub fn save(&self) -> Result<(), String> {
+        self.0.write_source(self.path())
+    }
+
+    pub fn into_inner(self) -> OpenMWConfiguration {
+        self.0
+    }
+}
+
+impl Deref for SingleConfig {
+    type Target = OpenMWConfiguration;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SingleConfig {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_single_config_fragment() {
+        let dir = temp_config(
+            "single_fragment",
+            "# fragment for My Mod\ndata=\"My Mod\"\nconfig=../elsewhere\ndata-local=Local\nresources=Resources\ncontent=MyMod.esp\n",
+        );
+
+        let mut fragment = SingleConfig::open(&dir).unwrap();
+        assert_eq!(fragment.sub_configs().count(), 1);
+        assert_eq!(fragment.user_config_path(), dir);
+        assert_eq!(fragment.data_directories(), vec![&dir.join("My Mod")]);
+        assert!(!dir.join("Local").exists());
+
+        fragment.add_content_file("MyMod Patch.esp").unwrap();
+        fragment.add_data_directory("Patches".into());
+        fragment.save().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.join("openmw.cfg")).unwrap(),
+            "# fragment for My Mod\ndata=\"My Mod\"\nconfig=../elsewhere\ndata-local=Local\nresources=Resources\ncontent=MyMod.esp\ncontent=MyMod Patch.esp\ndata=Patches\n"
+        );
+    }
+}
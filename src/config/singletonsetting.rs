@@ -27,9 +27,9 @@ macro_rules! impl_singleton_setting {
                         .position(|setting| matches!(setting, SettingValue::$variant(_)));
 
                     match (index, new) {
-                        (Some(i), Some(value)) => self.settings[i] = SettingValue::$variant(value),
-                        (None, Some(value)) => self.settings.push(SettingValue::$variant(value)),
-                        (Some(i), None) => { self.settings.remove(i); }
+                        (Some(i), Some(value)) => self.replace_setting_at(i, SettingValue::$variant(value)),
+                        (None, Some(value)) => self.push_setting(SettingValue::$variant(value)),
+                        (Some(i), None) => { self.remove_setting_at(i); }
                         (None, None) => {}
                     }
                 }
@@ -0,0 +1,261 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Undo and redo for editors.
+//! A snapshot is the settings list at one point in time. Taking one only shares the list the configuration already holds,
+//! Which gets copied the first time it's edited afterwards, so keeping plenty of snapshots around is cheap.
+//! `transaction` uses the same mechanism to roll back a multi-step edit which fails halfway.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use crate::{
+    SettingHandle,
+    config::{OpenMWConfiguration, SettingValue, events::ConfigNotification},
+};
+
+#[derive(Debug, Clone)]
+pub struct ConfigSnapshot {
+    settings: Arc<Vec<SettingValue>>,
+}
+
+impl ConfigSnapshot {
+    pub fn settings(&self) -> &[SettingValue] {
+        &self.settings
+    }
+
+    /// Whether both snapshots hold the same list, without comparing every setting
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.settings, &other.settings)
+    }
+}
+
+impl OpenMWConfiguration {
+    pub fn snapshot(&self) -> ConfigSnapshot {
+        ConfigSnapshot {
+            settings: Arc::clone(&self.settings),
+        }
+    }
+
+    /// The settings for changing, copied first if a snapshot still shares them
+    pub(crate) fn settings_mut(&mut self) -> &mut Vec<SettingValue> {
+        Arc::make_mut(&mut self.settings)
+    }
+
+    /// Puts the settings back the way they were when `snapshot` was taken.
+    /// Everything that differs is reported to the event sink and marked dirty, so `save_all` writes the restored state
+    /// Even to files which were saved in between.
+    pub fn restore(&mut self, snapshot: &ConfigSnapshot) {
+        let current = std::mem::replace(&mut self.settings, Arc::clone(&snapshot.settings));
+
+        let before: HashMap<SettingHandle, (usize, &SettingValue)> = current
+            .iter()
+            .enumerate()
+            .map(|(index, setting)| (setting.handle(), (index, setting)))
+            .collect();
+        let after: HashMap<SettingHandle, usize> = self
+            .settings
+            .iter()
+            .enumerate()
+            .map(|(index, setting)| (setting.handle(), index))
+            .collect();
+
+        for setting in current
+            .iter()
+            .filter(|setting| !after.contains_key(&setting.handle()))
+        {
+            self.notify(ConfigNotification::SettingRemoved(setting));
+        }
+
+        // Positions are compared among the settings both lists share, so an insertion doesn't count as moving everything after it
+        let shared_before: Vec<SettingHandle> = current
+            .iter()
+            .map(SettingValue::handle)
+            .filter(|handle| after.contains_key(handle))
+            .collect();
+        let mut shared_index = 0;
+
+        for setting in self.settings.iter() {
+            match before.get(&setting.handle()) {
+                None => self.notify(ConfigNotification::SettingAdded(setting)),
+                Some((_, previous)) => {
+                    if previous.to_string() != setting.to_string() {
+                        self.notify(ConfigNotification::SettingChanged(setting));
+                    }
+                    if shared_before.get(shared_index) != Some(&setting.handle()) {
+                        self.notify(ConfigNotification::SettingMoved(setting));
+                    }
+                    shared_index += 1;
+                }
+            }
+        }
+    }
+
+    /// Runs several edits as one: if `edit` returns an error, every change it made is undone before the error is handed back.
+    /// A rolled back transaction leaves the same files dirty as before it started, though the event sink still hears about both directions.
+    pub fn transaction<T, E, F>(&mut self, edit: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Self) -> Result<T, E>,
+    {
+        let snapshot = self.snapshot();
+        let dirty = self.dirty_sources();
+
+        edit(self).inspect_err(|_| {
+            self.restore(&snapshot);
+            self.dirty.restore(dirty.into_iter().collect());
+        })
+    }
+}
+
+/// Snapshots taken before each edit, with a limit on how far back undo goes
+#[derive(Debug, Clone)]
+pub struct UndoStack {
+    undo: VecDeque<ConfigSnapshot>,
+    redo: Vec<ConfigSnapshot>,
+    limit: usize,
+}
+
+impl UndoStack {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Call right before changing `config`. Anything that could have been redone is dropped.
+    pub fn record(&mut self, config: &OpenMWConfiguration) {
+        self.redo.clear();
+        self.undo.push_back(config.snapshot());
+
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Returns false if there was nothing to undo
+    pub fn undo(&mut self, config: &mut OpenMWConfiguration) -> bool {
+        let Some(snapshot) = self.undo.pop_back() else {
+            return false;
+        };
+
+        self.redo.push(config.snapshot());
+        config.restore(&snapshot);
+        true
+    }
+
+    /// Returns false if there was nothing to redo
+    pub fn redo(&mut self, config: &mut OpenMWConfiguration) -> bool {
+        let Some(snapshot) = self.redo.pop() else {
+            return false;
+        };
+
+        self.undo.push_back(config.snapshot());
+        config.restore(&snapshot);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_restore_snapshot() {
+        let dir = temp_config("snapshot", "content=Morrowind.esm\nfallback=iMaxSpeed,10\n");
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let original = config.to_string();
+        let snapshot = config.snapshot();
+        assert!(snapshot.ptr_eq(&config.snapshot()));
+
+        config.add_content_file("Tribunal.esm").unwrap();
+        config.set_game_setting_value("iMaxSpeed", "20").unwrap();
+        config.remove_content_file("Morrowind.esm");
+        assert!(!snapshot.ptr_eq(&config.snapshot()));
+        assert_eq!(snapshot.settings().len(), 2);
+        config.save_all().unwrap();
+        assert!(!config.is_dirty());
+
+        config.restore(&snapshot);
+        assert_eq!(config.to_string(), original);
+        assert_eq!(config.content_files(), vec!["Morrowind.esm"]);
+        assert!(config.is_dirty());
+    }
+
+    #[test]
+    fn test_transaction_rolls_back() {
+        let dir = temp_config("transaction", "content=Morrowind.esm\ndata=Mods\n");
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let original = config.to_string();
+
+        let result = config.transaction(|tx| {
+            tx.add_content_file("Tribunal.esm")?;
+            tx.remove_data_directory(&dir.join("Mods"));
+            tx.add_content_file("Morrowind.esm")
+        });
+
+        assert!(result.is_err());
+        assert_eq!(config.to_string(), original);
+        assert!(!config.is_dirty());
+
+        config
+            .transaction(|tx| tx.add_content_file("Tribunal.esm"))
+            .unwrap();
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "Tribunal.esm"]
+        );
+    }
+
+    #[test]
+    fn test_undo_stack() {
+        let dir = temp_config("undo_stack", "content=Morrowind.esm\n");
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let mut history = UndoStack::new(2);
+
+        for plugin in ["A.esp", "B.esp", "C.esp"] {
+            history.record(&config);
+            config.add_content_file(plugin).unwrap();
+        }
+
+        assert!(history.undo(&mut config));
+        assert!(history.undo(&mut config));
+        assert!(!history.undo(&mut config));
+        assert_eq!(config.content_files(), vec!["Morrowind.esm", "A.esp"]);
+
+        assert!(history.redo(&mut config));
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "A.esp", "B.esp"]
+        );
+
+        history.record(&config);
+        config.remove_content_file("A.esp");
+        assert!(!history.can_redo());
+        assert!(history.undo(&mut config));
+        assert_eq!(
+            config.content_files(),
+            vec!["Morrowind.esm", "A.esp", "B.esp"]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Numbers about the configuration as a whole, for a launcher's diagnostics screen.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::config::OpenMWConfiguration;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigStats {
+    /// How many entries there are of each key, eg `content` or `data`
+    pub entries: BTreeMap<String, usize>,
+    pub total_entries: usize,
+    /// The openmw.cfg files in the chain, the root one included
+    pub source_files: usize,
+    pub missing_data_directories: usize,
+    /// Content files which no data directory provides
+    pub missing_content_files: usize,
+    /// Total size of everything in the data directories, when it was asked for
+    pub data_directory_bytes: Option<u64>,
+}
+
+/// Everything under `path`, recursively. Anything unreadable counts as empty.
+pub(crate) fn directory_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+            Ok(_) => entry.metadata().map_or(0, |metadata| metadata.len()),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+impl OpenMWConfiguration {
+    /// Counts which only need what was already loaded, plus a listing of each data directory
+    pub fn stats(&self) -> ConfigStats {
+        let mut stats = ConfigStats {
+            total_entries: self.settings.len(),
+            source_files: self.chain_files().len(),
+            ..Default::default()
+        };
+
+        for setting in self.settings.iter() {
+            *stats.entries.entry(setting.key().to_string()).or_default() += 1;
+        }
+
+        stats.missing_data_directories = self
+            .data_directories()
+            .into_iter()
+            .filter(|dir| {
+                !self.fs.is_dir(dir) && !crate::android::is_content_uri(&dir.to_string_lossy())
+            })
+            .count();
+
+        let index = self.data_file_index();
+        stats.missing_content_files = self
+            .content_files_iter()
+            .filter(|plugin| !index.contains_key(&plugin.value().to_ascii_lowercase()))
+            .count();
+
+        stats
+    }
+
+    /// `stats`, plus how much disk space the data directories take up.
+    /// That walks every file in every directory, on a thread pool with `ParseOptions::parallel`.
+    pub fn stats_with_disk_usage(&self) -> ConfigStats {
+        let mut stats = self.stats();
+        let directories: Vec<&PathBuf> = self.data_directories();
+
+        #[cfg(feature = "parallel")]
+        let sizes = match self.parallel {
+            true => self.directory_sizes(&directories),
+            false => directories
+                .iter()
+                .map(|dir| directory_size(dir))
+                .collect::<Vec<_>>(),
+        };
+        #[cfg(not(feature = "parallel"))]
+        let sizes: Vec<u64> = directories.iter().map(|dir| directory_size(dir)).collect();
+
+        stats.data_directory_bytes = Some(sizes.into_iter().sum());
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_stats() {
+        let user = temp_config("stats_user", "content=Missing.esp\n");
+        let dir = temp_config(
+            "stats",
+            &format!(
+                "data=Data\ndata=Gone\ncontent=Morrowind.esm\nfallback=Key,1\nconfig={}\n",
+                user.display()
+            ),
+        );
+        std::fs::create_dir_all(dir.join("Data/meshes")).unwrap();
+        std::fs::write(dir.join("Data/Morrowind.esm"), [0u8; 100]).unwrap();
+        std::fs::write(dir.join("Data/meshes/a.nif"), [0u8; 20]).unwrap();
+
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let stats = config.stats();
+
+        assert_eq!(stats.source_files, 2);
+        assert_eq!(stats.entries["content"], 2);
+        assert_eq!(stats.entries["fallback"], 1);
+        assert_eq!(stats.total_entries, stats.entries.values().sum::<usize>());
+        assert_eq!(stats.missing_data_directories, 1);
+        assert_eq!(stats.missing_content_files, 1);
+        assert_eq!(stats.data_directory_bytes, None);
+
+        assert_eq!(
+            config.stats_with_disk_usage().data_directory_bytes,
+            Some(120)
+        );
+    }
+}
@@ -22,54 +22,213 @@ fn strip_special_components<P: AsRef<std::path::Path>>(input: P) -> PathBuf {
     result
 }
 
+/// Lowercases the whole of Unicode rather than just ASCII, so `ÄRGERNIS.esp` and `ärgernis.esp` are the same file
+pub fn fold_case(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Compares names the way a case-insensitive filesystem would, without allocating
+pub fn eq_fold_case(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .eq(b.chars().flat_map(char::to_lowercase))
+}
+
+/// The reverse of the quote handling in `parse_data_directory`, the way OpenMW's launcher writes paths
+pub fn quote_data_directory(data_dir: &str) -> String {
+    format!("\"{}\"", data_dir.replace('&', "&&").replace('"', "&\""))
+}
+
+/// Writes a path so `parse_data_directory` reads back exactly the same thing.
+/// Plain paths stay unquoted, and anything with quotes, escapes or surrounding whitespace gets quoted.
+/// So does a `#`, which would otherwise start an inline comment when those are turned on.
+pub fn serialize_data_directory(data_dir: &str) -> String {
+    let needs_quoting =
+        data_dir.is_empty() || data_dir.contains(['"', '&', '#']) || data_dir.trim() != data_dir;
+
+    match needs_quoting {
+        true => quote_data_directory(data_dir),
+        false => data_dir.to_string(),
+    }
+}
+
+/// Splits `key=value # comment` into the setting and the comment, starting from its `#`.
+/// Only a `#` after whitespace counts, and never one inside a quoted path.
+pub fn split_inline_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut previous = ' ';
+
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '&' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted && previous.is_whitespace() && index > 0 => {
+                return (line[..index].trim_end(), Some(&line[index..]));
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+
+    (line, None)
+}
+
+/// Splits `?token?rest` into `?token?` and `rest`
+pub fn split_path_token(data_dir: &str) -> Option<(&str, &str)> {
+    let end = data_dir.strip_prefix('?')?.find('?')? + 2;
+    Some(data_dir.split_at(end))
+}
+
+pub(crate) fn drive_letter(path: &str) -> Option<char> {
+    let mut chars = path.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => Some(letter),
+        _ => None,
+    }
+}
+
+/// Turns `\\?\C:\Mods` into `C:\Mods` and `\\?\UNC\server\share` into `\\server\share`,
+/// Since the verbatim forms skip the normalization everything else gets. Also uses backslashes throughout.
+pub fn strip_verbatim_prefix(path: &str) -> String {
+    let path = path.replace('/', "\\");
+
+    if let Some(share) = path
+        .strip_prefix(r"\\?\UNC\")
+        .or_else(|| path.strip_prefix(r"\\.\UNC\"))
+    {
+        return format!(r"\\{share}");
+    }
+
+    match path
+        .strip_prefix(r"\\?\")
+        .or_else(|| path.strip_prefix(r"\\.\"))
+        .filter(|rest| drive_letter(rest).is_some())
+    {
+        Some(rest) => rest.to_string(),
+        None => path,
+    }
+}
+
+/// Windows paths the way the engine resolves them: verbatim prefixes are dropped,
+/// And a drive-relative `D:Mods` goes under the config directory when that's on the same drive.
+/// On another drive it's taken from the drive's root, since the engine's working directory there can't be known.
+pub fn normalize_windows_path(config_dir: &str, data_dir: &str) -> String {
+    let path = strip_verbatim_prefix(data_dir);
+
+    let Some(drive) = drive_letter(&path) else {
+        return path;
+    };
+
+    let rest = &path[2..];
+    if rest.starts_with('\\') {
+        return path;
+    }
+
+    let config_dir = strip_verbatim_prefix(config_dir);
+    match drive_letter(&config_dir).is_some_and(|own| own.eq_ignore_ascii_case(&drive)) {
+        true => format!("{}\\{rest}", config_dir.trim_end_matches('\\')),
+        false => format!("{drive}:\\{rest}"),
+    }
+}
+
+/// The text inside the quotes of `"Data Files"`, with `&` escapes undone. Unquoted paths come back unchanged.
+pub fn unquote_data_directory(data_dir: &str) -> String {
+    if !data_dir.starts_with('"') {
+        return data_dir.to_string();
+    }
+
+    let mut result = String::new();
+    let mut i = 1;
+    let chars: Vec<char> = data_dir.chars().collect();
+    while i < chars.len() {
+        if chars[i] == '&' {
+            i += 1; // skip the next char (escape)
+        } else if chars[i] == '"' {
+            break;
+        }
+        if i < chars.len() {
+            result.push(chars[i]);
+        }
+        i += 1;
+    }
+
+    result
+}
+
 /// Parses a data directory string according to OpenMW rules.
 /// https://openmw.readthedocs.io/en/latest/reference/modding/paths.html#openmw-cfg-syntax
 pub fn parse_data_directory<P: AsRef<std::path::Path>>(
     config_dir: &P,
     mut data_dir: String,
 ) -> PathBuf {
-    // Quote handling
-    if data_dir.starts_with('"') {
-        let mut result = String::new();
-        let mut i = 1;
-        let chars: Vec<char> = data_dir.chars().collect();
-        while i < chars.len() {
-            if chars[i] == '&' {
-                i += 1; // skip the next char (escape)
-            } else if chars[i] == '"' {
-                break;
-            }
-            if i < chars.len() {
-                result.push(chars[i]);
-            }
-            i += 1;
-        }
-        data_dir = result;
-    }
+    data_dir = unquote_data_directory(&data_dir);
 
-    // Token replacement
-    if data_dir.starts_with("?userdata?") {
-        let suffix = data_dir["?userdata?".len()..].trim_start_matches(&['/', '\\'][..]);
+    if crate::android::is_content_uri(&data_dir) {
+        return PathBuf::from(data_dir);
+    }
 
-        data_dir = crate::default_userdata_path()
-            .join(suffix)
+    // Token replacement. Unknown tokens, or ones the platform has no directory for, are left alone
+    if let Some((token, suffix)) = split_path_token(&data_dir)
+        && let Some(base) = crate::paths::resolve_token(token)
+    {
+        data_dir = base
+            .join(suffix.trim_start_matches(&['/', '\\'][..]))
             .to_string_lossy()
             .to_string();
-    } else if data_dir.starts_with("?userconfig?") {
-        let suffix = data_dir["?userdata?".len()..].trim_start_matches(&['/', '\\'][..]);
+    }
 
-        data_dir = crate::default_config_path()
-            .join(suffix)
-            .to_string_lossy()
-            .to_string();
+    let mut config_dir = config_dir.as_ref().to_path_buf();
+    if cfg!(windows) {
+        let config = config_dir.to_string_lossy().into_owned();
+        data_dir = normalize_windows_path(&config, &data_dir);
+        config_dir = PathBuf::from(strip_verbatim_prefix(&config));
     }
 
-    let data_dir = data_dir.replace(['/', '\\'], &std::path::MAIN_SEPARATOR.to_string());
+    let data_dir = data_dir.replace(['/', '\\'], std::path::MAIN_SEPARATOR_STR);
 
     let mut path = PathBuf::from(&data_dir);
     if !path.is_absolute() {
-        path = config_dir.as_ref().join(path);
+        path = config_dir.join(path);
     }
 
     strip_special_components(path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_path_normalization() {
+        let config = r"C:\Users\me\Documents\My Games\OpenMW";
+
+        for (data_dir, expected) in [
+            (r"\\?\D:\Mods\Tamriel", r"D:\Mods\Tamriel"),
+            (r"\\?\UNC\nas\games\Mods", r"\\nas\games\Mods"),
+            (r"\\.\C:\Mods", r"C:\Mods"),
+            ("//nas/games/Mods", r"\\nas\games\Mods"),
+            (r"\\nas\games\Mods", r"\\nas\games\Mods"),
+            (
+                "C:Mods/Tamriel",
+                r"C:\Users\me\Documents\My Games\OpenMW\Mods\Tamriel",
+            ),
+            ("c:Mods", r"C:\Users\me\Documents\My Games\OpenMW\Mods"),
+            ("D:Mods", r"D:\Mods"),
+            ("D:/Mods", r"D:\Mods"),
+            ("Mods/Tamriel", r"Mods\Tamriel"),
+        ] {
+            assert_eq!(
+                normalize_windows_path(config, data_dir),
+                expected,
+                "{data_dir}"
+            );
+        }
+
+        assert_eq!(
+            normalize_windows_path(r"\\?\C:\OpenMW\", "C:Mods"),
+            r"C:\OpenMW\Mods"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ConfigError, GameSetting, SettingHandle, bail_config,
+    config::{OpenMWConfiguration, ParseOptions, SettingValue, directorysetting::DirectorySetting},
+};
+
+impl OpenMWConfiguration {
+    /// Writes a brand new openmw.cfg into `dir`, containing `settings`, and returns the `config=` entry which points at it.
+    /// An openmw.cfg which already exists there is never overwritten.
+    ///
+    /// With `register`, the entry is also added to the user openmw.cfg and the settings join the chain.
+    /// Like in the engine, the last `config=` is the user configuration, so the new directory becomes it!
+    pub fn create_subconfig(
+        &mut self,
+        dir: &Path,
+        settings: Vec<SettingValue>,
+        register: bool,
+    ) -> Result<DirectorySetting, ConfigError> {
+        let cfg_path = dir.join("openmw.cfg");
+        if self.read_only {
+            bail_config!(read_only, cfg_path);
+        } else if self.fs.exists(&cfg_path) {
+            bail_config!(sub_config_exists, dir);
+        }
+
+        self.fs.create_dir_all(dir)?;
+
+        let settings: Vec<SettingValue> = settings
+            .into_iter()
+            .map(|mut setting| {
+                setting.meta_mut().source_config = cfg_path.clone();
+                setting.meta_mut().handle = SettingHandle::next();
+                setting
+            })
+            .collect();
+
+        let contents: String = settings.iter().map(SettingValue::to_string).collect();
+        self.fs.write(&cfg_path, contents.as_bytes())?;
+
+        let entry = DirectorySetting::from_path(dir, self.user_config_path());
+
+        if register {
+            self.add_sub_config(dir)?;
+        }
+
+        Ok(entry)
+    }
+
+    /// Adds a `config=` entry to the user openmw.cfg and loads the configuration it points at.
+    /// Relative paths are resolved against the user config directory, as they would be when it's read back.
+    /// Anything which would make the chain loop back on itself is refused with `ConfigError::ConfigCycle`.
+    pub fn add_sub_config(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let entry = DirectorySetting::from_path(path, self.user_config_path());
+        let cfg_path = entry.parsed().join("openmw.cfg");
+
+        if self
+            .sub_configs()
+            .any(|existing| existing.parsed() == entry.parsed())
+        {
+            return Ok(());
+        }
+
+        if !self.fs.is_file(&cfg_path) {
+            bail_config!(cannot_find, cfg_path);
+        }
+
+        // Read it on its own first, to see where its config= lines lead without touching this configuration
+        let mut probe = OpenMWConfiguration {
+            root_config: cfg_path.clone(),
+            fs: self.fs.clone(),
+            ..Default::default()
+        };
+        probe.load(&cfg_path, &ParseOptions::default())?;
+
+        let chain = self.chain_files();
+        let probed = probe.chain_files();
+        if let Some(repeat) = probed.iter().position(|file| chain.contains(file)) {
+            let mut cycle = vec![self.user_config_path().join("openmw.cfg")];
+            cycle.extend(probed.into_iter().take(repeat + 1));
+            bail_config!(config_cycle, cycle);
+        }
+
+        self.push_setting(SettingValue::SubConfiguration(entry));
+        self.load(&cfg_path, &ParseOptions::default())
+    }
+
+    /// Removes the `config=` entry for `path`, along with every setting which came from it or from configs it pulled in.
+    /// Returns whether there was such an entry.
+    pub fn remove_sub_config(&mut self, path: &Path) -> bool {
+        let Some(entry) = self
+            .sub_configs()
+            .find(|existing| existing.refers_to(path))
+            .cloned()
+        else {
+            return false;
+        };
+
+        let mut removed = vec![entry.parsed().join("openmw.cfg")];
+        let mut index = 0;
+        while index < removed.len() {
+            let nested: Vec<PathBuf> = self
+                .sub_configs()
+                .filter(|sub| sub.meta().is_from(&removed[index]))
+                .map(|sub| sub.parsed().join("openmw.cfg"))
+                .filter(|file| !removed.contains(file))
+                .collect();
+
+            removed.extend(nested);
+            index += 1;
+        }
+
+        self.clear_matching(|setting| {
+            setting.handle() == entry.meta().handle()
+                || removed.iter().any(|file| setting.meta().is_from(file))
+        });
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{
+        OpenMWConfiguration, SettingValue, filesetting::FileSetting, util::temp_config,
+    };
+
+    #[test]
+    fn test_create_subconfig() {
+        let root = temp_config("create_subconfig", "content=Morrowind.esm\n");
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        let fragment = root.join("fragments").join("MyMod");
+
+        let plugin = SettingValue::ContentFile(FileSetting::new("MyMod.esp", &root));
+
+        let entry = config
+            .create_subconfig(&fragment, vec![plugin.clone()], true)
+            .unwrap();
+        assert_eq!(entry.parsed(), &fragment);
+
+        assert_eq!(
+            std::fs::read_to_string(fragment.join("openmw.cfg")).unwrap(),
+            "content=MyMod.esp\n"
+        );
+        assert!(config.has_content_file("MyMod.esp"));
+        assert_eq!(config.user_config_path(), fragment);
+        assert_eq!(config.dirty_sources(), vec![root.join("openmw.cfg")]);
+
+        config.save_all().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("openmw.cfg")).unwrap(),
+            format!("content=Morrowind.esm\nconfig={}\n", fragment.display())
+        );
+
+        assert!(matches!(
+            config.create_subconfig(&fragment, vec![plugin], false),
+            Err(crate::ConfigError::SubConfigExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_add_and_remove_sub_config() {
+        let root = temp_config("add_sub_config", "content=Morrowind.esm\n");
+        let child = root.join("child");
+        let grandchild = root.join("grandchild");
+        std::fs::create_dir_all(&child).unwrap();
+        std::fs::create_dir_all(&grandchild).unwrap();
+        std::fs::write(
+            child.join("openmw.cfg"),
+            format!("content=Child.esp\nconfig={}\n", grandchild.display()),
+        )
+        .unwrap();
+        std::fs::write(grandchild.join("openmw.cfg"), "content=Grandchild.esp\n").unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        config.add_sub_config(&child).unwrap();
+        assert!(config.has_content_file("Child.esp"));
+        assert!(config.has_content_file("Grandchild.esp"));
+        assert_eq!(config.user_config_path(), grandchild);
+
+        assert!(config.remove_sub_config(&child));
+        assert_eq!(config.sub_configs().count(), 0);
+        assert!(!config.has_content_file("Grandchild.esp"));
+        assert!(config.has_content_file("Morrowind.esm"));
+        assert!(!config.remove_sub_config(&child));
+    }
+
+    #[test]
+    fn test_sub_config_cycle_is_rejected() {
+        let root = temp_config("sub_config_cycle", "content=Morrowind.esm\n");
+        let child = root.join("child");
+        std::fs::create_dir_all(&child).unwrap();
+        std::fs::write(
+            child.join("openmw.cfg"),
+            format!("config={}\n", root.display()),
+        )
+        .unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+        let Err(crate::ConfigError::ConfigCycle { chain }) = config.add_sub_config(&child) else {
+            panic!("expected a cycle");
+        };
+
+        assert_eq!(
+            chain,
+            vec![
+                root.join("openmw.cfg"),
+                child.join("openmw.cfg"),
+                root.join("openmw.cfg")
+            ]
+        );
+        assert_eq!(config.sub_configs().count(), 0);
+    }
+}
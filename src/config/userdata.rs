@@ -0,0 +1,136 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Where the engine keeps what it writes while playing.
+//! Everything hangs off `user-data=`, which falls back to the platform's user data directory like it does in the engine.
+
+use std::path::PathBuf;
+
+use crate::{ConfigError, bail_config, config::OpenMWConfiguration};
+
+impl OpenMWConfiguration {
+    /// The `user-data=` directory, or the platform default when no openmw.cfg sets one
+    pub fn userdata_path(&self) -> PathBuf {
+        self.userdata()
+            .map_or_else(crate::default_userdata_path, |dir| dir.parsed().clone())
+    }
+
+    /// The `data-local=` directory, or `data` inside the user data directory
+    pub fn data_local_path(&self) -> PathBuf {
+        self.data_local().map_or_else(
+            || self.userdata_path().join("data"),
+            |dir| dir.parsed().clone(),
+        )
+    }
+
+    /// `data_local_path`, created first if it doesn't exist yet.
+    /// `new` already does this for a `data-local=` entry, unless told not to by `ParseOptions`.
+    pub fn local_data_dir_ensure(&self) -> Result<PathBuf, ConfigError> {
+        let path = self.data_local_path();
+
+        if !self.fs.is_dir(&path) {
+            if self.is_read_only() {
+                bail_config!(read_only, path);
+            }
+            self.fs.create_dir_all(&path)?;
+        }
+
+        Ok(path)
+    }
+
+    /// Files directly inside the data-local directory, sorted by name. Empty if it doesn't exist.
+    pub fn data_local_files(&self) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(self.data_local_path()) else {
+            return Vec::new();
+        };
+
+        let mut files: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Where a tool should put a plugin it generated, like merged leveled lists, so the engine loads it last.
+    /// That's data-local when it exists and can be written to, and otherwise the highest priority data directory which can.
+    /// Nothing is created, see `local_data_dir_ensure` for that.
+    pub fn writable_data_dir(&self) -> Option<PathBuf> {
+        let data_local = self.data_local_path();
+        if self.fs.is_dir(&data_local) && self.fs.can_write_to_dir(&data_local) {
+            return Some(data_local);
+        }
+
+        self.data_directories()
+            .into_iter()
+            .rev()
+            .find(|dir| self.fs.is_dir(dir) && self.fs.can_write_to_dir(dir))
+            .cloned()
+    }
+
+    /// One folder per character, each holding their `.omwsave` files
+    pub fn saves_path(&self) -> PathBuf {
+        self.userdata_path().join("saves")
+    }
+
+    pub fn screenshots_path(&self) -> PathBuf {
+        self.userdata_path().join("screenshots")
+    }
+
+    /// The navigation mesh cache, which is safe to delete and gets rebuilt as cells are visited
+    pub fn navmeshdb_path(&self) -> PathBuf {
+        self.userdata_path().join("navmesh.db")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_userdata_paths() {
+        let dir = temp_config("userdata_paths", "user-data=Profile\n");
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let profile = dir.join("Profile");
+
+        assert_eq!(config.userdata_path(), profile);
+        assert_eq!(config.data_local_path(), profile.join("data"));
+        assert_eq!(config.saves_path(), profile.join("saves"));
+        assert_eq!(config.screenshots_path(), profile.join("screenshots"));
+        assert_eq!(config.navmeshdb_path(), profile.join("navmesh.db"));
+
+        let dir = temp_config("userdata_defaults", "data-local=Local\n");
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+
+        assert_eq!(config.data_local_path(), dir.join("Local"));
+        assert_eq!(
+            config.saves_path(),
+            crate::default_userdata_path().join("saves")
+        );
+    }
+
+    #[test]
+    fn test_data_local_helpers() {
+        let dir = temp_config("data_local_helpers", "data=Mods\ndata-local=Local\n");
+        std::fs::create_dir_all(dir.join("Mods")).unwrap();
+
+        let options = crate::ParseOptions::default().creating_missing_dirs(false);
+        let config = OpenMWConfiguration::new_with_options(Some(dir.clone()), options).unwrap();
+        assert!(config.data_local_files().is_empty());
+        assert_eq!(config.writable_data_dir(), Some(dir.join("Mods")));
+
+        let local = config.local_data_dir_ensure().unwrap();
+        assert_eq!(local, dir.join("Local"));
+        std::fs::write(local.join("Merged Lists.esp"), "").unwrap();
+
+        assert_eq!(config.writable_data_dir(), Some(local.clone()));
+        assert_eq!(
+            config.data_local_files(),
+            vec![local.join("Merged Lists.esp")]
+        );
+    }
+}
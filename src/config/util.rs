@@ -3,29 +3,51 @@
 // Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
 
-pub fn debug_log(message: String) {
+/// Without the `tracing` feature, messages are printed when `CFG_DEBUG` is set
+#[cfg(not(feature = "tracing"))]
+pub fn debug_log(message: std::fmt::Arguments) {
     if std::env::var("CFG_DEBUG").is_ok() {
         println!("[CONFIG DEBUG]: {message}")
     }
 }
 
+#[cfg(feature = "tracing")]
+pub fn debug_log(message: std::fmt::Arguments) {
+    tracing::debug!("{message}")
+}
+
+/// Quotes and escapes a string for embedding in JSON output
+pub fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for char in value.chars() {
+        match char {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            char if (char as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", char as u32)),
+            char => quoted.push(char),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
 pub fn user_config_path(
     sub_configs: &Vec<&std::path::PathBuf>,
     fallthrough_dir: &std::path::PathBuf,
 ) -> std::path::PathBuf {
     sub_configs
-        .into_iter()
+        .iter()
         .last()
         .unwrap_or(&fallthrough_dir)
         .to_path_buf()
 }
 
-pub fn user_config_writable(path: &std::path::PathBuf) -> bool {
-    std::fs::metadata(path)
-        .map(|m| m.permissions().readonly() == false)
-        .unwrap_or(false)
-}
-
 pub fn can_write_to_dir<P: AsRef<std::path::Path>>(dir: &P) -> bool {
     let test_path = dir.as_ref().join(".openmw_cfg_write_test");
     match std::fs::File::create(&test_path) {
@@ -40,15 +62,16 @@ pub fn can_write_to_dir<P: AsRef<std::path::Path>>(dir: &P) -> bool {
 /// Transposes an input directory or file path to an openmw.cfg path
 /// Maybe could do with some additional validation
 pub fn input_config_path(
+    fs: &dyn crate::config::fs::ConfigFs,
     config_path: &std::path::Path,
 ) -> Result<std::path::PathBuf, crate::ConfigError> {
-    match std::fs::symlink_metadata(config_path) {
+    match fs.metadata(config_path) {
         Ok(metadata) => {
-            if metadata.is_file() {
+            if metadata.is_file {
                 Ok(config_path.to_path_buf())
-            } else if metadata.is_dir() {
+            } else if metadata.is_dir {
                 let maybe_config = config_path.join("openmw.cfg");
-                if maybe_config.is_file() {
+                if fs.is_file(&maybe_config) {
                     Ok(maybe_config)
                 } else {
                     crate::config::bail_config!(cannot_find, config_path);
@@ -66,3 +89,17 @@ pub fn input_config_path(
         }
     }
 }
+
+/// Writes an openmw.cfg into a fresh directory under the system temp dir
+#[cfg(test)]
+pub fn temp_config(name: &str, contents: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("openmw_config_test_{}", std::process::id()))
+        .join(name);
+
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("openmw.cfg"), contents).unwrap();
+
+    dir
+}
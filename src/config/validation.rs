@@ -0,0 +1,686 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::HashSet, fmt, path::PathBuf};
+
+use crate::{
+    ConfigError, GameSetting, SettingHandle, bail_config,
+    config::{
+        OpenMWConfiguration, SettingValue,
+        directorysetting::DirectorySetting,
+        filesetting::FileSetting,
+        messages::{Message, MessageId},
+        util::json_string,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The engine will refuse to start, or misbehave badly
+    Error,
+    /// Probably not what the user intended
+    Warning,
+    /// Harmless, but worth tidying up
+    Suggestion,
+}
+
+impl Severity {
+    /// Lowercase name, as used in JSON output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Suggestion => "suggestion",
+        }
+    }
+}
+
+/// Identifies one quick fix, and stays valid for as long as the setting it refers to exists.
+/// This is what a UI should hold on to between presenting a fix and applying it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FixId {
+    code: &'static str,
+    handle: SettingHandle,
+}
+
+impl fmt::Display for FixId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.code, self.handle)
+    }
+}
+
+impl FixId {
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// The setting this fix applies to
+    pub fn handle(&self) -> SettingHandle {
+        self.handle
+    }
+}
+
+/// What applying a fix actually does to the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixAction {
+    RemoveSetting(SettingHandle),
+    /// Rewrites the setting's value, given the way openmw.cfg would have it
+    ReplaceValue(SettingHandle, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickFix {
+    pub id: FixId,
+    pub description: Message,
+    pub action: FixAction,
+}
+
+/// One problem found in the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Short, stable identifier for the kind of problem, eg `missing-data-dir`
+    pub code: &'static str,
+    pub message: Message,
+    /// The offending line, if the problem is about one in particular
+    pub setting: Option<SettingHandle>,
+    pub fix: Option<QuickFix>,
+}
+
+impl Issue {
+    pub(crate) fn new(severity: Severity, code: &'static str, message: Message) -> Self {
+        Self {
+            severity,
+            code,
+            message,
+            setting: None,
+            fix: None,
+        }
+    }
+
+    /// Attaches the offending setting, along with a fix which removes it
+    pub(crate) fn removable(self, handle: SettingHandle, description: MessageId) -> Self {
+        self.fixable(handle, description, FixAction::RemoveSetting(handle))
+    }
+
+    /// Attaches the offending setting, along with a fix which gives it `value` instead
+    pub(crate) fn replaceable(
+        self,
+        handle: SettingHandle,
+        description: MessageId,
+        value: String,
+    ) -> Self {
+        self.fixable(handle, description, FixAction::ReplaceValue(handle, value))
+    }
+
+    /// Attaches the offending setting on its own, for problems only the user can sort out
+    pub(crate) fn about(mut self, handle: SettingHandle) -> Self {
+        self.setting = Some(handle);
+        self
+    }
+
+    fn fixable(mut self, handle: SettingHandle, description: MessageId, action: FixAction) -> Self {
+        self.setting = Some(handle);
+        self.fix = Some(QuickFix {
+            id: FixId {
+                code: self.code,
+                handle,
+            },
+            description: Message::new(description),
+            action,
+        });
+        self
+    }
+}
+
+/// Summary of `validate` and `lint`, for showing a single status indicator and a "fix common problems" button
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HealthReport {
+    pub errors: usize,
+    pub warnings: usize,
+    pub suggestions: usize,
+    /// Every fix which can be applied without asking the user anything
+    pub fixes: Vec<QuickFix>,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.errors == 0 && self.warnings == 0
+    }
+}
+
+/// Everything `validate` found, in a form scripts and CI jobs can consume
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationOutcome {
+    pub issues: Vec<Issue>,
+}
+
+impl From<Vec<Issue>> for ValidationOutcome {
+    fn from(issues: Vec<Issue>) -> Self {
+        Self { issues }
+    }
+}
+
+impl ValidationOutcome {
+    /// Bumped whenever `to_json` changes in a way which could break an existing consumer
+    pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .count()
+    }
+
+    pub fn worst(&self) -> Option<Severity> {
+        self.issues.iter().map(|issue| issue.severity).min()
+    }
+
+    /// 0 when there is nothing worse than a suggestion, 1 for warnings and 2 for errors
+    pub fn exit_code(&self) -> i32 {
+        match self.worst() {
+            Some(Severity::Error) => 2,
+            Some(Severity::Warning) => 1,
+            Some(Severity::Suggestion) | None => 0,
+        }
+    }
+
+    /// Messages are rendered in English, and their ID and arguments are included for anyone who wants to translate them
+    pub fn to_json(&self) -> String {
+        let message_json = |message: &Message| {
+            let args: Vec<String> = message
+                .args()
+                .iter()
+                .map(|(name, value)| format!("{}:{}", json_string(name), json_string(value)))
+                .collect();
+
+            format!(
+                "\"message\":{},\"message_id\":{},\"args\":{{{}}}",
+                json_string(&message.to_string()),
+                json_string(message.id().as_str()),
+                args.join(",")
+            )
+        };
+
+        let issues: Vec<String> = self
+            .issues
+            .iter()
+            .map(|issue| {
+                let setting = issue
+                    .setting
+                    .map_or("null".to_string(), |handle| handle.as_u64().to_string());
+
+                let fix = issue.fix.as_ref().map_or("null".to_string(), |fix| {
+                    let action = match &fix.action {
+                        FixAction::RemoveSetting(handle) => format!(
+                            "{{\"kind\":\"remove-setting\",\"setting\":{}}}",
+                            handle.as_u64()
+                        ),
+                        FixAction::ReplaceValue(handle, value) => format!(
+                            "{{\"kind\":\"replace-value\",\"setting\":{},\"value\":{}}}",
+                            handle.as_u64(),
+                            json_string(value)
+                        ),
+                    };
+
+                    format!(
+                        "{{\"id\":{},{},\"action\":{}}}",
+                        json_string(&fix.id.to_string()),
+                        message_json(&fix.description),
+                        action
+                    )
+                });
+
+                format!(
+                    "{{\"severity\":{},\"code\":{},{},\"setting\":{},\"fix\":{}}}",
+                    json_string(issue.severity.as_str()),
+                    json_string(issue.code),
+                    message_json(&issue.message),
+                    setting,
+                    fix
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"schema\":{},\"exit_code\":{},\"counts\":{{\"error\":{},\"warning\":{},\"suggestion\":{}}},\"issues\":[{}]}}",
+            Self::JSON_SCHEMA_VERSION,
+            self.exit_code(),
+            self.count(Severity::Error),
+            self.count(Severity::Warning),
+            self.count(Severity::Suggestion),
+            issues.join(",")
+        )
+    }
+}
+
+/// Gives a file or directory setting a new value, keeping its comments, origin and handle
+fn replace_value(setting: &mut SettingValue, value: &str) {
+    match setting {
+        SettingValue::DataDirectory(dir)
+        | SettingValue::UserData(dir)
+        | SettingValue::DataLocal(dir)
+        | SettingValue::Resources(dir)
+        | SettingValue::SubConfiguration(dir) => {
+            let meta = dir.meta().clone();
+            *dir = DirectorySetting::new(value, meta.source_config.clone());
+            *dir.meta_mut() = meta;
+        }
+        SettingValue::ContentFile(file)
+        | SettingValue::BethArchive(file)
+        | SettingValue::Groundcover(file) => {
+            let meta = file.meta().clone();
+            *file = FileSetting::new(value, &meta.source_config);
+            *file.meta_mut() = meta;
+        }
+        _ => {}
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Checks the configuration for common problems, in load order.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        self.check_data_directories(&mut issues);
+        self.check_game_settings(&mut issues);
+        self.check_lua_scripts(&mut issues);
+        self.check_groundcover(&mut issues);
+
+        if self.content_files_iter().next().is_none() {
+            issues.push(Issue::new(
+                Severity::Warning,
+                "no-content",
+                Message::new(MessageId::NoContent),
+            ));
+        }
+
+        issues
+    }
+
+    pub fn validation_outcome(&self) -> ValidationOutcome {
+        self.validate().into()
+    }
+
+    pub fn health(&self) -> HealthReport {
+        let mut report = HealthReport::default();
+
+        for issue in self.validate().into_iter().chain(self.lint()) {
+            match issue.severity {
+                Severity::Error => report.errors += 1,
+                Severity::Warning => report.warnings += 1,
+                Severity::Suggestion => report.suggestions += 1,
+            }
+
+            report.fixes.extend(issue.fix);
+        }
+
+        report
+    }
+
+    /// Applies one quick fix from `validate`, `health` or `lint`.
+    /// The configuration is checked again first, so a fix which no longer applies is an error rather than a surprise.
+    pub fn apply_fix(&mut self, fix_id: &FixId) -> Result<(), ConfigError> {
+        let Some(fix) = self
+            .validate()
+            .into_iter()
+            .chain(self.lint())
+            .filter_map(|issue| issue.fix)
+            .find(|fix| fix.id == *fix_id)
+        else {
+            bail_config!(fix_not_applicable, fix_id)
+        };
+
+        match fix.action {
+            FixAction::RemoveSetting(handle) => {
+                self.remove_setting(&handle);
+            }
+            FixAction::ReplaceValue(handle, value) => {
+                self.update_setting(&handle, |setting| replace_value(setting, &value))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies every available quick fix, returning how many were applied
+    pub fn apply_all_fixes(&mut self) -> usize {
+        let mut applied = 0;
+
+        while let Some(fix) = self.health().fixes.first() {
+            if self.apply_fix(&fix.id).is_err() {
+                break;
+            }
+            applied += 1;
+        }
+
+        applied
+    }
+
+    fn check_data_directories(&self, issues: &mut Vec<Issue>) {
+        let directories: Vec<_> = self.data_directories_iter().collect();
+        let paths: Vec<&PathBuf> = directories.iter().map(|dir| dir.parsed()).collect();
+
+        #[cfg(feature = "parallel")]
+        let exists = match self.parallel {
+            true => self.directories_exist(&paths),
+            false => paths
+                .iter()
+                .map(|path| self.fs.is_dir(path))
+                .collect::<Vec<_>>(),
+        };
+        #[cfg(not(feature = "parallel"))]
+        let exists: Vec<bool> = paths.iter().map(|path| self.fs.is_dir(path)).collect();
+
+        let canonical: Vec<Option<PathBuf>> = paths
+            .iter()
+            .zip(&exists)
+            .map(|(path, exists)| match exists {
+                true => self.fs.canonicalize(path).ok(),
+                false => None,
+            })
+            .collect();
+
+        for (index, dir) in directories.iter().enumerate() {
+            // Only the Android app can see inside a content:// URI, so there's no telling whether it exists
+            if !exists[index] && !crate::android::is_content_uri(&dir.parsed().to_string_lossy()) {
+                issues.push(
+                    Issue::new(
+                        Severity::Warning,
+                        "missing-data-dir",
+                        Message::new(MessageId::MissingDataDir).arg("path", dir.parsed().display()),
+                    )
+                    .removable(dir.meta().handle(), MessageId::FixRemoveDataDir),
+                );
+            }
+
+            // Only the last occurrence determines priority, so earlier ones are dead weight
+            if directories[index + 1..]
+                .iter()
+                .any(|later| later.parsed() == dir.parsed())
+            {
+                issues.push(
+                    Issue::new(
+                        Severity::Suggestion,
+                        "duplicate-data-dir",
+                        Message::new(MessageId::DuplicateDataDir)
+                            .arg("path", dir.parsed().display()),
+                    )
+                    .removable(dir.meta().handle(), MessageId::FixRemoveDuplicateDataDir),
+                );
+            }
+
+            // Two paths to one directory, through a symlink or bind mount, load it twice at different priorities
+            let alias = directories[index + 1..]
+                .iter()
+                .zip(&canonical[index + 1..])
+                .find(|(later, resolved)| {
+                    later.parsed() != dir.parsed()
+                        && resolved.is_some()
+                        && **resolved == canonical[index]
+                });
+
+            if let Some((later, _)) = alias {
+                issues.push(
+                    Issue::new(
+                        Severity::Warning,
+                        "aliased-data-dir",
+                        Message::new(MessageId::AliasedDataDir)
+                            .arg("path", dir.parsed().display())
+                            .arg("other", later.parsed().display()),
+                    )
+                    .removable(dir.meta().handle(), MessageId::FixRemoveDuplicateDataDir),
+                );
+            }
+        }
+    }
+
+    fn check_lua_scripts(&self, issues: &mut Vec<Issue>) {
+        if self.lua_scripts().next().is_none() {
+            return;
+        }
+
+        let index = self.data_file_index();
+        for scripts in self.lua_scripts() {
+            if !index.contains_key(&scripts.value().to_ascii_lowercase()) {
+                issues.push(
+                    Issue::new(
+                        Severity::Error,
+                        "missing-lua-scripts",
+                        Message::new(MessageId::MissingLuaScripts).arg("name", scripts.value()),
+                    )
+                    .removable(scripts.meta().handle(), MessageId::FixRemoveLuaScripts),
+                );
+            }
+        }
+    }
+
+    fn check_groundcover(&self, issues: &mut Vec<Issue>) {
+        if self.groundcover_iter().next().is_none() {
+            return;
+        }
+
+        // Loading a groundcover plugin as regular content as well crashes the engine
+        for plugin in self.content_files_iter() {
+            if self
+                .groundcover_iter()
+                .any(|grass| grass.is_named(plugin.value(), true))
+            {
+                issues.push(
+                    Issue::new(
+                        Severity::Error,
+                        "groundcover-in-content",
+                        Message::new(MessageId::GroundcoverInContent).arg("name", plugin.value()),
+                    )
+                    .removable(plugin.handle(), MessageId::FixRemoveGroundcoverContent),
+                );
+            }
+        }
+
+        let index = self.data_file_index();
+        for grass in self.groundcover_iter() {
+            if !index.contains_key(&grass.value().to_ascii_lowercase()) {
+                issues.push(
+                    Issue::new(
+                        Severity::Error,
+                        "missing-groundcover",
+                        Message::new(MessageId::MissingGroundcover).arg("name", grass.value()),
+                    )
+                    .removable(grass.handle(), MessageId::FixRemoveGroundcover),
+                );
+            }
+        }
+    }
+
+    fn check_game_settings(&self, issues: &mut Vec<Issue>) {
+        let mut seen = HashSet::new();
+
+        // Walk backwards so the effective (last) definition of each key is seen first
+        for setting in self.settings.iter().rev() {
+            if let SettingValue::GameSetting(game_setting) = setting
+                && !seen.insert(game_setting.key())
+            {
+                issues.push(
+                    Issue::new(
+                        Severity::Suggestion,
+                        "shadowed-fallback",
+                        Message::new(MessageId::ShadowedFallback).arg("key", game_setting.key()),
+                    )
+                    .removable(setting.handle(), MessageId::FixRemoveShadowedFallback),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_data_dirs() {
+        let dir = temp_config(
+            "symlinked_data_dirs",
+            "data=Mods\ndata=Linked\ncontent=Morrowind.esm\n",
+        );
+        std::fs::create_dir_all(dir.join("Mods")).unwrap();
+        let _ = std::fs::remove_file(dir.join("Linked"));
+        std::os::unix::fs::symlink(dir.join("Mods"), dir.join("Linked")).unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let issues = config.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, "aliased-data-dir");
+        assert_eq!(config.data_directories()[1], &dir.join("Linked"));
+
+        let canonical = dir.join("Mods").canonicalize().unwrap();
+        config.set_resolve_symlinks(true);
+        assert_eq!(config.data_directories(), vec![&canonical, &canonical]);
+        assert_eq!(config.validate()[0].code, "duplicate-data-dir");
+        assert!(config.to_string().contains("data=Linked\n"));
+
+        config.set_resolve_symlinks(false);
+        assert_eq!(config.data_directories()[1], &dir.join("Linked"));
+
+        let options = crate::ParseOptions::strict().resolving_symlinks();
+        let config = OpenMWConfiguration::new_with_options(Some(dir), options).unwrap();
+        assert_eq!(config.data_directories()[1], &canonical);
+    }
+
+    #[test]
+    fn test_health_counts_and_fixes() {
+        let dir = temp_config(
+            "health",
+            "data=Missing\ndata=Present\ndata=Present\nfallback=Key,1\nfallback=Key,2\ncontent=Morrowind.esm\n",
+        );
+        std::fs::create_dir_all(dir.join("Present")).unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let health = config.health();
+
+        assert_eq!(health.errors, 0);
+        assert_eq!(health.warnings, 1);
+        assert_eq!(health.suggestions, 2);
+        assert_eq!(health.fixes.len(), 3);
+        assert!(!health.is_healthy());
+
+        let missing_fix = health
+            .fixes
+            .iter()
+            .find(|fix| fix.id.code == "missing-data-dir")
+            .unwrap();
+        config.apply_fix(&missing_fix.id).unwrap();
+
+        assert!(matches!(
+            config.apply_fix(&missing_fix.id),
+            Err(ConfigError::FixNotApplicable(_))
+        ));
+
+        assert_eq!(config.apply_all_fixes(), 2);
+        assert!(config.health().is_healthy());
+        assert_eq!(config.data_directories(), vec![&dir.join("Present")]);
+        assert_eq!(config.get_game_setting("Key").unwrap().value(), "2");
+    }
+
+    #[test]
+    fn test_health_includes_lint() {
+        let dir = temp_config(
+            "health_lint",
+            "data=Mods\\Textures\ncontent=Morrowind.esm\n",
+        );
+        std::fs::create_dir_all(dir.join("Mods").join("Textures")).unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        assert!(config.validate().is_empty());
+
+        let health = config.health();
+        assert_eq!(health.suggestions, 1);
+        assert_eq!(health.fixes[0].id.code, "backslash-path");
+
+        assert_eq!(config.apply_all_fixes(), 1);
+        assert!(config.health().fixes.is_empty());
+    }
+
+    #[test]
+    fn test_missing_lua_scripts() {
+        let dir = temp_config(
+            "missing_lua_scripts",
+            "data=Mods\ncontent=Morrowind.esm\ncontent=Present.omwscripts\ncontent=Missing.OMWSCRIPTS\n",
+        );
+        std::fs::create_dir_all(dir.join("Mods")).unwrap();
+        std::fs::write(dir.join("Mods").join("present.omwscripts"), "").unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        assert_eq!(
+            config
+                .lua_scripts()
+                .map(|scripts| scripts.value())
+                .collect::<Vec<_>>(),
+            vec!["Present.omwscripts", "Missing.OMWSCRIPTS"]
+        );
+
+        let issues = config.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, "missing-lua-scripts");
+        assert_eq!(issues[0].severity, Severity::Error);
+
+        config
+            .apply_fix(&issues[0].fix.as_ref().unwrap().id)
+            .unwrap();
+        assert!(config.validate().is_empty());
+        assert!(config.has_content_file("Present.omwscripts"));
+    }
+
+    #[test]
+    fn test_groundcover_checks() {
+        let dir = temp_config(
+            "groundcover_checks",
+            "data=Mods\ncontent=Morrowind.esm\ncontent=grass.esp\ngroundcover=Grass.esp\ngroundcover=Missing.esp\n",
+        );
+        std::fs::create_dir_all(dir.join("Mods")).unwrap();
+        std::fs::write(dir.join("Mods").join("Grass.esp"), "").unwrap();
+
+        let mut config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let codes: Vec<_> = config.validate().iter().map(|issue| issue.code).collect();
+        assert_eq!(codes, vec!["groundcover-in-content", "missing-groundcover"]);
+
+        assert_eq!(config.apply_all_fixes(), 2);
+        assert_eq!(config.content_files(), vec!["Morrowind.esm"]);
+        assert_eq!(config.groundcover(), vec!["Grass.esp"]);
+    }
+
+    #[test]
+    fn test_empty_content_list() {
+        let dir = temp_config("health_no_content", "");
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        let issues = config.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].code, "no-content");
+        assert!(issues[0].fix.is_none());
+    }
+
+    #[test]
+    fn test_outcome_json_and_exit_code() {
+        let dir = temp_config("outcome_json", "data=Missing\n");
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+
+        let outcome = config.validation_outcome();
+        assert_eq!(outcome.exit_code(), 1);
+        assert_eq!(ValidationOutcome::default().exit_code(), 0);
+
+        let json = outcome.to_json();
+        assert!(json.starts_with(
+            "{\"schema\":1,\"exit_code\":1,\"counts\":{\"error\":0,\"warning\":2,\"suggestion\":0}"
+        ));
+        assert!(json.contains("\"code\":\"missing-data-dir\""));
+        assert!(json.contains("\"message_id\":\"issue-missing-data-dir\""));
+        assert!(json.contains("\"action\":{\"kind\":\"remove-setting\""));
+        assert!(json.contains("\"fix\":null"));
+        assert_eq!(json_string("a \"b\"\n"), "\"a \\\"b\\\"\\n\"");
+    }
+}
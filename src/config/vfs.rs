@@ -0,0 +1,242 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{ConfigError, config::OpenMWConfiguration};
+
+/// Normalizes a virtual path the same way the engine does for lookups:
+/// Lowercase, forward slashes, and no leading separator.
+pub fn normalize_vfs_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches('/')
+        .to_lowercase()
+}
+
+/// Where the winning copy of a virtual file actually lives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VfsSource {
+    Loose(PathBuf),
+    /// Packed inside a fallback-archive. Only produced with the `bsa` feature.
+    Archive(PathBuf),
+}
+
+impl VfsSource {
+    /// The loose file, or the archive containing the file
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            VfsSource::Loose(path) | VfsSource::Archive(path) => path,
+        }
+    }
+}
+
+/// A flattened view of every file visible through the configuration's data directories.
+/// Keys are normalized virtual paths (eg `meshes/xbase_anim.nif`), values are the real file which wins that path.
+/// With the `bsa` feature, the contents of fallback-archives are layered underneath all loose files, as in the engine.
+/// For anything beyond basic lookups, such as listing which files were overridden, see vfstool_lib.
+#[derive(Debug, Clone, Default)]
+pub struct VfsOverlay {
+    files: HashMap<String, VfsSource>,
+}
+
+impl VfsOverlay {
+    /// Case and separator-insensitive lookup of a virtual path
+    pub fn get(&self, virtual_path: &str) -> Option<&PathBuf> {
+        self.source(virtual_path).map(VfsSource::path)
+    }
+
+    pub fn source(&self, virtual_path: &str) -> Option<&VfsSource> {
+        self.files.get(&normalize_vfs_path(virtual_path))
+    }
+
+    pub fn contains(&self, virtual_path: &str) -> bool {
+        self.get(virtual_path).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Iteration order is unspecified
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PathBuf)> {
+        self.files
+            .iter()
+            .map(|(virtual_path, source)| (virtual_path, source.path()))
+    }
+
+    /// Symlinked directories are followed, but each real directory is only walked once so a link back up the tree can't loop forever.
+    /// Directories which can't be read are skipped, like missing data directories.
+    fn insert_dir(&mut self, root: &Path, dir: &Path, visited: &mut HashSet<PathBuf>) {
+        let Ok(canonical) = dir.canonicalize() else {
+            return;
+        };
+        if !visited.insert(canonical) {
+            return;
+        }
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+
+            if file_type.is_dir() || (file_type.is_symlink() && path.is_dir()) {
+                self.insert_dir(root, &path, visited);
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                self.files.insert(
+                    normalize_vfs_path(&relative.to_string_lossy()),
+                    VfsSource::Loose(path),
+                );
+            }
+        }
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Walks every data directory in load order, so that files from later (higher priority) directories replace earlier ones.
+    /// Directories which don't exist are skipped, as the engine does.
+    ///
+    /// With the `bsa` feature, each fallback-archive is located in the data directories (case-insensitively)
+    /// And its contents placed below every loose file, with later archives overriding earlier ones.
+    /// An archive which can't be found or read is an error, as it is for the engine.
+    pub fn vfs_overlay(&self) -> Result<VfsOverlay, ConfigError> {
+        let mut loose = VfsOverlay::default();
+
+        for dir in self.data_directories() {
+            if dir.is_dir() {
+                loose.insert_dir(dir, dir, &mut HashSet::new());
+            }
+        }
+
+        #[cfg(feature = "bsa")]
+        let loose = self.layer_archives(loose)?;
+
+        Ok(loose)
+    }
+
+    #[cfg(feature = "bsa")]
+    fn layer_archives(&self, loose: VfsOverlay) -> Result<VfsOverlay, ConfigError> {
+        let mut layered = VfsOverlay::default();
+
+        for archive in self.fallback_archives() {
+            let Some(archive_path) = loose.get(archive) else {
+                crate::bail_config!(archive_not_found, archive);
+            };
+
+            for file in crate::config::bsa::read_file_list(archive_path)? {
+                layered.files.insert(
+                    normalize_vfs_path(&file),
+                    VfsSource::Archive(archive_path.clone()),
+                );
+            }
+        }
+
+        layered.files.extend(loose.files);
+        Ok(layered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_later_directories_win() {
+        let dir = temp_config("vfs_priority", "data=Low\ndata=High\n");
+
+        for (data_dir, file) in [
+            ("Low", "Meshes/Foo.nif"),
+            ("Low", "Textures/Only_Low.dds"),
+            ("High", "meshes/foo.NIF"),
+        ] {
+            let path = dir.join(data_dir).join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let config = OpenMWConfiguration::new(Some(dir.clone())).unwrap();
+        let overlay = config.vfs_overlay().unwrap();
+
+        assert_eq!(overlay.len(), 2);
+        assert_eq!(
+            overlay.get("MESHES\\foo.nif"),
+            Some(&dir.join("High").join("meshes/foo.NIF"))
+        );
+        assert!(overlay.contains("textures/only_low.dds"));
+        assert!(!overlay.contains("textures/missing.dds"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loops_are_walked_once() {
+        let dir = temp_config("vfs_symlink_loop", "data=Data\n");
+        let meshes = dir.join("Data").join("meshes");
+        std::fs::create_dir_all(&meshes).unwrap();
+        std::fs::write(meshes.join("foo.nif"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("Data"), meshes.join("loop")).unwrap();
+
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let overlay = config.vfs_overlay().unwrap();
+
+        assert_eq!(overlay.len(), 1);
+        assert!(overlay.contains("meshes/foo.nif"));
+    }
+
+    #[cfg(feature = "bsa")]
+    #[test]
+    fn test_archives_sit_below_loose_files() {
+        use crate::config::bsa::tests::tes3_archive;
+
+        let dir = temp_config(
+            "vfs_archives",
+            "data=Data\nfallback-archive=Base.bsa\nfallback-archive=Patch.bsa\n",
+        );
+        let data = dir.join("Data");
+        std::fs::create_dir_all(data.join("meshes")).unwrap();
+        std::fs::write(data.join("meshes/loose.nif"), "").unwrap();
+        std::fs::write(
+            data.join("base.BSA"),
+            tes3_archive(&[
+                "meshes\\loose.nif",
+                "meshes\\base.nif",
+                "meshes\\patched.nif",
+            ]),
+        )
+        .unwrap();
+        std::fs::write(
+            data.join("Patch.bsa"),
+            tes3_archive(&["meshes\\patched.nif"]),
+        )
+        .unwrap();
+
+        let config = OpenMWConfiguration::new(Some(dir)).unwrap();
+        let overlay = config.vfs_overlay().unwrap();
+
+        assert_eq!(
+            overlay.source("meshes/loose.nif"),
+            Some(&VfsSource::Loose(data.join("meshes/loose.nif")))
+        );
+        assert_eq!(
+            overlay.source("meshes/base.nif"),
+            Some(&VfsSource::Archive(data.join("base.BSA")))
+        );
+        assert_eq!(
+            overlay.get("meshes/patched.nif"),
+            Some(&data.join("Patch.bsa"))
+        );
+    }
+}
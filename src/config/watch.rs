@@ -0,0 +1,298 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fs::metadata,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use crate::config::{
+    OpenMWConfiguration, ParseOptions,
+    events::{ConfigEventSink, ConfigNotification},
+    util,
+};
+
+/// Something happened to one of the openmw.cfg files in a configuration chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigEvent {
+    Modified(PathBuf),
+    /// The file joined the chain, either because a `config=` line now points at it or because it was recreated
+    Added(PathBuf),
+    /// The file was deleted, or nothing in the chain refers to it anymore
+    Removed(PathBuf),
+}
+
+impl ConfigEvent {
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            ConfigEvent::Modified(path) | ConfigEvent::Added(path) | ConfigEvent::Removed(path) => {
+                path
+            }
+        }
+    }
+}
+
+/// Modification time and length, since mtime alone is too coarse on some filesystems
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &PathBuf) -> Stamp {
+    let meta = metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// Polls every openmw.cfg in a configuration chain for changes.
+/// Only files which existed when the chain was loaded are tracked, so a user config which hasn't been created yet will show up once its parent is touched.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    root: PathBuf,
+    files: HashMap<PathBuf, Stamp>,
+}
+
+impl ConfigWatcher {
+    pub fn new(config: &OpenMWConfiguration) -> Self {
+        Self {
+            root: config.root_config_file().to_path_buf(),
+            files: config
+                .chain_files()
+                .into_iter()
+                .map(|path| {
+                    let stamp = stamp(&path);
+                    (path, stamp)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.keys()
+    }
+
+    /// Compares the chain against the last poll.
+    /// Whenever something changed, the chain is reloaded leniently to pick up added or dropped `config=` lines.
+    pub fn poll(&mut self) -> Vec<ConfigEvent> {
+        let mut events = Vec::new();
+
+        for (path, last) in self.files.iter_mut() {
+            let current = stamp(path);
+
+            match (&last, &current) {
+                (Some(_), None) => events.push(ConfigEvent::Removed(path.clone())),
+                (None, Some(_)) => events.push(ConfigEvent::Added(path.clone())),
+                (Some(old), Some(new)) if old != new => {
+                    events.push(ConfigEvent::Modified(path.clone()))
+                }
+                _ => {}
+            }
+
+            *last = current;
+        }
+
+        if events.is_empty() {
+            return events;
+        }
+
+        let chain = match OpenMWConfiguration::new_with_options(
+            Some(self.root.clone()),
+            ParseOptions::lenient(),
+        ) {
+            Ok(config) => config.chain_files(),
+            Err(error) => {
+                util::debug_log(format_args!(
+                    "WARNING: Unable to reload the configuration chain while watching it: {error}"
+                ));
+                return events;
+            }
+        };
+
+        self.files.retain(|path, _| {
+            let keep = chain.contains(path);
+            if !keep && !events.iter().any(|event| event.path() == path) {
+                events.push(ConfigEvent::Removed(path.clone()));
+            }
+            keep
+        });
+
+        for path in chain {
+            if let Entry::Vacant(entry) = self.files.entry(path) {
+                events.push(ConfigEvent::Added(entry.key().clone()));
+                let stamp = stamp(entry.key());
+                entry.insert(stamp);
+            }
+        }
+
+        events
+    }
+
+    fn run<S, F>(mut self, interval: Duration, should_stop: S, mut on_event: F)
+    where
+        S: Fn() -> bool,
+        F: FnMut(ConfigEvent),
+    {
+        while !should_stop() {
+            thread::sleep(interval);
+            self.poll().into_iter().for_each(&mut on_event);
+        }
+    }
+
+    /// Polls on a background thread, calling `on_event` for every change until the handle is dropped
+    pub fn spawn<F>(self, interval: Duration, on_event: F) -> WatchHandle
+    where
+        F: FnMut(ConfigEvent) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let thread = thread::spawn(move || {
+            self.run(
+                interval,
+                move || thread_stop.load(Ordering::Relaxed),
+                on_event,
+            )
+        });
+
+        WatchHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Same as `spawn`, delivering changes to an event sink as `ConfigNotification::FileChanged`
+    pub fn spawn_into(self, interval: Duration, sink: Arc<dyn ConfigEventSink>) -> WatchHandle {
+        self.spawn(interval, move |event| {
+            sink.notify(ConfigNotification::FileChanged(&event))
+        })
+    }
+
+    /// An async stream of change events, for `select!`-ing on alongside everything else.
+    /// Polling happens on its own thread, which exits once the stream is dropped.
+    #[cfg(feature = "tokio")]
+    pub fn stream(self, interval: Duration) -> ConfigEventStream {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let closed = sender.clone();
+
+        thread::spawn(move || {
+            self.run(
+                interval,
+                move || closed.is_closed(),
+                move |event| {
+                    let _ = sender.send(event);
+                },
+            )
+        });
+
+        ConfigEventStream { receiver }
+    }
+}
+
+/// Stops the watcher thread when dropped
+#[derive(Debug)]
+pub struct WatchHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchHandle {
+    /// Same as dropping the handle, but reads better at the call site
+    pub fn stop(self) {}
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct ConfigEventStream {
+    receiver: tokio::sync::mpsc::UnboundedReceiver<ConfigEvent>,
+}
+
+#[cfg(feature = "tokio")]
+impl futures_core::Stream for ConfigEventStream {
+    type Item = ConfigEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl OpenMWConfiguration {
+    /// Shorthand for `ConfigWatcher::new(self).spawn(interval, on_event)`
+    pub fn watch<F>(&self, interval: Duration, on_event: F) -> WatchHandle
+    where
+        F: FnMut(ConfigEvent) + Send + 'static,
+    {
+        ConfigWatcher::new(self).spawn(interval, on_event)
+    }
+
+    #[cfg(feature = "tokio")]
+    pub fn watch_stream(&self, interval: Duration) -> ConfigEventStream {
+        ConfigWatcher::new(self).stream(interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_poll_follows_the_chain() {
+        let root_dir = temp_config("watch_root", "content=Morrowind.esm\n");
+        let user_dir = temp_config("watch_user", "content=Tribunal.esm\n");
+        let (root, user) = (root_dir.join("openmw.cfg"), user_dir.join("openmw.cfg"));
+        let mut watcher = ConfigWatcher::new(&OpenMWConfiguration::new(Some(root_dir)).unwrap());
+
+        assert!(watcher.poll().is_empty());
+
+        std::fs::write(
+            &root,
+            format!("content=Morrowind.esm\nconfig={}\n", user_dir.display()),
+        )
+        .unwrap();
+
+        let events = watcher.poll();
+        assert!(events.contains(&ConfigEvent::Modified(root.clone())));
+        assert!(events.contains(&ConfigEvent::Added(user.clone())));
+
+        std::fs::write(&root, "content=Morrowind.esm\n").unwrap();
+
+        let events = watcher.poll();
+        assert!(events.contains(&ConfigEvent::Removed(user)));
+        assert_eq!(watcher.files().count(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_stream_yields_events() {
+        use futures_core::Stream;
+        use std::future::poll_fn;
+
+        let root_dir = temp_config("watch_stream", "content=Morrowind.esm\n");
+        let root = root_dir.join("openmw.cfg");
+        let config = OpenMWConfiguration::new(Some(root_dir)).unwrap();
+        let mut stream = config.watch_stream(Duration::from_millis(10));
+
+        std::fs::write(&root, "content=Morrowind.esm\ncontent=Bloodmoon.esm\n").unwrap();
+
+        let event = poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx)).await;
+        assert_eq!(event, Some(ConfigEvent::Modified(root)));
+    }
+}
@@ -0,0 +1,247 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! `extern "C"` interface for launchers and tools written in C or C++.
+//! `include/openmw_config.h` declares all of this, and can be regenerated with `cbindgen --config cbindgen.toml`.
+//!
+//! Functions which can fail return null or -1, after which `omwcfg_last_error` says why.
+//! Strings handed out by the library belong to the caller and go back through `omwcfg_string_free`.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char, c_int},
+    path::PathBuf,
+    ptr,
+};
+
+use crate::{CAPABILITIES, OpenMWConfiguration};
+
+/// Opaque handle to a loaded configuration
+pub struct OmwConfig(OpenMWConfiguration);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl ToString) {
+    // Interior NULs would truncate the message anyway, so just drop them
+    let message = message.to_string().replace('\0', "");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+/// Borrows a C string as UTF-8, recording an error if it's null or not valid UTF-8
+unsafe fn borrow_str<'a>(value: *const c_char) -> Option<&'a str> {
+    if value.is_null() {
+        set_last_error("Expected a string, but got a null pointer");
+        return None;
+    }
+
+    // SAFETY: the caller promises this is a valid, NUL-terminated string
+    match unsafe { CStr::from_ptr(value) }.to_str() {
+        Ok(value) => Some(value),
+        Err(error) => {
+            set_last_error(error);
+            None
+        }
+    }
+}
+
+/// Version of the binary interface, same as `ABI_VERSION`
+#[unsafe(no_mangle)]
+pub extern "C" fn omwcfg_abi_version() -> u32 {
+    crate::ABI_VERSION
+}
+
+/// Bits for every optional feature this build includes, same as `CAPABILITIES`
+#[unsafe(no_mangle)]
+pub extern "C" fn omwcfg_capabilities() -> u32 {
+    CAPABILITIES.bits()
+}
+
+/// Loads the configuration chain starting at `path`, or the default location if `path` is null.
+/// Returns null on failure.
+///
+/// # Safety
+/// `path` must be null or a valid, NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn omwcfg_open(path: *const c_char) -> *mut OmwConfig {
+    let path = match path.is_null() {
+        true => None,
+        // SAFETY: forwarded from the caller
+        false => match unsafe { borrow_str(path) } {
+            Some(path) => Some(PathBuf::from(path)),
+            None => return ptr::null_mut(),
+        },
+    };
+
+    match OpenMWConfiguration::new(path) {
+        Ok(config) => Box::into_raw(Box::new(OmwConfig(config))),
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a configuration from `omwcfg_open`. Null is ignored.
+///
+/// # Safety
+/// `config` must be null or a pointer from `omwcfg_open` which hasn't been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn omwcfg_free(config: *mut OmwConfig) {
+    if !config.is_null() {
+        // SAFETY: ownership came from Box::into_raw in omwcfg_open
+        drop(unsafe { Box::from_raw(config) });
+    }
+}
+
+/// How many `content=` entries are enabled
+///
+/// # Safety
+/// `config` must be a live pointer from `omwcfg_open`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn omwcfg_content_count(config: *const OmwConfig) -> usize {
+    // SAFETY: forwarded from the caller
+    match unsafe { config.as_ref() } {
+        Some(config) => config.0.content_files_iter().count(),
+        None => 0,
+    }
+}
+
+/// The content file at `index` in load order, or null if it's out of range.
+/// The string has to be released with `omwcfg_string_free`.
+///
+/// # Safety
+/// `config` must be a live pointer from `omwcfg_open`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn omwcfg_content_at(config: *const OmwConfig, index: usize) -> *mut c_char {
+    // SAFETY: forwarded from the caller
+    let Some(config) = (unsafe { config.as_ref() }) else {
+        set_last_error("Expected a configuration, but got a null pointer");
+        return ptr::null_mut();
+    };
+
+    match config.0.content_files_iter().nth(index) {
+        Some(plugin) => CString::new(plugin.value().as_str())
+            .map(CString::into_raw)
+            .unwrap_or_else(|error| {
+                set_last_error(error);
+                ptr::null_mut()
+            }),
+        None => {
+            set_last_error(format!("No content file at index {index}"));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Enables a plugin at the end of the load order. Returns 0 on success and -1 on failure.
+///
+/// # Safety
+/// `config` must be a live pointer from `omwcfg_open`, and `name` a valid, NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn omwcfg_add_content(config: *mut OmwConfig, name: *const c_char) -> c_int {
+    // SAFETY: forwarded from the caller
+    let Some(config) = (unsafe { config.as_mut() }) else {
+        set_last_error("Expected a configuration, but got a null pointer");
+        return -1;
+    };
+    // SAFETY: forwarded from the caller
+    let Some(name) = (unsafe { borrow_str(name) }) else {
+        return -1;
+    };
+
+    match config.0.add_content_file(name) {
+        Ok(()) => 0,
+        Err(error) => {
+            set_last_error(error);
+            -1
+        }
+    }
+}
+
+/// Writes the user openmw.cfg, same as `save_user`. Returns 0 on success and -1 on failure.
+///
+/// # Safety
+/// `config` must be a live pointer from `omwcfg_open`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn omwcfg_save_user(config: *const OmwConfig) -> c_int {
+    // SAFETY: forwarded from the caller
+    let Some(config) = (unsafe { config.as_ref() }) else {
+        set_last_error("Expected a configuration, but got a null pointer");
+        return -1;
+    };
+
+    match config.0.save_user() {
+        Ok(()) => 0,
+        Err(error) => {
+            set_last_error(error);
+            -1
+        }
+    }
+}
+
+/// Describes the last failure on this thread, or null if nothing has failed yet.
+/// The string stays owned by the library, and is only valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn omwcfg_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Frees a string returned by the library. Null is ignored.
+///
+/// # Safety
+/// `value` must be null or a string from this library which hasn't been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn omwcfg_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        // SAFETY: ownership came from CString::into_raw
+        drop(unsafe { CString::from_raw(value) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_content_round_trip() {
+        let dir = temp_config("ffi", "content=Morrowind.esm\n");
+        let path = CString::new(dir.to_string_lossy().as_ref()).unwrap();
+
+        unsafe {
+            let config = omwcfg_open(path.as_ptr());
+            assert!(!config.is_null());
+            assert_eq!(omwcfg_content_count(config), 1);
+
+            let plugin = CString::new("Tribunal.esm").unwrap();
+            assert_eq!(omwcfg_add_content(config, plugin.as_ptr()), 0);
+            assert_eq!(omwcfg_add_content(config, plugin.as_ptr()), -1);
+            assert!(!omwcfg_last_error().is_null());
+
+            let second = omwcfg_content_at(config, 1);
+            assert_eq!(CStr::from_ptr(second).to_str().unwrap(), "Tribunal.esm");
+            omwcfg_string_free(second);
+
+            assert!(omwcfg_content_at(config, 2).is_null());
+            omwcfg_free(config);
+        }
+    }
+
+    #[test]
+    fn test_open_failure_sets_error() {
+        let missing = CString::new("/definitely/not/a/real/openmw/dir").unwrap();
+
+        unsafe {
+            assert!(omwcfg_open(missing.as_ptr()).is_null());
+            assert!(!CStr::from_ptr(omwcfg_last_error()).to_bytes().is_empty());
+        }
+    }
+}
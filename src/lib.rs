@@ -3,60 +3,242 @@
 // Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
 
+mod capabilities;
+pub use capabilities::{ABI_VERSION, CAPABILITIES, Capabilities};
+
+pub mod android;
 mod config;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod paths;
+pub mod sandbox;
 pub use config::{
-    OpenMWConfiguration, directorysetting::DirectorySetting, encodingsetting::EncodingSetting,
-    error::ConfigError, filesetting::FileSetting, gamesetting::GameSettingType,
+    CLI_SOURCE, MAX_CONFIG_DEPTH, OpenMWConfiguration, SerializerInfo, SettingValue,
+    bulk::wildcard_match,
+    comments::{
+        CommentAuthor, CommentParagraph, DISABLED_MARKER, MANAGED_BY_MARKER, OMWCFG_MARKER,
+        classify_comment,
+    },
+    conflicts::{ConflictReport, DirectoryConflicts, FileConflict},
+    directorysetting::DirectorySetting,
+    discovery::{
+        ContentDiscovery, DiscoveredFile, MORROWIND_STEAM_APP_ID, MorrowindInstall,
+        find_morrowind_installs, find_morrowind_installs_in, steam_libraries,
+    },
+    document::{Document, DocumentLine},
+    duplicates::{DuplicateEntry, DuplicateKind},
+    encodingsetting::{EncodingSetting, EncodingType},
+    error::ConfigError,
+    events::{ChangeEvent, ChangeObserver, ConfigEventSink, ConfigNotification, ObserverId},
+    filesetting::{ContentKind, FileSetting},
+    flagsetting::FlagSetting,
+    fs::{ConfigFs, FsMetadata, MemoryFs, StdFs},
+    gamesetting::{ColorFormat, ColorValue, GameSettingType},
     genericsetting::GenericSetting,
+    ini::{IniImportReport, IniOrigin},
+    install::{InstallStrategy, ModInstall, UninstallAction},
+    installation::{InstallationImport, guess_ini_encoding},
+    interop::{ImportedSetup, parse_mash_list, parse_mo2_modlist, parse_mo2_plugins},
+    launcher_cfg::{LauncherConfig, LauncherProfile, LauncherSection},
+    loadorder::LoadOrder,
+    location::{ConfigLocation, ConfigSource, PathConvention, find_local_config},
+    merge::{Conflict, ConflictKind, ConflictSet, Resolution},
+    messages::{Message, MessageId, english_ftl},
+    modcontents::{ModContents, scan_mod_contents},
+    parseoptions::{DuplicatePolicy, FileEncoding, ParseOptions, WarningHandler},
+    plugins::{PluginHeader, read_plugin_header},
+    render::{RenderOptions, provenance_header},
+    saves::{CharacterSaves, SaveGame, SaveHeader, read_save_header},
+    screenshots::{Screenshot, ScreenshotFormat},
+    search::{SearchField, SearchMatch},
+    settingcontext::{ParseSetting, SettingContext},
+    single::SingleConfig,
+    snapshot::{ConfigSnapshot, UndoStack},
+    stats::ConfigStats,
+    validation::{FixAction, FixId, HealthReport, Issue, QuickFix, Severity, ValidationOutcome},
 };
 
+#[cfg(feature = "fluent")]
+pub use config::messages::Localizer;
+
+#[cfg(feature = "watch")]
+pub use config::watch::{ConfigEvent, ConfigWatcher, WatchHandle};
+
+#[cfg(feature = "tokio")]
+pub use config::watch::ConfigEventStream;
+
+#[cfg(feature = "vfs")]
+pub use config::vfs::{VfsOverlay, VfsSource, normalize_vfs_path};
+
+#[cfg(feature = "bsa")]
+pub use config::bsa::{ArchiveCheck, ArchiveFormat, archive_format, read_file_list};
+
+#[cfg(feature = "manifest")]
+pub use config::manifest::{FallbackValue, Manifest};
+
+#[cfg(feature = "manifest")]
+pub use config::modlist::{Modlist, ModlistReport};
+
+#[cfg(feature = "input")]
+pub use config::input::{Binding, Direction, InputBindings, InputElement};
+
+#[cfg(feature = "archive-install")]
+pub use config::archive_install::find_data_root;
+
+#[cfg(feature = "settings-cfg")]
+pub use config::{postprocessing::ShaderFile, settings_cfg::SettingsCfg};
+
+#[cfg(feature = "diagnostics")]
+pub use config::diagnostics::{Diagnostic, KNOWN_KEYS, SourceLocation, suggest_key};
+
 pub(crate) trait GameSetting: std::fmt::Display {
     fn meta(&self) -> &GameSettingMeta;
+    fn meta_mut(&mut self) -> &mut GameSettingMeta;
+}
+
+static NEXT_HANDLE: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Opaque identifier for one specific line of an openmw.cfg.
+/// Handles are never reused while the process is alive, so unlike an index into the settings list,
+/// They keep pointing at the same entry no matter what else gets added, removed, or reordered around it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct SettingHandle(u64);
+
+impl std::fmt::Display for SettingHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+impl SettingHandle {
+    /// The raw number behind the handle, for serializing it
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    pub(crate) fn next() -> Self {
+        Self(NEXT_HANDLE.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+    }
+
+    /// The handle `next` will give out, so anything created after this point compares greater or equal
+    pub(crate) fn upcoming() -> Self {
+        Self(NEXT_HANDLE.load(std::sync::atomic::Ordering::Relaxed))
+    }
+}
+
+/// Where a setting came from, which decides whether it can be written back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SettingOrigin {
+    /// A line in one of the openmw.cfg files
+    File,
+    /// Made up by the crate rather than written by anyone, like the data directory `data-local` implies.
+    /// Never saved, and left out of `render` unless asked for.
+    Synthetic,
+    /// Added or replaced through the API since loading
+    Api,
+    /// From `apply_cli_args`
+    Cli,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct GameSettingMeta {
     source_config: std::path::PathBuf,
+    origin: SettingOrigin,
     comment: String,
+    inline_comment: String,
+    handle: SettingHandle,
+    line: Option<usize>,
 }
 
-const NO_CONFIG_DIR: &str = "FAILURE: COULD NOT READ CONFIG DIRECTORY";
+impl GameSettingMeta {
+    pub(crate) fn new(source_config: std::path::PathBuf, comment: String) -> Self {
+        Self {
+            source_config,
+            origin: SettingOrigin::Api,
+            comment,
+            inline_comment: String::new(),
+            handle: SettingHandle::next(),
+            line: None,
+        }
+    }
 
-/// Path to input bindings and core configuration
-/// These functions are not expected to fail and should they fail, indicate either:
-/// a severe issue with the system
-/// or that an unsupported system is being used.
-pub fn default_config_path() -> std::path::PathBuf {
-    #[cfg(target_os = "android")]
-    std::path::PathBuf::from("/storage/emulated/0/Alpha3/config")
-
-    #[cfg(not(target_os = "android"))]
-    if cfg!(windows) {
-        dirs::document_dir()
-            .expect(NO_CONFIG_DIR)
-            .join("My Games")
-            .join("openmw")
-    } else {
-        dirs::preference_dir().expect(NO_CONFIG_DIR).join("openmw")
+    /// Which line of its openmw.cfg this setting was read from, counting from 1.
+    /// Settings which were added at runtime, or came from the command line, don't have one.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    pub fn origin(&self) -> SettingOrigin {
+        self.origin
+    }
+
+    /// The openmw.cfg (or directory containing it) which defined this setting
+    pub fn source_config(&self) -> &std::path::Path {
+        &self.source_config
+    }
+
+    /// Any comments and blank lines which preceded this setting
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    /// A `# comment` written after the value on the same line, only read when `ParseOptions::inline_comments` is set
+    pub fn inline_comment(&self) -> &str {
+        &self.inline_comment
+    }
+
+    /// Written back after the value. Should start with `#`, or it would become part of the value when read again.
+    pub fn set_inline_comment(&mut self, comment: impl Into<String>) {
+        self.inline_comment = comment.into();
+    }
+
+    pub fn handle(&self) -> SettingHandle {
+        self.handle
+    }
+
+    /// For settings built through the API. Lines which aren't comments already get a `#`,
+    /// So nothing passed in here can turn into a setting when the file is read back.
+    pub(crate) fn set_comment(&mut self, comment: Option<&str>) {
+        self.comment = comment.map_or_else(String::new, |comment| {
+            comment
+                .lines()
+                .map(
+                    |line| match line.trim().is_empty() || line.trim_start().starts_with('#') {
+                        true => format!("{line}\n"),
+                        false => format!("# {line}\n"),
+                    },
+                )
+                .collect()
+        });
+    }
+
+    /// Directory settings record the *directory* of the openmw.cfg which defined them,
+    /// Whereas everything else records the file itself. Accept either form here.
+    pub fn is_from(&self, config: &std::path::Path) -> bool {
+        let as_dir = |path: &std::path::Path| match path.file_name() {
+            Some(name) if name == "openmw.cfg" => path.parent().map(|p| p.to_path_buf()),
+            _ => Some(path.to_path_buf()),
+        };
+
+        as_dir(&self.source_config) == as_dir(config)
     }
 }
 
+/// Path to input bindings and core configuration.
+/// Comes from the active `paths::PathsProvider`, which honours `OPENMW_CONFIG` by default.
+pub fn default_config_path() -> std::path::PathBuf {
+    paths::paths_provider().config_dir()
+}
+
+/// Where a system-wide install keeps its openmw.cfg, for platforms which have one.
+/// Windows and Android installs only have the local openmw.cfg next to the executable.
+pub fn default_global_config_path() -> Option<std::path::PathBuf> {
+    paths::paths_provider().global_config_dir()
+}
+
 /// Path to save storage, screenshots, navmeshdb, and data-local
-/// These functions are not expected to fail and should they fail, indicate either:
-/// a severe issue with the system
-/// or that an unsupported system is being used.
 pub fn default_userdata_path() -> std::path::PathBuf {
-    #[cfg(target_os = "android")]
-    std::path::PathBuf::from("/storage/emulated/0/Alpha3")
-
-    #[cfg(not(target_os = "android"))]
-    if cfg!(windows) {
-        default_config_path()
-    } else {
-        dirs::data_dir()
-            .expect("FAILURE: COULD NOT READ USERDATA DIRECTORY")
-            .join("openmw")
-    }
+    paths::paths_provider().userdata_dir()
 }
 
 /// Path to the last-loading directory of openmw.cfg,
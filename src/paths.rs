@@ -0,0 +1,317 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Where the default directories come from.
+//! `default_config_path` and friends ask the current `PathsProvider`, which is `PlatformPaths` unless something else was installed.
+//! Tests and sandboxes can swap in `FixedPaths`, either for the whole process or just the current thread.
+
+use crate::sandbox::{self, Sandbox, XdgDir, host_path};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// Points at an openmw.cfg, or the directory holding one. Also read by `OpenMWConfiguration::from_env`.
+pub const CONFIG_ENV: &str = "OPENMW_CONFIG";
+
+const NO_CONFIG_DIR: &str = "FAILURE: COULD NOT READ CONFIG DIRECTORY";
+const NO_USERDATA_DIR: &str = "FAILURE: COULD NOT READ USERDATA DIRECTORY";
+
+pub trait PathsProvider: Send + Sync {
+    /// Directory of the user openmw.cfg
+    fn config_dir(&self) -> PathBuf;
+
+    /// Saves, screenshots, navmeshdb and data-local
+    fn userdata_dir(&self) -> PathBuf;
+
+    /// Directory of the system-wide openmw.cfg, for platforms which have one
+    fn global_config_dir(&self) -> Option<PathBuf>;
+
+    /// What `?local?` stands for: the directory holding the executable
+    fn local_dir(&self) -> Option<PathBuf>;
+
+    /// What `?global?` stands for: where a system-wide install keeps its shared data
+    fn global_data_dir(&self) -> Option<PathBuf>;
+}
+
+/// The tokens a data directory can start with, as OpenMW documents them
+pub const PATH_TOKENS: [&str; 4] = ["?local?", "?userconfig?", "?userdata?", "?global?"];
+
+/// The directory a token stands for, or `None` for unknown tokens and ones this platform has no directory for
+pub fn resolve_token(token: &str) -> Option<PathBuf> {
+    let provider = paths_provider();
+
+    match token {
+        "?local?" => provider.local_dir(),
+        "?userconfig?" => Some(provider.config_dir()),
+        "?userdata?" => Some(provider.userdata_dir()),
+        "?global?" => provider.global_data_dir(),
+        _ => None,
+    }
+}
+
+/// Every token next to what it currently resolves to
+pub fn token_table() -> Vec<(&'static str, Option<PathBuf>)> {
+    PATH_TOKENS
+        .into_iter()
+        .map(|token| (token, resolve_token(token)))
+        .collect()
+}
+
+fn sandboxed_xdg_dir(kind: XdgDir) -> Option<PathBuf> {
+    sandbox::openmw_xdg_dir(Sandbox::current(), kind, |name| std::env::var_os(name))
+}
+
+/// The engine's own defaults for the platform being built for.
+/// `OPENMW_CONFIG` overrides the config directory, and on Linux and the BSDs `XDG_CONFIG_HOME` and `XDG_DATA_HOME` are respected.
+/// Inside a Flatpak or Snap which isn't OpenMW's, they point at OpenMW's files instead, see `sandbox`.
+/// These are not expected to fail, and should they fail, indicate either a severe issue with the system
+/// Or that an unsupported system is being used.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlatformPaths;
+
+impl PathsProvider for PlatformPaths {
+    fn config_dir(&self) -> PathBuf {
+        if let Some(explicit) = std::env::var_os(CONFIG_ENV).filter(|value| !value.is_empty()) {
+            let explicit =
+                PathBuf::from(shellexpand::tilde(&explicit.to_string_lossy()).into_owned());
+
+            return match explicit.file_name() {
+                Some(name) if name == "openmw.cfg" => explicit
+                    .parent()
+                    .map_or(explicit.clone(), Path::to_path_buf),
+                _ => explicit,
+            };
+        }
+
+        if cfg!(target_os = "android") {
+            crate::android::android_root().join("config")
+        } else if cfg!(windows) {
+            dirs::document_dir()
+                .expect(NO_CONFIG_DIR)
+                .join("My Games")
+                .join("openmw")
+        } else if cfg!(target_os = "macos") {
+            dirs::home_dir()
+                .expect(NO_CONFIG_DIR)
+                .join("Library")
+                .join("Preferences")
+                .join("openmw")
+        } else {
+            sandboxed_xdg_dir(XdgDir::Config)
+                .unwrap_or_else(|| dirs::preference_dir().expect(NO_CONFIG_DIR))
+                .join("openmw")
+        }
+    }
+
+    fn userdata_dir(&self) -> PathBuf {
+        if cfg!(target_os = "android") {
+            crate::android::android_root()
+        } else if cfg!(windows) {
+            self.config_dir()
+        } else if cfg!(target_os = "macos") {
+            // Unlike the config, which lives in Preferences
+            dirs::home_dir()
+                .expect(NO_USERDATA_DIR)
+                .join("Library")
+                .join("Application Support")
+                .join("openmw")
+        } else {
+            sandboxed_xdg_dir(XdgDir::Data)
+                .unwrap_or_else(|| dirs::data_dir().expect(NO_USERDATA_DIR))
+                .join("openmw")
+        }
+    }
+
+    fn global_config_dir(&self) -> Option<PathBuf> {
+        if cfg!(target_os = "android") || cfg!(windows) {
+            None
+        } else if cfg!(target_os = "macos") {
+            Some(PathBuf::from("/Library/Preferences/openmw"))
+        } else {
+            Some(host_path(Sandbox::current(), Path::new("/etc/openmw")))
+        }
+    }
+
+    fn local_dir(&self) -> Option<PathBuf> {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    }
+
+    fn global_data_dir(&self) -> Option<PathBuf> {
+        if cfg!(target_os = "android") {
+            None
+        } else if cfg!(windows) {
+            std::env::var_os("ProgramFiles").map(|dir| PathBuf::from(dir).join("OpenMW"))
+        } else if cfg!(target_os = "macos") {
+            Some(PathBuf::from("/Library/Application Support/openmw"))
+        } else {
+            Some(host_path(
+                Sandbox::current(),
+                Path::new("/usr/share/games/openmw"),
+            ))
+        }
+    }
+}
+
+/// Directories chosen up front, for tests and sandboxes which can't touch the real ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedPaths {
+    pub config: PathBuf,
+    pub userdata: PathBuf,
+    pub global: Option<PathBuf>,
+    pub local: Option<PathBuf>,
+    pub global_data: Option<PathBuf>,
+}
+
+impl FixedPaths {
+    /// `config/`, `userdata/`, `local/` and `global/` inside `root`, with no global config
+    pub fn under(root: &Path) -> Self {
+        Self {
+            config: root.join("config"),
+            userdata: root.join("userdata"),
+            global: None,
+            local: Some(root.join("local")),
+            global_data: Some(root.join("global")),
+        }
+    }
+}
+
+impl PathsProvider for FixedPaths {
+    fn config_dir(&self) -> PathBuf {
+        self.config.clone()
+    }
+
+    fn userdata_dir(&self) -> PathBuf {
+        self.userdata.clone()
+    }
+
+    fn global_config_dir(&self) -> Option<PathBuf> {
+        self.global.clone()
+    }
+
+    fn local_dir(&self) -> Option<PathBuf> {
+        self.local.clone()
+    }
+
+    fn global_data_dir(&self) -> Option<PathBuf> {
+        self.global_data.clone()
+    }
+}
+
+static PROCESS_PROVIDER: RwLock<Option<Arc<dyn PathsProvider>>> = RwLock::new(None);
+
+thread_local! {
+    static THREAD_PROVIDER: RefCell<Option<Arc<dyn PathsProvider>>> = const { RefCell::new(None) };
+}
+
+/// Replaces the platform defaults for the whole process. `None` goes back to `PlatformPaths`.
+pub fn set_paths_provider(provider: Option<Arc<dyn PathsProvider>>) {
+    *PROCESS_PROVIDER
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = provider;
+}
+
+/// Runs `f` with `provider` in charge of every path lookup on this thread, which keeps parallel tests apart
+pub fn with_paths_provider<T>(provider: Arc<dyn PathsProvider>, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Arc<dyn PathsProvider>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            THREAD_PROVIDER.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(THREAD_PROVIDER.with(|current| current.replace(Some(provider))));
+    f()
+}
+
+/// The provider in charge right now: this thread's, then the process-wide one, then `PlatformPaths`
+pub fn paths_provider() -> Arc<dyn PathsProvider> {
+    THREAD_PROVIDER
+        .with(|current| current.borrow().clone())
+        .or_else(|| {
+            PROCESS_PROVIDER
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        })
+        .unwrap_or_else(|| Arc::new(PlatformPaths))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{OpenMWConfiguration, util::temp_config};
+
+    #[test]
+    fn test_thread_paths_provider() {
+        let root = temp_config("paths_provider", "");
+        let fixed = FixedPaths::under(&root);
+        std::fs::create_dir_all(&fixed.config).unwrap();
+        std::fs::write(
+            fixed.config.join("openmw.cfg"),
+            "data=?userdata?mods\ncontent=Morrowind.esm\n",
+        )
+        .unwrap();
+
+        let config = with_paths_provider(Arc::new(fixed.clone()), || {
+            assert_eq!(crate::default_config_path(), fixed.config);
+            assert_eq!(
+                crate::default_data_local_path(),
+                fixed.userdata.join("data")
+            );
+            assert_eq!(crate::default_global_config_path(), None);
+
+            OpenMWConfiguration::new(None).unwrap()
+        });
+
+        assert_eq!(config.root_config_file(), &fixed.config.join("openmw.cfg"));
+        assert_eq!(
+            config.data_directories(),
+            vec![&fixed.userdata.join("mods")]
+        );
+        assert_ne!(crate::default_config_path(), fixed.config);
+    }
+
+    #[test]
+    fn test_path_tokens() {
+        let root = temp_config("path_tokens", "");
+        let fixed = FixedPaths::under(&root);
+        let lines = PATH_TOKENS
+            .iter()
+            .map(|token| format!("data={token}/mods\n"))
+            .chain(["data=?unknown?mods\n".to_string()])
+            .collect::<String>();
+        std::fs::write(root.join("openmw.cfg"), lines).unwrap();
+
+        with_paths_provider(Arc::new(fixed.clone()), || {
+            assert_eq!(
+                token_table(),
+                vec![
+                    ("?local?", fixed.local.clone()),
+                    ("?userconfig?", Some(fixed.config.clone())),
+                    ("?userdata?", Some(fixed.userdata.clone())),
+                    ("?global?", fixed.global_data.clone()),
+                ]
+            );
+
+            let config = OpenMWConfiguration::new(Some(root.clone())).unwrap();
+            assert_eq!(
+                config.data_directories(),
+                vec![
+                    &root.join("local/mods"),
+                    &root.join("config/mods"),
+                    &root.join("userdata/mods"),
+                    &root.join("global/mods"),
+                    &root.join("?unknown?mods"),
+                ]
+            );
+        });
+    }
+}
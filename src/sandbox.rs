@@ -0,0 +1,220 @@
+// This file is part of Openmw_Config.
+// Openmw_Config is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// Openmw_Config is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with Openmw_Config. If not, see <https://www.gnu.org/licenses/>.
+
+//! Flatpak and Snap give every app its own home and XDG directories.
+//! From inside some other app's sandbox, such as a mod manager's, the engine's files aren't where the XDG variables point,
+//! So `PlatformPaths` asks here where OpenMW really keeps them: in its own Flatpak if that's installed, on the host otherwise.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Only exists inside a Flatpak sandbox
+pub const FLATPAK_INFO: &str = "/.flatpak-info";
+
+pub const OPENMW_FLATPAK_ID: &str = "org.openmw.OpenMW";
+
+pub const OPENMW_SNAP_NAME: &str = "openmw";
+
+/// Where a Flatpak sees the host's `/usr` and `/etc`, when it's allowed to
+pub const FLATPAK_HOST_ROOT: &str = "/run/host";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sandbox {
+    Flatpak { app_id: String },
+    Snap { name: String },
+}
+
+/// The two XDG base directories OpenMW uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XdgDir {
+    Config,
+    Data,
+}
+
+impl XdgDir {
+    /// Set by newer Flatpak versions to the host's value of the variable
+    fn host_variable(self) -> &'static str {
+        match self {
+            XdgDir::Config => "HOST_XDG_CONFIG_HOME",
+            XdgDir::Data => "HOST_XDG_DATA_HOME",
+        }
+    }
+
+    fn default_in_home(self) -> &'static str {
+        match self {
+            XdgDir::Config => ".config",
+            XdgDir::Data => ".local/share",
+        }
+    }
+
+    fn flatpak_name(self) -> &'static str {
+        match self {
+            XdgDir::Config => "config",
+            XdgDir::Data => "data",
+        }
+    }
+}
+
+fn flatpak_app_id(info: &str) -> Option<&str> {
+    let mut in_application = false;
+
+    for line in info.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_application = line == "[Application]";
+        } else if in_application && let Some(name) = line.strip_prefix("name=") {
+            return Some(name.trim());
+        }
+    }
+
+    None
+}
+
+impl Sandbox {
+    /// The sandbox this process runs in, worked out once
+    pub fn current() -> Option<&'static Sandbox> {
+        static CURRENT: OnceLock<Option<Sandbox>> = OnceLock::new();
+
+        CURRENT
+            .get_or_init(|| {
+                let info = std::fs::read_to_string(FLATPAK_INFO).ok();
+                Self::detect(info.as_deref(), |name| std::env::var_os(name))
+            })
+            .as_ref()
+    }
+
+    /// `current`, from the contents of /.flatpak-info if there is one and the given environment
+    pub fn detect(
+        flatpak_info: Option<&str>,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> Option<Self> {
+        if let Some(info) = flatpak_info {
+            let app_id = flatpak_app_id(info)
+                .map(str::to_string)
+                .or_else(|| env("FLATPAK_ID").map(|id| id.to_string_lossy().into_owned()))
+                .unwrap_or_default();
+
+            return Some(Sandbox::Flatpak { app_id });
+        }
+
+        env("SNAP_NAME").map(|name| Sandbox::Snap {
+            name: name.to_string_lossy().into_owned(),
+        })
+    }
+
+    /// OpenMW's own sandbox, where the XDG variables already lead to its files
+    pub fn is_openmw(&self) -> bool {
+        match self {
+            Sandbox::Flatpak { app_id } => app_id == OPENMW_FLATPAK_ID,
+            Sandbox::Snap { name } => name == OPENMW_SNAP_NAME,
+        }
+    }
+}
+
+/// `~/.var/app/org.openmw.OpenMW`, which holds the `config/openmw` and `data/openmw` of the Flatpak install
+pub fn openmw_flatpak_dir(home: &Path) -> PathBuf {
+    home.join(".var").join("app").join(OPENMW_FLATPAK_ID)
+}
+
+/// The XDG directory OpenMW uses, when it isn't the one this process would use itself.
+/// That's the OpenMW Flatpak's if it has an `openmw` folder there, and the host's otherwise.
+/// `None` outside a sandbox and inside OpenMW's own, where the usual lookup is right.
+pub fn openmw_xdg_dir(
+    sandbox: Option<&Sandbox>,
+    kind: XdgDir,
+    env: impl Fn(&str) -> Option<OsString>,
+) -> Option<PathBuf> {
+    let sandbox = sandbox.filter(|sandbox| !sandbox.is_openmw())?;
+
+    // A snap gets a HOME of its own, with the real one kept aside
+    let home = match sandbox {
+        Sandbox::Flatpak { .. } => env("HOME"),
+        Sandbox::Snap { .. } => env("SNAP_REAL_HOME"),
+    }
+    .map(PathBuf::from)?;
+
+    let flatpak = openmw_flatpak_dir(&home).join(kind.flatpak_name());
+    if flatpak.join("openmw").is_dir() {
+        return Some(flatpak);
+    }
+
+    Some(
+        env(kind.host_variable())
+            .filter(|value| !value.is_empty())
+            .map_or_else(|| home.join(kind.default_in_home()), PathBuf::from),
+    )
+}
+
+/// A system path like `/etc/openmw`, as this process can reach it: under /run/host inside a Flatpak
+pub fn host_path(sandbox: Option<&Sandbox>, path: &Path) -> PathBuf {
+    match sandbox {
+        Some(Sandbox::Flatpak { .. }) if path.starts_with("/usr") || path.starts_with("/etc") => {
+            Path::new(FLATPAK_HOST_ROOT).join(path.strip_prefix("/").unwrap_or(path))
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::util::temp_config;
+
+    #[test]
+    fn test_sandbox_paths() {
+        let home = temp_config("sandbox_home", "");
+        let env = |name: &str| match name {
+            "HOME" | "SNAP_REAL_HOME" => Some(home.clone().into_os_string()),
+            "FLATPAK_ID" => Some("ignored".into()),
+            _ => None,
+        };
+
+        let manager = Sandbox::detect(
+            Some("[Application]\nname=io.example.ModManager\nruntime=runtime/x\n"),
+            env,
+        )
+        .unwrap();
+        assert_eq!(
+            manager,
+            Sandbox::Flatpak {
+                app_id: "io.example.ModManager".into()
+            }
+        );
+        assert_eq!(Sandbox::detect(None, env), None);
+
+        let openmw = Sandbox::Flatpak {
+            app_id: OPENMW_FLATPAK_ID.into(),
+        };
+        assert_eq!(openmw_xdg_dir(Some(&openmw), XdgDir::Config, env), None);
+        assert_eq!(openmw_xdg_dir(None, XdgDir::Config, env), None);
+
+        // Without the OpenMW Flatpak, a native install on the host
+        assert_eq!(
+            openmw_xdg_dir(Some(&manager), XdgDir::Data, env),
+            Some(home.join(".local/share"))
+        );
+
+        let flatpak_config = openmw_flatpak_dir(&home).join("config");
+        std::fs::create_dir_all(flatpak_config.join("openmw")).unwrap();
+        assert_eq!(
+            openmw_xdg_dir(Some(&manager), XdgDir::Config, env),
+            Some(flatpak_config)
+        );
+
+        let snap = Sandbox::Snap {
+            name: "modmanager".into(),
+        };
+        assert_eq!(
+            host_path(Some(&manager), Path::new("/etc/openmw")),
+            Path::new("/run/host/etc/openmw")
+        );
+        assert_eq!(
+            host_path(Some(&snap), Path::new("/etc/openmw")),
+            Path::new("/etc/openmw")
+        );
+    }
+}